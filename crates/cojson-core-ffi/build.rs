@@ -0,0 +1,22 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Set to also write the header to `include/cojson_core.h`, after changing the C API.
+const UPDATE_HEADER_ENV: &str = "COJSON_FFI_UPDATE_HEADER";
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed={UPDATE_HEADER_ENV}");
+
+    let bindings = cbindgen::generate(&crate_dir).expect("Unable to generate the C header");
+    // The checked-in header is only rewritten on request, so building never touches the source
+    // tree; `tests/c_api.rs` checks it's up to date
+    bindings.write_to_file(out_dir.join("cojson_core.h"));
+    if env::var_os(UPDATE_HEADER_ENV).is_some() {
+        bindings.write_to_file(crate_dir.join("include").join("cojson_core.h"));
+    }
+}
@@ -0,0 +1,328 @@
+/* C ABI for cojson-core. See crates/cojson-core-ffi/README.md for ownership rules. */
+
+#ifndef COJSON_CORE_H
+#define COJSON_CORE_H
+
+/* Generated by cbindgen from crates/cojson-core-ffi. Do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// The outcome of a call.
+typedef enum CojsonStatus {
+  COJSON_STATUS_OK = 0,
+  // A required pointer argument was NULL.
+  COJSON_STATUS_NULL_ARGUMENT = 1,
+  // A string argument wasn't valid UTF-8.
+  COJSON_STATUS_INVALID_UTF8 = 2,
+  // The operation failed, with the reason in `cojson_last_error_code`.
+  COJSON_STATUS_ERROR = 3,
+  // A bug in the library. Handles passed to the call may be left in any state.
+  COJSON_STATUS_PANIC = 4,
+} CojsonStatus;
+
+// The transactions of one session of a CoValue, verified against the session's signer.
+// Mirrors the NAPI `SessionLog` class.
+typedef struct CojsonSessionLog CojsonSessionLog;
+
+// Bytes owned by the caller, freed with `cojson_buffer_free`.
+typedef struct CojsonBuffer {
+  uint8_t *data;
+  size_t len;
+} CojsonBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The stable code of the last error on this thread, like "SIGNATURE_VERIFICATION_FAILED", or
+// NULL if the last call succeeded. Valid until the next call on this thread.
+const char *cojson_last_error_code(void);
+
+// A description of the last error on this thread, or NULL if the last call succeeded. Valid
+// until the next call on this thread.
+const char *cojson_last_error_message(void);
+
+// Free a string returned by this library. NULL is ignored.
+//
+// # Safety
+// `value` must be NULL or a string returned by this library, not freed before.
+void cojson_string_free(char *value);
+
+// Free bytes returned by this library. An empty buffer is ignored.
+//
+// # Safety
+// `buffer` must be empty or returned by this library, not freed before.
+void cojson_buffer_free(struct CojsonBuffer buffer);
+
+// Sign a message with a "signerSecret_z" secret, writing the "signature_z" signature to
+// `out_signature`.
+//
+// # Safety
+// `message` must point to `message_len` bytes, `secret` must be NUL-terminated,
+// `out_signature` must be valid for writes.
+enum CojsonStatus cojson_sign(const uint8_t *message,
+                              size_t message_len,
+                              const char *secret,
+                              char **out_signature);
+
+// Check a signature against a message and a "signer_z" ID, writing the outcome to `out_valid`.
+// A well-formed signature that doesn't match is not an error.
+//
+// # Safety
+// `message` must point to `message_len` bytes, the strings must be NUL-terminated,
+// `out_valid` must be valid for writes.
+enum CojsonStatus cojson_verify(const char *signature,
+                                const uint8_t *message,
+                                size_t message_len,
+                                const char *id,
+                                bool *out_valid);
+
+// Write the "signer_z" ID of a "signerSecret_z" secret to `out_id`.
+//
+// # Safety
+// `secret` must be NUL-terminated, `out_id` must be valid for writes.
+enum CojsonStatus cojson_get_signer_id(const char *secret, char **out_id);
+
+// Write the "sealer_z" ID of a "sealerSecret_z" secret to `out_id`.
+//
+// # Safety
+// `secret` must be NUL-terminated, `out_id` must be valid for writes.
+enum CojsonStatus cojson_get_sealer_id(const char *secret, char **out_id);
+
+// Seal a message from `sender_secret` to `recipient_id`, writing the sealed bytes to `out`.
+//
+// # Safety
+// The byte arguments must point to their lengths in bytes, the strings must be
+// NUL-terminated, `out` must be valid for writes.
+enum CojsonStatus cojson_seal(const uint8_t *message,
+                              size_t message_len,
+                              const char *sender_secret,
+                              const char *recipient_id,
+                              const uint8_t *nonce_material,
+                              size_t nonce_material_len,
+                              struct CojsonBuffer *out);
+
+// Unseal a message sealed by `sender_id` to `recipient_secret`, writing the plain bytes to
+// `out`.
+//
+// # Safety
+// The byte arguments must point to their lengths in bytes, the strings must be
+// NUL-terminated, `out` must be valid for writes.
+enum CojsonStatus cojson_unseal(const uint8_t *sealed_message,
+                                size_t sealed_message_len,
+                                const char *recipient_secret,
+                                const char *sender_id,
+                                const uint8_t *nonce_material,
+                                size_t nonce_material_len,
+                                struct CojsonBuffer *out);
+
+// Seal a message to `recipient_id` from a fresh ephemeral key, writing the sealed bytes to
+// `out`.
+//
+// # Safety
+// `message` must point to `message_len` bytes, `recipient_id` must be NUL-terminated, `out`
+// must be valid for writes.
+enum CojsonStatus cojson_seal_anonymous(const uint8_t *message,
+                                        size_t message_len,
+                                        const char *recipient_id,
+                                        struct CojsonBuffer *out);
+
+// Unseal a message produced by `cojson_seal_anonymous`, writing the plain bytes to `out`.
+//
+// # Safety
+// `sealed_message` must point to `sealed_message_len` bytes, `recipient_secret` must be
+// NUL-terminated, `out` must be valid for writes.
+enum CojsonStatus cojson_unseal_anonymous(const uint8_t *sealed_message,
+                                          size_t sealed_message_len,
+                                          const char *recipient_secret,
+                                          struct CojsonBuffer *out);
+
+// Encrypt bytes with a "keySecret_z" key, writing the encrypted bytes to `out`.
+//
+// # Safety
+// The byte arguments must point to their lengths in bytes, `key_secret` must be
+// NUL-terminated, `out` must be valid for writes.
+enum CojsonStatus cojson_encrypt(const uint8_t *plaintext,
+                                 size_t plaintext_len,
+                                 const char *key_secret,
+                                 const uint8_t *nonce_material,
+                                 size_t nonce_material_len,
+                                 struct CojsonBuffer *out);
+
+// Decrypt bytes encrypted with a "keySecret_z" key, writing the plain bytes to `out`.
+//
+// # Safety
+// The byte arguments must point to their lengths in bytes, `key_secret` must be
+// NUL-terminated, `out` must be valid for writes.
+enum CojsonStatus cojson_decrypt(const uint8_t *ciphertext,
+                                 size_t ciphertext_len,
+                                 const char *key_secret,
+                                 const uint8_t *nonce_material,
+                                 size_t nonce_material_len,
+                                 struct CojsonBuffer *out);
+
+// Generate a new Ed25519 signing key, writing it with the "signerSecret_z" prefix to
+// `out_secret`.
+//
+// # Safety
+// `out_secret` must be valid for writes.
+enum CojsonStatus cojson_new_signer_secret(char **out_secret);
+
+// Generate a new X25519 private key, writing it with the "sealerSecret_z" prefix to
+// `out_secret`.
+//
+// # Safety
+// `out_secret` must be valid for writes.
+enum CojsonStatus cojson_new_sealer_secret(char **out_secret);
+
+// Generate a new key for encrypting transactions, writing its "key_z" ID to `out_key_id` and
+// its "keySecret_z" secret to `out_key_secret`.
+//
+// # Safety
+// `out_key_id` and `out_key_secret` must be valid for writes.
+enum CojsonStatus cojson_new_key_secret(char **out_key_id, char **out_key_secret);
+
+// Hash bytes with BLAKE3, writing the 32-byte hash to `out`.
+//
+// # Safety
+// `data` must point to `data_len` bytes, `out` must be valid for writes.
+enum CojsonStatus cojson_blake3_hash_once(const uint8_t *data,
+                                          size_t data_len,
+                                          struct CojsonBuffer *out);
+
+// Hash `context` followed by `data` with BLAKE3, writing the 32-byte hash to `out`.
+//
+// # Safety
+// The byte arguments must point to their lengths in bytes, `out` must be valid for writes.
+enum CojsonStatus cojson_blake3_hash_once_with_context(const uint8_t *data,
+                                                       size_t data_len,
+                                                       const uint8_t *context,
+                                                       size_t context_len,
+                                                       struct CojsonBuffer *out);
+
+// Derive the 24-byte nonce for `nonce_material`, as used by `cojson_encrypt` and
+// `cojson_seal`, writing it to `out`.
+//
+// # Safety
+// `nonce_material` must point to `nonce_material_len` bytes, `out` must be valid for writes.
+enum CojsonStatus cojson_generate_nonce(const uint8_t *nonce_material,
+                                        size_t nonce_material_len,
+                                        struct CojsonBuffer *out);
+
+// Create an empty session log. `signer_id` may be NULL, in which case signatures can't be
+// verified and `try_add` needs `skip_verify`.
+//
+// # Safety
+// The strings must be NUL-terminated, `out` must be valid for writes.
+enum CojsonStatus cojson_session_log_new(const char *co_id,
+                                         const char *session_id,
+                                         const char *signer_id,
+                                         struct CojsonSessionLog **out);
+
+// Copy a session log into a new, independent handle.
+//
+// # Safety
+// `log` must be a live handle, `out` must be valid for writes.
+enum CojsonStatus cojson_session_log_clone(struct CojsonSessionLog *log,
+                                           struct CojsonSessionLog **out);
+
+// Free a session log. NULL is ignored.
+//
+// # Safety
+// `log` must be NULL or a live handle, not used afterwards.
+void cojson_session_log_free(struct CojsonSessionLog *log);
+
+// Append `count` transactions, given as JSON strings, checking that `new_signature` signs the
+// resulting hash unless `skip_verify` is set.
+//
+// # Safety
+// `log` must be a live handle, `transactions_json` must point to `count` NUL-terminated
+// strings.
+enum CojsonStatus cojson_session_log_try_add(struct CojsonSessionLog *log,
+                                             const char *const *transactions_json,
+                                             size_t count,
+                                             const char *new_signature,
+                                             bool skip_verify);
+
+// Add a transaction with encrypted changes. `meta` may be NULL.
+//
+// Writes a JSON object with the `signature`, `encrypted_changes` and encrypted `meta` to
+// `out_json`.
+//
+// # Safety
+// `log` must be a live handle, the strings must be NUL-terminated, `out_json` must be valid
+// for writes.
+enum CojsonStatus cojson_session_log_add_new_private_transaction(struct CojsonSessionLog *log,
+                                                                 const char *changes_json,
+                                                                 const char *signer_secret,
+                                                                 const char *encryption_key,
+                                                                 const char *key_id,
+                                                                 uint64_t made_at,
+                                                                 const char *meta,
+                                                                 char **out_json);
+
+// Add a transaction with plain changes. `meta` may be NULL.
+//
+// Writes the new signature to `out_signature`.
+//
+// # Safety
+// `log` must be a live handle, the strings must be NUL-terminated, `out_signature` must be
+// valid for writes.
+enum CojsonStatus cojson_session_log_add_new_trusting_transaction(struct CojsonSessionLog *log,
+                                                                  const char *changes_json,
+                                                                  const char *signer_secret,
+                                                                  uint64_t made_at,
+                                                                  const char *meta,
+                                                                  char **out_signature);
+
+// Decrypt the changes of the private transaction at `tx_index`, writing them as JSON to
+// `out_json`.
+//
+// # Safety
+// `log` must be a live handle, `encryption_key` must be NUL-terminated, `out_json` must be
+// valid for writes.
+enum CojsonStatus cojson_session_log_decrypt_next_transaction_changes_json(struct CojsonSessionLog *log,
+                                                                           uint32_t tx_index,
+                                                                           const char *encryption_key,
+                                                                           char **out_json);
+
+// Decrypt the meta of the private transaction at `tx_index`, writing it as JSON to
+// `out_json`, or NULL if the transaction has no meta.
+//
+// # Safety
+// `log` must be a live handle, `encryption_key` must be NUL-terminated, `out_json` must be
+// valid for writes.
+enum CojsonStatus cojson_session_log_decrypt_next_transaction_meta_json(struct CojsonSessionLog *log,
+                                                                        uint32_t tx_index,
+                                                                        const char *encryption_key,
+                                                                        char **out_json);
+
+// Write the number of transactions in the log to `out_count`.
+//
+// # Safety
+// `log` must be a live handle, `out_count` must be valid for writes.
+enum CojsonStatus cojson_session_log_transaction_count(struct CojsonSessionLog *log,
+                                                       uint32_t *out_count);
+
+// Write the transaction at `tx_index`, as the JSON string it was added as, to `out_json`.
+//
+// # Safety
+// `log` must be a live handle, `out_json` must be valid for writes.
+enum CojsonStatus cojson_session_log_transaction_json(struct CojsonSessionLog *log,
+                                                      uint32_t tx_index,
+                                                      char **out_json);
+
+// Write the signature of the last transaction to `out_signature`, or NULL if the log is empty.
+//
+// # Safety
+// `log` must be a live handle, `out_signature` must be valid for writes.
+enum CojsonStatus cojson_session_log_last_signature(struct CojsonSessionLog *log,
+                                                    char **out_signature);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* COJSON_CORE_H */
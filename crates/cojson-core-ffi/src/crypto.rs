@@ -0,0 +1,290 @@
+use std::ffi::c_char;
+
+use cojson_core::core::new_random_key_secret;
+use cojson_core::crypto::{ed25519, encrypt, seal, signature, x25519};
+
+use crate::{bytes_arg, ffi_call, into_c_string, out_arg, str_arg, CojsonBuffer, CojsonStatus};
+
+/// Sign a message with a "signerSecret_z" secret, writing the "signature_z" signature to
+/// `out_signature`.
+///
+/// # Safety
+/// `message` must point to `message_len` bytes, `secret` must be NUL-terminated,
+/// `out_signature` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_sign(
+    message: *const u8,
+    message_len: usize,
+    secret: *const c_char,
+    out_signature: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let message = bytes_arg(message, message_len, "message")?;
+        let secret = str_arg(secret, "secret")?;
+        let out_signature = out_arg(out_signature, "out_signature")?;
+        *out_signature = into_c_string(signature::sign(message, secret)?, "out_signature")?;
+        Ok(())
+    })
+}
+
+/// Check a signature against a message and a "signer_z" ID, writing the outcome to `out_valid`.
+/// A well-formed signature that doesn't match is not an error.
+///
+/// # Safety
+/// `message` must point to `message_len` bytes, the strings must be NUL-terminated,
+/// `out_valid` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_verify(
+    signature: *const c_char,
+    message: *const u8,
+    message_len: usize,
+    id: *const c_char,
+    out_valid: *mut bool,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let signature = str_arg(signature, "signature")?;
+        let message = bytes_arg(message, message_len, "message")?;
+        let id = str_arg(id, "id")?;
+        *out_arg(out_valid, "out_valid")? = signature::verify(signature, message, id)?;
+        Ok(())
+    })
+}
+
+/// Write the "signer_z" ID of a "signerSecret_z" secret to `out_id`.
+///
+/// # Safety
+/// `secret` must be NUL-terminated, `out_id` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_get_signer_id(
+    secret: *const c_char,
+    out_id: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let secret = str_arg(secret, "secret")?;
+        let out_id = out_arg(out_id, "out_id")?;
+        *out_id = into_c_string(signature::get_signer_id(secret)?, "out_id")?;
+        Ok(())
+    })
+}
+
+/// Write the "sealer_z" ID of a "sealerSecret_z" secret to `out_id`.
+///
+/// # Safety
+/// `secret` must be NUL-terminated, `out_id` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_get_sealer_id(
+    secret: *const c_char,
+    out_id: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let secret = str_arg(secret, "secret")?;
+        let out_id = out_arg(out_id, "out_id")?;
+        *out_id = into_c_string(x25519::get_sealer_id(secret)?, "out_id")?;
+        Ok(())
+    })
+}
+
+/// Seal a message from `sender_secret` to `recipient_id`, writing the sealed bytes to `out`.
+///
+/// # Safety
+/// The byte arguments must point to their lengths in bytes, the strings must be
+/// NUL-terminated, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_seal(
+    message: *const u8,
+    message_len: usize,
+    sender_secret: *const c_char,
+    recipient_id: *const c_char,
+    nonce_material: *const u8,
+    nonce_material_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let message = bytes_arg(message, message_len, "message")?;
+        let sender_secret = str_arg(sender_secret, "sender_secret")?;
+        let recipient_id = str_arg(recipient_id, "recipient_id")?;
+        let nonce_material = bytes_arg(nonce_material, nonce_material_len, "nonce_material")?;
+        let out = out_arg(out, "out")?;
+        *out = seal::seal(message, sender_secret, recipient_id, nonce_material)?.into();
+        Ok(())
+    })
+}
+
+/// Unseal a message sealed by `sender_id` to `recipient_secret`, writing the plain bytes to
+/// `out`.
+///
+/// # Safety
+/// The byte arguments must point to their lengths in bytes, the strings must be
+/// NUL-terminated, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_unseal(
+    sealed_message: *const u8,
+    sealed_message_len: usize,
+    recipient_secret: *const c_char,
+    sender_id: *const c_char,
+    nonce_material: *const u8,
+    nonce_material_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let sealed_message = bytes_arg(sealed_message, sealed_message_len, "sealed_message")?;
+        let recipient_secret = str_arg(recipient_secret, "recipient_secret")?;
+        let sender_id = str_arg(sender_id, "sender_id")?;
+        let nonce_material = bytes_arg(nonce_material, nonce_material_len, "nonce_material")?;
+        let out = out_arg(out, "out")?;
+        *out = seal::unseal(sealed_message, recipient_secret, sender_id, nonce_material)?.into();
+        Ok(())
+    })
+}
+
+/// Seal a message to `recipient_id` from a fresh ephemeral key, writing the sealed bytes to
+/// `out`.
+///
+/// # Safety
+/// `message` must point to `message_len` bytes, `recipient_id` must be NUL-terminated, `out`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_seal_anonymous(
+    message: *const u8,
+    message_len: usize,
+    recipient_id: *const c_char,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let message = bytes_arg(message, message_len, "message")?;
+        let recipient_id = str_arg(recipient_id, "recipient_id")?;
+        let out = out_arg(out, "out")?;
+        *out = seal::seal_anonymous(message, recipient_id)?.into();
+        Ok(())
+    })
+}
+
+/// Unseal a message produced by `cojson_seal_anonymous`, writing the plain bytes to `out`.
+///
+/// # Safety
+/// `sealed_message` must point to `sealed_message_len` bytes, `recipient_secret` must be
+/// NUL-terminated, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_unseal_anonymous(
+    sealed_message: *const u8,
+    sealed_message_len: usize,
+    recipient_secret: *const c_char,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let sealed_message = bytes_arg(sealed_message, sealed_message_len, "sealed_message")?;
+        let recipient_secret = str_arg(recipient_secret, "recipient_secret")?;
+        let out = out_arg(out, "out")?;
+        *out = seal::unseal_anonymous(sealed_message, recipient_secret)?.into();
+        Ok(())
+    })
+}
+
+/// Encrypt bytes with a "keySecret_z" key, writing the encrypted bytes to `out`.
+///
+/// # Safety
+/// The byte arguments must point to their lengths in bytes, `key_secret` must be
+/// NUL-terminated, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_encrypt(
+    plaintext: *const u8,
+    plaintext_len: usize,
+    key_secret: *const c_char,
+    nonce_material: *const u8,
+    nonce_material_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let plaintext = bytes_arg(plaintext, plaintext_len, "plaintext")?;
+        let key_secret = str_arg(key_secret, "key_secret")?;
+        let nonce_material = bytes_arg(nonce_material, nonce_material_len, "nonce_material")?;
+        let out = out_arg(out, "out")?;
+        *out = encrypt::encrypt(plaintext, key_secret, nonce_material)?.into();
+        Ok(())
+    })
+}
+
+/// Decrypt bytes encrypted with a "keySecret_z" key, writing the plain bytes to `out`.
+///
+/// # Safety
+/// The byte arguments must point to their lengths in bytes, `key_secret` must be
+/// NUL-terminated, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_decrypt(
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    key_secret: *const c_char,
+    nonce_material: *const u8,
+    nonce_material_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let ciphertext = bytes_arg(ciphertext, ciphertext_len, "ciphertext")?;
+        let key_secret = str_arg(key_secret, "key_secret")?;
+        let nonce_material = bytes_arg(nonce_material, nonce_material_len, "nonce_material")?;
+        let out = out_arg(out, "out")?;
+        *out = encrypt::decrypt(ciphertext, key_secret, nonce_material)?.into();
+        Ok(())
+    })
+}
+
+/// Generate a new Ed25519 signing key, writing it with the "signerSecret_z" prefix to
+/// `out_secret`.
+///
+/// # Safety
+/// `out_secret` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_new_signer_secret(out_secret: *mut *mut c_char) -> CojsonStatus {
+    ffi_call(|| {
+        let out_secret = out_arg(out_secret, "out_secret")?;
+        let signing_key = ed25519::new_ed25519_signing_key();
+        let secret = format!("signerSecret_z{}", bs58::encode(signing_key).into_string());
+        *out_secret = into_c_string(secret, "out_secret")?;
+        Ok(())
+    })
+}
+
+/// Generate a new X25519 private key, writing it with the "sealerSecret_z" prefix to
+/// `out_secret`.
+///
+/// # Safety
+/// `out_secret` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_new_sealer_secret(out_secret: *mut *mut c_char) -> CojsonStatus {
+    ffi_call(|| {
+        let out_secret = out_arg(out_secret, "out_secret")?;
+        let private_key = x25519::new_x25519_private_key();
+        let secret = format!("sealerSecret_z{}", bs58::encode(private_key).into_string());
+        *out_secret = into_c_string(secret, "out_secret")?;
+        Ok(())
+    })
+}
+
+/// Generate a new key for encrypting transactions, writing its "key_z" ID to `out_key_id` and
+/// its "keySecret_z" secret to `out_key_secret`.
+///
+/// # Safety
+/// `out_key_id` and `out_key_secret` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_new_key_secret(
+    out_key_id: *mut *mut c_char,
+    out_key_secret: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let out_key_id = out_arg(out_key_id, "out_key_id")?;
+        let out_key_secret = out_arg(out_key_secret, "out_key_secret")?;
+        let (key_id, key_secret) = new_random_key_secret();
+        let key_id = into_c_string(key_id.0, "out_key_id")?;
+        // Only hand out the ID once both are ready, so neither leaks on failure
+        let key_secret = match into_c_string(key_secret.0, "out_key_secret") {
+            Ok(key_secret) => key_secret,
+            Err(err) => {
+                crate::cojson_string_free(key_id);
+                return Err(err);
+            }
+        };
+        *out_key_id = key_id;
+        *out_key_secret = key_secret;
+        Ok(())
+    })
+}
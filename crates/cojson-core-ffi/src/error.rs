@@ -0,0 +1,75 @@
+use std::fmt;
+use std::str::Utf8Error;
+
+use cojson_core::core::{CoJsonCoreError, ErrorCode};
+use cojson_core::crypto::CryptoError;
+
+use crate::CojsonStatus;
+
+/// Why a call failed, before it's turned into a status and the thread's last error.
+#[derive(Debug)]
+pub(crate) enum FfiError {
+    NullArgument(&'static str),
+    InvalidUtf8(&'static str, Utf8Error),
+    /// A result can't be returned as a C string.
+    InteriorNul(&'static str),
+    /// A numeric argument is larger than the core accepts.
+    OutOfRange(&'static str),
+    /// A transaction came out differently than the call expected, e.g. not private.
+    UnexpectedTransaction(&'static str),
+    Core(CoJsonCoreError),
+}
+
+impl FfiError {
+    pub(crate) fn status(&self) -> CojsonStatus {
+        match self {
+            FfiError::NullArgument(_) => CojsonStatus::NullArgument,
+            FfiError::InvalidUtf8(_, _) => CojsonStatus::InvalidUtf8,
+            _ => CojsonStatus::Error,
+        }
+    }
+
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            FfiError::NullArgument(_) => "NULL_ARGUMENT",
+            FfiError::InvalidUtf8(_, _) => ErrorCode::InvalidUtf8.as_str(),
+            FfiError::InteriorNul(_) => "INTERIOR_NUL",
+            FfiError::OutOfRange(_) => "OUT_OF_RANGE",
+            FfiError::UnexpectedTransaction(_) => "UNEXPECTED_TRANSACTION",
+            FfiError::Core(err) => err.code().as_str(),
+        }
+    }
+}
+
+impl fmt::Display for FfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfiError::NullArgument(name) => write!(f, "{name} must not be NULL"),
+            FfiError::InvalidUtf8(name, err) => write!(f, "Invalid UTF-8 in {name}: {err}"),
+            FfiError::InteriorNul(name) => write!(f, "{name} contains a NUL byte"),
+            FfiError::OutOfRange(name) => write!(f, "{name} is out of range"),
+            FfiError::UnexpectedTransaction(expected) => {
+                write!(f, "Expected a {expected} transaction")
+            }
+            FfiError::Core(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<CoJsonCoreError> for FfiError {
+    fn from(err: CoJsonCoreError) -> Self {
+        FfiError::Core(err)
+    }
+}
+
+impl From<CryptoError> for FfiError {
+    fn from(err: CryptoError) -> Self {
+        FfiError::Core(err.into())
+    }
+}
+
+impl From<serde_json::Error> for FfiError {
+    fn from(err: serde_json::Error) -> Self {
+        FfiError::Core(CoJsonCoreError::Json(err))
+    }
+}
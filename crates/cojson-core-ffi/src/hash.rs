@@ -0,0 +1,58 @@
+use cojson_core::hash::blake3;
+
+use crate::{bytes_arg, ffi_call, out_arg, CojsonBuffer, CojsonStatus};
+
+/// Hash bytes with BLAKE3, writing the 32-byte hash to `out`.
+///
+/// # Safety
+/// `data` must point to `data_len` bytes, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_blake3_hash_once(
+    data: *const u8,
+    data_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let data = bytes_arg(data, data_len, "data")?;
+        *out_arg(out, "out")? = blake3::blake3_hash_once(data).into();
+        Ok(())
+    })
+}
+
+/// Hash `context` followed by `data` with BLAKE3, writing the 32-byte hash to `out`.
+///
+/// # Safety
+/// The byte arguments must point to their lengths in bytes, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_blake3_hash_once_with_context(
+    data: *const u8,
+    data_len: usize,
+    context: *const u8,
+    context_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let data = bytes_arg(data, data_len, "data")?;
+        let context = bytes_arg(context, context_len, "context")?;
+        *out_arg(out, "out")? = blake3::blake3_hash_once_with_context(data, context).into();
+        Ok(())
+    })
+}
+
+/// Derive the 24-byte nonce for `nonce_material`, as used by `cojson_encrypt` and
+/// `cojson_seal`, writing it to `out`.
+///
+/// # Safety
+/// `nonce_material` must point to `nonce_material_len` bytes, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_generate_nonce(
+    nonce_material: *const u8,
+    nonce_material_len: usize,
+    out: *mut CojsonBuffer,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let nonce_material = bytes_arg(nonce_material, nonce_material_len, "nonce_material")?;
+        *out_arg(out, "out")? = blake3::generate_nonce(nonce_material).into();
+        Ok(())
+    })
+}
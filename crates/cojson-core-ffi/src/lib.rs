@@ -0,0 +1,191 @@
+//! A C ABI for cojson-core, for embedding it in C, C++, Go and anything else that speaks C.
+//! `include/cojson_core.h` is generated from this crate by cbindgen on every build.
+//!
+//! Conventions, for every function:
+//! - The return value is a `CojsonStatus`. On anything but `COJSON_STATUS_OK`,
+//!   `cojson_last_error_code` and `cojson_last_error_message` tell what went wrong.
+//! - Strings are NUL-terminated UTF-8. Byte arrays are a pointer and a length.
+//! - Results are written through `out` pointers. Strings returned that way are owned by the
+//!   caller and freed with `cojson_string_free`, byte arrays with `cojson_buffer_free`.
+//! - Session logs are opaque handles, freed with `cojson_session_log_free`. A handle must not
+//!   be used from several threads at once.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+pub mod crypto;
+mod error;
+pub mod hash;
+pub mod session_log;
+
+pub use crypto::*;
+pub use hash::*;
+pub use session_log::*;
+
+use error::FfiError;
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CojsonStatus {
+    Ok = 0,
+    /// A required pointer argument was NULL.
+    NullArgument = 1,
+    /// A string argument wasn't valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The operation failed, with the reason in `cojson_last_error_code`.
+    Error = 3,
+    /// A bug in the library. Handles passed to the call may be left in any state.
+    Panic = 4,
+}
+
+/// Bytes owned by the caller, freed with `cojson_buffer_free`.
+#[repr(C)]
+pub struct CojsonBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl From<Box<[u8]>> for CojsonBuffer {
+    fn from(bytes: Box<[u8]>) -> Self {
+        let len = bytes.len();
+        CojsonBuffer {
+            data: Box::into_raw(bytes).cast(),
+            len,
+        }
+    }
+}
+
+struct LastError {
+    code: CString,
+    message: CString,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(code: &str, message: String) {
+    let last_error = LastError {
+        code: CString::new(code).unwrap_or_default(),
+        message: CString::new(message.replace('\0', "\\0")).unwrap_or_default(),
+    };
+    LAST_ERROR.with(|cell| *cell.borrow_mut() = Some(last_error));
+}
+
+/// Run the body of an exported function, recording its error for `cojson_last_error_*` and
+/// keeping panics from unwinding into the caller.
+fn ffi_call(body: impl FnOnce() -> Result<(), FfiError>) -> CojsonStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => {
+            LAST_ERROR.with(|cell| *cell.borrow_mut() = None);
+            CojsonStatus::Ok
+        }
+        Ok(Err(err)) => {
+            set_last_error(err.code(), err.to_string());
+            err.status()
+        }
+        Err(_) => {
+            set_last_error("PANIC", "cojson-core panicked".to_string());
+            CojsonStatus::Panic
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be NULL or a NUL-terminated string that outlives `'a`.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &'static str) -> Result<&'a str, FfiError> {
+    optional_str_arg(ptr, name)?.ok_or(FfiError::NullArgument(name))
+}
+
+/// # Safety
+/// `ptr` must be NULL or a NUL-terminated string that outlives `'a`.
+unsafe fn optional_str_arg<'a>(
+    ptr: *const c_char,
+    name: &'static str,
+) -> Result<Option<&'a str>, FfiError> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(Some)
+        .map_err(|err| FfiError::InvalidUtf8(name, err))
+}
+
+/// # Safety
+/// `ptr` must point to `len` readable bytes that outlive `'a`, or be NULL with `len` 0.
+unsafe fn bytes_arg<'a>(
+    ptr: *const u8,
+    len: usize,
+    name: &'static str,
+) -> Result<&'a [u8], FfiError> {
+    if ptr.is_null() {
+        return match len {
+            0 => Ok(&[]),
+            _ => Err(FfiError::NullArgument(name)),
+        };
+    }
+    Ok(std::slice::from_raw_parts(ptr, len))
+}
+
+/// # Safety
+/// `ptr` must be NULL or valid for writes.
+unsafe fn out_arg<'a, T>(ptr: *mut T, name: &'static str) -> Result<&'a mut T, FfiError> {
+    ptr.as_mut().ok_or(FfiError::NullArgument(name))
+}
+
+fn into_c_string(value: String, name: &'static str) -> Result<*mut c_char, FfiError> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|_| FfiError::InteriorNul(name))
+}
+
+/// The stable code of the last error on this thread, like "SIGNATURE_VERIFICATION_FAILED", or
+/// NULL if the last call succeeded. Valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn cojson_last_error_code() -> *const c_char {
+    LAST_ERROR.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.code.as_ptr())
+    })
+}
+
+/// A description of the last error on this thread, or NULL if the last call succeeded. Valid
+/// until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn cojson_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.message.as_ptr())
+    })
+}
+
+/// Free a string returned by this library. NULL is ignored.
+///
+/// # Safety
+/// `value` must be NULL or a string returned by this library, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// Free bytes returned by this library. An empty buffer is ignored.
+///
+/// # Safety
+/// `buffer` must be empty or returned by this library, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_buffer_free(buffer: CojsonBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
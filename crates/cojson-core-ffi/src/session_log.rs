@@ -0,0 +1,335 @@
+use std::ffi::c_char;
+use std::ptr;
+
+use cojson_core::core::{
+    CoID, CoJsonCoreError, KeyID, KeySecret, SessionID, SessionLogInternal, Signature, SignerID,
+    SignerSecret, Transaction, TransactionMode,
+};
+use serde::Serialize;
+use serde_json::value::RawValue;
+
+use crate::{ffi_call, into_c_string, optional_str_arg, out_arg, str_arg, CojsonStatus, FfiError};
+
+/// The transactions of one session of a CoValue, verified against the session's signer.
+/// Mirrors the NAPI `SessionLog` class.
+pub struct CojsonSessionLog {
+    internal: SessionLogInternal,
+}
+
+#[derive(Serialize)]
+struct PrivateTransactionResult {
+    signature: String,
+    encrypted_changes: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<String>,
+}
+
+/// # Safety
+/// `log` must be NULL or a live handle from `cojson_session_log_new`.
+unsafe fn log_arg<'a>(log: *mut CojsonSessionLog) -> Result<&'a mut CojsonSessionLog, FfiError> {
+    out_arg(log, "log")
+}
+
+/// Create an empty session log. `signer_id` may be NULL, in which case signatures can't be
+/// verified and `try_add` needs `skip_verify`.
+///
+/// # Safety
+/// The strings must be NUL-terminated, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_new(
+    co_id: *const c_char,
+    session_id: *const c_char,
+    signer_id: *const c_char,
+    out: *mut *mut CojsonSessionLog,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let co_id = str_arg(co_id, "co_id")?;
+        let session_id = str_arg(session_id, "session_id")?;
+        let signer_id = optional_str_arg(signer_id, "signer_id")?;
+        let out = out_arg(out, "out")?;
+
+        let internal = SessionLogInternal::new(
+            CoID(co_id.to_string()),
+            SessionID(session_id.to_string()),
+            signer_id.map(|id| SignerID(id.to_string())),
+        );
+        *out = Box::into_raw(Box::new(CojsonSessionLog { internal }));
+        Ok(())
+    })
+}
+
+/// Copy a session log into a new, independent handle.
+///
+/// # Safety
+/// `log` must be a live handle, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_clone(
+    log: *mut CojsonSessionLog,
+    out: *mut *mut CojsonSessionLog,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let out = out_arg(out, "out")?;
+        *out = Box::into_raw(Box::new(CojsonSessionLog {
+            internal: log.internal.clone(),
+        }));
+        Ok(())
+    })
+}
+
+/// Free a session log. NULL is ignored.
+///
+/// # Safety
+/// `log` must be NULL or a live handle, not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_free(log: *mut CojsonSessionLog) {
+    if !log.is_null() {
+        drop(Box::from_raw(log));
+    }
+}
+
+/// Append `count` transactions, given as JSON strings, checking that `new_signature` signs the
+/// resulting hash unless `skip_verify` is set.
+///
+/// # Safety
+/// `log` must be a live handle, `transactions_json` must point to `count` NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_try_add(
+    log: *mut CojsonSessionLog,
+    transactions_json: *const *const c_char,
+    count: usize,
+    new_signature: *const c_char,
+    skip_verify: bool,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let new_signature = str_arg(new_signature, "new_signature")?;
+        let transactions_json: &[*const c_char] = match (transactions_json.is_null(), count) {
+            (true, 0) => &[],
+            (true, _) => return Err(FfiError::NullArgument("transactions_json")),
+            (false, _) => std::slice::from_raw_parts(transactions_json, count),
+        };
+
+        let transactions = transactions_json
+            .iter()
+            .map(|&tx| -> Result<_, FfiError> {
+                let tx = str_arg(tx, "transactions_json")?;
+                Ok(serde_json::from_str::<Box<RawValue>>(tx)?)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        log.internal.try_add(
+            transactions,
+            &Signature(new_signature.to_string()),
+            skip_verify,
+        )?;
+        Ok(())
+    })
+}
+
+/// Add a transaction with encrypted changes. `meta` may be NULL.
+///
+/// Writes a JSON object with the `signature`, `encrypted_changes` and encrypted `meta` to
+/// `out_json`.
+///
+/// # Safety
+/// `log` must be a live handle, the strings must be NUL-terminated, `out_json` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_add_new_private_transaction(
+    log: *mut CojsonSessionLog,
+    changes_json: *const c_char,
+    signer_secret: *const c_char,
+    encryption_key: *const c_char,
+    key_id: *const c_char,
+    made_at: u64,
+    meta: *const c_char,
+    out_json: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let changes_json = str_arg(changes_json, "changes_json")?;
+        let signer_secret = str_arg(signer_secret, "signer_secret")?;
+        let encryption_key = str_arg(encryption_key, "encryption_key")?;
+        let key_id = str_arg(key_id, "key_id")?;
+        let meta = optional_str_arg(meta, "meta")?;
+        let out_json = out_arg(out_json, "out_json")?;
+
+        let (signature, transaction) = log.internal.add_new_transaction(
+            changes_json,
+            TransactionMode::Private {
+                key_id: KeyID(key_id.to_string()),
+                key_secret: KeySecret(encryption_key.to_string()),
+            },
+            &SignerSecret(signer_secret.to_string()),
+            made_at,
+            meta.map(str::to_string),
+        )?;
+
+        let Transaction::Private(private_tx) = transaction else {
+            return Err(FfiError::UnexpectedTransaction("private"));
+        };
+        let result = PrivateTransactionResult {
+            signature: signature.0,
+            encrypted_changes: private_tx.encrypted_changes.value,
+            meta: private_tx.meta.map(|meta| meta.value),
+        };
+        *out_json = into_c_string(serde_json::to_string(&result)?, "out_json")?;
+        Ok(())
+    })
+}
+
+/// Add a transaction with plain changes. `meta` may be NULL.
+///
+/// Writes the new signature to `out_signature`.
+///
+/// # Safety
+/// `log` must be a live handle, the strings must be NUL-terminated, `out_signature` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_add_new_trusting_transaction(
+    log: *mut CojsonSessionLog,
+    changes_json: *const c_char,
+    signer_secret: *const c_char,
+    made_at: u64,
+    meta: *const c_char,
+    out_signature: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let changes_json = str_arg(changes_json, "changes_json")?;
+        let signer_secret = str_arg(signer_secret, "signer_secret")?;
+        let meta = optional_str_arg(meta, "meta")?;
+        let out_signature = out_arg(out_signature, "out_signature")?;
+
+        let (signature, _) = log.internal.add_new_transaction(
+            changes_json,
+            TransactionMode::Trusting,
+            &SignerSecret(signer_secret.to_string()),
+            made_at,
+            meta.map(str::to_string),
+        )?;
+        *out_signature = into_c_string(signature.0, "out_signature")?;
+        Ok(())
+    })
+}
+
+/// Decrypt the changes of the private transaction at `tx_index`, writing them as JSON to
+/// `out_json`.
+///
+/// # Safety
+/// `log` must be a live handle, `encryption_key` must be NUL-terminated, `out_json` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_decrypt_next_transaction_changes_json(
+    log: *mut CojsonSessionLog,
+    tx_index: u32,
+    encryption_key: *const c_char,
+    out_json: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let encryption_key = str_arg(encryption_key, "encryption_key")?;
+        let out_json = out_arg(out_json, "out_json")?;
+
+        let changes = log.internal.decrypt_next_transaction_changes_json(
+            tx_index,
+            KeySecret(encryption_key.to_string()),
+        )?;
+        *out_json = into_c_string(changes, "out_json")?;
+        Ok(())
+    })
+}
+
+/// Decrypt the meta of the private transaction at `tx_index`, writing it as JSON to
+/// `out_json`, or NULL if the transaction has no meta.
+///
+/// # Safety
+/// `log` must be a live handle, `encryption_key` must be NUL-terminated, `out_json` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_decrypt_next_transaction_meta_json(
+    log: *mut CojsonSessionLog,
+    tx_index: u32,
+    encryption_key: *const c_char,
+    out_json: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let encryption_key = str_arg(encryption_key, "encryption_key")?;
+        let out_json = out_arg(out_json, "out_json")?;
+
+        let meta = log
+            .internal
+            .decrypt_next_transaction_meta_json(tx_index, KeySecret(encryption_key.to_string()))?;
+        *out_json = match meta {
+            Some(meta) => into_c_string(meta, "out_json")?,
+            None => ptr::null_mut(),
+        };
+        Ok(())
+    })
+}
+
+/// Write the number of transactions in the log to `out_count`.
+///
+/// # Safety
+/// `log` must be a live handle, `out_count` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_transaction_count(
+    log: *mut CojsonSessionLog,
+    out_count: *mut u32,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let count = log.internal.transactions_json().len();
+        *out_arg(out_count, "out_count")? =
+            u32::try_from(count).map_err(|_| FfiError::OutOfRange("out_count"))?;
+        Ok(())
+    })
+}
+
+/// Write the transaction at `tx_index`, as the JSON string it was added as, to `out_json`.
+///
+/// # Safety
+/// `log` must be a live handle, `out_json` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_transaction_json(
+    log: *mut CojsonSessionLog,
+    tx_index: u32,
+    out_json: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let out_json = out_arg(out_json, "out_json")?;
+        let tx = log
+            .internal
+            .transactions_json()
+            .get(tx_index as usize)
+            .ok_or(FfiError::Core(CoJsonCoreError::TransactionNotFound(
+                tx_index,
+            )))?;
+        *out_json = into_c_string(tx.clone(), "out_json")?;
+        Ok(())
+    })
+}
+
+/// Write the signature of the last transaction to `out_signature`, or NULL if the log is empty.
+///
+/// # Safety
+/// `log` must be a live handle, `out_signature` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cojson_session_log_last_signature(
+    log: *mut CojsonSessionLog,
+    out_signature: *mut *mut c_char,
+) -> CojsonStatus {
+    ffi_call(|| {
+        let log = log_arg(log)?;
+        let out_signature = out_arg(out_signature, "out_signature")?;
+        *out_signature = match log.internal.last_signature() {
+            Some(signature) => into_c_string(signature.0.clone(), "out_signature")?,
+            None => ptr::null_mut(),
+        };
+        Ok(())
+    })
+}
@@ -0,0 +1,58 @@
+//! Compiles `tests/session_log.c` against the static library and the checked-in header, and
+//! runs it.
+
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn target_dir() -> PathBuf {
+    // target/<profile>/deps/c_api-<hash>
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn test_header_is_up_to_date() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let checked_in = fs::read_to_string(crate_dir.join("include").join("cojson_core.h")).unwrap();
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/cojson_core.h"));
+    assert!(
+        checked_in == generated,
+        "include/cojson_core.h is out of date, rebuild with COJSON_FFI_UPDATE_HEADER=1"
+    );
+}
+
+#[test]
+fn test_c_program() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let static_lib = target_dir().join("libcojson_core_ffi.a");
+    assert!(static_lib.exists(), "missing {}", static_lib.display());
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cojson_core_ffi_session_log");
+    let status = Command::new(cc)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests").join("session_log.c"))
+        .arg(&static_lib)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling session_log.c failed");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(
+        output.status.success(),
+        "session_log.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ok");
+}
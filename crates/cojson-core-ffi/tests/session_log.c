@@ -0,0 +1,250 @@
+/* Exercises the C ABI the way an embedder would. Exits non-zero on the first failed check. */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "cojson_core.h"
+
+#define CHECK(cond)                                                         \
+  do {                                                                      \
+    if (!(cond)) {                                                          \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,     \
+              #cond);                                                       \
+      exit(1);                                                              \
+    }                                                                       \
+  } while (0)
+
+#define CHECK_OK(call)                                                      \
+  do {                                                                      \
+    CojsonStatus status_ = (call);                                          \
+    if (status_ != COJSON_STATUS_OK) {                                      \
+      fprintf(stderr, "%s:%d: %s failed with %d: %s (%s)\n", __FILE__,      \
+              __LINE__, #call, (int)status_, cojson_last_error_code(),      \
+              cojson_last_error_message());                                 \
+      exit(1);                                                              \
+    }                                                                       \
+  } while (0)
+
+static const char *CO_ID = "co_zTest";
+static const char *SESSION_ID = "co_zTest_session_zC";
+
+static void test_session_log(void) {
+  char *signer_secret = NULL;
+  char *signer_id = NULL;
+  CHECK_OK(cojson_new_signer_secret(&signer_secret));
+  CHECK_OK(cojson_get_signer_id(signer_secret, &signer_id));
+
+  CojsonSessionLog *log = NULL;
+  CHECK_OK(cojson_session_log_new(CO_ID, SESSION_ID, signer_id, &log));
+
+  char *trusting_signature = NULL;
+  CHECK_OK(cojson_session_log_add_new_trusting_transaction(
+      log, "[{\"op\":\"set\",\"key\":\"a\",\"value\":1}]", signer_secret, 1, NULL,
+      &trusting_signature));
+  CHECK(strncmp(trusting_signature, "signature_z", 11) == 0);
+
+  char *key_id = NULL;
+  char *key_secret = NULL;
+  CHECK_OK(cojson_new_key_secret(&key_id, &key_secret));
+
+  char *private_json = NULL;
+  CHECK_OK(cojson_session_log_add_new_private_transaction(
+      log, "[{\"op\":\"set\",\"key\":\"b\",\"value\":2}]", signer_secret, key_secret,
+      key_id, 2, "{\"note\":1}", &private_json));
+  CHECK(strstr(private_json, "\"encrypted_changes\":\"encrypted_U") != NULL);
+
+  char *changes = NULL;
+  CHECK_OK(cojson_session_log_decrypt_next_transaction_changes_json(log, 1, key_secret,
+                                                                    &changes));
+  CHECK(strcmp(changes, "[{\"op\":\"set\",\"key\":\"b\",\"value\":2}]") == 0);
+
+  char *meta = NULL;
+  CHECK_OK(cojson_session_log_decrypt_next_transaction_meta_json(log, 1, key_secret, &meta));
+  CHECK(strcmp(meta, "{\"note\":1}") == 0);
+
+  char *no_meta = (char *)"unset";
+  CHECK_OK(cojson_session_log_decrypt_next_transaction_meta_json(log, 0, key_secret, &no_meta));
+  CHECK(no_meta == NULL);
+
+  /* Replay the transactions into a fresh log, verifying the last signature */
+  uint32_t count = 0;
+  CHECK_OK(cojson_session_log_transaction_count(log, &count));
+  CHECK(count == 2);
+  char *transactions[2];
+  for (uint32_t i = 0; i < count; i++) {
+    CHECK_OK(cojson_session_log_transaction_json(log, i, &transactions[i]));
+  }
+  char *last_signature = NULL;
+  CHECK_OK(cojson_session_log_last_signature(log, &last_signature));
+
+  CojsonSessionLog *copy = NULL;
+  CHECK_OK(cojson_session_log_new(CO_ID, SESSION_ID, signer_id, &copy));
+
+  /* The first transaction alone doesn't match the last signature */
+  CHECK(cojson_session_log_try_add(copy, (const char *const *)transactions, 1, last_signature,
+                                   false) == COJSON_STATUS_ERROR);
+  CHECK(strcmp(cojson_last_error_code(), "SIGNATURE_VERIFICATION_FAILED") == 0);
+
+  CHECK_OK(cojson_session_log_try_add(copy, (const char *const *)transactions, count,
+                                      last_signature, false));
+  CHECK(cojson_last_error_code() == NULL);
+
+  CojsonSessionLog *clone = NULL;
+  CHECK_OK(cojson_session_log_clone(copy, &clone));
+  char *clone_signature = NULL;
+  CHECK_OK(cojson_session_log_last_signature(clone, &clone_signature));
+  CHECK(strcmp(clone_signature, last_signature) == 0);
+
+  char *index_out_of_range = NULL;
+  CHECK(cojson_session_log_transaction_json(clone, 5, &index_out_of_range) ==
+        COJSON_STATUS_ERROR);
+  CHECK(strcmp(cojson_last_error_code(), "TRANSACTION_NOT_FOUND") == 0);
+  CHECK(index_out_of_range == NULL);
+  CHECK(cojson_session_log_transaction_json(clone, UINT32_MAX, &index_out_of_range) ==
+        COJSON_STATUS_ERROR);
+  CHECK(strcmp(cojson_last_error_code(), "TRANSACTION_NOT_FOUND") == 0);
+  CHECK(index_out_of_range == NULL);
+
+  for (uint32_t i = 0; i < count; i++) {
+    cojson_string_free(transactions[i]);
+  }
+  cojson_string_free(clone_signature);
+  cojson_string_free(last_signature);
+  cojson_string_free(meta);
+  cojson_string_free(changes);
+  cojson_string_free(private_json);
+  cojson_string_free(key_secret);
+  cojson_string_free(key_id);
+  cojson_string_free(trusting_signature);
+  cojson_string_free(signer_id);
+  cojson_string_free(signer_secret);
+  cojson_session_log_free(clone);
+  cojson_session_log_free(copy);
+  cojson_session_log_free(log);
+}
+
+static void test_sign_and_verify(void) {
+  const uint8_t message[] = "hello";
+  char *secret = NULL;
+  char *id = NULL;
+  char *signature = NULL;
+  CHECK_OK(cojson_new_signer_secret(&secret));
+  CHECK_OK(cojson_get_signer_id(secret, &id));
+  CHECK_OK(cojson_sign(message, 5, secret, &signature));
+
+  bool valid = false;
+  CHECK_OK(cojson_verify(signature, message, 5, id, &valid));
+  CHECK(valid);
+  CHECK_OK(cojson_verify(signature, message, 4, id, &valid));
+  CHECK(!valid);
+
+  CHECK(cojson_sign(message, 5, "not a secret", &signature) == COJSON_STATUS_ERROR);
+  CHECK(strcmp(cojson_last_error_code(), "INVALID_PREFIX") == 0);
+
+  cojson_string_free(signature);
+  cojson_string_free(id);
+  cojson_string_free(secret);
+}
+
+static void test_seal_and_encrypt(void) {
+  const uint8_t message[] = "secret message";
+  const uint8_t nonce_material[] = "nonce";
+  char *sender_secret = NULL;
+  char *sender_id = NULL;
+  char *recipient_secret = NULL;
+  char *recipient_id = NULL;
+  CHECK_OK(cojson_new_sealer_secret(&sender_secret));
+  CHECK_OK(cojson_get_sealer_id(sender_secret, &sender_id));
+  CHECK_OK(cojson_new_sealer_secret(&recipient_secret));
+  CHECK_OK(cojson_get_sealer_id(recipient_secret, &recipient_id));
+
+  CojsonBuffer sealed = {0};
+  CojsonBuffer unsealed = {0};
+  CHECK_OK(cojson_seal(message, sizeof message, sender_secret, recipient_id, nonce_material,
+                       sizeof nonce_material, &sealed));
+  CHECK_OK(cojson_unseal(sealed.data, sealed.len, recipient_secret, sender_id, nonce_material,
+                         sizeof nonce_material, &unsealed));
+  CHECK(unsealed.len == sizeof message && memcmp(unsealed.data, message, sizeof message) == 0);
+  cojson_buffer_free(unsealed);
+  cojson_buffer_free(sealed);
+
+  CHECK_OK(cojson_seal_anonymous(message, sizeof message, recipient_id, &sealed));
+  CHECK_OK(cojson_unseal_anonymous(sealed.data, sealed.len, recipient_secret, &unsealed));
+  CHECK(unsealed.len == sizeof message && memcmp(unsealed.data, message, sizeof message) == 0);
+  cojson_buffer_free(unsealed);
+  cojson_buffer_free(sealed);
+
+  char *key_id = NULL;
+  char *key_secret = NULL;
+  CojsonBuffer encrypted = {0};
+  CojsonBuffer decrypted = {0};
+  CHECK_OK(cojson_new_key_secret(&key_id, &key_secret));
+  CHECK_OK(cojson_encrypt(message, sizeof message, key_secret, nonce_material,
+                          sizeof nonce_material, &encrypted));
+  CHECK_OK(cojson_decrypt(encrypted.data, encrypted.len, key_secret, nonce_material,
+                          sizeof nonce_material, &decrypted));
+  CHECK(decrypted.len == sizeof message && memcmp(decrypted.data, message, sizeof message) == 0);
+  cojson_buffer_free(decrypted);
+  cojson_buffer_free(encrypted);
+
+  cojson_string_free(key_secret);
+  cojson_string_free(key_id);
+  cojson_string_free(recipient_id);
+  cojson_string_free(recipient_secret);
+  cojson_string_free(sender_id);
+  cojson_string_free(sender_secret);
+}
+
+static void test_hashing(void) {
+  /* BLAKE3 of the empty input */
+  const uint8_t expected[4] = {0xaf, 0x13, 0x49, 0xb9};
+  CojsonBuffer hash = {0};
+  CHECK_OK(cojson_blake3_hash_once(NULL, 0, &hash));
+  CHECK(hash.len == 32 && memcmp(hash.data, expected, sizeof expected) == 0);
+  cojson_buffer_free(hash);
+
+  CojsonBuffer with_context = {0};
+  CHECK_OK(cojson_blake3_hash_once_with_context((const uint8_t *)"data", 4,
+                                                (const uint8_t *)"context", 7, &with_context));
+  CHECK(with_context.len == 32);
+  cojson_buffer_free(with_context);
+
+  CojsonBuffer nonce = {0};
+  CHECK_OK(cojson_generate_nonce((const uint8_t *)"nonce", 5, &nonce));
+  CHECK(nonce.len == 24);
+  cojson_buffer_free(nonce);
+}
+
+static void test_null_arguments(void) {
+  CojsonSessionLog *log = NULL;
+  CHECK(cojson_session_log_new(NULL, SESSION_ID, NULL, &log) == COJSON_STATUS_NULL_ARGUMENT);
+  CHECK(strcmp(cojson_last_error_code(), "NULL_ARGUMENT") == 0);
+  CHECK(strstr(cojson_last_error_message(), "co_id") != NULL);
+  CHECK(log == NULL);
+
+  CHECK(cojson_blake3_hash_once((const uint8_t *)"x", 1, NULL) ==
+        COJSON_STATUS_NULL_ARGUMENT);
+  CHECK(cojson_blake3_hash_once(NULL, 1, NULL) == COJSON_STATUS_NULL_ARGUMENT);
+
+  const char invalid_utf8[] = {(char)0xff, 0};
+  CHECK(cojson_session_log_new(invalid_utf8, SESSION_ID, NULL, &log) ==
+        COJSON_STATUS_INVALID_UTF8);
+  CHECK(strcmp(cojson_last_error_code(), "INVALID_UTF8") == 0);
+
+  /* Freeing NULL is a no-op */
+  cojson_session_log_free(NULL);
+  cojson_string_free(NULL);
+  cojson_buffer_free((CojsonBuffer){0});
+}
+
+int main(void) {
+  test_session_log();
+  test_sign_and_verify();
+  test_seal_and_encrypt();
+  test_hashing();
+  test_null_arguments();
+  puts("ok");
+  return 0;
+}
@@ -167,6 +167,14 @@ export declare function newEd25519SigningKey(): Uint8Array
  */
 export declare function newX25519PrivateKey(): Uint8Array
 
+/**
+ * NAPI-exposed function to decode a BIP39 mnemonic passphrase back into the secret seed.
+ * - `passphrase`: Words separated by whitespace
+ * - `wordlist`: Optional list of 2048 words, defaults to the BIP39 English wordlist
+ * Returns the secret seed or throws JsError on unknown words or a checksum mismatch.
+ */
+export declare function passphraseToSecretSeed(passphrase: string, wordlist?: Array<string> | undefined | null): Uint8Array
+
 /**
  * NAPI-exposed function for sealing a message using X25519 + XSalsa20-Poly1305.
  * Provides authenticated encryption with perfect forward secrecy.
@@ -178,6 +186,23 @@ export declare function newX25519PrivateKey(): Uint8Array
  */
 export declare function seal(message: Uint8Array, senderSecret: string, recipientId: string, nonceMaterial: Uint8Array): Uint8Array
 
+/**
+ * NAPI-exposed function for sealing a message without a sender identity.
+ * A fresh ephemeral X25519 key is generated for every message and prepended to the output.
+ * - `message`: Raw bytes to seal
+ * - `recipient_id`: Base58-encoded recipient's public key with "sealer_z" prefix
+ * Returns the ephemeral public key followed by the sealed bytes, or throws JsError if sealing fails.
+ */
+export declare function sealAnonymous(message: Uint8Array, recipientId: string): Uint8Array
+
+/**
+ * NAPI-exposed function to encode a secret seed as a BIP39 mnemonic passphrase.
+ * - `secret_seed`: 16-32 bytes of seed material (32 for account secret seeds)
+ * - `wordlist`: Optional list of 2048 words, defaults to the BIP39 English wordlist
+ * Returns the space-separated passphrase or throws JsError if the seed or wordlist is invalid.
+ */
+export declare function secretSeedToPassphrase(secretSeed: Uint8Array, wordlist?: Array<string> | undefined | null): string
+
 /**
  * NAPI-exposed function to sign a message using Ed25519.
  * - `message`: Raw bytes to sign
@@ -197,6 +222,14 @@ export declare function sign(message: Uint8Array, secret: Uint8Array): string
  */
 export declare function unseal(sealedMessage: Uint8Array, recipientSecret: string, senderId: string, nonceMaterial: Uint8Array): Uint8Array
 
+/**
+ * NAPI-exposed function for unsealing a message sealed with `sealAnonymous`.
+ * - `sealed_message`: The ephemeral public key followed by the sealed bytes
+ * - `recipient_secret`: Base58-encoded recipient's private key with "sealerSecret_z" prefix
+ * Returns unsealed bytes or throws JsError if unsealing fails.
+ */
+export declare function unsealAnonymous(sealedMessage: Uint8Array, recipientSecret: string): Uint8Array
+
 /**
  * NAPI-exposed function to verify an Ed25519 signature.
  * - `signature`: Raw signature bytes
@@ -1,5 +1,8 @@
+use crate::CojsonCoreError;
 use cojson_core::crypto::ed25519;
+use cojson_core::crypto::CryptoError;
 use napi::bindgen_prelude::Uint8Array;
+use napi::Env;
 use napi_derive::napi;
 
 /// Generate a new Ed25519 signing key using secure random number generation.
@@ -13,10 +16,10 @@ pub fn new_ed25519_signing_key() -> Uint8Array {
 /// - `signing_key`: 32 bytes of signing key material
 /// Returns 32 bytes of verifying key material or throws JsError if key is invalid.
 #[napi]
-pub fn ed25519_verifying_key(signing_key: &[u8]) -> napi::Result<Uint8Array> {
+pub fn ed25519_verifying_key(env: Env, signing_key: &[u8]) -> napi::Result<Uint8Array> {
   ed25519::ed25519_verifying_key(signing_key)
     .map(|key| key.into())
-    .map_err(|err| napi::Error::new(napi::Status::GenericFailure, err.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to sign a message using Ed25519.
@@ -24,10 +27,10 @@ pub fn ed25519_verifying_key(signing_key: &[u8]) -> napi::Result<Uint8Array> {
 /// - `message`: Raw bytes to sign
 /// Returns 64 bytes of signature material or throws JsError if signing fails.
 #[napi]
-pub fn ed25519_sign(signing_key: &[u8], message: &[u8]) -> napi::Result<Uint8Array> {
+pub fn ed25519_sign(env: Env, signing_key: &[u8], message: &[u8]) -> napi::Result<Uint8Array> {
   ed25519::ed25519_sign(signing_key, message)
     .map(|signature| signature.into())
-    .map_err(|err| napi::Error::new(napi::Status::GenericFailure, err.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to verify an Ed25519 signature.
@@ -37,22 +40,23 @@ pub fn ed25519_sign(signing_key: &[u8], message: &[u8]) -> napi::Result<Uint8Arr
 /// Returns true if signature is valid, false otherwise, or throws JsError if verification fails.
 #[napi]
 pub fn ed25519_verify(
+  env: Env,
   verifying_key: &[u8],
   message: &[u8],
   signature: &[u8],
 ) -> napi::Result<bool> {
   ed25519::ed25519_verify(verifying_key, message, signature)
-    .map_err(|err| napi::Error::new(napi::Status::GenericFailure, err.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to validate and copy Ed25519 signing key bytes.
 /// - `bytes`: 32 bytes of signing key material to validate
 /// Returns the same 32 bytes if valid or throws JsError if invalid.
 #[napi]
-pub fn ed25519_signing_key_from_bytes(bytes: &[u8]) -> napi::Result<Uint8Array> {
-  let key_bytes: [u8; 32] = bytes
-    .try_into()
-    .map_err(|_| napi::Error::new(napi::Status::GenericFailure, "Invalid signing key length"))?;
+pub fn ed25519_signing_key_from_bytes(env: Env, bytes: &[u8]) -> napi::Result<Uint8Array> {
+  let key_bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+    CojsonCoreError::from(CryptoError::InvalidKeyLength(32, bytes.len())).into_napi_error(&env)
+  })?;
   Ok(key_bytes.into())
 }
 
@@ -60,10 +64,10 @@ pub fn ed25519_signing_key_from_bytes(bytes: &[u8]) -> napi::Result<Uint8Array>
 /// - `signing_key`: 32 bytes of signing key material
 /// Returns 32 bytes of public key material or throws JsError if key is invalid.
 #[napi]
-pub fn ed25519_signing_key_to_public(signing_key: &[u8]) -> napi::Result<Uint8Array> {
+pub fn ed25519_signing_key_to_public(env: Env, signing_key: &[u8]) -> napi::Result<Uint8Array> {
   ed25519::ed25519_verifying_key(signing_key)
     .map(|key| key.into())
-    .map_err(|err| napi::Error::new(napi::Status::GenericFailure, err.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to sign a message with an Ed25519 signing key.
@@ -71,20 +75,24 @@ pub fn ed25519_signing_key_to_public(signing_key: &[u8]) -> napi::Result<Uint8Ar
 /// - `message`: Raw bytes to sign
 /// Returns 64 bytes of signature material or throws JsError if signing fails.
 #[napi]
-pub fn ed25519_signing_key_sign(signing_key: &[u8], message: &[u8]) -> napi::Result<Uint8Array> {
+pub fn ed25519_signing_key_sign(
+  env: Env,
+  signing_key: &[u8],
+  message: &[u8],
+) -> napi::Result<Uint8Array> {
   ed25519::ed25519_sign(signing_key, message)
     .map(|signature| signature.into())
-    .map_err(|err| napi::Error::new(napi::Status::GenericFailure, err.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to validate and copy Ed25519 verifying key bytes.
 /// - `bytes`: 32 bytes of verifying key material to validate
 /// Returns the same 32 bytes if valid or throws JsError if invalid.
 #[napi]
-pub fn ed25519_verifying_key_from_bytes(bytes: &[u8]) -> napi::Result<Uint8Array> {
-  let key_bytes: [u8; 32] = bytes
-    .try_into()
-    .map_err(|_| napi::Error::new(napi::Status::GenericFailure, "Invalid verifying key length"))?;
+pub fn ed25519_verifying_key_from_bytes(env: Env, bytes: &[u8]) -> napi::Result<Uint8Array> {
+  let key_bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+    CojsonCoreError::from(CryptoError::InvalidKeyLength(32, bytes.len())).into_napi_error(&env)
+  })?;
   Ok(key_bytes.into())
 }
 
@@ -92,9 +100,9 @@ pub fn ed25519_verifying_key_from_bytes(bytes: &[u8]) -> napi::Result<Uint8Array
 /// - `bytes`: 64 bytes of signature material to validate
 /// Returns the same 64 bytes if valid or throws JsError if invalid.
 #[napi]
-pub fn ed25519_signature_from_bytes(bytes: &[u8]) -> napi::Result<Uint8Array> {
-  let sig_bytes: [u8; 64] = bytes
-    .try_into()
-    .map_err(|_| napi::Error::new(napi::Status::GenericFailure, "Invalid signature length"))?;
+pub fn ed25519_signature_from_bytes(env: Env, bytes: &[u8]) -> napi::Result<Uint8Array> {
+  let sig_bytes: [u8; 64] = bytes.try_into().map_err(|_| {
+    CojsonCoreError::from(CryptoError::InvalidSignatureLength).into_napi_error(&env)
+  })?;
   Ok(sig_bytes.into())
 }
@@ -1,5 +1,7 @@
+use crate::CojsonCoreError;
 use cojson_core::crypto::encrypt as encrypt_crypto;
 use napi::bindgen_prelude::Uint8Array;
+use napi::Env;
 use napi_derive::napi;
 
 /// NAPI-exposed function to encrypt bytes with a key secret and nonce material.
@@ -9,13 +11,14 @@ use napi_derive::napi;
 /// Returns the encrypted bytes or throws a JsError if encryption fails.
 #[napi]
 pub fn encrypt(
+  env: Env,
   value: &[u8],
   key_secret: String,
   nonce_material: &[u8],
 ) -> napi::Result<Uint8Array> {
   encrypt_crypto::encrypt(value, &key_secret, nonce_material)
     .map(|encrypted| encrypted.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to decrypt bytes with a key secret and nonce material.
@@ -25,11 +28,12 @@ pub fn encrypt(
 /// Returns the decrypted bytes or throws a JsError if decryption fails.
 #[napi]
 pub fn decrypt(
+  env: Env,
   ciphertext: &[u8],
   key_secret: String,
   nonce_material: &[u8],
 ) -> napi::Result<Uint8Array> {
   encrypt_crypto::decrypt(ciphertext, &key_secret, nonce_material)
     .map(|decrypted| decrypted.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
@@ -0,0 +1,46 @@
+use crate::CojsonCoreError;
+use cojson_core::crypto::passphrase as passphrase_crypto;
+use napi::bindgen_prelude::Uint8Array;
+use napi::Env;
+use napi_derive::napi;
+
+/// NAPI-exposed function to encode a secret seed as a BIP39 mnemonic passphrase.
+/// - `secret_seed`: 16-32 bytes of seed material (32 for account secret seeds)
+/// - `wordlist`: Optional list of 2048 words, defaults to the BIP39 English wordlist
+/// Returns the space-separated passphrase or throws JsError if the seed or wordlist is invalid.
+#[napi]
+pub fn secret_seed_to_passphrase(
+  env: Env,
+  secret_seed: &[u8],
+  wordlist: Option<Vec<String>>,
+) -> napi::Result<String> {
+  match wordlist {
+    Some(wordlist) => passphrase_crypto::secret_seed_to_passphrase(secret_seed, &wordlist),
+    None => passphrase_crypto::secret_seed_to_passphrase(
+      secret_seed,
+      passphrase_crypto::english_wordlist(),
+    ),
+  }
+  .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
+}
+
+/// NAPI-exposed function to decode a BIP39 mnemonic passphrase back into the secret seed.
+/// - `passphrase`: Words separated by whitespace
+/// - `wordlist`: Optional list of 2048 words, defaults to the BIP39 English wordlist
+/// Returns the secret seed or throws JsError on unknown words or a checksum mismatch.
+#[napi]
+pub fn passphrase_to_secret_seed(
+  env: Env,
+  passphrase: String,
+  wordlist: Option<Vec<String>>,
+) -> napi::Result<Uint8Array> {
+  match wordlist {
+    Some(wordlist) => passphrase_crypto::passphrase_to_secret_seed(&passphrase, &wordlist),
+    None => passphrase_crypto::passphrase_to_secret_seed(
+      &passphrase,
+      passphrase_crypto::english_wordlist(),
+    ),
+  }
+  .map(|secret_seed| secret_seed.into())
+  .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
+}
@@ -1,5 +1,7 @@
+use crate::CojsonCoreError;
 use cojson_core::crypto::seal as seal_crypto;
 use napi::bindgen_prelude::Uint8Array;
+use napi::Env;
 use napi_derive::napi;
 
 /// NAPI-exposed function for sealing a message using X25519 + XSalsa20-Poly1305.
@@ -11,6 +13,7 @@ use napi_derive::napi;
 /// Returns sealed bytes or throws JsError if sealing fails.
 #[napi]
 pub fn seal(
+  env: Env,
   message: &[u8],
   sender_secret: String,
   recipient_id: String,
@@ -18,7 +21,7 @@ pub fn seal(
 ) -> napi::Result<Uint8Array> {
   seal_crypto::seal(message, &sender_secret, &recipient_id, nonce_material)
     .map(|sealed| sealed.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function for unsealing a message using X25519 + XSalsa20-Poly1305.
@@ -30,6 +33,7 @@ pub fn seal(
 /// Returns unsealed bytes or throws JsError if unsealing fails.
 #[napi]
 pub fn unseal(
+  env: Env,
   sealed_message: &[u8],
   recipient_secret: String,
   sender_id: String,
@@ -42,5 +46,32 @@ pub fn unseal(
     nonce_material,
   )
   .map(|unsealed| unsealed.into())
-  .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+  .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
+}
+
+/// NAPI-exposed function for sealing a message without a sender identity.
+/// A fresh ephemeral X25519 key is generated for every message and prepended to the output.
+/// - `message`: Raw bytes to seal
+/// - `recipient_id`: Base58-encoded recipient's public key with "sealer_z" prefix
+/// Returns the ephemeral public key followed by the sealed bytes, or throws JsError if sealing fails.
+#[napi]
+pub fn seal_anonymous(env: Env, message: &[u8], recipient_id: String) -> napi::Result<Uint8Array> {
+  seal_crypto::seal_anonymous(message, &recipient_id)
+    .map(|sealed| sealed.into())
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
+}
+
+/// NAPI-exposed function for unsealing a message sealed with `sealAnonymous`.
+/// - `sealed_message`: The ephemeral public key followed by the sealed bytes
+/// - `recipient_secret`: Base58-encoded recipient's private key with "sealerSecret_z" prefix
+/// Returns unsealed bytes or throws JsError if unsealing fails.
+#[napi]
+pub fn unseal_anonymous(
+  env: Env,
+  sealed_message: &[u8],
+  recipient_secret: String,
+) -> napi::Result<Uint8Array> {
+  seal_crypto::unseal_anonymous(sealed_message, &recipient_secret)
+    .map(|unsealed| unsealed.into())
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
@@ -1,4 +1,6 @@
+use crate::CojsonCoreError;
 use cojson_core::crypto::signature;
+use napi::Env;
 use napi_derive::napi;
 
 /// NAPI-exposed function to sign a message using Ed25519.
@@ -6,15 +8,10 @@ use napi_derive::napi;
 /// - `secret`: Raw Ed25519 signing key bytes
 /// Returns base58-encoded signature with "signature_z" prefix or throws JsError if signing fails.
 #[napi]
-pub fn sign(message: &[u8], secret: &[u8]) -> napi::Result<String> {
-  let secret_str = std::str::from_utf8(secret).map_err(|e| {
-    napi::Error::new(
-      napi::Status::GenericFailure,
-      format!("Invalid UTF-8 in secret: {:?}", e),
-    )
-  })?;
-  signature::sign(message, secret_str)
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+pub fn sign(env: Env, message: &[u8], secret: &[u8]) -> napi::Result<String> {
+  let secret_str = std::str::from_utf8(secret)
+    .map_err(|e| CojsonCoreError::InvalidUtf8("secret", e).into_napi_error(&env))?;
+  signature::sign(message, secret_str).map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to verify an Ed25519 signature.
@@ -23,34 +20,21 @@ pub fn sign(message: &[u8], secret: &[u8]) -> napi::Result<String> {
 /// - `id`: Raw Ed25519 verifying key bytes
 /// Returns true if signature is valid, false otherwise, or throws JsError if verification fails.
 #[napi]
-pub fn verify(signature: &[u8], message: &[u8], id: &[u8]) -> napi::Result<bool> {
-  let signature_str = std::str::from_utf8(signature).map_err(|e| {
-    napi::Error::new(
-      napi::Status::GenericFailure,
-      format!("Invalid UTF-8 in signature: {:?}", e),
-    )
-  })?;
-  let id_str = std::str::from_utf8(id).map_err(|e| {
-    napi::Error::new(
-      napi::Status::GenericFailure,
-      format!("Invalid UTF-8 in id: {:?}", e),
-    )
-  })?;
+pub fn verify(env: Env, signature: &[u8], message: &[u8], id: &[u8]) -> napi::Result<bool> {
+  let signature_str = std::str::from_utf8(signature)
+    .map_err(|e| CojsonCoreError::InvalidUtf8("signature", e).into_napi_error(&env))?;
+  let id_str = std::str::from_utf8(id)
+    .map_err(|e| CojsonCoreError::InvalidUtf8("id", e).into_napi_error(&env))?;
   signature::verify(signature_str, message, id_str)
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to derive a signer ID from a signing key.
 /// - `secret`: Raw Ed25519 signing key bytes
 /// Returns base58-encoded verifying key with "signer_z" prefix or throws JsError if derivation fails.
 #[napi]
-pub fn get_signer_id(secret: &[u8]) -> napi::Result<String> {
-  let secret_str = std::str::from_utf8(secret).map_err(|e| {
-    napi::Error::new(
-      napi::Status::GenericFailure,
-      format!("Invalid UTF-8 in secret: {:?}", e),
-    )
-  })?;
-  signature::get_signer_id(secret_str)
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+pub fn get_signer_id(env: Env, secret: &[u8]) -> napi::Result<String> {
+  let secret_str = std::str::from_utf8(secret)
+    .map_err(|e| CojsonCoreError::InvalidUtf8("secret", e).into_napi_error(&env))?;
+  signature::get_signer_id(secret_str).map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
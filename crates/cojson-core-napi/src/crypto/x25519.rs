@@ -1,5 +1,7 @@
+use crate::CojsonCoreError;
 use cojson_core::crypto::x25519;
 use napi::bindgen_prelude::Uint8Array;
+use napi::Env;
 use napi_derive::napi;
 
 /// Generate a new X25519 private key using secure random number generation.
@@ -14,10 +16,10 @@ pub fn new_x25519_private_key() -> Uint8Array {
 /// - `private_key`: 32 bytes of private key material
 /// Returns 32 bytes of public key material or throws JsError if key is invalid.
 #[napi]
-pub fn x25519_public_key(private_key: &[u8]) -> napi::Result<Uint8Array> {
+pub fn x25519_public_key(env: Env, private_key: &[u8]) -> napi::Result<Uint8Array> {
   x25519::x25519_public_key(private_key)
     .map(|public_key| public_key.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to perform X25519 Diffie-Hellman key exchange.
@@ -25,23 +27,22 @@ pub fn x25519_public_key(private_key: &[u8]) -> napi::Result<Uint8Array> {
 /// - `public_key`: 32 bytes of public key material
 /// Returns 32 bytes of shared secret material or throws JsError if key exchange fails.
 #[napi]
-pub fn x25519_diffie_hellman(private_key: &[u8], public_key: &[u8]) -> napi::Result<Uint8Array> {
+pub fn x25519_diffie_hellman(
+  env: Env,
+  private_key: &[u8],
+  public_key: &[u8],
+) -> napi::Result<Uint8Array> {
   x25519::x25519_diffie_hellman(private_key, public_key)
     .map(|shared_secret| shared_secret.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function to derive a sealer ID from a sealer secret.
 /// - `secret`: Raw bytes of the sealer secret
 /// Returns a base58-encoded sealer ID with "sealer_z" prefix or throws JsError if derivation fails.
 #[napi]
-pub fn get_sealer_id(secret: &[u8]) -> napi::Result<String> {
-  let secret_str = std::str::from_utf8(secret).map_err(|e| {
-    napi::Error::new(
-      napi::Status::GenericFailure,
-      format!("Invalid UTF-8 in secret: {:?}", e),
-    )
-  })?;
-  x25519::get_sealer_id(secret_str)
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+pub fn get_sealer_id(env: Env, secret: &[u8]) -> napi::Result<String> {
+  let secret_str = std::str::from_utf8(secret)
+    .map_err(|e| CojsonCoreError::InvalidUtf8("secret", e).into_napi_error(&env))?;
+  x25519::get_sealer_id(secret_str).map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
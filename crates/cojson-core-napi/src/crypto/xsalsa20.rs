@@ -1,6 +1,8 @@
 use crate::hash::blake3::generate_nonce;
+use crate::CojsonCoreError;
 use cojson_core::crypto::xsalsa20;
 use napi::bindgen_prelude::Uint8Array;
+use napi::Env;
 use napi_derive::napi;
 
 /// NAPI-exposed function for XSalsa20 encryption without authentication.
@@ -11,6 +13,7 @@ use napi_derive::napi;
 /// Note: This function does not provide authentication. Use encrypt_xsalsa20_poly1305 for authenticated encryption.
 #[napi]
 pub fn encrypt_xsalsa20(
+  env: Env,
   key: &[u8],
   nonce_material: &[u8],
   plaintext: &[u8],
@@ -18,7 +21,7 @@ pub fn encrypt_xsalsa20(
   let nonce: Uint8Array = generate_nonce(nonce_material);
   xsalsa20::encrypt_xsalsa20_raw(key, &nonce, plaintext)
     .map(|b| b.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
 
 /// NAPI-exposed function for XSalsa20 decryption without authentication.
@@ -29,6 +32,7 @@ pub fn encrypt_xsalsa20(
 /// Note: This function does not provide authentication. Use decrypt_xsalsa20_poly1305 for authenticated decryption.
 #[napi]
 pub fn decrypt_xsalsa20(
+  env: Env,
   key: &[u8],
   nonce_material: &[u8],
   ciphertext: &[u8],
@@ -36,5 +40,5 @@ pub fn decrypt_xsalsa20(
   let nonce = generate_nonce(nonce_material);
   xsalsa20::decrypt_xsalsa20_raw(key, &nonce, ciphertext)
     .map(|v| v.into())
-    .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
 }
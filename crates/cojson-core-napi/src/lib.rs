@@ -1,10 +1,14 @@
 use cojson_core::core::{
-  CoID, CoJsonCoreError, KeyID, KeySecret, SessionID, SessionLogInternal, Signature, SignerID,
-  SignerSecret, Transaction, TransactionMode,
+  CoID, CoJsonCoreError, ErrorCode, KeyID, KeySecret, SessionID, SessionLogInternal, Signature,
+  SignerID, SignerSecret, Transaction, TransactionMode,
 };
+use cojson_core::crypto::CryptoError;
+use napi::bindgen_prelude::JsObjectValue;
+use napi::{Env, JsValue};
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
+use serde_json::{json, Map, Value as JsonValue};
 use thiserror::Error;
 
 pub mod hash {
@@ -15,6 +19,7 @@ pub mod hash {
 pub mod crypto {
   pub mod ed25519;
   pub mod encrypt;
+  pub mod passphrase;
   pub mod seal;
   pub mod signature;
   pub mod x25519;
@@ -22,6 +27,7 @@ pub mod crypto {
 
   pub use ed25519::*;
   pub use encrypt::*;
+  pub use passphrase::*;
   pub use seal::*;
   pub use signature::*;
   pub use x25519::*;
@@ -34,10 +40,62 @@ pub enum CojsonCoreError {
   CoJson(#[from] CoJsonCoreError),
   #[error(transparent)]
   Serde(#[from] serde_json::Error),
+  #[error("Invalid UTF-8 in {0}: {1}")]
+  InvalidUtf8(&'static str, std::str::Utf8Error),
   #[error("String Error: {0:?}")]
   Js(String),
 }
 
+impl CojsonCoreError {
+  fn code(&self) -> Option<ErrorCode> {
+    match self {
+      CojsonCoreError::CoJson(err) => Some(err.code()),
+      CojsonCoreError::Serde(_) => Some(ErrorCode::InvalidJson),
+      CojsonCoreError::InvalidUtf8(_, _) => Some(ErrorCode::InvalidUtf8),
+      CojsonCoreError::Js(_) => None,
+    }
+  }
+
+  fn details(&self) -> Map<String, JsonValue> {
+    let details = match self {
+      CojsonCoreError::CoJson(err) => return err.details(),
+      CojsonCoreError::Serde(err) => json!({ "line": err.line(), "column": err.column() }),
+      CojsonCoreError::InvalidUtf8(field, err) => {
+        json!({ "field": field, "validUpTo": err.valid_up_to() })
+      }
+      CojsonCoreError::Js(_) => json!({}),
+    };
+    details.as_object().cloned().unwrap_or_default()
+  }
+
+  /// Convert into a JS `Error` carrying the stable error code in `code`
+  /// and structured fields (tx index, expected vs. actual lengths, ...) in `details`.
+  pub(crate) fn into_napi_error(self, env: &Env) -> napi::Error {
+    let reason = self.to_string();
+    let Some(code) = self.code() else {
+      return napi::Error::new(napi::Status::GenericFailure, reason);
+    };
+
+    let js_error = || -> napi::Result<napi::Error> {
+      let mut error = env.create_error(napi::Error::new(
+        napi::Status::GenericFailure,
+        reason.clone(),
+      ))?;
+      error.set_named_property("code", code.as_str())?;
+      error.set_named_property("details", env.to_js_value(&self.details())?)?;
+      Ok(error.to_unknown().into())
+    };
+
+    js_error().unwrap_or_else(|_| napi::Error::new(napi::Status::GenericFailure, reason.clone()))
+  }
+}
+
+impl From<CryptoError> for CojsonCoreError {
+  fn from(err: CryptoError) -> Self {
+    CojsonCoreError::CoJson(err.into())
+  }
+}
+
 impl From<CojsonCoreError> for String {
   fn from(err: CojsonCoreError) -> Self {
     err.to_string()
@@ -79,32 +137,32 @@ impl SessionLog {
   #[napi]
   pub fn try_add(
     &mut self,
+    env: Env,
     transactions_json: Vec<String>,
     new_signature_str: String,
     skip_verify: bool,
   ) -> napi::Result<()> {
     let transactions: Vec<Box<RawValue>> = transactions_json
       .into_iter()
-      .map(|s| {
-        serde_json::from_str(&s)
-          .map_err(|e| CojsonCoreError::Js(format!("Failed to parse transaction string: {}", e)))
-      })
+      .map(|s| serde_json::from_str(&s).map_err(CoJsonCoreError::Json))
       .collect::<Result<Vec<_>, _>>()
-      .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))?;
+      .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))?;
 
     let new_signature = Signature(new_signature_str);
 
     self
       .internal
       .try_add(transactions, &new_signature, skip_verify)
-      .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))?;
+      .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))?;
 
     Ok(())
   }
 
   #[napi]
+  #[allow(clippy::too_many_arguments)]
   pub fn add_new_private_transaction(
     &mut self,
+    env: Env,
     changes_json: String,
     signer_secret: String,
     encryption_key: String,
@@ -124,7 +182,7 @@ impl SessionLog {
         made_at as u64,
         meta,
       )
-      .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))?;
+      .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))?;
 
     // Extract encrypted_changes from the private transaction
     let result = match transaction {
@@ -147,6 +205,7 @@ impl SessionLog {
   #[napi]
   pub fn add_new_trusting_transaction(
     &mut self,
+    env: Env,
     changes_json: String,
     signer_secret: String,
     made_at: f64,
@@ -161,7 +220,7 @@ impl SessionLog {
         made_at as u64,
         meta,
       )
-      .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))?;
+      .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))?;
 
     Ok(signature.0)
   }
@@ -169,24 +228,26 @@ impl SessionLog {
   #[napi]
   pub fn decrypt_next_transaction_changes_json(
     &self,
+    env: Env,
     tx_index: u32,
     encryption_key: String,
   ) -> napi::Result<String> {
     self
       .internal
       .decrypt_next_transaction_changes_json(tx_index, KeySecret(encryption_key))
-      .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+      .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
   }
 
   #[napi]
   pub fn decrypt_next_transaction_meta_json(
     &self,
+    env: Env,
     tx_index: u32,
     encryption_key: String,
   ) -> napi::Result<Option<String>> {
     self
       .internal
       .decrypt_next_transaction_meta_json(tx_index, KeySecret(encryption_key))
-      .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+      .map_err(|e| CojsonCoreError::from(e).into_napi_error(&env))
   }
 }
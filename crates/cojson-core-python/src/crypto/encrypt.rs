@@ -0,0 +1,38 @@
+use crate::core_err;
+use cojson_core::crypto::encrypt as encrypt_crypto;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// Encrypt bytes with a key secret and nonce material.
+/// - `value`: The raw bytes to encrypt
+/// - `key_secret`: A base58-encoded key secret with "keySecret_z" prefix
+/// - `nonce_material`: Raw bytes used to generate the nonce
+/// Returns the encrypted bytes or raises CojsonError if encryption fails.
+#[pyfunction]
+pub fn encrypt<'py>(
+  py: Python<'py>,
+  value: &[u8],
+  key_secret: &str,
+  nonce_material: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+  encrypt_crypto::encrypt(value, key_secret, nonce_material)
+    .map(|encrypted| PyBytes::new(py, &encrypted))
+    .map_err(core_err)
+}
+
+/// Decrypt bytes with a key secret and nonce material.
+/// - `ciphertext`: The encrypted bytes to decrypt
+/// - `key_secret`: A base58-encoded key secret with "keySecret_z" prefix
+/// - `nonce_material`: Raw bytes used to generate the nonce (must match encryption)
+/// Returns the decrypted bytes or raises CojsonError if decryption fails.
+#[pyfunction]
+pub fn decrypt<'py>(
+  py: Python<'py>,
+  ciphertext: &[u8],
+  key_secret: &str,
+  nonce_material: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+  encrypt_crypto::decrypt(ciphertext, key_secret, nonce_material)
+    .map(|decrypted| PyBytes::new(py, &decrypted))
+    .map_err(core_err)
+}
@@ -0,0 +1,27 @@
+use cojson_core::core::new_random_key_secret;
+use cojson_core::crypto::{ed25519, x25519};
+use pyo3::prelude::*;
+
+/// Generate a new Ed25519 signing key.
+/// Returns it base58-encoded with "signerSecret_z" prefix, as `sign` takes it.
+#[pyfunction]
+pub fn new_signer_secret() -> String {
+  let signing_key = ed25519::new_ed25519_signing_key();
+  format!("signerSecret_z{}", bs58::encode(signing_key).into_string())
+}
+
+/// Generate a new X25519 private key.
+/// Returns it base58-encoded with "sealerSecret_z" prefix, as `seal` takes it.
+#[pyfunction]
+pub fn new_sealer_secret() -> String {
+  let private_key = x25519::new_x25519_private_key();
+  format!("sealerSecret_z{}", bs58::encode(private_key).into_string())
+}
+
+/// Generate a new key for encrypting transactions.
+/// Returns the key ID with "key_z" prefix and the secret with "keySecret_z" prefix.
+#[pyfunction]
+pub fn new_key_secret() -> (String, String) {
+  let (key_id, key_secret) = new_random_key_secret();
+  (key_id.0, key_secret.0)
+}
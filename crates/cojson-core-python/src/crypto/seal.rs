@@ -0,0 +1,81 @@
+use crate::core_err;
+use cojson_core::crypto::seal as seal_crypto;
+use cojson_core::crypto::x25519;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// Seal a message using X25519 + XSalsa20-Poly1305.
+/// - `message`: Raw bytes to seal
+/// - `sender_secret`: Base58-encoded sender's private key with "sealerSecret_z" prefix
+/// - `recipient_id`: Base58-encoded recipient's public key with "sealer_z" prefix
+/// - `nonce_material`: Raw bytes used to generate the nonce
+/// Returns sealed bytes or raises CojsonError if sealing fails.
+#[pyfunction]
+pub fn seal<'py>(
+  py: Python<'py>,
+  message: &[u8],
+  sender_secret: &str,
+  recipient_id: &str,
+  nonce_material: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+  seal_crypto::seal(message, sender_secret, recipient_id, nonce_material)
+    .map(|sealed| PyBytes::new(py, &sealed))
+    .map_err(core_err)
+}
+
+/// Unseal a message sealed with `seal`.
+/// - `sealed_message`: The sealed bytes to decrypt
+/// - `recipient_secret`: Base58-encoded recipient's private key with "sealerSecret_z" prefix
+/// - `sender_id`: Base58-encoded sender's public key with "sealer_z" prefix
+/// - `nonce_material`: Raw bytes used to generate the nonce (must match sealing)
+/// Returns unsealed bytes or raises CojsonError if unsealing fails.
+#[pyfunction]
+pub fn unseal<'py>(
+  py: Python<'py>,
+  sealed_message: &[u8],
+  recipient_secret: &str,
+  sender_id: &str,
+  nonce_material: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+  seal_crypto::unseal(sealed_message, recipient_secret, sender_id, nonce_material)
+    .map(|unsealed| PyBytes::new(py, &unsealed))
+    .map_err(core_err)
+}
+
+/// Seal a message without a sender identity, with a fresh ephemeral X25519 key prepended to the output.
+/// - `message`: Raw bytes to seal
+/// - `recipient_id`: Base58-encoded recipient's public key with "sealer_z" prefix
+/// Returns the ephemeral public key followed by the sealed bytes, or raises CojsonError if sealing fails.
+#[pyfunction]
+pub fn seal_anonymous<'py>(
+  py: Python<'py>,
+  message: &[u8],
+  recipient_id: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+  seal_crypto::seal_anonymous(message, recipient_id)
+    .map(|sealed| PyBytes::new(py, &sealed))
+    .map_err(core_err)
+}
+
+/// Unseal a message sealed with `seal_anonymous`.
+/// - `sealed_message`: The ephemeral public key followed by the sealed bytes
+/// - `recipient_secret`: Base58-encoded recipient's private key with "sealerSecret_z" prefix
+/// Returns unsealed bytes or raises CojsonError if unsealing fails.
+#[pyfunction]
+pub fn unseal_anonymous<'py>(
+  py: Python<'py>,
+  sealed_message: &[u8],
+  recipient_secret: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+  seal_crypto::unseal_anonymous(sealed_message, recipient_secret)
+    .map(|unsealed| PyBytes::new(py, &unsealed))
+    .map_err(core_err)
+}
+
+/// Derive a sealer ID from a sealer secret.
+/// - `secret`: Base58-encoded private key with "sealerSecret_z" prefix
+/// Returns base58-encoded public key with "sealer_z" prefix or raises CojsonError if derivation fails.
+#[pyfunction]
+pub fn get_sealer_id(secret: &str) -> PyResult<String> {
+  x25519::get_sealer_id(secret).map_err(core_err)
+}
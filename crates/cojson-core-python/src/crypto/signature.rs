@@ -0,0 +1,30 @@
+use crate::core_err;
+use cojson_core::crypto::signature as signature_crypto;
+use pyo3::prelude::*;
+
+/// Sign a message using Ed25519.
+/// - `message`: Raw bytes to sign
+/// - `secret`: Base58-encoded signing key with "signerSecret_z" prefix
+/// Returns base58-encoded signature with "signature_z" prefix or raises CojsonError if signing fails.
+#[pyfunction]
+pub fn sign(message: &[u8], secret: &str) -> PyResult<String> {
+  signature_crypto::sign(message, secret).map_err(core_err)
+}
+
+/// Verify an Ed25519 signature.
+/// - `signature`: Base58-encoded signature with "signature_z" prefix
+/// - `message`: Raw bytes that were signed
+/// - `id`: Base58-encoded verifying key with "signer_z" prefix
+/// Returns true if signature is valid, false otherwise, or raises CojsonError if the inputs are malformed.
+#[pyfunction]
+pub fn verify(signature: &str, message: &[u8], id: &str) -> PyResult<bool> {
+  signature_crypto::verify(signature, message, id).map_err(core_err)
+}
+
+/// Derive a signer ID from a signing key.
+/// - `secret`: Base58-encoded signing key with "signerSecret_z" prefix
+/// Returns base58-encoded verifying key with "signer_z" prefix or raises CojsonError if derivation fails.
+#[pyfunction]
+pub fn get_signer_id(secret: &str) -> PyResult<String> {
+  signature_crypto::get_signer_id(secret).map_err(core_err)
+}
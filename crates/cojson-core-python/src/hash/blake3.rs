@@ -0,0 +1,63 @@
+use cojson_core::hash;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// Generate a 24-byte nonce from input material using BLAKE3.
+/// - `nonce_material`: Raw bytes to derive the nonce from
+/// Returns 24 bytes suitable for use as a nonce in cryptographic operations.
+/// This function is deterministic - the same input will produce the same nonce.
+#[pyfunction]
+pub fn generate_nonce<'py>(py: Python<'py>, nonce_material: &[u8]) -> Bound<'py, PyBytes> {
+  PyBytes::new(py, &hash::blake3::generate_nonce(nonce_material))
+}
+
+/// Hash data once using BLAKE3.
+/// - `data`: Raw bytes to hash
+/// Returns 32 bytes of hash output.
+#[pyfunction]
+pub fn blake3_hash_once<'py>(py: Python<'py>, data: &[u8]) -> Bound<'py, PyBytes> {
+  PyBytes::new(py, &hash::blake3::blake3_hash_once(data))
+}
+
+/// Hash data once using BLAKE3 with a context prefix.
+/// - `data`: Raw bytes to hash
+/// - `context`: Context bytes to prefix to the data
+/// Returns 32 bytes of hash output.
+/// This is useful for domain separation - the same data hashed with different contexts will produce different outputs.
+#[pyfunction]
+pub fn blake3_hash_once_with_context<'py>(
+  py: Python<'py>,
+  data: &[u8],
+  context: &[u8],
+) -> Bound<'py, PyBytes> {
+  PyBytes::new(
+    py,
+    &hash::blake3::blake3_hash_once_with_context(data, context),
+  )
+}
+
+/// An incremental BLAKE3 hasher, for data that arrives in pieces.
+#[pyclass(module = "cojson_core")]
+#[derive(Default, Clone)]
+pub struct Blake3Hasher(blake3::Hasher);
+
+#[pymethods]
+impl Blake3Hasher {
+  #[new]
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn update(&mut self, data: &[u8]) {
+    self.0.update(data);
+  }
+
+  pub fn finalize<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+    PyBytes::new(py, self.0.finalize().as_bytes())
+  }
+
+  #[pyo3(name = "clone")]
+  pub fn clone_py(&self) -> Self {
+    self.clone()
+  }
+}
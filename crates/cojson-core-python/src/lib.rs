@@ -0,0 +1,281 @@
+use cojson_core::core::{
+  CoID, CoJsonCoreError, ErrorCode, KeyID, KeySecret, SessionID, SessionLogInternal, Signature,
+  SignerID, SignerSecret, Transaction, TransactionMode,
+};
+use cojson_core::crypto::CryptoError;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::{json, Map, Value as JsonValue};
+use std::sync::{Mutex, MutexGuard};
+use thiserror::Error;
+
+pub mod hash {
+  pub mod blake3;
+  pub use blake3::*;
+}
+
+pub mod crypto {
+  pub mod encrypt;
+  pub mod keys;
+  pub mod seal;
+  pub mod signature;
+
+  pub use encrypt::*;
+  pub use keys::*;
+  pub use seal::*;
+  pub use signature::*;
+}
+
+pyo3::create_exception!(
+  cojson_core,
+  CojsonError,
+  PyException,
+  "Raised by cojson_core, with the stable error code in `code` and structured fields in `details`."
+);
+
+#[derive(Error, Debug)]
+pub enum CojsonCoreError {
+  #[error(transparent)]
+  CoJson(#[from] CoJsonCoreError),
+  #[error(transparent)]
+  Serde(#[from] serde_json::Error),
+}
+
+impl CojsonCoreError {
+  fn code(&self) -> ErrorCode {
+    match self {
+      CojsonCoreError::CoJson(err) => err.code(),
+      CojsonCoreError::Serde(_) => ErrorCode::InvalidJson,
+    }
+  }
+
+  fn details(&self) -> Map<String, JsonValue> {
+    match self {
+      CojsonCoreError::CoJson(err) => err.details(),
+      CojsonCoreError::Serde(err) => json!({ "line": err.line(), "column": err.column() })
+        .as_object()
+        .cloned()
+        .unwrap_or_default(),
+    }
+  }
+}
+
+impl From<CryptoError> for CojsonCoreError {
+  fn from(err: CryptoError) -> Self {
+    CojsonCoreError::CoJson(err.into())
+  }
+}
+
+impl From<CojsonCoreError> for PyErr {
+  /// A `CojsonError` carrying the stable error code in `code` and structured fields
+  /// (tx index, expected vs. actual lengths, ...) as a dict in `details`.
+  fn from(err: CojsonCoreError) -> Self {
+    let py_err = CojsonError::new_err(err.to_string());
+    Python::with_gil(|py| {
+      let set_fields = || -> PyResult<()> {
+        let value = py_err.value(py);
+        value.setattr("code", err.code().as_str())?;
+        let details = serde_json::to_string(&err.details()).unwrap_or_else(|_| "{}".to_string());
+        let details = py.import("json")?.call_method1("loads", (details,))?;
+        value.setattr("details", details)
+      };
+      // The message alone still says what went wrong
+      let _ = set_fields();
+    });
+    py_err
+  }
+}
+
+fn core_err(err: impl Into<CojsonCoreError>) -> PyErr {
+  err.into().into()
+}
+
+/// The transactions of one session of a CoValue, verified against the session's signer.
+#[pyclass(module = "cojson_core")]
+pub struct SessionLog {
+  // Python objects can be shared between threads, and the log's crypto caches aren't `Sync`
+  internal: Mutex<SessionLogInternal>,
+}
+
+impl SessionLog {
+  fn internal(&self) -> MutexGuard<'_, SessionLogInternal> {
+    // A panic while holding the lock can't leave the log half-updated, so keep using it
+    self
+      .internal
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PrivateTransactionResult {
+  signature: String,
+  encrypted_changes: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  meta: Option<String>,
+}
+
+#[pymethods]
+impl SessionLog {
+  #[new]
+  #[pyo3(signature = (co_id, session_id, signer_id=None))]
+  pub fn new(co_id: String, session_id: String, signer_id: Option<String>) -> SessionLog {
+    let internal =
+      SessionLogInternal::new(CoID(co_id), SessionID(session_id), signer_id.map(SignerID));
+    SessionLog {
+      internal: Mutex::new(internal),
+    }
+  }
+
+  #[pyo3(name = "clone")]
+  pub fn clone_py(&self) -> SessionLog {
+    SessionLog {
+      internal: Mutex::new(self.internal().clone()),
+    }
+  }
+
+  /// Append transactions, given as their original JSON strings, checking that `new_signature`
+  /// signs the resulting hash unless `skip_verify` is set. The strings are hashed as they are,
+  /// so re-serialized ones won't verify.
+  pub fn try_add(
+    &self,
+    transactions_json: Vec<String>,
+    new_signature: String,
+    skip_verify: bool,
+  ) -> PyResult<()> {
+    let transactions: Vec<Box<RawValue>> = transactions_json
+      .into_iter()
+      .map(|s| serde_json::from_str(&s).map_err(CoJsonCoreError::Json))
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(core_err)?;
+
+    self
+      .internal()
+      .try_add(transactions, &Signature(new_signature), skip_verify)
+      .map_err(core_err)?;
+    Ok(())
+  }
+
+  /// Add a transaction with encrypted changes.
+  ///
+  /// Returns a JSON object with the `signature`, `encrypted_changes` and encrypted `meta`.
+  #[pyo3(signature = (changes_json, signer_secret, encryption_key, key_id, made_at, meta=None))]
+  pub fn add_new_private_transaction(
+    &self,
+    changes_json: &str,
+    signer_secret: String,
+    encryption_key: String,
+    key_id: String,
+    made_at: u64,
+    meta: Option<String>,
+  ) -> PyResult<String> {
+    let (signature, transaction) = self
+      .internal()
+      .add_new_transaction(
+        changes_json,
+        TransactionMode::Private {
+          key_id: KeyID(key_id),
+          key_secret: KeySecret(encryption_key),
+        },
+        &SignerSecret(signer_secret),
+        made_at,
+        meta,
+      )
+      .map_err(core_err)?;
+
+    let Transaction::Private(private_tx) = transaction else {
+      return Err(CojsonError::new_err("Expected a private transaction"));
+    };
+    let result = PrivateTransactionResult {
+      signature: signature.0,
+      encrypted_changes: private_tx.encrypted_changes.value,
+      meta: private_tx.meta.map(|meta| meta.value),
+    };
+    serde_json::to_string(&result).map_err(core_err)
+  }
+
+  /// Add a transaction with plain changes.
+  ///
+  /// Returns the new signature.
+  #[pyo3(signature = (changes_json, signer_secret, made_at, meta=None))]
+  pub fn add_new_trusting_transaction(
+    &self,
+    changes_json: &str,
+    signer_secret: String,
+    made_at: u64,
+    meta: Option<String>,
+  ) -> PyResult<String> {
+    let (signature, _) = self
+      .internal()
+      .add_new_transaction(
+        changes_json,
+        TransactionMode::Trusting,
+        &SignerSecret(signer_secret),
+        made_at,
+        meta,
+      )
+      .map_err(core_err)?;
+    Ok(signature.0)
+  }
+
+  pub fn decrypt_next_transaction_changes_json(
+    &self,
+    tx_index: u32,
+    encryption_key: String,
+  ) -> PyResult<String> {
+    self
+      .internal()
+      .decrypt_next_transaction_changes_json(tx_index, KeySecret(encryption_key))
+      .map_err(core_err)
+  }
+
+  pub fn decrypt_next_transaction_meta_json(
+    &self,
+    tx_index: u32,
+    encryption_key: String,
+  ) -> PyResult<Option<String>> {
+    self
+      .internal()
+      .decrypt_next_transaction_meta_json(tx_index, KeySecret(encryption_key))
+      .map_err(core_err)
+  }
+
+  /// Every transaction as the JSON string it was added as.
+  pub fn transactions_json(&self) -> Vec<String> {
+    self.internal().transactions_json().clone()
+  }
+
+  pub fn last_signature(&self) -> Option<String> {
+    self
+      .internal()
+      .last_signature()
+      .map(|signature| signature.0.clone())
+  }
+}
+
+#[pymodule]
+#[pyo3(name = "cojson_core")]
+fn cojson_core_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add("CojsonError", m.py().get_type::<CojsonError>())?;
+  m.add_class::<SessionLog>()?;
+  m.add_class::<hash::Blake3Hasher>()?;
+  m.add_function(wrap_pyfunction!(hash::blake3_hash_once, m)?)?;
+  m.add_function(wrap_pyfunction!(hash::blake3_hash_once_with_context, m)?)?;
+  m.add_function(wrap_pyfunction!(hash::generate_nonce, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::sign, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::verify, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::get_signer_id, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::seal::seal, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::unseal, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::seal_anonymous, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::unseal_anonymous, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::get_sealer_id, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::encrypt::encrypt, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::decrypt, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::new_signer_secret, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::new_sealer_secret, m)?)?;
+  m.add_function(wrap_pyfunction!(crypto::new_key_secret, m)?)?;
+  Ok(())
+}
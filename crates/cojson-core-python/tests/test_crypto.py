@@ -0,0 +1,92 @@
+import unittest
+
+import cojson_core
+from cojson_core import (
+    Blake3Hasher,
+    CojsonError,
+    blake3_hash_once,
+    blake3_hash_once_with_context,
+    decrypt,
+    encrypt,
+    generate_nonce,
+    get_sealer_id,
+    get_signer_id,
+    new_key_secret,
+    new_sealer_secret,
+    new_signer_secret,
+    seal,
+    seal_anonymous,
+    sign,
+    unseal,
+    unseal_anonymous,
+    verify,
+)
+
+
+class HashTest(unittest.TestCase):
+    def test_hash_once(self):
+        digest = blake3_hash_once(b"test input")
+        self.assertEqual(len(digest), 32)
+        self.assertEqual(digest, blake3_hash_once(b"test input"))
+        self.assertNotEqual(digest, blake3_hash_once(b"different input"))
+        self.assertNotEqual(digest, blake3_hash_once_with_context(b"test input", b"context"))
+
+    def test_incremental_hasher(self):
+        hasher = Blake3Hasher()
+        hasher.update(b"test ")
+        snapshot = hasher.clone()
+        hasher.update(b"input")
+        self.assertEqual(hasher.finalize(), blake3_hash_once(b"test input"))
+        self.assertEqual(snapshot.finalize(), blake3_hash_once(b"test "))
+
+    def test_generate_nonce(self):
+        nonce = generate_nonce(b"nonce material")
+        self.assertEqual(len(nonce), 24)
+        self.assertEqual(nonce, generate_nonce(b"nonce material"))
+
+
+class SignatureTest(unittest.TestCase):
+    def test_sign_and_verify(self):
+        secret = new_signer_secret()
+        signer_id = get_signer_id(secret)
+        self.assertTrue(signer_id.startswith("signer_z"))
+
+        signature = sign(b"hello", secret)
+        self.assertTrue(signature.startswith("signature_z"))
+        self.assertTrue(verify(signature, b"hello", signer_id))
+        self.assertFalse(verify(signature, b"goodbye", signer_id))
+        self.assertFalse(verify(signature, b"hello", get_signer_id(new_signer_secret())))
+
+    def test_invalid_secret(self):
+        with self.assertRaises(CojsonError) as raised:
+            sign(b"hello", "sealerSecret_zWrongKind")
+        self.assertEqual(raised.exception.code, "INVALID_PREFIX")
+
+
+class EncryptionTest(unittest.TestCase):
+    def test_seal_and_unseal(self):
+        sender, recipient = new_sealer_secret(), new_sealer_secret()
+        sealed = seal(b"secret message", sender, get_sealer_id(recipient), b"nonce")
+        self.assertNotEqual(sealed, b"secret message")
+        self.assertEqual(unseal(sealed, recipient, get_sealer_id(sender), b"nonce"), b"secret message")
+        with self.assertRaises(CojsonError):
+            unseal(sealed, recipient, get_sealer_id(sender), b"other nonce")
+
+    def test_seal_anonymous(self):
+        recipient = new_sealer_secret()
+        sealed = seal_anonymous(b"secret message", get_sealer_id(recipient))
+        self.assertEqual(unseal_anonymous(sealed, recipient), b"secret message")
+
+    def test_encrypt_and_decrypt(self):
+        key_id, key_secret = new_key_secret()
+        self.assertTrue(key_id.startswith("key_z"))
+        encrypted = encrypt(b"plain", key_secret, b"nonce")
+        self.assertEqual(decrypt(encrypted, key_secret, b"nonce"), b"plain")
+        self.assertNotEqual(decrypt(encrypted, new_key_secret()[1], b"nonce"), b"plain")
+
+    def test_module_exports(self):
+        self.assertTrue(issubclass(cojson_core.CojsonError, Exception))
+
+
+if __name__ == "__main__":
+    unittest.main()
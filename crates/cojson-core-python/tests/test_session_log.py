@@ -0,0 +1,98 @@
+import json
+import unittest
+from pathlib import Path
+
+from cojson_core import CojsonError, SessionLog, get_signer_id, new_key_secret, new_signer_secret
+
+DATA = Path(__file__).resolve().parents[2] / "cojson-core" / "data"
+
+
+def load_fixture(name):
+    root = json.loads((DATA / name).read_text())
+    session_id, session = next(iter(root["exampleBase"].items()))
+    transactions = [json.dumps(tx, separators=(",", ":")) for tx in session["transactions"]]
+    return root, session_id, session, transactions
+
+
+class SessionLogFixtureTest(unittest.TestCase):
+    def test_verify_multi_tx_session(self):
+        root, session_id, session, transactions = load_fixture("multiTxSession.json")
+        co_id = session_id.split("_session_")[0]
+        log = SessionLog(co_id, session_id, root["signerID"])
+
+        log.try_add(transactions, session["lastSignature"], False)
+        self.assertEqual(len(log.transactions_json()), 5)
+        self.assertEqual(log.last_signature(), session["lastSignature"])
+
+    def test_reject_wrong_signature(self):
+        root, session_id, session, transactions = load_fixture("multiTxSession.json")
+        co_id = session_id.split("_session_")[0]
+        log = SessionLog(co_id, session_id, root["signerID"])
+
+        with self.assertRaises(CojsonError) as raised:
+            log.try_add(transactions[:4], session["lastSignature"], False)
+        self.assertEqual(raised.exception.code, "SIGNATURE_VERIFICATION_FAILED")
+        self.assertEqual(log.transactions_json(), [])
+
+    def test_decrypt_changes(self):
+        root, session_id, session, transactions = load_fixture("singleTxSession.json")
+        log = SessionLog(root["coID"], session_id, root["signerID"])
+        # Skipping verification because we don't have the right initial state
+        log.try_add(transactions, session["lastSignature"], True)
+
+        changes = log.decrypt_next_transaction_changes_json(0, root["knownKeys"][0]["secret"])
+        self.assertEqual(
+            json.loads(changes),
+            [{"after": "start", "op": "app", "value": "co_zMphsnYN6GU8nn2HDY5suvyGufY"}],
+        )
+
+    def test_decrypt_meta(self):
+        root, session_id, session, transactions = load_fixture("singleTxSessionMeta.json")
+        log = SessionLog(root["coID"], session_id, root["signerID"])
+        log.try_add(transactions, session["lastSignature"], True)
+
+        meta = log.decrypt_next_transaction_meta_json(0, root["knownKeys"][0]["secret"])
+        self.assertEqual(json.loads(meta), {"meta": {"test": "test"}})
+
+
+class SessionLogWriteTest(unittest.TestCase):
+    def setUp(self):
+        self.signer_secret = new_signer_secret()
+        signer_id = get_signer_id(self.signer_secret)
+        self.session_id = "co_zTest_session_zPython"
+        self.log = SessionLog("co_zTest", self.session_id, signer_id)
+
+    def test_add_transactions_and_verify_copy(self):
+        signature = self.log.add_new_trusting_transaction('[{"op":"set","key":"a","value":1}]', self.signer_secret, 1)
+        key_id, key_secret = new_key_secret()
+        private = json.loads(
+            self.log.add_new_private_transaction(
+                '[{"op":"set","key":"b","value":2}]', self.signer_secret, key_secret, key_id, 2, '{"note":1}'
+            )
+        )
+        self.assertTrue(private["encrypted_changes"].startswith("encrypted_U"))
+        self.assertNotEqual(private["signature"], signature)
+
+        self.assertEqual(
+            self.log.decrypt_next_transaction_changes_json(1, key_secret), '[{"op":"set","key":"b","value":2}]'
+        )
+        self.assertEqual(self.log.decrypt_next_transaction_meta_json(1, key_secret), '{"note":1}')
+
+        # Another reader verifies what we wrote
+        copy = SessionLog("co_zTest", self.session_id, get_signer_id(self.signer_secret))
+        copy.try_add(self.log.transactions_json(), self.log.last_signature(), False)
+        self.assertEqual(copy.transactions_json(), self.log.transactions_json())
+
+        clone = self.log.clone()
+        self.log.add_new_trusting_transaction("[]", self.signer_secret, 3)
+        self.assertEqual(len(clone.transactions_json()), 2)
+
+    def test_error_details(self):
+        with self.assertRaises(CojsonError) as raised:
+            self.log.add_new_trusting_transaction("[]", "signerSecret_zNope!", 1)
+        self.assertIsInstance(raised.exception.code, str)
+        self.assertIsInstance(raised.exception.details, dict)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -18,6 +18,10 @@ import {
   decrypt,
   seal, 
   unseal,
+  sealAnonymous,
+  unsealAnonymous,
+  secretSeedToPassphrase,
+  passphraseToSecretSeed,
   sign,
   verify,
   getSealerId,
@@ -315,6 +319,106 @@ describe("seal/unseal", () => {
   });
 });
 
+describe("sealAnonymous/unsealAnonymous", () => {
+  const encoder = new TextEncoder();
+  const newRecipient = () => {
+    const secret = "sealerSecret_z" + base58.encode(newX25519PrivateKey());
+    return { secret, id: getSealerId(encoder.encode(secret)) };
+  };
+
+  test("seal and unseal roundtrip", () => {
+    const { secret: recipientSecret, id: recipientId } = newRecipient();
+    const message = encoder.encode("Secret message");
+
+    const sealed = sealAnonymous(message, recipientId);
+    // ephemeral public key + ciphertext + poly1305 tag
+    expect(sealed.length).toBe(32 + message.length + 16);
+
+    const unsealed = unsealAnonymous(sealed, recipientSecret);
+    expect(Array.from(unsealed)).toEqual(Array.from(message));
+  });
+
+  test("uses a fresh ephemeral key per message", () => {
+    const { id: recipientId } = newRecipient();
+    const message = encoder.encode("Secret message");
+    const sealed1 = sealAnonymous(message, recipientId);
+    const sealed2 = sealAnonymous(message, recipientId);
+    expect(Array.from(sealed1.slice(0, 32))).not.toEqual(Array.from(sealed2.slice(0, 32)));
+  });
+
+  test("wrong recipient fails", () => {
+    const { id: recipientId } = newRecipient();
+    const { secret: otherSecret } = newRecipient();
+    const sealed = sealAnonymous(encoder.encode("test"), recipientId);
+    expect(() => unsealAnonymous(sealed, otherSecret)).toThrow();
+  });
+
+  test("invalid inputs", () => {
+    const { secret: recipientSecret } = newRecipient();
+    expect(() => sealAnonymous(encoder.encode("test"), "invalid_key")).toThrow();
+    expect(() => unsealAnonymous(new Uint8Array(10), recipientSecret)).toThrow();
+  });
+});
+
+describe("secretSeedToPassphrase/passphraseToSecretSeed", () => {
+  const zeroPassphrase = Array(23).fill("abandon").concat("art").join(" ");
+
+  test("matches the BIP39 test vector", () => {
+    const passphrase = secretSeedToPassphrase(new Uint8Array(32));
+    expect(passphrase).toBe(zeroPassphrase);
+    expect(Array.from(passphraseToSecretSeed(passphrase))).toEqual(Array(32).fill(0));
+  });
+
+  test("roundtrips a random seed", () => {
+    const secretSeed = crypto.getRandomValues(new Uint8Array(32));
+    const passphrase = secretSeedToPassphrase(secretSeed);
+    expect(passphrase.split(" ").length).toBe(24);
+    expect(Array.from(passphraseToSecretSeed(passphrase))).toEqual(Array.from(secretSeed));
+  });
+
+  test("supports a custom wordlist", () => {
+    const wordlist = Array.from({ length: 2048 }, (_, i) => `w${i}`);
+    const secretSeed = new Uint8Array(32).fill(42);
+    const passphrase = secretSeedToPassphrase(secretSeed, wordlist);
+    expect(passphrase.startsWith("w337 ")).toBe(true);
+    expect(Array.from(passphraseToSecretSeed(passphrase, wordlist))).toEqual(Array.from(secretSeed));
+  });
+
+  test("rejects unknown words and checksum mismatches", () => {
+    expect(() => passphraseToSecretSeed(zeroPassphrase.replace("art", "notaword"))).toThrow(/Unknown passphrase word/);
+    expect(() => passphraseToSecretSeed(zeroPassphrase.replace("art", "abandon"))).toThrow(/checksum mismatch/);
+    expect(() => secretSeedToPassphrase(new Uint8Array(31))).toThrow();
+  });
+});
+
+describe("errors", () => {
+  const catchError = (fn: () => unknown): any => {
+    try {
+      fn();
+    } catch (error) {
+      return error;
+    }
+    throw new Error("Expected function to throw");
+  };
+
+  test("carry a stable code and structured details", () => {
+    const error = catchError(() => ed25519SigningKeyFromBytes(new Uint8Array(31)));
+    expect(error).toBeInstanceOf(Error);
+    expect(error.code).toBe("INVALID_KEY_LENGTH");
+    expect(error.details).toEqual({ expected: 32, actual: 31 });
+  });
+
+  test("expose crypto error codes", () => {
+    const error = catchError(() => passphraseToSecretSeed(Array(24).fill("notaword").join(" ")));
+    expect(error.code).toBe("UNKNOWN_PASSPHRASE_WORD");
+    expect(error.details).toEqual({ word: "notaword" });
+
+    expect(catchError(() => unsealAnonymous(new Uint8Array(10), "sealerSecret_z1")).code).toBe(
+      "INVALID_SEALED_MESSAGE_LENGTH",
+    );
+  });
+});
+
 describe("sign/verify (Ed25519, base58-wrapped)", () => {
   const encoder = new TextEncoder()
   const decoder = new TextDecoder();
@@ -1,4 +1,6 @@
 use wasm_bindgen::prelude::*;
+use crate::CojsonCoreWasmError;
+use cojson_core::crypto::CryptoError;
 use cojson_core::crypto::ed25519;
 
 /// Generate a new Ed25519 signing key using secure random number generation.
@@ -12,8 +14,8 @@ pub fn new_ed25519_signing_key() -> Box<[u8]> {
 /// - `signing_key`: 32 bytes of signing key material
 /// Returns 32 bytes of verifying key material or throws JsError if key is invalid.
 #[wasm_bindgen(js_name = ed25519VerifyingKey)]
-pub fn ed25519_verifying_key(signing_key: &[u8]) -> Result<Box<[u8]>, JsError> {
-    ed25519::ed25519_verifying_key(signing_key).map_err(|e| JsError::new(&e.to_string()))
+pub fn ed25519_verifying_key(signing_key: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
+    Ok(ed25519::ed25519_verifying_key(signing_key)?)
 }
 
 /// WASM-exposed function to sign a message using Ed25519.
@@ -21,7 +23,7 @@ pub fn ed25519_verifying_key(signing_key: &[u8]) -> Result<Box<[u8]>, JsError> {
 /// - `message`: Raw bytes to sign
 /// Returns 64 bytes of signature material or throws JsError if signing fails.
 #[wasm_bindgen(js_name = ed25519Sign)]
-pub fn ed25519_sign(signing_key: &[u8], message: &[u8]) -> Result<Box<[u8]>, JsError> {
+pub fn ed25519_sign(signing_key: &[u8], message: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(ed25519::ed25519_sign(signing_key, message)?.into())
 }
 
@@ -35,7 +37,7 @@ pub fn ed25519_verify(
     verifying_key: &[u8],
     message: &[u8],
     signature: &[u8],
-) -> Result<bool, JsError> {
+) -> Result<bool, CojsonCoreWasmError> {
     Ok(ed25519::ed25519_verify(verifying_key, message, signature)?)
 }
 
@@ -43,10 +45,10 @@ pub fn ed25519_verify(
 /// - `bytes`: 32 bytes of signing key material to validate
 /// Returns the same 32 bytes if valid or throws JsError if invalid.
 #[wasm_bindgen(js_name = ed25519SigningKeyFromBytes)]
-pub fn ed25519_signing_key_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, JsError> {
+pub fn ed25519_signing_key_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     let key_bytes: [u8; 32] = bytes
         .try_into()
-        .map_err(|_| JsError::new("Invalid signing key length"))?;
+        .map_err(|_| CryptoError::InvalidKeyLength(32, bytes.len()))?;
     Ok(key_bytes.into())
 }
 
@@ -54,7 +56,7 @@ pub fn ed25519_signing_key_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, JsError
 /// - `signing_key`: 32 bytes of signing key material
 /// Returns 32 bytes of public key material or throws JsError if key is invalid.
 #[wasm_bindgen(js_name = ed25519SigningKeyToPublic)]
-pub fn ed25519_signing_key_to_public(signing_key: &[u8]) -> Result<Box<[u8]>, JsError> {
+pub fn ed25519_signing_key_to_public(signing_key: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(ed25519::ed25519_verifying_key(signing_key)?)
 }
 
@@ -63,7 +65,7 @@ pub fn ed25519_signing_key_to_public(signing_key: &[u8]) -> Result<Box<[u8]>, Js
 /// - `message`: Raw bytes to sign
 /// Returns 64 bytes of signature material or throws JsError if signing fails.
 #[wasm_bindgen(js_name = ed25519SigningKeySign)]
-pub fn ed25519_signing_key_sign(signing_key: &[u8], message: &[u8]) -> Result<Box<[u8]>, JsError> {
+pub fn ed25519_signing_key_sign(signing_key: &[u8], message: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(ed25519::ed25519_sign(signing_key, message)?.into())
 }
 
@@ -71,10 +73,10 @@ pub fn ed25519_signing_key_sign(signing_key: &[u8], message: &[u8]) -> Result<Bo
 /// - `bytes`: 32 bytes of verifying key material to validate
 /// Returns the same 32 bytes if valid or throws JsError if invalid.
 #[wasm_bindgen(js_name = ed25519VerifyingKeyFromBytes)]
-pub fn ed25519_verifying_key_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, JsError> {
+pub fn ed25519_verifying_key_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     let key_bytes: [u8; 32] = bytes
         .try_into()
-        .map_err(|_| JsError::new("Invalid verifying key length"))?;
+        .map_err(|_| CryptoError::InvalidKeyLength(32, bytes.len()))?;
     Ok(key_bytes.into())
 }
 
@@ -82,9 +84,9 @@ pub fn ed25519_verifying_key_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, JsErr
 /// - `bytes`: 64 bytes of signature material to validate
 /// Returns the same 64 bytes if valid or throws JsError if invalid.
 #[wasm_bindgen(js_name = ed25519SignatureFromBytes)]
-pub fn ed25519_signature_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, JsError> {
+pub fn ed25519_signature_from_bytes(bytes: &[u8]) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     let sig_bytes: [u8; 64] = bytes
         .try_into()
-        .map_err(|_| JsError::new("Invalid signature length"))?;
+        .map_err(|_| CryptoError::InvalidSignatureLength)?;
     Ok(sig_bytes.into())
 }
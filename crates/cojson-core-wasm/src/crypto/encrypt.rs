@@ -1,4 +1,5 @@
 use wasm_bindgen::prelude::*;
+use crate::CojsonCoreWasmError;
 use cojson_core::crypto::encrypt as encrypt_crypto;
 
 /// WASM-exposed function to encrypt bytes with a key secret and nonce material.
@@ -11,7 +12,7 @@ pub fn encrypt(
     value: &[u8],
     key_secret: &str,
     nonce_material: &[u8],
-) -> Result<Box<[u8]>, JsError> {
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(encrypt_crypto::encrypt(value, key_secret, nonce_material)?)
 }
 
@@ -25,6 +26,6 @@ pub fn decrypt(
     ciphertext: &[u8],
     key_secret: &str,
     nonce_material: &[u8],
-) -> Result<Box<[u8]>, JsError> {
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(encrypt_crypto::decrypt(ciphertext, key_secret, nonce_material)?)
 }
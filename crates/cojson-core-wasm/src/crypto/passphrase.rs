@@ -0,0 +1,39 @@
+use wasm_bindgen::prelude::*;
+use crate::CojsonCoreWasmError;
+use cojson_core::crypto::passphrase as passphrase_crypto;
+
+/// WASM-exposed function to encode a secret seed as a BIP39 mnemonic passphrase.
+/// - `secret_seed`: 16-32 bytes of seed material (32 for account secret seeds)
+/// - `wordlist`: Optional list of 2048 words, defaults to the BIP39 English wordlist
+/// Returns the space-separated passphrase or throws JsError if the seed or wordlist is invalid.
+#[wasm_bindgen(js_name = secretSeedToPassphrase)]
+pub fn secret_seed_to_passphrase(
+    secret_seed: &[u8],
+    wordlist: Option<Vec<String>>,
+) -> Result<String, CojsonCoreWasmError> {
+    Ok(match wordlist {
+        Some(wordlist) => passphrase_crypto::secret_seed_to_passphrase(secret_seed, &wordlist)?,
+        None => passphrase_crypto::secret_seed_to_passphrase(
+            secret_seed,
+            passphrase_crypto::english_wordlist(),
+        )?,
+    })
+}
+
+/// WASM-exposed function to decode a BIP39 mnemonic passphrase back into the secret seed.
+/// - `passphrase`: Words separated by whitespace
+/// - `wordlist`: Optional list of 2048 words, defaults to the BIP39 English wordlist
+/// Returns the secret seed or throws JsError on unknown words or a checksum mismatch.
+#[wasm_bindgen(js_name = passphraseToSecretSeed)]
+pub fn passphrase_to_secret_seed(
+    passphrase: &str,
+    wordlist: Option<Vec<String>>,
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
+    Ok(match wordlist {
+        Some(wordlist) => passphrase_crypto::passphrase_to_secret_seed(passphrase, &wordlist)?,
+        None => passphrase_crypto::passphrase_to_secret_seed(
+            passphrase,
+            passphrase_crypto::english_wordlist(),
+        )?,
+    })
+}
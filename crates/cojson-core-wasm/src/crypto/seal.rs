@@ -1,4 +1,5 @@
 use wasm_bindgen::prelude::*;
+use crate::CojsonCoreWasmError;
 use cojson_core::crypto::seal as seal_crypto;
 
 /// WASM-exposed function for sealing a message using X25519 + XSalsa20-Poly1305.
@@ -14,7 +15,7 @@ pub fn seal(
     sender_secret: &str,
     recipient_id: &str,
     nonce_material: &[u8],
-) -> Result<Box<[u8]>, JsError> {
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(seal_crypto::seal(message, sender_secret, recipient_id, nonce_material)?)
 }
 
@@ -31,6 +32,25 @@ pub fn unseal(
     recipient_secret: &str,
     sender_id: &str,
     nonce_material: &[u8],
-) -> Result<Box<[u8]>, JsError> {
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     Ok(seal_crypto::unseal(sealed_message, recipient_secret, sender_id, nonce_material)?)
 }
+
+/// WASM-exposed function for sealing a message without a sender identity.
+/// A fresh ephemeral X25519 key is generated for every message and prepended to the output.
+/// - `message`: Raw bytes to seal
+/// - `recipient_id`: Base58-encoded recipient's public key with "sealer_z" prefix
+/// Returns the ephemeral public key followed by the sealed bytes, or throws JsError if sealing fails.
+#[wasm_bindgen(js_name = sealAnonymous)]
+pub fn seal_anonymous(message: &[u8], recipient_id: &str) -> Result<Box<[u8]>, CojsonCoreWasmError> {
+    Ok(seal_crypto::seal_anonymous(message, recipient_id)?)
+}
+
+/// WASM-exposed function for unsealing a message sealed with `sealAnonymous`.
+/// - `sealed_message`: The ephemeral public key followed by the sealed bytes
+/// - `recipient_secret`: Base58-encoded recipient's private key with "sealerSecret_z" prefix
+/// Returns unsealed bytes or throws JsError if unsealing fails.
+#[wasm_bindgen(js_name = unsealAnonymous)]
+pub fn unseal_anonymous(sealed_message: &[u8], recipient_secret: &str) -> Result<Box<[u8]>, CojsonCoreWasmError> {
+    Ok(seal_crypto::unseal_anonymous(sealed_message, recipient_secret)?)
+}
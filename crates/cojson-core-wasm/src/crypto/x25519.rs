@@ -1,4 +1,5 @@
 use wasm_bindgen::prelude::*;
+use crate::CojsonCoreWasmError;
 use cojson_core::crypto::x25519;
 /// Generate a new X25519 private key using secure random number generation.
 /// Returns 32 bytes of raw key material suitable for use with other X25519 functions.
@@ -12,7 +13,7 @@ pub fn new_x25519_private_key() -> Vec<u8> {
 /// - `private_key`: 32 bytes of private key material
 /// Returns 32 bytes of public key material or throws JsError if key is invalid.
 #[wasm_bindgen(js_name = x25519PublicKey)]
-pub fn x25519_public_key(private_key: &[u8]) -> Result<Vec<u8>, JsError> {
+pub fn x25519_public_key(private_key: &[u8]) -> Result<Vec<u8>, CojsonCoreWasmError> {
     Ok(x25519::x25519_public_key(private_key)?.into())
 }
 
@@ -21,7 +22,7 @@ pub fn x25519_public_key(private_key: &[u8]) -> Result<Vec<u8>, JsError> {
 /// - `public_key`: 32 bytes of public key material
 /// Returns 32 bytes of shared secret material or throws JsError if key exchange fails.
 #[wasm_bindgen(js_name = x25519DiffieHellman)]
-pub fn x25519_diffie_hellman(private_key: &[u8], public_key: &[u8]) -> Result<Vec<u8>, JsError> {
+pub fn x25519_diffie_hellman(private_key: &[u8], public_key: &[u8]) -> Result<Vec<u8>, CojsonCoreWasmError> {
     Ok(x25519::x25519_diffie_hellman(private_key, public_key)?.into())
 }
 
@@ -29,8 +30,8 @@ pub fn x25519_diffie_hellman(private_key: &[u8], public_key: &[u8]) -> Result<Ve
 /// - `secret`: Raw bytes of the sealer secret
 /// Returns a base58-encoded sealer ID with "sealer_z" prefix or throws JsError if derivation fails.
 #[wasm_bindgen(js_name = getSealerId)]
-pub fn get_sealer_id(secret: &[u8]) -> Result<String, JsError> {
+pub fn get_sealer_id(secret: &[u8]) -> Result<String, CojsonCoreWasmError> {
     let secret_str = std::str::from_utf8(secret)
-        .map_err(|e| JsError::new(&format!("Invalid UTF-8 in secret: {:?}", e)))?;
+        .map_err(|e| CojsonCoreWasmError::InvalidUtf8("secret", e))?;
     Ok(x25519::get_sealer_id(secret_str)?)
 }
@@ -1,5 +1,6 @@
 use crate::hash::blake3::generate_nonce;
 use wasm_bindgen::prelude::*;
+use crate::CojsonCoreWasmError;
 use cojson_core::crypto::xsalsa20 as xsalsa20_crypto;
 
 /// WASM-exposed function for XSalsa20 encryption without authentication.
@@ -13,7 +14,7 @@ pub fn encrypt_xsalsa20(
     key: &[u8],
     nonce_material: &[u8],
     plaintext: &[u8],
-) -> Result<Box<[u8]>, JsError> {
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     let nonce = generate_nonce(nonce_material);
     Ok(xsalsa20_crypto::encrypt_xsalsa20_raw(key, &nonce, plaintext)?)
 }
@@ -29,7 +30,7 @@ pub fn decrypt_xsalsa20(
     key: &[u8],
     nonce_material: &[u8],
     ciphertext: &[u8],
-) -> Result<Box<[u8]>, JsError> {
+) -> Result<Box<[u8]>, CojsonCoreWasmError> {
     let nonce = generate_nonce(nonce_material);
     Ok(xsalsa20_crypto::decrypt_xsalsa20_raw(key, &nonce, ciphertext)?)
 }
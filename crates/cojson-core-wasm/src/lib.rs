@@ -1,8 +1,10 @@
 use cojson_core::core::{
-    CoID, CoJsonCoreError, KeyID, KeySecret, SessionID, SessionLogInternal, Signature, SignerID,
-    SignerSecret, Transaction, TransactionMode,
+    CoID, CoJsonCoreError, ErrorCode, KeyID, KeySecret, SessionID, SessionLogInternal, Signature,
+    SignerID, SignerSecret, Transaction, TransactionMode,
 };
+use cojson_core::crypto::CryptoError;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
 use serde_json::value::RawValue;
 use thiserror::Error;
 use wasm_bindgen::prelude::*;
@@ -15,6 +17,7 @@ pub mod hash {
 pub mod crypto {
     pub mod ed25519;
     pub mod encrypt;
+    pub mod passphrase;
     pub mod seal;
     pub mod signature;
     pub mod x25519;
@@ -22,6 +25,7 @@ pub mod crypto {
 
     pub use ed25519::*;
     pub use encrypt::*;
+    pub use passphrase::*;
     pub use seal::*;
     pub use signature::*;
     pub use x25519::*;
@@ -36,13 +40,57 @@ pub enum CojsonCoreWasmError {
     Serde(#[from] serde_json::Error),
     #[error(transparent)]
     SerdeWasmBindgen(#[from] serde_wasm_bindgen::Error),
+    #[error("Invalid UTF-8 in {0}: {1}")]
+    InvalidUtf8(&'static str, std::str::Utf8Error),
     #[error("JsValue Error: {0:?}")]
     Js(JsValue),
 }
 
+impl CojsonCoreWasmError {
+    fn code(&self) -> Option<ErrorCode> {
+        match self {
+            CojsonCoreWasmError::CoJson(err) => Some(err.code()),
+            CojsonCoreWasmError::Serde(_) => Some(ErrorCode::InvalidJson),
+            CojsonCoreWasmError::InvalidUtf8(_, _) => Some(ErrorCode::InvalidUtf8),
+            CojsonCoreWasmError::SerdeWasmBindgen(_) | CojsonCoreWasmError::Js(_) => None,
+        }
+    }
+
+    fn details(&self) -> Map<String, JsonValue> {
+        let details = match self {
+            CojsonCoreWasmError::CoJson(err) => return err.details(),
+            CojsonCoreWasmError::Serde(err) => json!({ "line": err.line(), "column": err.column() }),
+            CojsonCoreWasmError::InvalidUtf8(field, err) => {
+                json!({ "field": field, "validUpTo": err.valid_up_to() })
+            }
+            CojsonCoreWasmError::SerdeWasmBindgen(_) | CojsonCoreWasmError::Js(_) => json!({}),
+        };
+        details.as_object().cloned().unwrap_or_default()
+    }
+}
+
+impl From<CryptoError> for CojsonCoreWasmError {
+    fn from(err: CryptoError) -> Self {
+        CojsonCoreWasmError::CoJson(err.into())
+    }
+}
+
+/// Errors are thrown as JS `Error`s carrying the stable error code in `code`
+/// and structured fields (tx index, expected vs. actual lengths, ...) in `details`.
 impl From<CojsonCoreWasmError> for JsValue {
     fn from(err: CojsonCoreWasmError) -> Self {
-        JsValue::from_str(&err.to_string())
+        let js_error = js_sys::Error::new(&err.to_string());
+
+        if let Some(code) = err.code() {
+            let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+            let details = err.details().serialize(&serializer).unwrap_or(JsValue::UNDEFINED);
+
+            // Setting properties on a freshly created Error object can't fail.
+            let _ = js_sys::Reflect::set(&js_error, &"code".into(), &code.as_str().into());
+            let _ = js_sys::Reflect::set(&js_error, &"details".into(), &details);
+        }
+
+        js_error.into()
     }
 }
 
@@ -87,14 +135,7 @@ impl SessionLog {
     ) -> Result<(), CojsonCoreWasmError> {
         let transactions: Vec<Box<RawValue>> = transactions_json
             .into_iter()
-            .map(|s| {
-                serde_json::from_str(&s).map_err(|e| {
-                    CojsonCoreWasmError::Js(JsValue::from(format!(
-                        "Failed to parse transaction string: {}",
-                        e
-                    )))
-                })
-            })
+            .map(|s| serde_json::from_str(&s).map_err(CoJsonCoreError::Json))
             .collect::<Result<Vec<_>, _>>()?;
 
         let new_signature = Signature(new_signature_str);
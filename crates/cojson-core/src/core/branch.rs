@@ -0,0 +1,717 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+use crate::core::{
+    stable_stringify, stringify_meta, CoID, CoJsonCoreError, CryptoProvider, KeyID, KeySecret,
+    SessionID, SessionLogInternal, Signature, SignerSecret, Transaction, TransactionID,
+    TransactionMode,
+};
+
+/// How many transactions of each session are known, as `KnownStateSessions`.
+pub type KnownStateSessions = BTreeMap<SessionID, u32>;
+
+/// Raise the counts in `target` to the ones in `source`, like `combineKnownStateSessions`.
+pub fn combine_known_state_sessions(target: &mut KnownStateSessions, source: &KnownStateSessions) {
+    for (session_id, &count) in source {
+        let current = target.entry(session_id.clone()).or_default();
+        *current = (*current).max(count);
+    }
+}
+
+/// The header of the branch `branch_name` of `source_id`, like `getBranchHeader`.
+///
+/// Branch headers have no uniqueness, so the same name, owner and source always give the same
+/// branch.
+pub fn branch_header(
+    co_type: &str,
+    branch_name: &str,
+    owner_id: &CoID,
+    source_id: &CoID,
+) -> JsonValue {
+    json!({
+        "type": co_type,
+        "meta": {
+            "branch": branch_name,
+            "source": source_id,
+        },
+        "ruleset": {
+            "type": "ownedByGroup",
+            "group": owner_id,
+        },
+        "uniqueness": "",
+    })
+}
+
+/// The ID of the branch `branch_name` of `source_id`, like `getBranchId`.
+pub fn branch_id(co_type: &str, branch_name: &str, owner_id: &CoID, source_id: &CoID) -> CoID {
+    CoID::for_header(&branch_header(co_type, branch_name, owner_id, source_id))
+}
+
+/// Meta of the first transaction of a branch, with the source's known state it starts from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchStartCommit {
+    pub from: KnownStateSessions,
+}
+
+/// Meta of the transaction that records a branch in its source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchPointerCommit {
+    pub branch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<CoID>,
+}
+
+/// Meta of the transaction that ends a merge, with the branch transactions merged so far.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeCommit {
+    pub merged: KnownStateSessions,
+    pub branch: CoID,
+}
+
+/// Meta of a transaction merged from a branch, pointing back at the original transaction.
+///
+/// Everything but the index is left out when it's the same as for the previous transaction of
+/// the session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergedTransactionMetadata {
+    /// The original transaction's index.
+    pub mi: u32,
+    /// How long before the merge the original transaction was made.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub t: Option<i64>,
+    /// The original transaction's session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s: Option<SessionID>,
+    /// The branch the original transaction was made in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b: Option<CoID>,
+}
+
+impl MergedTransactionMetadata {
+    /// Read a transaction's meta as merge metadata, if it has an `mi`.
+    pub fn from_meta(meta: &JsonValue) -> Option<Self> {
+        meta.get("mi")?;
+        serde_json::from_value(meta.clone()).ok()
+    }
+}
+
+/// Reconstruct where a merged transaction was originally made, like `parseMetaInformation`.
+/// - `previous`: The ID and `madeAt` the previous transaction of the session resolved to
+pub fn resolve_merged_transaction(
+    current_tx: &TransactionID,
+    current_made_at: u64,
+    meta: &MergedTransactionMetadata,
+    previous: Option<(&TransactionID, u64)>,
+) -> (TransactionID, u64) {
+    // A zero `t` falls back to the previous transaction, as it does in TS
+    let made_at = match (meta.t, previous) {
+        (Some(t), _) if t != 0 => {
+            (current_made_at as i128 - t as i128).clamp(0, u64::MAX as i128) as u64
+        }
+        (_, Some((_, previous_made_at))) => previous_made_at,
+        _ => current_made_at,
+    };
+
+    let session_id = meta
+        .s
+        .clone()
+        .or_else(|| previous.map(|(tx, _)| tx.session_id.clone()));
+    let Some(session_id) = session_id else {
+        return (current_tx.clone(), made_at);
+    };
+    let branch = meta
+        .b
+        .clone()
+        .or_else(|| previous.and_then(|(tx, _)| tx.branch.clone()));
+
+    (
+        TransactionID {
+            session_id,
+            tx_index: meta.mi,
+            branch,
+        },
+        made_at,
+    )
+}
+
+/// A transaction of a CoValue, with the ID and `madeAt` of the original transaction when it was
+/// merged from a branch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedTransaction {
+    /// Where the transaction is in the CoValue's session logs.
+    pub current_tx: TransactionID,
+    pub current_made_at: u64,
+    /// The original transaction, like `txID`.
+    pub tx: TransactionID,
+    /// When the original transaction was made, like `madeAt`.
+    pub made_at: u64,
+    pub changes_json: String,
+    pub meta: Option<JsonValue>,
+    pub private: bool,
+}
+
+/// Read a CoValue's transactions, reconstructing the original IDs of merged ones.
+/// - `branch_id`: The CoValue's ID if it's a branch, so IDs carry the branch
+/// - `key_for`: Resolves the secrets of the read keys private transactions were made with
+///
+/// Transactions are in session order, then in the order they were made. Private transactions
+/// whose key can't be resolved are skipped, as are merged transactions claiming to be made after
+/// they were merged.
+pub fn resolve_transactions<'a, C, F>(
+    branch_id: Option<&CoID>,
+    sessions: impl IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)>,
+    mut key_for: F,
+) -> Result<Vec<ResolvedTransaction>, CoJsonCoreError>
+where
+    C: CryptoProvider + 'a,
+    F: FnMut(&KeyID) -> Option<KeySecret>,
+{
+    let mut resolved = Vec::new();
+    for (session_id, log) in sessions {
+        let mut previous: Option<(TransactionID, u64)> = None;
+        for tx_index in 0..log.transactions_json().len() as u32 {
+            let read = read_transaction(
+                branch_id,
+                session_id,
+                log,
+                tx_index,
+                &mut previous,
+                &mut key_for,
+            )?;
+            if let TransactionRead::Resolved(tx) = read {
+                resolved.push(tx);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// What reading one transaction of a session gave.
+pub(crate) enum TransactionRead {
+    Resolved(ResolvedTransaction),
+    /// A private transaction whose key couldn't be resolved.
+    Undecryptable,
+    /// A merged transaction claiming to be made after it was merged.
+    Rejected,
+}
+
+/// Read the transaction at `tx_index` of a session, as `resolve_transactions` does.
+/// - `previous`: The ID and `madeAt` the previous transaction of the session resolved to, which
+///   is updated to this one's
+pub(crate) fn read_transaction<C, F>(
+    branch_id: Option<&CoID>,
+    session_id: &SessionID,
+    log: &SessionLogInternal<C>,
+    tx_index: u32,
+    previous: &mut Option<(TransactionID, u64)>,
+    key_for: &mut F,
+) -> Result<TransactionRead, CoJsonCoreError>
+where
+    C: CryptoProvider,
+    F: FnMut(&KeyID) -> Option<KeySecret>,
+{
+    let current_tx = TransactionID {
+        session_id: session_id.clone(),
+        tx_index,
+        branch: branch_id.cloned(),
+    };
+    let tx_json = log
+        .transactions_json()
+        .get(tx_index as usize)
+        .ok_or(CoJsonCoreError::TransactionNotFound(tx_index))?;
+    let (changes_json, meta_json, made_at, private) = match serde_json::from_str(tx_json)? {
+        Transaction::Private(tx) => {
+            let made_at = tx.made_at.as_u64().unwrap_or_default();
+            let Some(key_secret) = key_for(&tx.key_used) else {
+                *previous = Some((current_tx, made_at));
+                return Ok(TransactionRead::Undecryptable);
+            };
+            let changes =
+                log.decrypt_next_transaction_changes_json(tx_index, key_secret.clone())?;
+            let meta = log.decrypt_next_transaction_meta_json(tx_index, key_secret)?;
+            (changes, meta, made_at, true)
+        }
+        Transaction::Trusting(tx) => (
+            tx.changes,
+            tx.meta,
+            tx.made_at.as_u64().unwrap_or_default(),
+            false,
+        ),
+    };
+    let meta: Option<JsonValue> = meta_json
+        .map(|meta| serde_json::from_str(&meta))
+        .transpose()?;
+
+    let (tx, source_made_at) = match meta.as_ref().and_then(MergedTransactionMetadata::from_meta) {
+        Some(merged) => resolve_merged_transaction(
+            &current_tx,
+            made_at,
+            &merged,
+            previous.as_ref().map(|(tx, made_at)| (tx, *made_at)),
+        ),
+        None => (current_tx.clone(), made_at),
+    };
+    *previous = Some((tx.clone(), source_made_at));
+
+    // Guards against a tampered `t` moving a transaction before an access revocation
+    if source_made_at > made_at {
+        return Ok(TransactionRead::Rejected);
+    }
+    Ok(TransactionRead::Resolved(ResolvedTransaction {
+        current_tx,
+        current_made_at: made_at,
+        tx,
+        made_at: source_made_at,
+        changes_json,
+        meta,
+        private,
+    }))
+}
+
+/// A transaction to make in the source when merging a branch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedTransaction {
+    pub changes_json: String,
+    pub meta: MergedTransactionMetadata,
+    pub private: bool,
+}
+
+/// The transactions merging a branch makes in its source, like `mergeBranch`: the branch's
+/// transactions pointing back at the originals, then the merge commit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergePlan {
+    pub transactions: Vec<MergedTransaction>,
+    pub commit: MergeCommit,
+}
+
+/// Plan merging a branch into its source.
+/// - `branch_transactions`: The branch's own transactions, as `resolve_transactions` reads them
+///   with the sessions in the order the branch got them
+/// - `branch_sessions`: The branch's known state
+/// - `merge_commits`: The merge commits already in the source, for any branch
+/// - `now`: When the merge is made
+///
+/// Transactions are merged in the order `getValidTransactions` returns them for a branch loaded in
+/// one go: trusting ones first, then private ones, which `toProcessTransactions` only gets once
+/// they're decrypted. Transactions merged before and ones without changes are left out. Returns
+/// None if there's nothing left to merge.
+pub fn plan_merge<'a>(
+    branch_id: &CoID,
+    branch_transactions: &[ResolvedTransaction],
+    branch_sessions: &KnownStateSessions,
+    merge_commits: impl IntoIterator<Item = &'a MergeCommit>,
+    now: u64,
+) -> Result<Option<MergePlan>, CoJsonCoreError> {
+    let mut already_merged = KnownStateSessions::new();
+    for commit in merge_commits {
+        if &commit.branch == branch_id {
+            combine_known_state_sessions(&mut already_merged, &commit.merged);
+        }
+    }
+
+    let mut transactions = Vec::new();
+    let mut last_session_id: Option<&SessionID> = None;
+    let mut last_branch: Option<&CoID> = None;
+    let mut last_made_at = 0;
+    let (trusting, private): (Vec<_>, Vec<_>) =
+        branch_transactions.iter().partition(|tx| !tx.private);
+    for tx in trusting.into_iter().chain(private) {
+        let merged_count = already_merged
+            .get(&tx.current_tx.session_id)
+            .copied()
+            .unwrap_or_default();
+        if tx.current_tx.tx_index < merged_count {
+            continue;
+        }
+        let changes: JsonValue = serde_json::from_str(&tx.changes_json)?;
+        if changes.as_array().is_none_or(Vec::is_empty) {
+            continue;
+        }
+
+        let meta = MergedTransactionMetadata {
+            mi: tx.tx.tx_index,
+            t: (tx.made_at != last_made_at).then(|| now as i64 - tx.made_at as i64),
+            s: (last_session_id != Some(&tx.tx.session_id)).then(|| tx.tx.session_id.clone()),
+            b: if last_branch != tx.tx.branch.as_ref() {
+                tx.tx.branch.clone()
+            } else {
+                None
+            },
+        };
+        transactions.push(MergedTransaction {
+            // Re-serialized like `makeTransaction` does with the parsed changes
+            changes_json: stable_stringify(&changes),
+            meta,
+            private: tx.private,
+        });
+        last_session_id = Some(&tx.tx.session_id);
+        last_branch = tx.tx.branch.as_ref();
+        last_made_at = tx.made_at;
+    }
+
+    if transactions.is_empty() {
+        return Ok(None);
+    }
+
+    // Only the sessions that moved since the last merge
+    let merged = branch_sessions
+        .iter()
+        .filter(|(session_id, &count)| {
+            already_merged.get(*session_id).copied().unwrap_or_default() < count
+        })
+        .map(|(session_id, &count)| (session_id.clone(), count))
+        .collect();
+
+    Ok(Some(MergePlan {
+        transactions,
+        commit: MergeCommit {
+            merged,
+            branch: branch_id.clone(),
+        },
+    }))
+}
+
+/// Make a planned merge in our session of the source, private transactions and the merge commit
+/// with the source's current read key.
+///
+/// Returns the signature after the merge commit.
+pub fn write_merge<C: CryptoProvider>(
+    log: &mut SessionLogInternal<C>,
+    signer_secret: &SignerSecret,
+    key_id: &KeyID,
+    key_secret: &KeySecret,
+    plan: &MergePlan,
+    now: u64,
+) -> Result<Signature, CoJsonCoreError> {
+    let private = || TransactionMode::Private {
+        key_id: key_id.clone(),
+        key_secret: key_secret.clone(),
+    };
+    for tx in &plan.transactions {
+        let mode = if tx.private {
+            private()
+        } else {
+            TransactionMode::Trusting
+        };
+        log.add_new_transaction(
+            &tx.changes_json,
+            mode,
+            signer_secret,
+            now,
+            Some(stringify_meta(&tx.meta)?),
+        )?;
+    }
+    let (signature, _) = log.add_new_transaction(
+        "[]",
+        private(),
+        signer_secret,
+        now,
+        Some(stringify_meta(&plan.commit)?),
+    )?;
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::core::new_random_key_secret;
+    use crate::core::test_utils::test_session as session;
+
+    fn add(log: &mut SessionLogInternal, secret: &SignerSecret, changes: &str, made_at: u64) {
+        log.add_new_transaction(changes, TransactionMode::Trusting, secret, made_at, None)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_branch_header_and_id() {
+        let owner = CoID("co_zGroup".to_string());
+        let source = CoID("co_zSource".to_string());
+        let header = branch_header("comap", "draft", &owner, &source);
+        assert_eq!(
+            header.to_string(),
+            r#"{"meta":{"branch":"draft","source":"co_zSource"},"ruleset":{"group":"co_zGroup","type":"ownedByGroup"},"type":"comap","uniqueness":""}"#
+        );
+
+        let id = branch_id("comap", "draft", &owner, &source);
+        assert_eq!(id, CoID::for_header(&header));
+        assert_eq!(branch_id("comap", "draft", &owner, &source), id);
+        assert_ne!(branch_id("comap", "other", &owner, &source), id);
+        assert_ne!(
+            branch_id("comap", "draft", &CoID("co_zOther".to_string()), &source),
+            id
+        );
+
+        let pointer = BranchPointerCommit {
+            branch: "draft".to_string(),
+            owner_id: None,
+        };
+        assert_eq!(stringify_meta(&pointer).unwrap(), r#"{"branch":"draft"}"#);
+    }
+
+    #[test]
+    fn test_plan_merge_compacts_meta() {
+        let branch = branch_id(
+            "colist",
+            "draft",
+            &CoID("co_zGroup".to_string()),
+            &CoID("co_zSource".to_string()),
+        );
+        let (alice, mut alice_log, alice_secret) = session(&branch, 1);
+        let (bob, mut bob_log, bob_secret) = session(&branch, 2);
+        // The branch start has no changes and isn't merged
+        alice_log
+            .add_new_transaction(
+                "[]",
+                TransactionMode::Trusting,
+                &alice_secret,
+                90,
+                Some(r#"{"from":{}}"#.to_string()),
+            )
+            .unwrap();
+        add(&mut alice_log, &alice_secret, r#"["a"]"#, 100);
+        add(&mut alice_log, &alice_secret, r#"["b"]"#, 100);
+        add(&mut bob_log, &bob_secret, r#"["c"]"#, 150);
+
+        let transactions = resolve_transactions(
+            Some(&branch),
+            [(&alice, &alice_log), (&bob, &bob_log)],
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(transactions[1].tx.branch.as_ref(), Some(&branch));
+
+        let sessions = KnownStateSessions::from([(alice.clone(), 3), (bob.clone(), 1)]);
+        let plan = plan_merge(&branch, &transactions, &sessions, [], 200)
+            .unwrap()
+            .unwrap();
+        let metas: Vec<String> = plan
+            .transactions
+            .iter()
+            .map(|tx| stringify_meta(&tx.meta).unwrap())
+            .collect();
+        assert_eq!(
+            metas,
+            [
+                format!(r#"{{"b":"{}","mi":1,"s":"{}","t":100}}"#, branch.0, alice.0),
+                r#"{"mi":2}"#.to_string(),
+                format!(r#"{{"mi":0,"s":"{}","t":50}}"#, bob.0),
+            ]
+        );
+        assert_eq!(plan.commit.merged, sessions);
+
+        // Merged already, until Bob adds more
+        assert!(
+            plan_merge(&branch, &transactions, &sessions, [&plan.commit], 300)
+                .unwrap()
+                .is_none()
+        );
+        add(&mut bob_log, &bob_secret, r#"["d"]"#, 250);
+        let transactions = resolve_transactions(
+            Some(&branch),
+            [(&alice, &alice_log), (&bob, &bob_log)],
+            |_| None,
+        )
+        .unwrap();
+        let sessions = KnownStateSessions::from([(alice.clone(), 3), (bob.clone(), 2)]);
+        let plan = plan_merge(&branch, &transactions, &sessions, [&plan.commit], 300)
+            .unwrap()
+            .unwrap();
+        assert_eq!(plan.transactions.len(), 1);
+        assert_eq!(plan.transactions[0].meta.mi, 1);
+        assert_eq!(
+            plan.commit.merged,
+            KnownStateSessions::from([(bob.clone(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_plan_merge_matches_fixture() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Fixture {
+            #[serde(rename = "type")]
+            co_type: String,
+            branch: String,
+            source: CoID,
+            owner: CoID,
+            now: u64,
+            sessions: Vec<FixtureSession>,
+            expected: Expected,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct FixtureSession {
+            #[serde(rename = "sessionID")]
+            session_id: SessionID,
+            transactions: Vec<FixtureTransaction>,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct FixtureTransaction {
+            privacy: String,
+            made_at: u64,
+            changes: String,
+            meta: Option<String>,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Expected {
+            header: String,
+            id: CoID,
+            transactions: Vec<ExpectedTransaction>,
+            merge_commit: String,
+        }
+        #[derive(Deserialize)]
+        struct ExpectedTransaction {
+            privacy: String,
+            changes: String,
+            meta: String,
+        }
+
+        let data =
+            fs::read_to_string("data/branchMerge.json").expect("Unable to read branchMerge.json");
+        let fixture: Fixture = serde_json::from_str(&data).unwrap();
+
+        let header = branch_header(
+            &fixture.co_type,
+            &fixture.branch,
+            &fixture.owner,
+            &fixture.source,
+        );
+        assert_eq!(stable_stringify(&header), fixture.expected.header);
+        let branch = branch_id(
+            &fixture.co_type,
+            &fixture.branch,
+            &fixture.owner,
+            &fixture.source,
+        );
+        assert_eq!(branch, fixture.expected.id);
+
+        // The sessions in the order the branch got them
+        let mut sessions = KnownStateSessions::new();
+        let mut transactions = Vec::new();
+        for session in &fixture.sessions {
+            sessions.insert(
+                session.session_id.clone(),
+                session.transactions.len() as u32,
+            );
+            for (tx_index, tx) in session.transactions.iter().enumerate() {
+                let tx_id = TransactionID {
+                    session_id: session.session_id.clone(),
+                    tx_index: tx_index as u32,
+                    branch: Some(branch.clone()),
+                };
+                transactions.push(ResolvedTransaction {
+                    current_tx: tx_id.clone(),
+                    current_made_at: tx.made_at,
+                    tx: tx_id,
+                    made_at: tx.made_at,
+                    changes_json: tx.changes.clone(),
+                    meta: tx
+                        .meta
+                        .as_deref()
+                        .map(|meta| serde_json::from_str(meta).unwrap()),
+                    private: tx.privacy == "private",
+                });
+            }
+        }
+
+        let plan = plan_merge(&branch, &transactions, &sessions, [], fixture.now)
+            .unwrap()
+            .unwrap();
+        let merged: Vec<(String, String, String)> = plan
+            .transactions
+            .iter()
+            .map(|tx| {
+                let privacy = if tx.private { "private" } else { "trusting" };
+                (
+                    privacy.to_string(),
+                    tx.changes_json.clone(),
+                    stringify_meta(&tx.meta).unwrap(),
+                )
+            })
+            .collect();
+        let expected: Vec<(String, String, String)> = fixture
+            .expected
+            .transactions
+            .into_iter()
+            .map(|tx| (tx.privacy, tx.changes, tx.meta))
+            .collect();
+        assert_eq!(merged, expected);
+        assert_eq!(
+            stringify_meta(&plan.commit).unwrap(),
+            fixture.expected.merge_commit
+        );
+    }
+
+    #[test]
+    fn test_merge_restores_original_ids() {
+        let source = CoID("co_zSource".to_string());
+        let branch = branch_id("colist", "draft", &CoID("co_zGroup".to_string()), &source);
+        let (alice, mut alice_log, alice_secret) = session(&branch, 1);
+        let (key_id, key_secret) = new_random_key_secret();
+        add(&mut alice_log, &alice_secret, r#"["a"]"#, 100);
+        alice_log
+            .add_new_transaction(
+                r#"["b"]"#,
+                TransactionMode::Private {
+                    key_id: key_id.clone(),
+                    key_secret: key_secret.clone(),
+                },
+                &alice_secret,
+                120,
+                None,
+            )
+            .unwrap();
+        let key_for = |id: &KeyID| (id == &key_id).then(|| key_secret.clone());
+        let branch_transactions =
+            resolve_transactions(Some(&branch), [(&alice, &alice_log)], key_for).unwrap();
+        let sessions = KnownStateSessions::from([(alice.clone(), 2)]);
+        let plan = plan_merge(&branch, &branch_transactions, &sessions, [], 200)
+            .unwrap()
+            .unwrap();
+
+        let (bob, mut bob_log, bob_secret) = session(&source, 2);
+        write_merge(&mut bob_log, &bob_secret, &key_id, &key_secret, &plan, 200).unwrap();
+        assert_eq!(bob_log.transactions_json().len(), 3);
+
+        let merged = resolve_transactions(None, [(&bob, &bob_log)], key_for).unwrap();
+        for (merged, original) in merged.iter().zip(&branch_transactions) {
+            assert_eq!(merged.tx, original.tx);
+            assert_eq!(merged.made_at, original.made_at);
+            assert_eq!(merged.current_made_at, 200);
+            assert_eq!(merged.changes_json, original.changes_json);
+            assert_eq!(merged.private, original.private);
+        }
+        let commit: MergeCommit = serde_json::from_value(merged[2].meta.clone().unwrap()).unwrap();
+        assert_eq!(commit, plan.commit);
+        assert_eq!(merged[2].tx, merged[2].current_tx);
+    }
+
+    #[test]
+    fn test_reject_merged_transaction_from_the_future() {
+        let source = CoID("co_zSource".to_string());
+        let (session_id, mut log, secret) = session(&source, 1);
+        for t in [-10, 0, 50] {
+            log.add_new_transaction(
+                r#"["x"]"#,
+                TransactionMode::Trusting,
+                &secret,
+                100,
+                Some(format!(r#"{{"mi":0,"s":"{}","t":{t}}}"#, session_id.0)),
+            )
+            .unwrap();
+        }
+
+        // A zero `t` falls back to the previous madeAt, so the second is skipped as well
+        let resolved = resolve_transactions(None, [(&session_id, &log)], |_| None).unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].current_tx.tx_index, 2);
+        assert_eq!(resolved[0].made_at, 50);
+    }
+}
@@ -1,14 +1,15 @@
-use ed25519_dalek::SigningKey;
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use lru::LruCache;
 use salsa20::{cipher::Key, XSalsa20};
 use std::{cell::RefCell, num::NonZero};
 
-use crate::core::{CoJsonCoreError, KeySecret, SignerSecret};
+use crate::core::{CoJsonCoreError, KeySecret, SignerID, SignerSecret};
 
 #[derive(Debug, Clone)]
 pub struct CryptoCache {
     xsalsa20_key_cache: RefCell<LruCache<KeySecret, Key<XSalsa20>>>,
     ed25519_signing_key_cache: RefCell<LruCache<SignerSecret, SigningKey>>,
+    ed25519_verifying_key_cache: RefCell<LruCache<SignerID, VerifyingKey>>,
 }
 
 impl CryptoCache {
@@ -16,6 +17,7 @@ impl CryptoCache {
         Self {
             xsalsa20_key_cache: RefCell::new(LruCache::new(NonZero::new(2).unwrap())),
             ed25519_signing_key_cache: RefCell::new(LruCache::new(NonZero::new(2).unwrap())),
+            ed25519_verifying_key_cache: RefCell::new(LruCache::new(NonZero::new(2).unwrap())),
         }
     }
 
@@ -45,6 +47,19 @@ impl CryptoCache {
         cache.put(signer_secret.to_owned(), signing_key.clone());
         Ok(signing_key)
     }
+
+    /// Get or derive the Ed25519 VerifyingKey from a SignerID, using the cache.
+    /// This avoid to run bs58 decoding multiple times for the same key.
+    pub fn get_ed25519_verifying_key(&self, signer_id: &SignerID) -> Result<VerifyingKey, CoJsonCoreError> {
+        let mut cache = self.ed25519_verifying_key_cache.borrow_mut();
+        if let Some(verifying_key) = cache.get(signer_id) {
+            return Ok(*verifying_key);
+        }
+
+        let verifying_key: VerifyingKey = signer_id.try_into()?;
+        cache.put(signer_id.to_owned(), verifying_key);
+        Ok(verifying_key)
+    }
 }
 
 
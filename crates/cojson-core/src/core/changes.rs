@@ -0,0 +1,477 @@
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::core::{
+    stringify_changes, CoJsonCoreError, CoListEntry, ListAnchor, ListEdge, ListOpPayload,
+    MapOpPayload, OpID, TransactionID,
+};
+
+/// The largest integer JS can represent exactly, `Number.MAX_SAFE_INTEGER`.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Check that every value in `changes` reads back the same in JS, then serialize them for
+/// `add_new_transaction`.
+fn build_changes<T: Serialize>(changes: &[T]) -> Result<String, CoJsonCoreError> {
+    let value = serde_json::to_value(changes)?;
+    validate_value(&value, &mut "$".to_string())?;
+    stringify_changes(changes)
+}
+
+fn validate_value(value: &JsonValue, path: &mut String) -> Result<(), CoJsonCoreError> {
+    match value {
+        JsonValue::Number(number) => {
+            let unsafe_integer = match (number.as_u64(), number.as_i64()) {
+                (Some(n), _) => n > MAX_SAFE_INTEGER,
+                (None, Some(n)) => n.unsigned_abs() > MAX_SAFE_INTEGER,
+                (None, None) => false,
+            };
+            if unsafe_integer {
+                return Err(CoJsonCoreError::InvalidChangeValue {
+                    path: path.clone(),
+                    reason: format!("{number} is outside of the safe integer range"),
+                });
+            }
+        }
+        JsonValue::Array(items) => {
+            for (idx, item) in items.iter().enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{idx}]"));
+                validate_value(item, path)?;
+                path.truncate(len);
+            }
+        }
+        JsonValue::Object(object) => {
+            for (key, item) in object {
+                let len = path.len();
+                path.push_str(&format!(".{key}"));
+                validate_value(item, path)?;
+                path.truncate(len);
+            }
+        }
+        JsonValue::Null | JsonValue::Bool(_) | JsonValue::String(_) => {}
+    }
+    Ok(())
+}
+
+/// Builds the changes of a CoMap transaction, like `set`, `assign` and `delete`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapChanges {
+    ops: Vec<MapOpPayload>,
+}
+
+impl MapChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<JsonValue>) -> Self {
+        self.ops.push(MapOpPayload::Set {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn delete(mut self, key: impl Into<String>) -> Self {
+        self.ops.push(MapOpPayload::Del { key: key.into() });
+        self
+    }
+
+    pub fn ops(&self) -> &[MapOpPayload] {
+        &self.ops
+    }
+
+    /// Validate the values and serialize the changes for `add_new_transaction`.
+    pub fn build(&self) -> Result<String, CoJsonCoreError> {
+        build_changes(&self.ops)
+    }
+}
+
+/// Builds the changes of a CoList transaction, like `appendItems`, `prepend`, `delete` and
+/// `replace`.
+///
+/// Indices refer to the items the builder was made from, as `CoValueHistory::list_at` reads
+/// them, however many changes are added.
+#[derive(Debug, Clone)]
+pub struct ListChanges<'a> {
+    entries: &'a [CoListEntry],
+    tx: TransactionID,
+    ops: Vec<ListOpPayload>,
+}
+
+impl<'a> ListChanges<'a> {
+    /// - `entries`: The current items
+    /// - `tx`: The ID the transaction will get, like `next_transaction_id` gives, so changes
+    ///   can refer to items inserted earlier in the same transaction
+    pub fn new(entries: &'a [CoListEntry], tx: TransactionID) -> Self {
+        Self {
+            entries,
+            tx,
+            ops: Vec::new(),
+        }
+    }
+
+    fn entry(&self, idx: usize) -> Result<&'a CoListEntry, CoJsonCoreError> {
+        self.entries
+            .get(idx)
+            .ok_or(CoJsonCoreError::InvalidListIndex(idx))
+    }
+
+    fn push(&mut self, op: ListOpPayload) -> OpID {
+        let op_id = OpID::new(&self.tx, self.ops.len() as u32);
+        self.ops.push(op);
+        op_id
+    }
+
+    /// Insert `items` after the item at `after`, or at the end if None.
+    pub fn append(
+        &mut self,
+        items: impl IntoIterator<Item = JsonValue>,
+        after: Option<usize>,
+    ) -> Result<&mut Self, CoJsonCoreError> {
+        let anchor = match (self.entries.is_empty(), after) {
+            (true, None | Some(0)) => ListAnchor::Edge(ListEdge::Start),
+            (true, Some(after)) => return Err(CoJsonCoreError::InvalidListIndex(after)),
+            (false, after) => {
+                let after = after.unwrap_or(self.entries.len() - 1);
+                ListAnchor::Op(self.entry(after)?.op_id.clone())
+            }
+        };
+        self.append_after(items, anchor);
+        Ok(self)
+    }
+
+    fn append_after(&mut self, items: impl IntoIterator<Item = JsonValue>, anchor: ListAnchor) {
+        let mut items: Vec<JsonValue> = items.into_iter().collect();
+        // The newest insertion after an item comes right after it, so insert in reverse
+        if anchor != ListAnchor::Edge(ListEdge::Start) {
+            items.reverse();
+        }
+        for value in items {
+            self.push(ListOpPayload::App {
+                value,
+                after: anchor.clone(),
+            });
+        }
+    }
+
+    /// Insert `item` before the item at `before`. `before` may be the length of the list to
+    /// insert at the end.
+    pub fn prepend(
+        &mut self,
+        item: JsonValue,
+        before: usize,
+    ) -> Result<&mut Self, CoJsonCoreError> {
+        self.prepend_op(item, before)?;
+        Ok(self)
+    }
+
+    fn prepend_op(&mut self, value: JsonValue, before: usize) -> Result<OpID, CoJsonCoreError> {
+        let before = match self.entries.get(before) {
+            Some(entry) => ListAnchor::Op(entry.op_id.clone()),
+            None if before == self.entries.len() => ListAnchor::Edge(ListEdge::End),
+            None => return Err(CoJsonCoreError::InvalidListIndex(before)),
+        };
+        Ok(self.push(ListOpPayload::Pre { value, before }))
+    }
+
+    pub fn delete(&mut self, at: usize) -> Result<&mut Self, CoJsonCoreError> {
+        let insertion = self.entry(at)?.op_id.clone();
+        self.push(ListOpPayload::Del { insertion });
+        Ok(self)
+    }
+
+    /// Replace the item at `at`, inserting the new item after it before deleting it.
+    pub fn replace(&mut self, at: usize, item: JsonValue) -> Result<&mut Self, CoJsonCoreError> {
+        let insertion = self.entry(at)?.op_id.clone();
+        self.push(ListOpPayload::App {
+            value: item,
+            after: ListAnchor::Op(insertion.clone()),
+        });
+        self.push(ListOpPayload::Del { insertion });
+        Ok(self)
+    }
+
+    pub fn ops(&self) -> &[ListOpPayload] {
+        &self.ops
+    }
+
+    /// Validate the values and serialize the changes for `add_new_transaction`.
+    pub fn build(&self) -> Result<String, CoJsonCoreError> {
+        build_changes(&self.ops)
+    }
+}
+
+/// Builds the changes of a CoPlainText transaction, like `insertBefore`, `insertAfter` and
+/// `deleteRange`. Text is split into grapheme clusters, one item each.
+///
+/// Unlike on the TS side, long insertions aren't split over several transactions.
+#[derive(Debug, Clone)]
+pub struct PlainTextChanges<'a> {
+    list: ListChanges<'a>,
+}
+
+impl<'a> PlainTextChanges<'a> {
+    /// See `ListChanges::new`.
+    pub fn new(entries: &'a [CoListEntry], tx: TransactionID) -> Self {
+        Self {
+            list: ListChanges::new(entries, tx),
+        }
+    }
+
+    fn graphemes(text: &str) -> Vec<JsonValue> {
+        text.graphemes(true)
+            .map(|grapheme| JsonValue::String(grapheme.to_string()))
+            .collect()
+    }
+
+    /// Insert `text` so it starts at grapheme `idx`.
+    pub fn insert_before(&mut self, idx: usize, text: &str) -> Result<&mut Self, CoJsonCoreError> {
+        let mut graphemes = Self::graphemes(text);
+        if idx == 0 {
+            // The first grapheme goes before the start, the rest after it
+            if graphemes.is_empty() {
+                return Ok(self);
+            }
+            let first = self.list.prepend_op(graphemes.remove(0), 0)?;
+            self.list.append_after(graphemes, ListAnchor::Op(first));
+        } else {
+            self.list.append(graphemes, Some(idx - 1))?;
+        }
+        Ok(self)
+    }
+
+    /// Insert `text` after grapheme `idx`, or at the end if `idx` is the length of the text.
+    pub fn insert_after(&mut self, idx: usize, text: &str) -> Result<&mut Self, CoJsonCoreError> {
+        let len = self.list.entries.len();
+        let after = if idx >= len {
+            idx.saturating_sub(1)
+        } else {
+            idx
+        };
+        self.list.append(Self::graphemes(text), Some(after))?;
+        Ok(self)
+    }
+
+    /// Delete the graphemes from `from` up to, but not including, `to`.
+    pub fn delete_range(&mut self, from: usize, to: usize) -> Result<&mut Self, CoJsonCoreError> {
+        for idx in from..to {
+            self.list.delete(idx)?;
+        }
+        Ok(self)
+    }
+
+    pub fn ops(&self) -> &[ListOpPayload] {
+        self.list.ops()
+    }
+
+    /// Serialize the changes for `add_new_transaction`.
+    pub fn build(&self) -> Result<String, CoJsonCoreError> {
+        self.list.build()
+    }
+}
+
+/// Builds the changes of a CoStream transaction, like `push`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamChanges {
+    items: Vec<JsonValue>,
+}
+
+impl StreamChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(mut self, item: impl Into<JsonValue>) -> Self {
+        self.items.push(item.into());
+        self
+    }
+
+    /// Validate the items and serialize the changes for `add_new_transaction`.
+    pub fn build(&self) -> Result<String, CoJsonCoreError> {
+        build_changes(&self.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        test_utils::test_session, CoID, CoValueHistory, HistoryFilter, SessionID,
+        SessionLogInternal, SignerSecret, TransactionMode,
+    };
+    use serde_json::json;
+
+    struct Writer {
+        session_id: SessionID,
+        log: SessionLogInternal,
+        secret: SignerSecret,
+        made_at: u64,
+    }
+
+    impl Writer {
+        fn new() -> Self {
+            let (session_id, log, secret) = test_session(&CoID("co_zList".to_string()), 1);
+            Self {
+                session_id,
+                log,
+                secret,
+                made_at: 0,
+            }
+        }
+
+        fn history(&self) -> CoValueHistory {
+            CoValueHistory::from_session_logs(None, [(&self.session_id, &self.log)], |_| None)
+                .unwrap()
+        }
+
+        fn entries(&self) -> Vec<CoListEntry> {
+            self.history().list_at(&HistoryFilter::All).unwrap()
+        }
+
+        fn text(&self) -> String {
+            self.history().plain_text_at(&HistoryFilter::All).unwrap()
+        }
+
+        fn write(&mut self, changes_json: &str) {
+            self.made_at += 1;
+            self.log
+                .add_new_transaction(
+                    changes_json,
+                    TransactionMode::Trusting,
+                    &self.secret,
+                    self.made_at,
+                    None,
+                )
+                .unwrap();
+        }
+
+        fn edit_list(&mut self, edit: impl FnOnce(&mut ListChanges)) {
+            let entries = self.entries();
+            let mut changes = ListChanges::new(&entries, self.log.next_transaction_id());
+            edit(&mut changes);
+            let changes_json = changes.build().unwrap();
+            self.write(&changes_json);
+        }
+
+        fn edit_text(&mut self, edit: impl FnOnce(&mut PlainTextChanges)) {
+            let entries = self.entries();
+            let mut changes = PlainTextChanges::new(&entries, self.log.next_transaction_id());
+            edit(&mut changes);
+            let changes_json = changes.build().unwrap();
+            self.write(&changes_json);
+        }
+    }
+
+    #[test]
+    fn test_map_changes() {
+        let changes = MapChanges::new()
+            .set("title", "draft")
+            .set("tags", json!(["a", {"n": 1}]))
+            .delete("old");
+        assert_eq!(
+            changes.build().unwrap(),
+            r#"[{"key":"title","op":"set","value":"draft"},{"key":"tags","op":"set","value":["a",{"n":1}]},{"key":"old","op":"del"}]"#
+        );
+
+        let err = MapChanges::new()
+            .set("tags", json!(["a", {"n": u64::MAX}]))
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CoJsonCoreError::InvalidChangeValue { ref path, .. } if path == "$[0].value[1].n"
+        ));
+        assert!(StreamChanges::new().push(-(1i64 << 53)).build().is_err());
+        assert_eq!(
+            StreamChanges::new().push(1.5).push("x").build().unwrap(),
+            r#"[1.5,"x"]"#
+        );
+    }
+
+    #[test]
+    fn test_list_changes() {
+        let mut writer = Writer::new();
+        writer.edit_list(|list| {
+            list.append([json!("a"), json!("b")], None).unwrap();
+        });
+        writer.edit_list(|list| {
+            list.append([json!("x"), json!("y")], Some(0)).unwrap();
+            list.prepend(json!("start"), 0).unwrap();
+            list.prepend(json!("end"), 2).unwrap();
+        });
+        let values = |writer: &Writer| -> Vec<JsonValue> {
+            writer
+                .entries()
+                .into_iter()
+                .map(|entry| entry.value)
+                .collect()
+        };
+        assert_eq!(
+            values(&writer),
+            [
+                json!("start"),
+                json!("a"),
+                json!("x"),
+                json!("y"),
+                json!("b"),
+                json!("end")
+            ]
+        );
+
+        writer.edit_list(|list| {
+            list.replace(1, json!("A")).unwrap();
+            list.delete(5).unwrap();
+        });
+        assert_eq!(
+            values(&writer),
+            [
+                json!("start"),
+                json!("A"),
+                json!("x"),
+                json!("y"),
+                json!("b")
+            ]
+        );
+
+        let entries = writer.entries();
+        let mut list = ListChanges::new(&entries, writer.log.next_transaction_id());
+        assert!(matches!(
+            list.delete(5),
+            Err(CoJsonCoreError::InvalidListIndex(5))
+        ));
+        assert!(matches!(
+            list.prepend(json!(1), 6),
+            Err(CoJsonCoreError::InvalidListIndex(6))
+        ));
+        assert!(matches!(
+            ListChanges::new(&[], writer.log.next_transaction_id()).append([json!(1)], Some(1)),
+            Err(CoJsonCoreError::InvalidListIndex(1))
+        ));
+    }
+
+    #[test]
+    fn test_plain_text_changes() {
+        let mut writer = Writer::new();
+        writer.edit_text(|text| {
+            text.insert_before(0, "hello").unwrap();
+        });
+        assert_eq!(writer.text(), "hello");
+
+        writer.edit_text(|text| {
+            text.insert_before(0, "👍🏽 ").unwrap();
+            text.insert_after(4, "!").unwrap();
+        });
+        assert_eq!(writer.text(), "👍🏽 hello!");
+        // The emoji with its skin tone is a single item
+        assert_eq!(writer.entries().len(), 8);
+
+        writer.edit_text(|text| {
+            text.delete_range(0, 2).unwrap();
+            text.insert_after(8, "?").unwrap();
+            assert!(text.insert_after(9, "?").is_err());
+        });
+        assert_eq!(writer.text(), "hello!?");
+    }
+}
@@ -0,0 +1,24 @@
+/// The session size after which a signature is kept as a `signatureAfter` checkpoint, so content
+/// can be sent in pieces of about this size, as `MAX_RECOMMENDED_TX_SIZE` in `config.ts`.
+pub const MAX_RECOMMENDED_TX_SIZE: usize = 100 * 1024;
+
+/// The largest transaction that can be made, as `MAX_TX_SIZE_BYTES` in `config.ts`.
+pub const MAX_TX_SIZE_BYTES: usize = 1024 * 1024;
+
+/// Size limits of a session log, defaulting to the ones in `config.ts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionLimits {
+    /// The largest serialized changes a new transaction may have, in bytes.
+    pub max_tx_size: usize,
+    /// How many bytes of transactions go between signature checkpoints.
+    pub recommended_chunk_size: usize,
+}
+
+impl Default for TransactionLimits {
+    fn default() -> Self {
+        Self {
+            max_tx_size: MAX_TX_SIZE_BYTES,
+            recommended_chunk_size: MAX_RECOMMENDED_TX_SIZE,
+        }
+    }
+}
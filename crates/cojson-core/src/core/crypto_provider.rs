@@ -0,0 +1,276 @@
+use ed25519_dalek::{Signature as Ed25519Signature, Signer, Verifier};
+use rand::{rngs::OsRng, RngCore};
+use salsa20::{
+    cipher::{KeyIvInit, StreamCipher},
+    XSalsa20,
+};
+
+use crate::core::{
+    CoJsonCoreError, CryptoCache, KeySecret, SealerID, SealerSecret, Signature, SignerID,
+    SignerSecret,
+};
+
+/// An incremental hasher, used to maintain the running hash of a session log.
+pub trait IncrementalHasher: Clone {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(&self) -> [u8; 32];
+}
+
+impl IncrementalHasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finalize(&self) -> [u8; 32] {
+        *blake3::Hasher::finalize(self).as_bytes()
+    }
+}
+
+/// The cryptographic primitives used by the core, mirroring `CryptoProvider` on the TS side.
+///
+/// Implementations must be byte-compatible with `DefaultCryptoProvider` to interoperate with
+/// other peers, but can change where and how the work happens (e.g. an HSM-backed signer, or a
+/// deterministic provider for tests).
+pub trait CryptoProvider: Clone {
+    type Hasher: IncrementalHasher;
+
+    /// Create a new incremental hasher with an empty state.
+    fn new_hasher(&self) -> Self::Hasher;
+
+    /// Hash data once.
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        let mut hasher = self.new_hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Derive a 24-byte nonce from nonce material.
+    /// This must be deterministic - the same material has to produce the same nonce.
+    fn generate_nonce(&self, nonce_material: &[u8]) -> [u8; 24] {
+        let mut nonce = [0u8; 24];
+        nonce.copy_from_slice(&self.hash(nonce_material)[..24]);
+        nonce
+    }
+
+    /// Fill the buffer with random bytes.
+    fn random_bytes(&self, buf: &mut [u8]);
+
+    /// Sign a message, returning a "signature_z" encoded signature.
+    fn sign(&self, message: &[u8], signer_secret: &SignerSecret)
+        -> Result<Signature, CoJsonCoreError>;
+
+    /// Verify a signature against a signer ID.
+    /// Returns false if the signature doesn't match, or an error if the inputs are malformed.
+    fn verify(
+        &self,
+        message: &[u8],
+        signature: &Signature,
+        signer_id: &SignerID,
+    ) -> Result<bool, CoJsonCoreError>;
+
+    /// Encrypt bytes with XSalsa20 (without authentication), as used for transaction contents.
+    fn encrypt(
+        &self,
+        plaintext: &[u8],
+        key_secret: &KeySecret,
+        nonce: &[u8; 24],
+    ) -> Result<Vec<u8>, CoJsonCoreError>;
+
+    /// Decrypt bytes encrypted with `encrypt`.
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        key_secret: &KeySecret,
+        nonce: &[u8; 24],
+    ) -> Result<Vec<u8>, CoJsonCoreError>;
+
+    /// Seal a message using X25519 + XSalsa20-Poly1305.
+    fn seal(
+        &self,
+        message: &[u8],
+        from: &SealerSecret,
+        to: &SealerID,
+        nonce_material: &[u8],
+    ) -> Result<Vec<u8>, CoJsonCoreError>;
+
+    /// Unseal a message sealed with `seal`.
+    fn unseal(
+        &self,
+        sealed_message: &[u8],
+        sealer: &SealerSecret,
+        from: &SealerID,
+        nonce_material: &[u8],
+    ) -> Result<Vec<u8>, CoJsonCoreError>;
+}
+
+/// The default provider: ed25519-dalek, salsa20, x25519-dalek and blake3,
+/// with decoded keys kept in a small LRU cache.
+#[derive(Debug, Clone)]
+pub struct DefaultCryptoProvider {
+    cache: CryptoCache,
+}
+
+impl DefaultCryptoProvider {
+    pub fn new() -> Self {
+        Self {
+            cache: CryptoCache::new(),
+        }
+    }
+
+    fn apply_xsalsa20(
+        &self,
+        data: &[u8],
+        key_secret: &KeySecret,
+        nonce: &[u8; 24],
+    ) -> Result<Vec<u8>, CoJsonCoreError> {
+        let key = self.cache.get_xsalsa20_key(key_secret)?;
+        let mut buffer = data.to_vec();
+        let mut cipher = XSalsa20::new(&key, nonce.into());
+        cipher.apply_keystream(&mut buffer);
+        Ok(buffer)
+    }
+}
+
+impl Default for DefaultCryptoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CryptoProvider for DefaultCryptoProvider {
+    type Hasher = blake3::Hasher;
+
+    fn new_hasher(&self) -> Self::Hasher {
+        blake3::Hasher::new()
+    }
+
+    fn random_bytes(&self, buf: &mut [u8]) {
+        OsRng.fill_bytes(buf);
+    }
+
+    fn sign(
+        &self,
+        message: &[u8],
+        signer_secret: &SignerSecret,
+    ) -> Result<Signature, CoJsonCoreError> {
+        let signing_key = self.cache.get_ed25519_signing_key(signer_secret)?;
+        Ok(signing_key.sign(message).into())
+    }
+
+    fn verify(
+        &self,
+        message: &[u8],
+        signature: &Signature,
+        signer_id: &SignerID,
+    ) -> Result<bool, CoJsonCoreError> {
+        let verifying_key = self.cache.get_ed25519_verifying_key(signer_id)?;
+        let signature: Ed25519Signature = signature.try_into()?;
+        Ok(verifying_key.verify(message, &signature).is_ok())
+    }
+
+    fn encrypt(
+        &self,
+        plaintext: &[u8],
+        key_secret: &KeySecret,
+        nonce: &[u8; 24],
+    ) -> Result<Vec<u8>, CoJsonCoreError> {
+        self.apply_xsalsa20(plaintext, key_secret, nonce)
+    }
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        key_secret: &KeySecret,
+        nonce: &[u8; 24],
+    ) -> Result<Vec<u8>, CoJsonCoreError> {
+        // XSalsa20 is symmetric
+        self.apply_xsalsa20(ciphertext, key_secret, nonce)
+    }
+
+    fn seal(
+        &self,
+        message: &[u8],
+        from: &SealerSecret,
+        to: &SealerID,
+        nonce_material: &[u8],
+    ) -> Result<Vec<u8>, CoJsonCoreError> {
+        Ok(crate::crypto::seal(message, &from.0, &to.0, nonce_material)?.into_vec())
+    }
+
+    fn unseal(
+        &self,
+        sealed_message: &[u8],
+        sealer: &SealerSecret,
+        from: &SealerID,
+        nonce_material: &[u8],
+    ) -> Result<Vec<u8>, CoJsonCoreError> {
+        Ok(crate::crypto::unseal(sealed_message, &sealer.0, &from.0, nonce_material)?.into_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{get_sealer_id, new_x25519_private_key};
+    use crate::hash::blake3::{blake3_hash_once, generate_nonce};
+    use ed25519_dalek::SigningKey;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_default_provider_matches_free_functions() {
+        let crypto = DefaultCryptoProvider::new();
+
+        assert_eq!(&crypto.hash(b"test input")[..], &*blake3_hash_once(b"test input"));
+        assert_eq!(&crypto.generate_nonce(b"test input")[..], &*generate_nonce(b"test input"));
+
+        let mut hasher = crypto.new_hasher();
+        IncrementalHasher::update(&mut hasher, b"test ");
+        IncrementalHasher::update(&mut hasher, b"input");
+        assert_eq!(IncrementalHasher::finalize(&hasher), crypto.hash(b"test input"));
+    }
+
+    #[test]
+    fn test_default_provider_sign_verify() {
+        let crypto = DefaultCryptoProvider::new();
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let signer_id: SignerID = signing_key.verifying_key().into();
+        let signer_secret: SignerSecret = signing_key.into();
+
+        let signature = crypto.sign(b"hello", &signer_secret).unwrap();
+        assert!(crypto.verify(b"hello", &signature, &signer_id).unwrap());
+        assert!(!crypto.verify(b"goodbye", &signature, &signer_id).unwrap());
+
+        let invalid_signer = SignerID("signer_zinvalid!!".to_string());
+        assert!(crypto.verify(b"hello", &signature, &invalid_signer).is_err());
+    }
+
+    #[test]
+    fn test_default_provider_encrypt_decrypt() {
+        let crypto = DefaultCryptoProvider::new();
+        let key_secret = KeySecret(format!("keySecret_z{}", bs58::encode([7u8; 32]).into_string()));
+        let nonce = crypto.generate_nonce(b"nonce material");
+
+        let ciphertext = crypto.encrypt(b"plaintext", &key_secret, &nonce).unwrap();
+        assert_ne!(ciphertext, b"plaintext");
+        assert_eq!(crypto.decrypt(&ciphertext, &key_secret, &nonce).unwrap(), b"plaintext");
+    }
+
+    #[test]
+    fn test_default_provider_seal_unseal() {
+        let crypto = DefaultCryptoProvider::new();
+        let sender = SealerSecret(format!(
+            "sealerSecret_z{}",
+            bs58::encode(new_x25519_private_key()).into_string()
+        ));
+        let recipient = SealerSecret(format!(
+            "sealerSecret_z{}",
+            bs58::encode(new_x25519_private_key()).into_string()
+        ));
+        let sender_id = SealerID(get_sealer_id(&sender.0).unwrap());
+        let recipient_id = SealerID(get_sealer_id(&recipient.0).unwrap());
+
+        let sealed = crypto.seal(b"secret", &sender, &recipient_id, b"nonce").unwrap();
+        let unsealed = crypto.unseal(&sealed, &recipient, &sender_id, b"nonce").unwrap();
+        assert_eq!(unsealed, b"secret");
+    }
+}
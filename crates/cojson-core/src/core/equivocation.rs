@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    CoID, CoJsonCoreError, CryptoProvider, IncrementalHasher, SessionID, Signature, SignerID,
+};
+
+/// A session hash together with the signature its signer made over it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedSessionHash {
+    /// How many transactions of the session the hash covers.
+    pub tx_count: u32,
+    /// `hash_z...`, as signed.
+    pub hash: String,
+    pub signature: Signature,
+}
+
+/// Proof that a signer forked one of its sessions: two validly signed hashes over the same
+/// number of transactions, which disagree from `fork_index` on.
+///
+/// Session hashes are chained over every transaction, so the shared prefix is included to
+/// replay both sides from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EquivocationEvidence {
+    #[serde(rename = "coID")]
+    pub co_id: CoID,
+    #[serde(rename = "sessionID")]
+    pub session_id: SessionID,
+    #[serde(rename = "signerID")]
+    pub signer_id: SignerID,
+    /// The index of the first transaction the two sides disagree on.
+    pub fork_index: u32,
+    /// The hash over the transactions before `fork_index`, which both sides share.
+    pub prefix_hash: String,
+    pub prefix_transactions: Vec<String>,
+    /// What we had accepted.
+    pub accepted: SignedSessionHash,
+    pub accepted_transactions: Vec<String>,
+    /// What the peer presented.
+    pub conflicting: SignedSessionHash,
+    pub conflicting_transactions: Vec<String>,
+}
+
+impl EquivocationEvidence {
+    /// Check that replaying the shared prefix and then each side gives the signed hashes, that
+    /// both cover the same number of transactions with different hashes, and that both are
+    /// signed by `signer_id`.
+    pub fn verify<C: CryptoProvider>(&self, crypto: &C) -> Result<bool, CoJsonCoreError> {
+        let fork_index = self.fork_index as usize;
+        let tx_count = self.accepted.tx_count as usize;
+        let well_formed = self.prefix_transactions.len() == fork_index
+            && self.conflicting.tx_count == self.accepted.tx_count
+            && fork_index + self.accepted_transactions.len() == tx_count
+            && fork_index + self.conflicting_transactions.len() == tx_count
+            && self.accepted.hash != self.conflicting.hash
+            && self.accepted_transactions.first() != self.conflicting_transactions.first();
+        if !well_formed || self.accepted_transactions.is_empty() {
+            return Ok(false);
+        }
+
+        let mut prefix = crypto.new_hasher();
+        for tx in &self.prefix_transactions {
+            prefix.update(tx.as_bytes());
+        }
+        if session_hash(&prefix) != self.prefix_hash {
+            return Ok(false);
+        }
+        for (signed, transactions) in [
+            (&self.accepted, &self.accepted_transactions),
+            (&self.conflicting, &self.conflicting_transactions),
+        ] {
+            let mut hasher = prefix.clone();
+            for tx in transactions {
+                hasher.update(tx.as_bytes());
+            }
+            if session_hash(&hasher) != signed.hash {
+                return Ok(false);
+            }
+            let message = format!("\"{}\"", signed.hash);
+            if !crypto.verify(message.as_bytes(), &signed.signature, &self.signer_id)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// The `hash_z...` form of a session hash, as it's signed.
+pub(crate) fn session_hash(hasher: &impl IncrementalHasher) -> String {
+    format!("hash_z{}", bs58::encode(hasher.finalize()).into_string())
+}
+
+/// Equivocations detected so far, for reporting them to whoever can act on them.
+#[derive(Debug, Clone, Default)]
+pub struct EquivocationLog {
+    evidence: Vec<EquivocationEvidence>,
+}
+
+impl EquivocationLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record evidence, unless the same fork was recorded already.
+    ///
+    /// Returns whether it was new.
+    pub fn record(&mut self, evidence: EquivocationEvidence) -> bool {
+        let known = self.evidence.iter().any(|recorded| {
+            recorded.co_id == evidence.co_id
+                && recorded.session_id == evidence.session_id
+                && recorded.fork_index == evidence.fork_index
+                && recorded.conflicting.hash == evidence.conflicting.hash
+        });
+        if !known {
+            self.evidence.push(evidence);
+        }
+        !known
+    }
+
+    pub fn evidence(&self) -> &[EquivocationEvidence] {
+        &self.evidence
+    }
+
+    pub fn len(&self) -> usize {
+        self.evidence.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.evidence.is_empty()
+    }
+
+    /// All recorded evidence as a JSON array.
+    pub fn export_json(&self) -> String {
+        serde_json::to_string(&self.evidence).expect("evidence is always serializable")
+    }
+
+    /// Take the recorded evidence, leaving the log empty.
+    pub fn take(&mut self) -> Vec<EquivocationEvidence> {
+        std::mem::take(&mut self.evidence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::{test_agent, TestWriter};
+    use crate::core::{DefaultCryptoProvider, SignerSecret};
+    use serde_json::json;
+
+    fn signed(signer_secret: &SignerSecret, tx_count: u32, hash: &str) -> SignedSessionHash {
+        let signature = DefaultCryptoProvider::new()
+            .sign(format!("\"{hash}\"").as_bytes(), signer_secret)
+            .unwrap();
+        SignedSessionHash {
+            tx_count,
+            hash: hash.to_string(),
+            signature,
+        }
+    }
+
+    /// A writer that wrote `[0]` and `[1]`, and the evidence of it writing `["x"]` after `[0]`
+    /// as well.
+    fn evidence() -> (EquivocationEvidence, TestWriter) {
+        let mut writer = TestWriter::new(&CoID("co_zTest".to_string()), 1);
+        writer.add("[0]");
+        let mut fork = writer.clone();
+        writer.add("[1]");
+        let forked = fork.add("[\"x\"]");
+        let err = writer
+            .log()
+            .check_consistent(1, &forked.new_transactions, &forked.last_signature)
+            .unwrap_err();
+        let CoJsonCoreError::Equivocation(evidence) = err else {
+            panic!("expected evidence, got {err}");
+        };
+        (*evidence, writer)
+    }
+
+    fn hash_of(transactions: &[String]) -> String {
+        let mut hasher = DefaultCryptoProvider::new().new_hasher();
+        for tx in transactions {
+            hasher.update(tx.as_bytes());
+        }
+        session_hash(&hasher)
+    }
+
+    #[test]
+    fn test_record_and_export() {
+        let (evidence, _) = evidence();
+        let mut later = evidence.clone();
+        later.fork_index = 2;
+
+        let mut log = EquivocationLog::new();
+        assert!(log.record(evidence.clone()));
+        assert!(!log.record(evidence));
+        assert!(log.record(later));
+        assert_eq!(log.len(), 2);
+
+        let exported: serde_json::Value = serde_json::from_str(&log.export_json()).unwrap();
+        assert_eq!(exported[0]["forkIndex"], json!(1));
+        assert_eq!(exported[0]["coID"], json!("co_zTest"));
+        assert_eq!(exported[0]["conflicting"]["txCount"], json!(2));
+        assert!(exported[0]["prefixHash"]
+            .as_str()
+            .unwrap()
+            .starts_with("hash_z"));
+
+        let roundtrip: Vec<EquivocationEvidence> =
+            serde_json::from_str(&log.export_json()).unwrap();
+        assert_eq!(roundtrip, log.take());
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_verify_evidence() {
+        let crypto = DefaultCryptoProvider::new();
+        let (evidence, writer) = evidence();
+        assert!(evidence.verify(&crypto).unwrap());
+
+        // Not a fork if both sides agree
+        let mut same = evidence.clone();
+        same.conflicting = same.accepted.clone();
+        same.conflicting_transactions = same.accepted_transactions.clone();
+        assert!(!same.verify(&crypto).unwrap());
+
+        // Signed by someone else
+        let mut forged = evidence.clone();
+        let (_, other_secret) = test_agent(2);
+        forged.conflicting = signed(&other_secret, 2, &forged.conflicting.hash);
+        assert!(!forged.verify(&crypto).unwrap());
+
+        // Transactions that don't hash to the signed hashes
+        let mut unbound = evidence.clone();
+        unbound.conflicting_transactions = vec!["{\"b\":1}".to_string()];
+        assert!(!unbound.verify(&crypto).unwrap());
+
+        // A prefix that isn't the one both sides were hashed from
+        let mut other_prefix = evidence;
+        other_prefix.prefix_transactions = vec!["{\"a\":1}".to_string()];
+        other_prefix.prefix_hash = hash_of(&other_prefix.prefix_transactions);
+        assert!(!other_prefix.verify(&crypto).unwrap());
+
+        // Two honest checkpoints of the same session don't make a fork, whichever transactions
+        // they're presented with
+        let mut writer = TestWriter::new(writer.co_id(), 1);
+        writer.add("[0]");
+        let second = writer.add("[1]").last_signature;
+        let third = writer.add("[2]").last_signature;
+        let transactions = writer.log().transactions_json().clone();
+        let checkpoint = |tx_count: usize, signature: &Signature| SignedSessionHash {
+            tx_count: tx_count as u32,
+            hash: hash_of(&transactions[..tx_count]),
+            signature: signature.clone(),
+        };
+        let mut framed = EquivocationEvidence {
+            co_id: writer.co_id().clone(),
+            session_id: writer.session_id().clone(),
+            signer_id: writer.signer_id().clone(),
+            fork_index: 1,
+            prefix_hash: hash_of(&transactions[..1]),
+            prefix_transactions: transactions[..1].to_vec(),
+            accepted: checkpoint(2, &second),
+            accepted_transactions: transactions[1..2].to_vec(),
+            conflicting: checkpoint(3, &third),
+            conflicting_transactions: transactions[1..3].to_vec(),
+        };
+        assert!(!framed.verify(&crypto).unwrap());
+        framed.conflicting.tx_count = 2;
+        framed.conflicting_transactions = vec!["{\"b\":1}".to_string()];
+        assert!(!framed.verify(&crypto).unwrap());
+    }
+}
@@ -1,5 +1,12 @@
+use crate::core::{EquivocationEvidence, SessionID};
+use crate::crypto::CryptoError;
+use serde::Serialize;
+use serde_json::{json, Map, Value as JsonValue};
+use std::fmt;
 use thiserror::Error;
 
+/// Every error `cojson-core` returns. Crypto failures are nested as `Crypto`, so this is the one
+/// type callers match on, and bindings convert `CryptoError`s into it.
 #[derive(Error, Debug)]
 pub enum CoJsonCoreError {
     #[error("Transaction not found at index {0}")]
@@ -20,12 +27,327 @@ pub enum CoJsonCoreError {
     #[error("Signature verification failed: (hash: {0})")]
     SignatureVerification(String),
 
-    #[error("Invalid decoding prefix")] 
+    #[error("Invalid decoding prefix")]
     InvalidDecodingPrefix,
 
-    #[error("Invalid key length")] 
-    InvalidKeyLength(usize, usize),
+    #[error("Invalid sealed prefix in key revelation")]
+    InvalidSealedPrefix,
 
-    #[error("Invalid base58")] 
-    InvalidBase58(#[from] bs58::decode::Error),
+    #[error("Transaction is too large to be synced ({size} > {max} bytes)")]
+    TransactionTooLarge { size: usize, max: usize },
+
+    #[error("Expected {expected} transactions, got {actual}")]
+    TransactionCountMismatch { expected: u32, actual: u32 },
+
+    #[error("Transactions hash to {actual}, but {expected} was validated")]
+    HashMismatch { expected: String, actual: String },
+
+    #[error("Invalid stream item in transaction {0}")]
+    InvalidStreamItem(u32),
+
+    #[error("Expected a single stream, but {0} sessions pushed items")]
+    MultipleStreams(usize),
+
+    #[error("Binary stream hasn't ended")]
+    BinaryStreamIncomplete,
+
+    #[error("Binary stream has {actual} bytes, but announced {expected}")]
+    BinaryStreamSizeMismatch { expected: u64, actual: u64 },
+
+    #[error("Invalid value at {path}: {reason}")]
+    InvalidChangeValue { path: String, reason: String },
+
+    #[error("Invalid list index {0}")]
+    InvalidListIndex(usize),
+
+    #[error("Invalid CoMap field {key}: {reason}")]
+    InvalidCoMapField { key: String, reason: String },
+
+    #[error("Session {} was forked by its signer at transaction {}", .0.session_id.0, .0.fork_index)]
+    Equivocation(Box<EquivocationEvidence>),
+
+    #[error("Session {} conflicts with the accepted transactions from {tx_index} on", .session_id.0)]
+    SessionConflict { session_id: SessionID, tx_index: u32 },
+
+    /// Malformed keys and signatures, and failed crypto operations.
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+}
+
+/// Stable, machine-readable error codes shared by `CoJsonCoreError` and `CryptoError`.
+///
+/// The string form (e.g. `SIGNATURE_VERIFICATION_FAILED`) is what the bindings expose as
+/// `error.code`, and won't change when error messages are reworded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    TransactionNotFound,
+    SignatureVerificationFailed,
+    DecryptionFailed,
+    InvalidPrefix,
+    InvalidKeyLength,
+    InvalidNonceLength,
+    InvalidSignatureLength,
+    InvalidSealedMessageLength,
+    InvalidVerifyingKey,
+    InvalidPublicKey,
+    InvalidBase58,
+    InvalidBase64,
+    InvalidUtf8,
+    InvalidJson,
+    InvalidSecretSeedLength,
+    InvalidWordlistLength,
+    InvalidPassphraseLength,
+    UnknownPassphraseWord,
+    PassphraseChecksumMismatch,
+    CipherError,
+    Equivocation,
+    SessionConflict,
+    TransactionTooLarge,
+    TransactionCountMismatch,
+    HashMismatch,
+    InvalidStreamItem,
+    MultipleStreams,
+    BinaryStreamIncomplete,
+    BinaryStreamSizeMismatch,
+    InvalidChangeValue,
+    InvalidListIndex,
+    InvalidCoMapField,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::TransactionNotFound => "TRANSACTION_NOT_FOUND",
+            ErrorCode::SignatureVerificationFailed => "SIGNATURE_VERIFICATION_FAILED",
+            ErrorCode::DecryptionFailed => "DECRYPTION_FAILED",
+            ErrorCode::InvalidPrefix => "INVALID_PREFIX",
+            ErrorCode::InvalidKeyLength => "INVALID_KEY_LENGTH",
+            ErrorCode::InvalidNonceLength => "INVALID_NONCE_LENGTH",
+            ErrorCode::InvalidSignatureLength => "INVALID_SIGNATURE_LENGTH",
+            ErrorCode::InvalidSealedMessageLength => "INVALID_SEALED_MESSAGE_LENGTH",
+            ErrorCode::InvalidVerifyingKey => "INVALID_VERIFYING_KEY",
+            ErrorCode::InvalidPublicKey => "INVALID_PUBLIC_KEY",
+            ErrorCode::InvalidBase58 => "INVALID_BASE58",
+            ErrorCode::InvalidBase64 => "INVALID_BASE64",
+            ErrorCode::InvalidUtf8 => "INVALID_UTF8",
+            ErrorCode::InvalidJson => "INVALID_JSON",
+            ErrorCode::InvalidSecretSeedLength => "INVALID_SECRET_SEED_LENGTH",
+            ErrorCode::InvalidWordlistLength => "INVALID_WORDLIST_LENGTH",
+            ErrorCode::InvalidPassphraseLength => "INVALID_PASSPHRASE_LENGTH",
+            ErrorCode::UnknownPassphraseWord => "UNKNOWN_PASSPHRASE_WORD",
+            ErrorCode::PassphraseChecksumMismatch => "PASSPHRASE_CHECKSUM_MISMATCH",
+            ErrorCode::CipherError => "CIPHER_ERROR",
+            ErrorCode::Equivocation => "EQUIVOCATION",
+            ErrorCode::SessionConflict => "SESSION_CONFLICT",
+            ErrorCode::TransactionTooLarge => "TRANSACTION_TOO_LARGE",
+            ErrorCode::TransactionCountMismatch => "TRANSACTION_COUNT_MISMATCH",
+            ErrorCode::HashMismatch => "HASH_MISMATCH",
+            ErrorCode::InvalidStreamItem => "INVALID_STREAM_ITEM",
+            ErrorCode::MultipleStreams => "MULTIPLE_STREAMS",
+            ErrorCode::BinaryStreamIncomplete => "BINARY_STREAM_INCOMPLETE",
+            ErrorCode::BinaryStreamSizeMismatch => "BINARY_STREAM_SIZE_MISMATCH",
+            ErrorCode::InvalidChangeValue => "INVALID_CHANGE_VALUE",
+            ErrorCode::InvalidListIndex => "INVALID_LIST_INDEX",
+            ErrorCode::InvalidCoMapField => "INVALID_CO_MAP_FIELD",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn details(value: JsonValue) -> Map<String, JsonValue> {
+    match value {
+        JsonValue::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+impl CoJsonCoreError {
+    /// The stable error code for this error. Errors wrapping a `CryptoError` report its code.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CoJsonCoreError::TransactionNotFound(_) => ErrorCode::TransactionNotFound,
+            CoJsonCoreError::InvalidEncryptedPrefix => ErrorCode::InvalidPrefix,
+            CoJsonCoreError::Base64Decode(_) => ErrorCode::InvalidBase64,
+            CoJsonCoreError::Utf8(_) => ErrorCode::InvalidUtf8,
+            CoJsonCoreError::Json(_) => ErrorCode::InvalidJson,
+            CoJsonCoreError::SignatureVerification(_) => ErrorCode::SignatureVerificationFailed,
+            CoJsonCoreError::InvalidDecodingPrefix => ErrorCode::InvalidPrefix,
+            CoJsonCoreError::InvalidSealedPrefix => ErrorCode::InvalidPrefix,
+            CoJsonCoreError::Equivocation(_) => ErrorCode::Equivocation,
+            CoJsonCoreError::SessionConflict { .. } => ErrorCode::SessionConflict,
+            CoJsonCoreError::TransactionTooLarge { .. } => ErrorCode::TransactionTooLarge,
+            CoJsonCoreError::TransactionCountMismatch { .. } => ErrorCode::TransactionCountMismatch,
+            CoJsonCoreError::HashMismatch { .. } => ErrorCode::HashMismatch,
+            CoJsonCoreError::InvalidStreamItem(_) => ErrorCode::InvalidStreamItem,
+            CoJsonCoreError::MultipleStreams(_) => ErrorCode::MultipleStreams,
+            CoJsonCoreError::BinaryStreamIncomplete => ErrorCode::BinaryStreamIncomplete,
+            CoJsonCoreError::BinaryStreamSizeMismatch { .. } => ErrorCode::BinaryStreamSizeMismatch,
+            CoJsonCoreError::InvalidChangeValue { .. } => ErrorCode::InvalidChangeValue,
+            CoJsonCoreError::InvalidListIndex(_) => ErrorCode::InvalidListIndex,
+            CoJsonCoreError::InvalidCoMapField { .. } => ErrorCode::InvalidCoMapField,
+            CoJsonCoreError::Crypto(err) => err.code(),
+        }
+    }
+
+    /// Structured fields describing the error, e.g. the failing tx index or expected vs. actual lengths.
+    /// Keys are camelCase so they can be handed to JS as-is.
+    pub fn details(&self) -> Map<String, JsonValue> {
+        match self {
+            CoJsonCoreError::TransactionNotFound(tx_index) => details(json!({ "txIndex": tx_index })),
+            CoJsonCoreError::InvalidEncryptedPrefix => details(json!({ "expected": "encrypted_U" })),
+            CoJsonCoreError::Base64Decode(err) => details(json!({ "reason": err.to_string() })),
+            CoJsonCoreError::Utf8(err) => {
+                details(json!({ "validUpTo": err.utf8_error().valid_up_to() }))
+            }
+            CoJsonCoreError::Json(err) => details(json!({
+                "line": err.line(),
+                "column": err.column(),
+                "reason": err.to_string(),
+            })),
+            CoJsonCoreError::SignatureVerification(hash) => details(json!({ "hash": hash })),
+            CoJsonCoreError::InvalidDecodingPrefix => Map::new(),
+            CoJsonCoreError::InvalidSealedPrefix => details(json!({ "expected": "sealed_U" })),
+            CoJsonCoreError::TransactionTooLarge { size, max } => {
+                details(json!({ "size": size, "max": max }))
+            }
+            CoJsonCoreError::TransactionCountMismatch { expected, actual } => {
+                details(json!({ "expected": expected, "actual": actual }))
+            }
+            CoJsonCoreError::HashMismatch { expected, actual } => {
+                details(json!({ "expected": expected, "actual": actual }))
+            }
+            CoJsonCoreError::InvalidStreamItem(tx_index) => details(json!({ "txIndex": tx_index })),
+            CoJsonCoreError::MultipleStreams(streams) => details(json!({ "streams": streams })),
+            CoJsonCoreError::BinaryStreamIncomplete => Map::new(),
+            CoJsonCoreError::BinaryStreamSizeMismatch { expected, actual } => {
+                details(json!({ "expected": expected, "actual": actual }))
+            }
+            CoJsonCoreError::InvalidChangeValue { path, reason } => {
+                details(json!({ "path": path, "reason": reason }))
+            }
+            CoJsonCoreError::InvalidListIndex(index) => details(json!({ "index": index })),
+            CoJsonCoreError::InvalidCoMapField { key, reason } => {
+                details(json!({ "key": key, "reason": reason }))
+            }
+            CoJsonCoreError::Equivocation(evidence) => details(json!({
+                "sessionID": evidence.session_id,
+                "txIndex": evidence.fork_index,
+            })),
+            CoJsonCoreError::SessionConflict { session_id, tx_index } => {
+                details(json!({ "sessionID": session_id, "txIndex": tx_index }))
+            }
+            CoJsonCoreError::Crypto(err) => err.details(),
+        }
+    }
+}
+
+impl CryptoError {
+    /// The stable error code for this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CryptoError::InvalidKeyLength(_, _) => ErrorCode::InvalidKeyLength,
+            CryptoError::InvalidNonceLength => ErrorCode::InvalidNonceLength,
+            CryptoError::InvalidSealedMessageLength(_) => ErrorCode::InvalidSealedMessageLength,
+            CryptoError::InvalidSealerSecretFormat => ErrorCode::InvalidPrefix,
+            CryptoError::InvalidSignatureLength => ErrorCode::InvalidSignatureLength,
+            CryptoError::InvalidVerifyingKey(_) => ErrorCode::InvalidVerifyingKey,
+            CryptoError::InvalidPublicKey(_) => ErrorCode::InvalidPublicKey,
+            CryptoError::WrongTag => ErrorCode::DecryptionFailed,
+            CryptoError::CipherError => ErrorCode::CipherError,
+            CryptoError::InvalidPrefix(_, _) => ErrorCode::InvalidPrefix,
+            CryptoError::Base58Error(_) => ErrorCode::InvalidBase58,
+            CryptoError::InvalidSecretSeedLength(_) => ErrorCode::InvalidSecretSeedLength,
+            CryptoError::InvalidWordlistLength(_) => ErrorCode::InvalidWordlistLength,
+            CryptoError::InvalidPassphraseLength(_) => ErrorCode::InvalidPassphraseLength,
+            CryptoError::UnknownPassphraseWord(_) => ErrorCode::UnknownPassphraseWord,
+            CryptoError::PassphraseChecksumMismatch => ErrorCode::PassphraseChecksumMismatch,
+        }
+    }
+
+    /// Structured fields describing the error. Keys are camelCase so they can be handed to JS as-is.
+    pub fn details(&self) -> Map<String, JsonValue> {
+        match self {
+            CryptoError::InvalidKeyLength(expected, actual) => {
+                details(json!({ "expected": expected, "actual": actual }))
+            }
+            CryptoError::InvalidSealedMessageLength(actual) => details(json!({ "actual": actual })),
+            CryptoError::InvalidSealerSecretFormat => {
+                details(json!({ "field": "sealer secret", "expected": "sealerSecret_z" }))
+            }
+            CryptoError::InvalidVerifyingKey(reason) | CryptoError::InvalidPublicKey(reason) => {
+                details(json!({ "reason": reason }))
+            }
+            CryptoError::InvalidPrefix(prefix, field) => {
+                details(json!({ "field": field, "expected": prefix }))
+            }
+            CryptoError::Base58Error(reason) => details(json!({ "reason": reason })),
+            CryptoError::InvalidSecretSeedLength(actual) => details(json!({ "actual": actual })),
+            CryptoError::InvalidWordlistLength(actual) => {
+                details(json!({ "expected": crate::crypto::WORDLIST_LENGTH, "actual": actual }))
+            }
+            CryptoError::InvalidPassphraseLength(words) => details(json!({ "words": words })),
+            CryptoError::UnknownPassphraseWord(word) => details(json!({ "word": word })),
+            CryptoError::InvalidNonceLength
+            | CryptoError::InvalidSignatureLength
+            | CryptoError::WrongTag
+            | CryptoError::CipherError
+            | CryptoError::PassphraseChecksumMismatch => Map::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::KeySecret;
+
+    #[test]
+    fn test_error_codes_and_details() {
+        let err = CoJsonCoreError::TransactionNotFound(3);
+        assert_eq!(err.code(), ErrorCode::TransactionNotFound);
+        assert_eq!(err.code().as_str(), "TRANSACTION_NOT_FOUND");
+        assert_eq!(JsonValue::Object(err.details()), json!({ "txIndex": 3 }));
+
+        // Malformed keys are crypto errors, wherever they're decoded
+        let key_secret = KeySecret(format!("keySecret_z{}", bs58::encode([0; 31]).into_string()));
+        let err = <[u8; 32]>::try_from(&key_secret).unwrap_err();
+        assert!(matches!(
+            err,
+            CoJsonCoreError::Crypto(CryptoError::InvalidKeyLength(32, 31))
+        ));
+        assert_eq!(err.to_string(), "Invalid key length (expected 32, got 31)");
+        assert_eq!(
+            JsonValue::Object(err.details()),
+            json!({ "expected": 32, "actual": 31 })
+        );
+
+        // Wrapped crypto errors keep their own code and details
+        let err: CoJsonCoreError = CryptoError::UnknownPassphraseWord("foo".to_string()).into();
+        assert_eq!(err.code(), ErrorCode::UnknownPassphraseWord);
+        assert_eq!(JsonValue::Object(err.details()), json!({ "word": "foo" }));
+
+        let err = crate::crypto::encrypt(b"data", "invalid", b"nonce").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::InvalidPrefix);
+        assert_eq!(
+            JsonValue::Object(err.details()),
+            json!({ "field": "key secret", "expected": "keySecret_z" })
+        );
+    }
+
+    #[test]
+    fn test_error_code_serializes_as_string() {
+        for code in [
+            ErrorCode::SignatureVerificationFailed,
+            ErrorCode::InvalidBase64,
+            ErrorCode::PassphraseChecksumMismatch,
+        ] {
+            assert_eq!(serde_json::to_value(code).unwrap(), json!(code.as_str()));
+        }
+    }
 }
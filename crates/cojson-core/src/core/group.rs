@@ -0,0 +1,430 @@
+use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use rand::{rngs::OsRng, RngCore};
+use serde_json::{json, Value as JsonValue};
+
+use crate::core::{
+    CoID, CoJsonCoreError, KeyID, KeySecret, MapOpPayload, SealerID, SealerSecret, TransactionID,
+};
+use crate::crypto::{decrypt, encrypt, seal, unseal};
+
+/// The member key used to reveal a read key to everyone, in plaintext.
+pub const EVERYONE: &str = "everyone";
+
+/// Generate a new random read key, returning its ID and secret.
+/// Matches `newRandomKeySecret` on the TS side: a 32-byte secret and a 12-byte ID.
+pub fn new_random_key_secret() -> (KeyID, KeySecret) {
+    let mut secret = [0u8; 32];
+    let mut id = [0u8; 12];
+    OsRng.fill_bytes(&mut secret);
+    OsRng.fill_bytes(&mut id);
+
+    (
+        KeyID(format!("key_z{}", bs58::encode(id).into_string())),
+        KeySecret(format!("keySecret_z{}", bs58::encode(secret).into_string())),
+    )
+}
+
+/// The group CoMap key under which `key_id` is revealed to `member`
+/// (an account ID, an agent ID, another key ID or "everyone").
+pub fn revelation_key(key_id: &KeyID, member: &str) -> String {
+    format!("{}_for_{}", key_id.0, member)
+}
+
+/// Nonce material for a key revelation: `{in: groupID, tx: transactionID}`,
+/// where `tx` is the transaction that carries the revelation.
+fn revelation_nonce_material(group_id: &CoID, tx: &TransactionID) -> Vec<u8> {
+    json!({ "in": group_id, "tx": tx }).to_string().into_bytes()
+}
+
+/// Nonce material used to encrypt one key secret with another.
+fn key_for_key_nonce_material(encrypted_id: &KeyID, encrypting_id: &KeyID) -> Vec<u8> {
+    json!({ "encryptedID": encrypted_id, "encryptingID": encrypting_id })
+        .to_string()
+        .into_bytes()
+}
+
+/// Seal a key secret to a member's sealer ID, producing a `sealed_U...` string.
+pub fn seal_key_secret(
+    key_secret: &KeySecret,
+    from: &SealerSecret,
+    to: &SealerID,
+    group_id: &CoID,
+    tx: &TransactionID,
+) -> Result<String, CoJsonCoreError> {
+    let message = serde_json::to_string(&key_secret.0)?;
+    let sealed = seal(
+        message.as_bytes(),
+        &from.0,
+        &to.0,
+        &revelation_nonce_material(group_id, tx),
+    )?;
+    Ok(format!("sealed_U{}", URL_SAFE.encode(sealed)))
+}
+
+/// Unseal a key secret that was revealed to us by the owner of `from`.
+/// `tx` must be the transaction the revelation was made in.
+pub fn unseal_key_secret(
+    sealed: &str,
+    sealer: &SealerSecret,
+    from: &SealerID,
+    group_id: &CoID,
+    tx: &TransactionID,
+) -> Result<KeySecret, CoJsonCoreError> {
+    let sealed_b64 = sealed
+        .strip_prefix("sealed_U")
+        .ok_or(CoJsonCoreError::InvalidSealedPrefix)?;
+    let sealed_bytes = URL_SAFE.decode(sealed_b64)?;
+    let plaintext = unseal(
+        &sealed_bytes,
+        &sealer.0,
+        &from.0,
+        &revelation_nonce_material(group_id, tx),
+    )?;
+    Ok(KeySecret(serde_json::from_slice(&plaintext)?))
+}
+
+/// Encrypt the `to_encrypt` key secret with the `encrypting` one, producing the
+/// `encrypted_U...` value stored under `{to_encrypt}_for_{encrypting}`.
+pub fn encrypt_key_secret(
+    to_encrypt: (&KeyID, &KeySecret),
+    encrypting: (&KeyID, &KeySecret),
+) -> Result<String, CoJsonCoreError> {
+    let message = serde_json::to_string(&to_encrypt.1 .0)?;
+    let ciphertext = encrypt(
+        message.as_bytes(),
+        &encrypting.1 .0,
+        &key_for_key_nonce_material(to_encrypt.0, encrypting.0),
+    )?;
+    Ok(format!("encrypted_U{}", URL_SAFE.encode(ciphertext)))
+}
+
+/// Decrypt a key secret stored under `{encrypted_id}_for_{encrypting_id}`.
+pub fn decrypt_key_secret(
+    encrypted: &str,
+    encrypted_id: &KeyID,
+    encrypting: (&KeyID, &KeySecret),
+) -> Result<KeySecret, CoJsonCoreError> {
+    let ciphertext_b64 = encrypted
+        .strip_prefix("encrypted_U")
+        .ok_or(CoJsonCoreError::InvalidEncryptedPrefix)?;
+    let ciphertext = URL_SAFE.decode(ciphertext_b64)?;
+    let plaintext = decrypt(
+        &ciphertext,
+        &encrypting.1 .0,
+        &key_for_key_nonce_material(encrypted_id, encrypting.0),
+    )?;
+    Ok(KeySecret(serde_json::from_slice(&plaintext)?))
+}
+
+/// Someone a read key gets revealed to.
+#[derive(Debug, Clone)]
+pub enum RevelationTarget {
+    /// An account or agent, identified by its member key in the group, with the sealer ID of its agent.
+    Member {
+        member_id: String,
+        sealer_id: SealerID,
+    },
+    /// Everyone, which stores the key secret in plaintext.
+    Everyone,
+}
+
+/// Build the `set` op revealing `key_id` to a single target.
+pub fn reveal_key(
+    key_id: &KeyID,
+    key_secret: &KeySecret,
+    target: &RevelationTarget,
+    revealer: &SealerSecret,
+    group_id: &CoID,
+    tx: &TransactionID,
+) -> Result<MapOpPayload, CoJsonCoreError> {
+    let (member, value) = match target {
+        RevelationTarget::Member {
+            member_id,
+            sealer_id,
+        } => (
+            member_id.as_str(),
+            seal_key_secret(key_secret, revealer, sealer_id, group_id, tx)?,
+        ),
+        RevelationTarget::Everyone => (EVERYONE, key_secret.0.clone()),
+    };
+
+    Ok(MapOpPayload::Set {
+        key: revelation_key(key_id, member),
+        value: JsonValue::String(value),
+    })
+}
+
+/// The result of rotating a group's read key.
+#[derive(Debug, Clone)]
+pub struct ReadKeyRotation {
+    pub key_id: KeyID,
+    pub key_secret: KeySecret,
+    /// Group CoMap changes to be added as a single trusting transaction with ID `tx`.
+    pub changes: Vec<MapOpPayload>,
+}
+
+/// Generate a new read key and the group changes that reveal it to every target,
+/// encrypt the previous read key (if any) under the new one and point `readKey` at it.
+///
+/// All revelations are sealed with `{in: group_id, tx}` as nonce material, so the
+/// returned changes must be committed in exactly the transaction identified by `tx`.
+pub fn rotate_read_key(
+    group_id: &CoID,
+    tx: &TransactionID,
+    revealer: &SealerSecret,
+    previous: Option<(&KeyID, &KeySecret)>,
+    targets: &[RevelationTarget],
+) -> Result<ReadKeyRotation, CoJsonCoreError> {
+    let (key_id, key_secret) = new_random_key_secret();
+
+    let mut changes = targets
+        .iter()
+        .map(|target| reveal_key(&key_id, &key_secret, target, revealer, group_id, tx))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some((previous_id, previous_secret)) = previous {
+        changes.push(MapOpPayload::Set {
+            key: revelation_key(previous_id, &key_id.0),
+            value: JsonValue::String(encrypt_key_secret(
+                (previous_id, previous_secret),
+                (&key_id, &key_secret),
+            )?),
+        });
+    }
+
+    changes.push(MapOpPayload::Set {
+        key: "readKey".to_string(),
+        value: JsonValue::String(key_id.0.clone()),
+    });
+
+    Ok(ReadKeyRotation {
+        key_id,
+        key_secret,
+        changes,
+    })
+}
+
+/// Find the last string value set for `key` in a list of changes.
+fn last_set_string<'a>(changes: &'a [MapOpPayload], key: &str) -> Option<&'a str> {
+    changes.iter().rev().find_map(|op| match op {
+        MapOpPayload::Set { key: k, value } if k == key => value.as_str(),
+        _ => None,
+    })
+}
+
+/// Read the secret of `key_id` from the changes of a single group transaction,
+/// either from a revelation to `member` (sealed by `revealer`) or from one to everyone.
+/// Returns `None` if the transaction doesn't reveal the key to us.
+pub fn read_revealed_key(
+    changes: &[MapOpPayload],
+    key_id: &KeyID,
+    member: &str,
+    sealer: &SealerSecret,
+    revealer: &SealerID,
+    group_id: &CoID,
+    tx: &TransactionID,
+) -> Result<Option<KeySecret>, CoJsonCoreError> {
+    if let Some(secret) = last_set_string(changes, &revelation_key(key_id, EVERYONE)) {
+        return Ok(Some(KeySecret(secret.to_string())));
+    }
+
+    last_set_string(changes, &revelation_key(key_id, member))
+        .map(|sealed| unseal_key_secret(sealed, sealer, revealer, group_id, tx))
+        .transpose()
+}
+
+/// Read the secret of `key_id` from a `{key_id}_for_{encrypting_id}` entry in the given changes.
+/// Returns `None` if the changes don't contain such an entry.
+pub fn read_previous_key(
+    changes: &[MapOpPayload],
+    key_id: &KeyID,
+    encrypting: (&KeyID, &KeySecret),
+) -> Result<Option<KeySecret>, CoJsonCoreError> {
+    last_set_string(changes, &revelation_key(key_id, &encrypting.0 .0))
+        .map(|encrypted| decrypt_key_secret(encrypted, key_id, encrypting))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{parse_map_changes, stringify_changes, SessionID};
+    use crate::crypto::{get_sealer_id, new_x25519_private_key};
+
+    fn new_sealer() -> (SealerSecret, SealerID) {
+        let secret = format!(
+            "sealerSecret_z{}",
+            bs58::encode(new_x25519_private_key()).into_string()
+        );
+        let id = get_sealer_id(&secret).unwrap();
+        (SealerSecret(secret), SealerID(id))
+    }
+
+    fn test_tx() -> TransactionID {
+        TransactionID {
+            session_id: SessionID("co_zTestGroup_session_zTest".to_string()),
+            tx_index: 3,
+            branch: None,
+        }
+    }
+
+    #[test]
+    fn test_new_random_key_secret() {
+        let (key_id, key_secret) = new_random_key_secret();
+        assert!(key_id.0.starts_with("key_z"));
+        assert!(key_secret.0.starts_with("keySecret_z"));
+
+        let bytes: [u8; 32] = (&key_secret).try_into().unwrap();
+        assert_eq!(bytes.len(), 32);
+
+        let (other_id, _) = new_random_key_secret();
+        assert_ne!(key_id, other_id);
+    }
+
+    #[test]
+    fn test_revelation_nonce_material_is_stable() {
+        let material = revelation_nonce_material(&CoID("co_zGroup".to_string()), &test_tx());
+        assert_eq!(
+            String::from_utf8(material).unwrap(),
+            r#"{"in":"co_zGroup","tx":{"sessionID":"co_zTestGroup_session_zTest","txIndex":3}}"#
+        );
+    }
+
+    #[test]
+    fn test_seal_unseal_key_secret() {
+        let (admin_secret, admin_id) = new_sealer();
+        let (member_secret, member_id) = new_sealer();
+        let group_id = CoID("co_zGroup".to_string());
+        let (_, key_secret) = new_random_key_secret();
+
+        let sealed =
+            seal_key_secret(&key_secret, &admin_secret, &member_id, &group_id, &test_tx()).unwrap();
+        assert!(sealed.starts_with("sealed_U"));
+
+        let unsealed =
+            unseal_key_secret(&sealed, &member_secret, &admin_id, &group_id, &test_tx()).unwrap();
+        assert_eq!(unsealed, key_secret);
+
+        // A different transaction ID yields different nonce material.
+        let other_tx = TransactionID {
+            tx_index: 4,
+            ..test_tx()
+        };
+        assert!(unseal_key_secret(&sealed, &member_secret, &admin_id, &group_id, &other_tx).is_err());
+
+        assert!(matches!(
+            unseal_key_secret("encrypted_Uabc", &member_secret, &admin_id, &group_id, &test_tx()),
+            Err(CoJsonCoreError::InvalidSealedPrefix)
+        ));
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_key_secret() {
+        let (old_id, old_secret) = new_random_key_secret();
+        let (new_id, new_secret) = new_random_key_secret();
+
+        let encrypted = encrypt_key_secret((&old_id, &old_secret), (&new_id, &new_secret)).unwrap();
+        assert!(encrypted.starts_with("encrypted_U"));
+
+        let decrypted = decrypt_key_secret(&encrypted, &old_id, (&new_id, &new_secret)).unwrap();
+        assert_eq!(decrypted, old_secret);
+    }
+
+    #[test]
+    fn test_rotate_read_key() {
+        let (admin_secret, admin_id) = new_sealer();
+        let (member_secret, member_id) = new_sealer();
+        let group_id = CoID("co_zGroup".to_string());
+        let tx = test_tx();
+        let (old_id, old_secret) = new_random_key_secret();
+
+        let rotation = rotate_read_key(
+            &group_id,
+            &tx,
+            &admin_secret,
+            Some((&old_id, &old_secret)),
+            &[
+                RevelationTarget::Member {
+                    member_id: "co_zAdmin".to_string(),
+                    sealer_id: admin_id.clone(),
+                },
+                RevelationTarget::Member {
+                    member_id: "co_zMember".to_string(),
+                    sealer_id: member_id,
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(rotation.changes.len(), 4);
+        assert_eq!(
+            rotation.changes.last().unwrap(),
+            &MapOpPayload::Set {
+                key: "readKey".to_string(),
+                value: JsonValue::String(rotation.key_id.0.clone()),
+            }
+        );
+
+        // Round-trip through the serialized transaction changes, as a reader would see them.
+        let changes = parse_map_changes(&stringify_changes(&rotation.changes).unwrap()).unwrap();
+
+        let revealed = read_revealed_key(
+            &changes,
+            &rotation.key_id,
+            "co_zMember",
+            &member_secret,
+            &admin_id,
+            &group_id,
+            &tx,
+        )
+        .unwrap();
+        assert_eq!(revealed, Some(rotation.key_secret.clone()));
+
+        let not_revealed = read_revealed_key(
+            &changes,
+            &rotation.key_id,
+            "co_zSomeoneElse",
+            &member_secret,
+            &admin_id,
+            &group_id,
+            &tx,
+        )
+        .unwrap();
+        assert_eq!(not_revealed, None);
+
+        let previous =
+            read_previous_key(&changes, &old_id, (&rotation.key_id, &rotation.key_secret)).unwrap();
+        assert_eq!(previous, Some(old_secret));
+    }
+
+    #[test]
+    fn test_reveal_key_to_everyone() {
+        let (admin_secret, admin_id) = new_sealer();
+        let (member_secret, _) = new_sealer();
+        let group_id = CoID("co_zGroup".to_string());
+        let (key_id, key_secret) = new_random_key_secret();
+
+        let op = reveal_key(
+            &key_id,
+            &key_secret,
+            &RevelationTarget::Everyone,
+            &admin_secret,
+            &group_id,
+            &test_tx(),
+        )
+        .unwrap();
+        assert_eq!(op.key(), format!("{}_for_everyone", key_id.0));
+
+        let revealed = read_revealed_key(
+            &[op],
+            &key_id,
+            "co_zMember",
+            &member_secret,
+            &admin_id,
+            &group_id,
+            &test_tx(),
+        )
+        .unwrap();
+        assert_eq!(revealed, Some(key_secret));
+    }
+}
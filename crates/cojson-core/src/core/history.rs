@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::core::branch::{read_transaction, TransactionRead};
+use crate::core::{
+    parse_list_changes, parse_map_changes, CoID, CoJsonCoreError, CryptoProvider, KeyID, KeySecret,
+    KnownStateSessions, ListAnchor, ListEdge, ListOpPayload, MapOpPayload, OpID,
+    ResolvedTransaction, SessionID, SessionLogInternal, TransactionID,
+};
+
+/// Which transactions a query on a CoValue's history considers.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum HistoryFilter {
+    /// Every transaction.
+    #[default]
+    All,
+    /// Transactions made at or before a time, like `atTime`.
+    AtTime(u64),
+    /// Transactions within a known state. Sessions missing from it are left out.
+    KnownState(KnownStateSessions),
+}
+
+impl HistoryFilter {
+    fn includes(&self, tx: &ResolvedTransaction) -> bool {
+        match self {
+            HistoryFilter::AtTime(time) => tx.made_at <= *time,
+            _ => self.includes_id(&tx.current_tx),
+        }
+    }
+
+    /// Whether the transaction at `current_tx` in the session logs is let through. Filters by
+    /// time let every ID through.
+    pub fn includes_id(&self, current_tx: &TransactionID) -> bool {
+        match self {
+            HistoryFilter::All | HistoryFilter::AtTime(_) => true,
+            HistoryFilter::KnownState(sessions) => sessions
+                .get(&current_tx.session_id)
+                .is_some_and(|&count| current_tx.tx_index < count),
+        }
+    }
+}
+
+/// An item of a CoList, like the entries of `RawCoList`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoListEntry {
+    pub value: JsonValue,
+    pub made_at: u64,
+    pub op_id: OpID,
+}
+
+/// One transaction of a CoValue's history, for exporting it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeLogEntry {
+    #[serde(rename = "txID")]
+    pub tx_id: TransactionID,
+    /// The account or agent that made the transaction.
+    pub author: String,
+    pub made_at: u64,
+    pub private: bool,
+    pub changes: Vec<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<JsonValue>,
+}
+
+/// A CoValue's transactions in the order they apply, to read its content as it was at any point.
+///
+/// Built from the session logs, which are left untouched, and extended with what they get
+/// later.
+#[derive(Debug, Clone, Default)]
+pub struct CoValueHistory {
+    transactions: Vec<ResolvedTransaction>,
+    /// How far each session was read, and what its last transaction resolved to.
+    read: HashMap<SessionID, (u32, Option<(TransactionID, u64)>)>,
+    /// Private transactions left out because their key couldn't be resolved.
+    undecryptable: Vec<TransactionID>,
+}
+
+/// An insertion into a CoList, with the insertions placed right before and after it.
+struct Insertion<'a> {
+    value: &'a JsonValue,
+    made_at: u64,
+    predecessors: Vec<&'a OpID>,
+    successors: Vec<&'a OpID>,
+}
+
+impl CoValueHistory {
+    /// Read a CoValue's history from its session logs.
+    /// - `branch_id`: The CoValue's ID if it's a branch
+    /// - `key_for`: Resolves the secrets of the read keys private transactions were made with
+    ///
+    /// Private transactions whose key can't be resolved are left out.
+    pub fn from_session_logs<'a, C, F>(
+        branch_id: Option<&CoID>,
+        sessions: impl IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)>,
+        key_for: F,
+    ) -> Result<Self, CoJsonCoreError>
+    where
+        C: CryptoProvider + 'a,
+        F: FnMut(&KeyID) -> Option<KeySecret>,
+    {
+        let mut history = Self::default();
+        history.extend(branch_id, sessions, key_for)?;
+        Ok(history)
+    }
+
+    /// Read the transactions the session logs got since the history was read from them, which
+    /// must be the same logs in the same order.
+    ///
+    /// Returns how many transactions were added.
+    pub fn extend<'a, C, F>(
+        &mut self,
+        branch_id: Option<&CoID>,
+        sessions: impl IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)>,
+        mut key_for: F,
+    ) -> Result<usize, CoJsonCoreError>
+    where
+        C: CryptoProvider + 'a,
+        F: FnMut(&KeyID) -> Option<KeySecret>,
+    {
+        let before = self.transactions.len();
+        let mut session_order = HashMap::new();
+        for (position, (session_id, log)) in sessions.into_iter().enumerate() {
+            session_order.insert(session_id.clone(), position);
+            let (read, previous) = self.read.entry(session_id.clone()).or_default();
+            let count = log.transactions_json().len() as u32;
+            for tx_index in *read..count {
+                let tx =
+                    read_transaction(branch_id, session_id, log, tx_index, previous, &mut key_for)?;
+                match tx {
+                    TransactionRead::Resolved(tx) => self.transactions.push(tx),
+                    TransactionRead::Undecryptable => {
+                        self.undecryptable.push(TransactionID {
+                            session_id: session_id.clone(),
+                            tx_index,
+                            branch: branch_id.cloned(),
+                        });
+                    }
+                    TransactionRead::Rejected => {}
+                }
+            }
+            *read = count;
+        }
+
+        if self.transactions.len() > before {
+            // Transactions made at the same time stay in session order, like
+            // `compareTransactions`
+            self.transactions.sort_by_key(|tx| {
+                let session = session_order.get(&tx.current_tx.session_id);
+                (tx.made_at, session.copied(), tx.current_tx.tx_index)
+            });
+        }
+        Ok(self.transactions.len() - before)
+    }
+
+    /// How many transactions were read.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// The private transactions left out because their key couldn't be resolved.
+    pub fn undecryptable(&self) -> &[TransactionID] {
+        &self.undecryptable
+    }
+
+    /// The transactions the filter lets through, in order.
+    pub fn transactions<'a>(
+        &'a self,
+        filter: &'a HistoryFilter,
+    ) -> impl Iterator<Item = &'a ResolvedTransaction> + 'a {
+        self.transactions.iter().filter(|tx| filter.includes(tx))
+    }
+
+    /// The content of a CoMap, like `asObject`.
+    pub fn map_at(
+        &self,
+        filter: &HistoryFilter,
+    ) -> Result<Map<String, JsonValue>, CoJsonCoreError> {
+        let mut object = Map::new();
+        for tx in self.transactions(filter) {
+            apply_map_changes(&mut object, &tx.changes_json)?;
+        }
+        Ok(object)
+    }
+
+    /// The items of a CoList, like `entries`.
+    pub fn list_at(&self, filter: &HistoryFilter) -> Result<Vec<CoListEntry>, CoJsonCoreError> {
+        let changes = self
+            .transactions(filter)
+            .map(|tx| Ok((tx, parse_list_changes(&tx.changes_json)?)))
+            .collect::<Result<Vec<_>, CoJsonCoreError>>()?;
+        let op_ids: Vec<Vec<OpID>> = changes
+            .iter()
+            .map(|(tx, ops)| {
+                (0..ops.len() as u32)
+                    .map(|idx| OpID::new(&tx.tx, idx))
+                    .collect()
+            })
+            .collect();
+
+        let mut insertions: HashMap<&OpID, Insertion> = HashMap::new();
+        let mut after_start = Vec::new();
+        let mut before_end = Vec::new();
+        let mut deleted = HashSet::new();
+        for ((tx, ops), op_ids) in changes.iter().zip(&op_ids) {
+            for (op, op_id) in ops.iter().zip(op_ids) {
+                let (value, anchor, is_pre) = match op {
+                    ListOpPayload::Pre { value, before } => (value, before, true),
+                    ListOpPayload::App { value, after } => (value, after, false),
+                    ListOpPayload::Del { insertion } => {
+                        deleted.insert(insertion);
+                        continue;
+                    }
+                };
+                // The same insertion can come in twice through repeated merges
+                if insertions.contains_key(op_id) {
+                    continue;
+                }
+                match (anchor, is_pre) {
+                    (ListAnchor::Edge(ListEdge::End), true) => before_end.push(op_id),
+                    (ListAnchor::Edge(ListEdge::Start), false) => after_start.push(op_id),
+                    (ListAnchor::Edge(_), _) => {}
+                    (ListAnchor::Op(anchor), is_pre) => {
+                        if let Some(entry) = insertions.get_mut(anchor) {
+                            if is_pre {
+                                entry.predecessors.push(op_id);
+                            } else {
+                                entry.successors.push(op_id);
+                            }
+                        }
+                    }
+                }
+                insertions.insert(
+                    op_id,
+                    Insertion {
+                        value,
+                        made_at: tx.made_at,
+                        predecessors: Vec::new(),
+                        successors: Vec::new(),
+                    },
+                );
+            }
+        }
+
+        let mut entries = Vec::new();
+        for op_id in after_start.into_iter().chain(before_end) {
+            // Depth first like `fillArrayFromOpID`, with the next insertion to visit on top
+            let mut todo = vec![op_id];
+            let mut predecessors_visited = HashSet::new();
+            while let Some(&current) = todo.last() {
+                let Some(entry) = insertions.get(current) else {
+                    todo.pop();
+                    continue;
+                };
+                if !entry.predecessors.is_empty() && predecessors_visited.insert(current) {
+                    todo.extend(&entry.predecessors);
+                    continue;
+                }
+                todo.pop();
+                if !deleted.contains(current) {
+                    entries.push(CoListEntry {
+                        value: entry.value.clone(),
+                        made_at: entry.made_at,
+                        op_id: current.clone(),
+                    });
+                }
+                // Later insertions after the same item end up on top, so they come first
+                todo.extend(&entry.successors);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The text of a CoPlainText, like `toString`.
+    pub fn plain_text_at(&self, filter: &HistoryFilter) -> Result<String, CoJsonCoreError> {
+        Ok(self
+            .list_at(filter)?
+            .iter()
+            .map(|entry| match &entry.value {
+                JsonValue::String(grapheme) => grapheme.clone(),
+                other => other.to_string(),
+            })
+            .collect())
+    }
+
+    /// Every transaction the filter lets through, with its decrypted changes.
+    pub fn change_log(
+        &self,
+        filter: &HistoryFilter,
+    ) -> Result<Vec<ChangeLogEntry>, CoJsonCoreError> {
+        self.transactions(filter)
+            .map(|tx| {
+                Ok(ChangeLogEntry {
+                    tx_id: tx.tx.clone(),
+                    author: tx.tx.session_id.author().to_string(),
+                    made_at: tx.made_at,
+                    private: tx.private,
+                    changes: serde_json::from_str(&tx.changes_json)?,
+                    meta: tx.meta.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Apply the changes of a CoMap transaction to its content.
+pub(crate) fn apply_map_changes(
+    object: &mut Map<String, JsonValue>,
+    changes_json: &str,
+) -> Result<(), CoJsonCoreError> {
+    for change in parse_map_changes(changes_json)? {
+        match change {
+            MapOpPayload::Set { key, value } => {
+                object.insert(key, value);
+            }
+            MapOpPayload::Del { key } => {
+                object.remove(&key);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::test_session;
+    use crate::core::{new_random_key_secret, SignerSecret, TransactionMode};
+    use serde_json::json;
+
+    fn session(seed: u8) -> (SessionID, SessionLogInternal, SignerSecret) {
+        test_session(&CoID("co_zHistory".to_string()), seed)
+    }
+
+    fn add(log: &mut SessionLogInternal, secret: &SignerSecret, changes: JsonValue, made_at: u64) {
+        log.add_new_transaction(
+            &changes.to_string(),
+            TransactionMode::Trusting,
+            secret,
+            made_at,
+            None,
+        )
+        .unwrap();
+    }
+
+    fn op(session_id: &SessionID, tx_index: u32, change_idx: u32) -> JsonValue {
+        json!({"sessionID": session_id, "txIndex": tx_index, "changeIdx": change_idx})
+    }
+
+    #[test]
+    fn test_map_at_time_and_known_state() {
+        let (alice, mut alice_log, alice_secret) = session(1);
+        let (bob, mut bob_log, bob_secret) = session(2);
+        add(
+            &mut alice_log,
+            &alice_secret,
+            json!([{"op": "set", "key": "title", "value": "draft"}]),
+            10,
+        );
+        add(
+            &mut bob_log,
+            &bob_secret,
+            json!([{"op": "set", "key": "title", "value": "final"}]),
+            20,
+        );
+        add(
+            &mut alice_log,
+            &alice_secret,
+            json!([{"op": "set", "key": "done", "value": true}, {"op": "del", "key": "title"}]),
+            30,
+        );
+        let history = CoValueHistory::from_session_logs(
+            None,
+            [(&alice, &alice_log), (&bob, &bob_log)],
+            |_| None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            history.map_at(&HistoryFilter::AtTime(5)).unwrap(),
+            Map::new()
+        );
+        assert_eq!(
+            JsonValue::Object(history.map_at(&HistoryFilter::AtTime(25)).unwrap()),
+            json!({"title": "final"})
+        );
+        assert_eq!(
+            JsonValue::Object(history.map_at(&HistoryFilter::All).unwrap()),
+            json!({"done": true})
+        );
+
+        // Without Bob's session, and only Alice's first transaction
+        let known = KnownStateSessions::from([(alice.clone(), 1)]);
+        assert_eq!(
+            JsonValue::Object(history.map_at(&HistoryFilter::KnownState(known)).unwrap()),
+            json!({"title": "draft"})
+        );
+        assert_eq!(alice_log.transactions_json().len(), 2);
+    }
+
+    #[test]
+    fn test_extend_reads_new_transactions() {
+        let (alice, mut alice_log, alice_secret) = session(1);
+        let (bob, mut bob_log, bob_secret) = session(2);
+        add(
+            &mut alice_log,
+            &alice_secret,
+            json!([{"op": "set", "key": "a", "value": 1}]),
+            20,
+        );
+        let mut history = CoValueHistory::from_session_logs(
+            None,
+            [(&alice, &alice_log), (&bob, &bob_log)],
+            |_| None,
+        )
+        .unwrap();
+
+        add(
+            &mut bob_log,
+            &bob_secret,
+            json!([{"op": "set", "key": "a", "value": 2}]),
+            10,
+        );
+        add(
+            &mut alice_log,
+            &alice_secret,
+            json!([{"op": "set", "key": "b", "value": 3}]),
+            20,
+        );
+        let sessions = [(&alice, &alice_log), (&bob, &bob_log)];
+        assert_eq!(history.extend(None, sessions, |_| None).unwrap(), 2);
+        assert_eq!(history.extend(None, sessions, |_| None).unwrap(), 0);
+
+        let fresh = CoValueHistory::from_session_logs(None, sessions, |_| None).unwrap();
+        assert!(history
+            .transactions(&HistoryFilter::All)
+            .eq(fresh.transactions(&HistoryFilter::All)));
+        assert_eq!(
+            JsonValue::Object(history.map_at(&HistoryFilter::All).unwrap()),
+            json!({"a": 1, "b": 3})
+        );
+    }
+
+    #[test]
+    fn test_list_and_plain_text_at() {
+        let (alice, mut log, secret) = session(1);
+        // "a" then "c" after it, then "b" after "a" later, so it comes first
+        add(
+            &mut log,
+            &secret,
+            json!([
+                {"op": "app", "value": "a", "after": "start"},
+                {"op": "app", "value": "c", "after": op(&alice, 0, 0)},
+            ]),
+            1,
+        );
+        add(
+            &mut log,
+            &secret,
+            json!([{"op": "app", "value": "b", "after": op(&alice, 0, 0)}]),
+            2,
+        );
+        add(
+            &mut log,
+            &secret,
+            json!([{"op": "pre", "value": "z", "before": "end"}]),
+            3,
+        );
+        add(
+            &mut log,
+            &secret,
+            json!([{"op": "del", "insertion": op(&alice, 0, 1)}]),
+            4,
+        );
+        let history = CoValueHistory::from_session_logs(None, [(&alice, &log)], |_| None).unwrap();
+
+        assert_eq!(history.plain_text_at(&HistoryFilter::All).unwrap(), "abz");
+        assert_eq!(
+            history.plain_text_at(&HistoryFilter::AtTime(3)).unwrap(),
+            "abcz"
+        );
+        let known = KnownStateSessions::from([(alice.clone(), 2)]);
+        assert_eq!(
+            history
+                .plain_text_at(&HistoryFilter::KnownState(known))
+                .unwrap(),
+            "abc"
+        );
+
+        let entries = history.list_at(&HistoryFilter::All).unwrap();
+        assert_eq!(entries[1].made_at, 2);
+        assert_eq!(entries[1].op_id, OpID::new(&history.transactions[1].tx, 0));
+    }
+
+    #[test]
+    fn test_change_log() {
+        let (alice, mut log, secret) = session(1);
+        let (key_id, key_secret) = new_random_key_secret();
+        add(
+            &mut log,
+            &secret,
+            json!([{"op": "set", "key": "a", "value": 1}]),
+            10,
+        );
+        log.add_new_transaction(
+            r#"[{"key":"a","op":"del"}]"#,
+            TransactionMode::Private {
+                key_id: key_id.clone(),
+                key_secret: key_secret.clone(),
+            },
+            &secret,
+            20,
+            None,
+        )
+        .unwrap();
+        let history =
+            CoValueHistory::from_session_logs(None, [(&alice, &log)], |_| Some(key_secret.clone()))
+                .unwrap();
+
+        let change_log = history.change_log(&HistoryFilter::All).unwrap();
+        assert_eq!(change_log.len(), 2);
+        assert_eq!(change_log[0].author, alice.author());
+        assert!(change_log[0].author.starts_with("sealer_zTest/signer_z"));
+        let exported = serde_json::to_value(&change_log[1]).unwrap();
+        assert_eq!(
+            exported,
+            json!({
+                "txID": {"sessionID": alice, "txIndex": 1},
+                "author": alice.author(),
+                "madeAt": 20,
+                "private": true,
+                "changes": [{"op": "del", "key": "a"}],
+            })
+        );
+    }
+}
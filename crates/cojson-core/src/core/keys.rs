@@ -1,4 +1,5 @@
-use crate::core::CoJsonCoreError;
+use crate::core::{stable_stringify, CoJsonCoreError};
+use crate::crypto::CryptoError;
 use ed25519_dalek::{Signature as Ed25519Signature, SigningKey, VerifyingKey};
 use serde::{Deserialize, Serialize};
 
@@ -17,6 +18,17 @@ impl From<VerifyingKey> for SignerID {
     }
 }
 
+impl TryFrom<&SignerID> for VerifyingKey {
+    type Error = CoJsonCoreError;
+    fn try_from(val: &SignerID) -> Result<Self, Self::Error> {
+        let key_bytes = decode_z(&val.0)?;
+        VerifyingKey::from_bytes(&key_bytes.try_into().map_err(
+            |e: Vec<u8>| CryptoError::InvalidKeyLength(32, e.len()),
+        )?)
+        .map_err(|_| CryptoError::InvalidVerifyingKey(val.0.clone()).into())
+    }
+}
+
 /// A secret signing key, encoded as "signerSecret_z" followed by base58-encoded private key bytes.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -36,7 +48,7 @@ impl TryFrom<&SignerSecret> for SigningKey {
     fn try_from(val: &SignerSecret) -> Result<Self, Self::Error> {
         let key_bytes = decode_z(&val.0)?;
         Ok(SigningKey::from_bytes(&key_bytes.try_into().map_err(
-            |e: Vec<u8>| CoJsonCoreError::InvalidKeyLength(32, e.len()),
+            |e: Vec<u8>| CryptoError::InvalidKeyLength(32, e.len()),
         )?))
     }
 }
@@ -62,7 +74,7 @@ impl TryFrom<&Signature> for Ed25519Signature {
         Ok(Ed25519Signature::from_bytes(
             &signature_bytes
                 .try_into()
-                .map_err(|e: Vec<u8>| CoJsonCoreError::InvalidKeyLength(64, e.len()))?,
+                .map_err(|e: Vec<u8>| CryptoError::InvalidKeyLength(64, e.len()))?,
         ))
     }
 }
@@ -98,15 +110,40 @@ impl TryFrom<&KeySecret> for [u8; 32] {
         let key_bytes = decode_z(&val.0)?;
         key_bytes
             .try_into()
-            .map_err(|e: Vec<u8>| CoJsonCoreError::InvalidKeyLength(32, e.len()))
+            .map_err(|e: Vec<u8>| CryptoError::InvalidKeyLength(32, e.len()).into())
     }
 }
 
-/// A unique identifier for a CoValue.
+/// A public sealing key, encoded as "sealer_z" followed by base58-encoded X25519 public key bytes.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
+pub struct SealerID(pub String);
+
+/// A secret sealing key, encoded as "sealerSecret_z" followed by base58-encoded X25519 private key bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SealerSecret(pub String);
+
+/// A unique identifier for a CoValue.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct CoID(pub String);
 
+/// Length of the header hash a CoID is derived from, matching `shortHashLength` on the TS side.
+pub const SHORT_HASH_LENGTH: usize = 19;
+
+impl CoID {
+    /// Derive the ID of a CoValue from its header, matching `idforHeader` on the TS side:
+    /// "co_z" followed by the base58-encoded first 19 bytes of the BLAKE3 hash of the stable-stringified header.
+    pub fn for_header(header: &serde_json::Value) -> Self {
+        let hash = blake3::hash(stable_stringify(header).as_bytes());
+        CoID(format!(
+            "co_z{}",
+            bs58::encode(&hash.as_bytes()[..SHORT_HASH_LENGTH]).into_string()
+        ))
+    }
+}
+
 /// Decode a base58 string with a "_z" prefix.
 /// Used for decoding keys and other encoded values.
 pub(crate) fn decode_z(value: &str) -> Result<Vec<u8>, CoJsonCoreError> {
@@ -116,7 +153,7 @@ pub(crate) fn decode_z(value: &str) -> Result<Vec<u8>, CoJsonCoreError> {
         + 2;
     bs58::decode(&value[prefix_end..])
         .into_vec()
-        .map_err(CoJsonCoreError::InvalidBase58)
+        .map_err(|err| CryptoError::Base58Error(err.to_string()).into())
 }
 
 #[cfg(test)]
@@ -186,4 +223,28 @@ mod tests {
         let decoded_bytes = decode_z(&hash.0).unwrap();
         assert_eq!(decoded_bytes, blake3_hash.as_bytes());
     }
+
+    #[test]
+    fn test_co_id_for_header() {
+        let header: serde_json::Value = serde_json::from_str(
+            r#"{"type":"comap","ruleset":{"type":"unsafeAllowAll"},"meta":null,"uniqueness":"abc"}"#,
+        )
+        .unwrap();
+        let reordered: serde_json::Value = serde_json::from_str(
+            r#"{"uniqueness":"abc","meta":null,"ruleset":{"type":"unsafeAllowAll"},"type":"comap"}"#,
+        )
+        .unwrap();
+
+        let id = CoID::for_header(&header);
+        assert!(id.0.starts_with("co_z"));
+        assert_eq!(decode_z(&id.0).unwrap().len(), SHORT_HASH_LENGTH);
+
+        // Key order doesn't matter, like with stableStringify
+        assert_eq!(CoID::for_header(&reordered), id);
+
+        let expected_hash = blake3::hash(
+            br#"{"meta":null,"ruleset":{"type":"unsafeAllowAll"},"type":"comap","uniqueness":"abc"}"#,
+        );
+        assert_eq!(decode_z(&id.0).unwrap(), &expected_hash.as_bytes()[..SHORT_HASH_LENGTH]);
+    }
 }
@@ -15,11 +15,25 @@ impl NonceGenerator {
         }
     }
 
+    pub fn co_id(&self) -> &CoID {
+        &self.co_id
+    }
+
+    pub fn session_id(&self) -> &SessionID {
+        &self.session_id
+    }
+
     pub fn get_nonce(&self, tx_index: u32) -> [u8; 24] {
         let nonce_material = self.generate_nonce_material(tx_index);
         self.generate_json_nonce(&nonce_material)
     }
 
+    /// Get the serialized nonce material for a given transaction index,
+    /// for callers that derive the nonce themselves (e.g. through a CryptoProvider).
+    pub fn nonce_material(&self, tx_index: u32) -> Vec<u8> {
+        serde_json::to_vec(&self.generate_nonce_material(tx_index)).unwrap()
+    }
+
     /// Generate the nonce material (as JSON) for a given transaction index.
     /// This ensures each transaction gets a unique nonce based on session and index.
     fn generate_nonce_material(&self, tx_index: u32) -> JsonValue {
@@ -0,0 +1,141 @@
+use crate::core::{CoID, CoJsonCoreError, SessionID, TransactionID};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// A single CoMap operation, as stored in a transaction's changes array.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum MapOpPayload {
+    Set { key: String, value: JsonValue },
+    Del { key: String },
+}
+
+impl MapOpPayload {
+    /// The CoMap key this operation targets.
+    pub fn key(&self) -> &str {
+        match self {
+            MapOpPayload::Set { key, .. } => key,
+            MapOpPayload::Del { key } => key,
+        }
+    }
+}
+
+/// The ID of a single change: its transaction and its index among the transaction's changes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OpID {
+    #[serde(rename = "sessionID")]
+    pub session_id: SessionID,
+    #[serde(rename = "txIndex")]
+    pub tx_index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<CoID>,
+    #[serde(rename = "changeIdx")]
+    pub change_idx: u32,
+}
+
+impl OpID {
+    pub fn new(tx: &TransactionID, change_idx: u32) -> Self {
+        Self {
+            session_id: tx.session_id.clone(),
+            tx_index: tx.tx_index,
+            branch: tx.branch.clone(),
+            change_idx,
+        }
+    }
+}
+
+/// Either end of a CoList.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListEdge {
+    Start,
+    End,
+}
+
+/// What a CoList insertion is placed next to: another insertion, or an end of the list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ListAnchor {
+    Edge(ListEdge),
+    Op(OpID),
+}
+
+/// A single CoList (or CoPlainText) operation, as stored in a transaction's changes array.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum ListOpPayload {
+    Pre { value: JsonValue, before: ListAnchor },
+    App { value: JsonValue, after: ListAnchor },
+    Del { insertion: OpID },
+}
+
+/// Serialize a JSON value the same way `stableStringify` does on the TS side, e.g. before
+/// hashing, signing or encrypting it.
+pub fn stable_stringify(value: &JsonValue) -> String {
+    // serde_json::Map is backed by a BTreeMap, so object keys come out sorted.
+    value.to_string()
+}
+
+/// Serialize a list of changes like `stable_stringify`, so the resulting string can be passed
+/// straight to `add_new_transaction`.
+pub fn stringify_changes<T: Serialize>(changes: &[T]) -> Result<String, CoJsonCoreError> {
+    Ok(stable_stringify(&serde_json::to_value(changes)?))
+}
+
+/// Serialize transaction meta like `stable_stringify`.
+pub fn stringify_meta<T: Serialize>(meta: &T) -> Result<String, CoJsonCoreError> {
+    Ok(stable_stringify(&serde_json::to_value(meta)?))
+}
+
+/// Parse a decrypted changes JSON string into CoMap operations.
+pub fn parse_map_changes(changes_json: &str) -> Result<Vec<MapOpPayload>, CoJsonCoreError> {
+    Ok(serde_json::from_str(changes_json)?)
+}
+
+/// Parse a decrypted changes JSON string into CoList operations.
+pub fn parse_list_changes(changes_json: &str) -> Result<Vec<ListOpPayload>, CoJsonCoreError> {
+    Ok(serde_json::from_str(changes_json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stringify_changes_sorts_keys() {
+        let changes = vec![
+            MapOpPayload::Set {
+                key: "readKey".to_string(),
+                value: JsonValue::String("key_z123".to_string()),
+            },
+            MapOpPayload::Del {
+                key: "old".to_string(),
+            },
+        ];
+
+        let json = stringify_changes(&changes).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"key":"readKey","op":"set","value":"key_z123"},{"key":"old","op":"del"}]"#
+        );
+        assert_eq!(parse_map_changes(&json).unwrap(), changes);
+    }
+
+    #[test]
+    fn test_list_changes_roundtrip() {
+        let json = r#"[{"after":"start","op":"app","value":"a"},{"before":{"changeIdx":0,"sessionID":"s","txIndex":1},"op":"pre","value":1},{"insertion":{"branch":"co_zB","changeIdx":2,"sessionID":"s","txIndex":0},"op":"del"}]"#;
+        let changes = parse_list_changes(json).unwrap();
+        assert_eq!(
+            changes[0],
+            ListOpPayload::App {
+                value: JsonValue::String("a".to_string()),
+                after: ListAnchor::Edge(ListEdge::Start),
+            }
+        );
+        assert!(matches!(
+            &changes[2],
+            ListOpPayload::Del { insertion } if insertion.branch.is_some()
+        ));
+        assert_eq!(stringify_changes(&changes).unwrap(), json);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::core::{CoID, CoJsonCoreError, MapChanges};
+
+/// A CoMap's content, as `CoValueHistory::map_at` reads it.
+pub type CoMapContent = Map<String, JsonValue>;
+
+/// The keys of a CoMap schema with their values, None for optional fields that aren't set.
+pub type CoMapFields = Vec<(&'static str, Option<JsonValue>)>;
+
+/// A Rust type a CoMap maps onto, usually implemented with `#[derive(CoMap)]`.
+pub trait CoMapSchema: Sized {
+    /// Read from a CoMap's content.
+    fn from_co_map(content: &CoMapContent) -> Result<Self, CoJsonCoreError>;
+
+    /// Every field's key and value.
+    fn co_map_fields(&self) -> Result<CoMapFields, CoJsonCoreError>;
+
+    /// The changes that set every field, for a new CoMap. Optional fields that aren't set are
+    /// left out.
+    fn to_map_changes(&self) -> Result<MapChanges, CoJsonCoreError> {
+        Ok(self
+            .co_map_fields()?
+            .into_iter()
+            .fold(MapChanges::new(), |changes, (key, value)| match value {
+                Some(value) => changes.set(key, value),
+                None => changes,
+            }))
+    }
+
+    /// The changes that update a CoMap read as `previous` to this, for the fields that differ.
+    /// Optional fields that are no longer set are deleted.
+    fn changes_since(&self, previous: &Self) -> Result<MapChanges, CoJsonCoreError> {
+        let previous = previous.co_map_fields()?;
+        let mut changes = MapChanges::new();
+        for ((key, value), (_, previous)) in self.co_map_fields()?.into_iter().zip(previous) {
+            if value == previous {
+                continue;
+            }
+            changes = match value {
+                Some(value) => changes.set(key, value),
+                None => changes.delete(key),
+            };
+        }
+        Ok(changes)
+    }
+}
+
+fn invalid_field(key: &str, reason: impl fmt::Display) -> CoJsonCoreError {
+    CoJsonCoreError::InvalidCoMapField {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Read a required field of a CoMap.
+pub fn read_co_map_field<T: DeserializeOwned>(
+    content: &CoMapContent,
+    key: &str,
+) -> Result<T, CoJsonCoreError> {
+    let value = content
+        .get(key)
+        .ok_or_else(|| invalid_field(key, "missing"))?;
+    T::deserialize(value).map_err(|err| invalid_field(key, err))
+}
+
+/// Read an optional field of a CoMap, None if it isn't set.
+pub fn read_optional_co_map_field<T: DeserializeOwned>(
+    content: &CoMapContent,
+    key: &str,
+) -> Result<Option<T>, CoJsonCoreError> {
+    content
+        .get(key)
+        .map(|value| Option::<T>::deserialize(value).map_err(|err| invalid_field(key, err)))
+        .transpose()
+        .map(Option::flatten)
+}
+
+/// Serialize a field's value for a CoMap.
+pub fn write_co_map_field<T: Serialize>(
+    key: &str,
+    value: &T,
+) -> Result<Option<JsonValue>, CoJsonCoreError> {
+    serde_json::to_value(value)
+        .map(Some)
+        .map_err(|err| invalid_field(key, err))
+}
+
+/// Serialize an optional field's value for a CoMap, None if it isn't set.
+pub fn write_optional_co_map_field<T: Serialize>(
+    key: &str,
+    value: &Option<T>,
+) -> Result<Option<JsonValue>, CoJsonCoreError> {
+    match value {
+        Some(value) => write_co_map_field(key, value),
+        None => Ok(None),
+    }
+}
+
+/// A reference to another CoValue, typed by the schema of what it points to. Stored as the
+/// plain `co_z...` ID.
+pub struct CoRef<T> {
+    id: CoID,
+    _schema: PhantomData<fn() -> T>,
+}
+
+impl<T> CoRef<T> {
+    pub fn new(id: CoID) -> Self {
+        Self {
+            id,
+            _schema: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> &CoID {
+        &self.id
+    }
+}
+
+impl<T> From<CoRef<T>> for CoID {
+    fn from(reference: CoRef<T>) -> Self {
+        reference.id
+    }
+}
+
+impl<T> Clone for CoRef<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.id.clone())
+    }
+}
+
+impl<T> fmt::Debug for CoRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CoRef").field(&self.id.0).finish()
+    }
+}
+
+impl<T> PartialEq for CoRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for CoRef<T> {}
+
+impl<T> Hash for CoRef<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> Serialize for CoRef<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for CoRef<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = CoID::deserialize(deserializer)?;
+        if !id.0.starts_with("co_z") {
+            return Err(de::Error::custom(format!("{} is not a CoValue ID", id.0)));
+        }
+        Ok(Self::new(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Task {
+        title: String,
+        done: Option<bool>,
+        parent: CoRef<Task>,
+    }
+
+    impl CoMapSchema for Task {
+        fn from_co_map(content: &CoMapContent) -> Result<Self, CoJsonCoreError> {
+            Ok(Self {
+                title: read_co_map_field(content, "title")?,
+                done: read_optional_co_map_field(content, "done")?,
+                parent: read_co_map_field(content, "parent")?,
+            })
+        }
+
+        fn co_map_fields(&self) -> Result<CoMapFields, CoJsonCoreError> {
+            Ok(vec![
+                ("title", write_co_map_field("title", &self.title)?),
+                ("done", write_optional_co_map_field("done", &self.done)?),
+                ("parent", write_co_map_field("parent", &self.parent)?),
+            ])
+        }
+    }
+
+    fn content(value: JsonValue) -> CoMapContent {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_read_and_update() {
+        let task =
+            Task::from_co_map(&content(json!({"title": "a", "parent": "co_zParent"}))).unwrap();
+        assert_eq!(task.done, None);
+        assert_eq!(task.parent.id(), &CoID("co_zParent".to_string()));
+        assert_eq!(
+            task.to_map_changes().unwrap().build().unwrap(),
+            r#"[{"key":"title","op":"set","value":"a"},{"key":"parent","op":"set","value":"co_zParent"}]"#
+        );
+
+        let previous = Task {
+            title: "a".to_string(),
+            done: Some(true),
+            parent: task.parent.clone(),
+        };
+        assert_eq!(
+            task.changes_since(&previous).unwrap().build().unwrap(),
+            r#"[{"key":"done","op":"del"}]"#
+        );
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let err = Task::from_co_map(&content(json!({"parent": "co_zParent"}))).err();
+        assert!(matches!(
+            err,
+            Some(CoJsonCoreError::InvalidCoMapField { ref key, ref reason }) if key == "title" && reason == "missing"
+        ));
+        let err = Task::from_co_map(&content(json!({"title": "a", "parent": "nope"}))).err();
+        assert!(matches!(
+            err,
+            Some(CoJsonCoreError::InvalidCoMapField { ref key, .. }) if key == "parent"
+        ));
+    }
+}
@@ -1,24 +1,48 @@
 
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
-use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
-use salsa20::{
-    cipher::{Key, KeyIvInit, StreamCipher},
-    XSalsa20,
-};
 use serde::{Deserialize, Serialize};
 use serde_json::{value::RawValue, Number, Value as JsonValue};
-use crate::core::{CryptoCache, NonceGenerator, CoJsonCoreError};
-use crate::core::keys::{SignerID, SignerSecret, Signature, KeyID, KeySecret, CoID, decode_z};
+use crate::core::{
+    CryptoProvider, DefaultCryptoProvider, IncrementalHasher, NonceGenerator, CoJsonCoreError,
+};
+use crate::core::keys::{SignerID, SignerSecret, Signature, KeyID, KeySecret, CoID};
+use crate::core::{
+    EquivocationEvidence, SessionNewContent, SignedSessionHash, TransactionLimits,
+};
+use crate::core::equivocation::session_hash;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SessionID(pub String);
 
+impl SessionID {
+    /// The account or agent that made the session, like `accountOrAgentIDfromSessionID`.
+    pub fn author(&self) -> &str {
+        self.0.split("_session").next().unwrap_or_default()
+    }
+
+    /// The signer of a session created directly by an agent (`sealer_z.../signer_z..._session_z...`).
+    /// Returns None for account sessions, whose signer has to be looked up in the account.
+    pub fn agent_signer_id(&self) -> Option<SignerID> {
+        let (agent_id, _) = self.0.split_once("_session_")?;
+        let (sealer_id, signer_id) = agent_id.split_once('/')?;
+        if !sealer_id.starts_with("sealer_z") || !signer_id.starts_with("signer_z") {
+            return None;
+        }
+        Some(SignerID(signer_id.to_string()))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TransactionID {
     #[serde(rename = "sessionID")]
     pub session_id: SessionID,
     #[serde(rename = "txIndex")]
     pub tx_index: u32,
+    /// The branch the transaction was made in, for transactions of branched CoValues.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<CoID>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -68,58 +92,244 @@ pub enum TransactionMode {
 
 
 
+/// Check that `signature` is `signer_id`'s signature over the session hash in `hasher`.
+/// Sessions without a signer never verify.
+pub(crate) fn verify_session_hash<C: CryptoProvider>(
+    crypto: &C,
+    hasher: &C::Hasher,
+    signer_id: Option<&SignerID>,
+    signature: &Signature,
+) -> Result<(), CoJsonCoreError> {
+    let new_hash_encoded_stringified = format!(
+        "\"hash_z{}\"",
+        bs58::encode(hasher.finalize()).into_string()
+    );
+
+    // Verify the signature using the public key, if present.
+    let verified = match signer_id {
+        Some(signer_id) => crypto.verify(
+            new_hash_encoded_stringified.as_bytes(),
+            signature,
+            signer_id,
+        )?,
+        // No public key available for verification.
+        None => false,
+    };
+
+    if !verified {
+        return Err(CoJsonCoreError::SignatureVerification(
+            new_hash_encoded_stringified.replace("\"", ""),
+        ));
+    }
+    Ok(())
+}
+
+/// The size a transaction counts towards signature checkpoints with, like `getTransactionSize`:
+/// the length of its changes (or encrypted changes) as a JS string.
+pub fn transaction_size(tx_json: &str) -> usize {
+    #[derive(Deserialize)]
+    struct Changes<'a> {
+        #[serde(borrow)]
+        changes: Option<Cow<'a, str>>,
+        #[serde(rename = "encryptedChanges", borrow)]
+        encrypted_changes: Option<Cow<'a, str>>,
+    }
+
+    let changes = serde_json::from_str::<Changes>(tx_json)
+        .ok()
+        .and_then(|tx| tx.changes.or(tx.encrypted_changes));
+    match changes {
+        Some(changes) => changes.encode_utf16().count(),
+        None => tx_json.len(),
+    }
+}
+
+/// Count a batch of `size` bytes towards the next signature checkpoint, returning whether the
+/// batch's signature becomes one. Storage keeps its checkpoints with this too, so they match the
+/// ones of the session log.
+pub fn reaches_checkpoint(
+    size_since_checkpoint: &mut usize,
+    size: usize,
+    limits: &TransactionLimits,
+) -> bool {
+    *size_since_checkpoint += size;
+    if *size_since_checkpoint > limits.recommended_chunk_size {
+        *size_since_checkpoint = 0;
+        return true;
+    }
+    false
+}
+
 #[derive(Clone)]
-pub struct SessionLogInternal {
-    public_key: Option<VerifyingKey>,
-    hasher: blake3::Hasher,
+pub struct SessionLogInternal<C: CryptoProvider = DefaultCryptoProvider> {
+    signer_id: Option<SignerID>,
+    hasher: C::Hasher,
     transactions_json: Vec<String>,
     last_signature: Option<Signature>,
+    /// Signatures kept after roughly every `recommended_chunk_size` bytes, keyed by the index of
+    /// the transaction they're after, like `signatureAfter` in `SessionMap.ts`.
+    signature_after: BTreeMap<u32, Signature>,
+    tx_size_since_checkpoint: usize,
+    limits: TransactionLimits,
     nonce_generator: NonceGenerator,
-    crypto_cache: CryptoCache,
+    crypto: C,
 }
 
 
 impl SessionLogInternal {
     /// Create a new session log, optionally with a public key for signature verification.
     pub fn new(co_id: CoID, session_id: SessionID, signer_id: Option<SignerID>) -> Self {
-        let hasher = blake3::Hasher::new();
-
-        // If a signer_id is provided, decode and parse it as a VerifyingKey.
-        let public_key = match signer_id {
-            Some(signer_id) => Some(
-                VerifyingKey::try_from(
-                    decode_z(&signer_id.0)
-                        .expect("Invalid public key")
-                        .as_slice(),
-                )
-                .expect("Invalid public key"),
-            ),
-            None => None,
-        };
+        Self::with_crypto(co_id, session_id, signer_id, DefaultCryptoProvider::new())
+    }
+}
 
+impl<C: CryptoProvider> SessionLogInternal<C> {
+    /// Create a new session log that uses the given crypto provider for hashing,
+    /// signing, verification and encryption.
+    pub fn with_crypto(
+        co_id: CoID,
+        session_id: SessionID,
+        signer_id: Option<SignerID>,
+        crypto: C,
+    ) -> Self {
         Self {
-            public_key,
-            hasher,
+            signer_id,
+            hasher: crypto.new_hasher(),
             transactions_json: Vec::new(),
             last_signature: None,
+            signature_after: BTreeMap::new(),
+            tx_size_since_checkpoint: 0,
+            limits: TransactionLimits::default(),
             nonce_generator: NonceGenerator::new(co_id, session_id),
-            crypto_cache: CryptoCache::new(),
+            crypto,
         }
     }
 
+    /// Use other size limits than the defaults from `config.ts`.
+    pub fn with_limits(mut self, limits: TransactionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Resume a log from a hash state and checkpoints validated elsewhere, holding
+    /// `transactions_json`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_parts(
+        nonce_generator: NonceGenerator,
+        signer_id: Option<SignerID>,
+        hasher: C::Hasher,
+        transactions_json: Vec<String>,
+        last_signature: Option<Signature>,
+        signature_after: BTreeMap<u32, Signature>,
+        tx_size_since_checkpoint: usize,
+        limits: TransactionLimits,
+        crypto: C,
+    ) -> Self {
+        Self {
+            signer_id,
+            hasher,
+            transactions_json,
+            last_signature,
+            signature_after,
+            tx_size_since_checkpoint,
+            limits,
+            nonce_generator,
+            crypto,
+        }
+    }
+
+    pub(crate) fn hasher(&self) -> &C::Hasher {
+        &self.hasher
+    }
+
+    pub(crate) fn nonce_generator(&self) -> &NonceGenerator {
+        &self.nonce_generator
+    }
+
+    pub(crate) fn tx_size_since_checkpoint(&self) -> usize {
+        self.tx_size_since_checkpoint
+    }
+
+    /// Get a reference to the crypto provider used by this session log.
+    pub fn crypto(&self) -> &C {
+        &self.crypto
+    }
+
     /// Get a reference to the list of serialized transaction JSON strings.
     pub fn transactions_json(&self) -> &Vec<String> {
         &self.transactions_json
     }
 
+    /// The ID the next transaction added to this session will get.
+    pub fn next_transaction_id(&self) -> TransactionID {
+        TransactionID {
+            session_id: self.nonce_generator.session_id().clone(),
+            tx_index: self.transactions_json.len() as u32,
+            branch: None,
+        }
+    }
+
     /// Get the last signature, if any.
     pub fn last_signature(&self) -> Option<&Signature> {
         self.last_signature.as_ref()
     }
 
+    /// Get the signature checkpoints, keyed by the index of the transaction they're after.
+    pub fn signature_after(&self) -> &BTreeMap<u32, Signature> {
+        &self.signature_after
+    }
+
+    pub fn limits(&self) -> &TransactionLimits {
+        &self.limits
+    }
+
+    /// Count transactions towards the next checkpoint, keeping `signature` as one once the
+    /// recommended chunk size is exceeded.
+    fn track_checkpoint(&mut self, size: usize, signature: &Signature) {
+        if reaches_checkpoint(&mut self.tx_size_since_checkpoint, size, &self.limits) {
+            let last_idx = self.transactions_json.len() as u32 - 1;
+            self.signature_after.insert(last_idx, signature.clone());
+        }
+    }
+
+    /// Split the transactions from index `after` on into pieces to send, each ending at a
+    /// signature checkpoint or at the end of the session, like `newContentSince` does.
+    ///
+    /// Pieces stay around `recommended_chunk_size`, except where single transactions are larger.
+    pub fn content_pieces(&self, after: u32) -> Vec<SessionNewContent> {
+        let mut pieces = Vec::new();
+        let Some(last_signature) = &self.last_signature else {
+            return pieces;
+        };
+        let end = self.transactions_json.len() as u32;
+
+        let mut start = after;
+        while start < end {
+            let (piece_end, signature) = match self.signature_after.range(start..end).next() {
+                Some((idx, signature)) => (idx + 1, signature),
+                None => (end, last_signature),
+            };
+            pieces.push(SessionNewContent {
+                after: start,
+                new_transactions: self.transactions_json[start as usize..piece_end as usize]
+                    .iter()
+                    .map(|tx| RawValue::from_string(tx.clone()).expect("transactions are valid JSON"))
+                    .collect(),
+                last_signature: signature.clone(),
+            });
+            start = piece_end;
+        }
+        pieces
+    }
+
+    /// Get the signer new transactions are verified against, if any.
+    pub fn signer_id(&self) -> Option<&SignerID> {
+        self.signer_id.as_ref()
+    }
+
     /// Compute the hash that would result after adding the given transactions.
     /// This is used for signature verification.
-    fn expected_hash_after(&self, transactions: &[Box<RawValue>]) -> blake3::Hasher {
+    fn expected_hash_after(&self, transactions: &[Box<RawValue>]) -> C::Hasher {
         let mut hasher = self.hasher.clone();
         for tx in transactions {
             hasher.update(tx.get().as_bytes());
@@ -136,42 +346,23 @@ impl SessionLogInternal {
         new_signature: &Signature,
         skip_verify: bool,
     ) -> Result<(), CoJsonCoreError> {
-        if !skip_verify {
-            // Compute the hash after adding the new transactions.
-            let hasher = self.expected_hash_after(&transactions);
-            let new_hash_encoded_stringified = format!(
-                "\"hash_z{}\"",
-                bs58::encode(hasher.finalize().as_bytes()).into_string()
-            );
-
-            // Verify the signature using the public key, if present.
-            if let Some(public_key) = self.public_key {
-                match public_key.verify(
-                    new_hash_encoded_stringified.as_bytes(),
-                    &(new_signature.try_into()?),
-                ) {
-                    Ok(()) => {}
-                    Err(_) => {
-                        return Err(CoJsonCoreError::SignatureVerification(
-                            new_hash_encoded_stringified.replace("\"", ""),
-                        ));
-                    }
-                }
-            } else {
-                // No public key available for verification.
-                return Err(CoJsonCoreError::SignatureVerification(
-                    new_hash_encoded_stringified.replace("\"", ""),
-                ));
-            }
+        // Compute the hash after adding the new transactions.
+        // This is tracked even when skipping verification, so later batches can still be verified.
+        let hasher = self.expected_hash_after(&transactions);
 
-            // Update the internal hasher state to the new hash.
-            self.hasher = hasher;
+        if !skip_verify {
+            verify_session_hash(&self.crypto, &hasher, self.signer_id.as_ref(), new_signature)?;
         }
 
+        // Update the internal hasher state to the new hash.
+        self.hasher = hasher;
+
         // Add the new transactions to the log.
-        for tx in transactions {
+        for tx in &transactions {
             self.transactions_json.push(tx.get().to_string());
         }
+        let size = transactions.iter().map(|tx| transaction_size(tx.get())).sum();
+        self.track_checkpoint(size, new_signature);
 
         // Update the last signature.
         self.last_signature = Some(new_signature.clone());
@@ -179,6 +370,98 @@ impl SessionLogInternal {
         Ok(())
     }
 
+    /// Check that a batch of transactions starting at index `after` agrees with what we have
+    /// accepted already, before skipping the part we have.
+    ///
+    /// If it disagrees and `signature` is still valid for it, the signer has signed two forks of
+    /// this session, and the error carries both signed hashes as evidence. Without a signature
+    /// of ours where their batch ends to compare with, it's only a `SessionConflict`. Batches
+    /// that don't overlap with ours, and sessions without a signer, can't be checked and pass.
+    pub fn check_consistent(
+        &self,
+        after: u32,
+        transactions: &[Box<RawValue>],
+        signature: &Signature,
+    ) -> Result<(), CoJsonCoreError> {
+        let have = self.transactions_json.len();
+        let after = after as usize;
+        let Some(signer_id) = &self.signer_id else {
+            return Ok(());
+        };
+        if after >= have {
+            return Ok(());
+        }
+        let Some(fork) = transactions
+            .iter()
+            .zip(&self.transactions_json[after..])
+            .position(|(theirs, ours)| theirs.get() != ours)
+        else {
+            return Ok(());
+        };
+        // Replay the shared prefix, then their transactions on top of it
+        let fork_index = after + fork;
+        let mut prefix = self.crypto.new_hasher();
+        for tx in &self.transactions_json[..fork_index] {
+            prefix.update(tx.as_bytes());
+        }
+        let mut hasher = prefix.clone();
+        for tx in &transactions[fork..] {
+            hasher.update(tx.get().as_bytes());
+        }
+        let conflicting_hash = session_hash(&hasher);
+        let verified = self.crypto.verify(
+            format!("\"{conflicting_hash}\"").as_bytes(),
+            signature,
+            signer_id,
+        )?;
+        if !verified {
+            return Err(CoJsonCoreError::SignatureVerification(conflicting_hash));
+        }
+
+        // Evidence compares two signatures over the same number of transactions, so it needs
+        // one of ours where their batch ends
+        let tx_count = after + transactions.len();
+        let accepted_signature = if tx_count == have {
+            self.last_signature.as_ref()
+        } else {
+            self.signature_after.get(&(tx_count as u32 - 1))
+        };
+        let Some(accepted_signature) = accepted_signature else {
+            return Err(CoJsonCoreError::SessionConflict {
+                session_id: self.nonce_generator.session_id().clone(),
+                tx_index: fork_index as u32,
+            });
+        };
+        let mut accepted_hasher = prefix.clone();
+        for tx in &self.transactions_json[fork_index..tx_count] {
+            accepted_hasher.update(tx.as_bytes());
+        }
+
+        Err(CoJsonCoreError::Equivocation(Box::new(EquivocationEvidence {
+            co_id: self.nonce_generator.co_id().clone(),
+            session_id: self.nonce_generator.session_id().clone(),
+            signer_id: signer_id.clone(),
+            fork_index: fork_index as u32,
+            prefix_hash: session_hash(&prefix),
+            prefix_transactions: self.transactions_json[..fork_index].to_vec(),
+            accepted: SignedSessionHash {
+                tx_count: tx_count as u32,
+                hash: session_hash(&accepted_hasher),
+                signature: accepted_signature.clone(),
+            },
+            accepted_transactions: self.transactions_json[fork_index..tx_count].to_vec(),
+            conflicting: SignedSessionHash {
+                tx_count: tx_count as u32,
+                hash: conflicting_hash,
+                signature: signature.clone(),
+            },
+            conflicting_transactions: transactions[fork..]
+                .iter()
+                .map(|tx| tx.get().to_string())
+                .collect(),
+        })))
+    }
+
     /// Add a new transaction (private or trusting), encrypting as needed, and sign the new hash.
     /// Returns the new signature and the transaction object.
     pub fn add_new_transaction(
@@ -189,6 +472,14 @@ impl SessionLogInternal {
         made_at: u64,
         meta: Option<String>,
     ) -> Result<(Signature, Transaction), CoJsonCoreError> {
+        // Like `validateTxSizeLimitInBytes`, so it can still be synced.
+        if changes_json.len() > self.limits.max_tx_size {
+            return Err(CoJsonCoreError::TransactionTooLarge {
+                size: changes_json.len(),
+                max: self.limits.max_tx_size,
+            });
+        }
+
         // Build the transaction object depending on the mode.
         let new_tx = match mode {
             TransactionMode::Private { key_id, key_secret } => {
@@ -196,30 +487,25 @@ impl SessionLogInternal {
                 let tx_index = self.transactions_json.len() as u32;
 
                 // Generate a unique nonce for this transaction.
-                let nonce = self.nonce_generator.get_nonce(tx_index);
-
-                // Prepare the secret key bytes for encryption.
-                let key: Key<XSalsa20> = self.crypto_cache.get_xsalsa20_key(&key_secret)?;
+                let nonce = self.transaction_nonce(tx_index);
 
                 // Encrypt the changes JSON.
-                let mut ciphertext = changes_json.as_bytes().to_vec();
-                let mut cipher = XSalsa20::new(&key, &nonce.into());
-                cipher.apply_keystream(&mut ciphertext);
+                let ciphertext = self.crypto.encrypt(changes_json.as_bytes(), &key_secret, &nonce)?;
                 let encrypted_str = format!("encrypted_U{}", URL_SAFE.encode(&ciphertext));
 
                 // Optionally encrypt the meta field.
-                let encrypted_meta = meta.map(|meta| {
-                    let mut ciphertext = meta.as_bytes().to_vec();
-                    let mut cipher = XSalsa20::new(&key, &nonce.into());
-                    cipher.apply_keystream(&mut ciphertext);
+                let encrypted_meta = meta
+                    .map(|meta| {
+                        let ciphertext = self.crypto.encrypt(meta.as_bytes(), &key_secret, &nonce)?;
 
-                    let encrypted_meta = format!("encrypted_U{}", URL_SAFE.encode(&ciphertext));
+                        let encrypted_meta = format!("encrypted_U{}", URL_SAFE.encode(&ciphertext));
 
-                    Encrypted {
-                        value: encrypted_meta,
-                        _phantom: std::marker::PhantomData,
-                    }
-                });
+                        Ok::<_, CoJsonCoreError>(Encrypted {
+                            value: encrypted_meta,
+                            _phantom: std::marker::PhantomData,
+                        })
+                    })
+                    .transpose()?;
 
                 // Build the private transaction.
                 Transaction::Private(PrivateTransaction {
@@ -246,6 +532,7 @@ impl SessionLogInternal {
 
         // Serialize the transaction to JSON and update the hash state.
         let tx_json = serde_json::to_string(&new_tx).unwrap();
+        let size = transaction_size(&tx_json);
         self.hasher.update(tx_json.as_bytes());
         self.transactions_json.push(tx_json);
 
@@ -253,19 +540,25 @@ impl SessionLogInternal {
         let new_hash = self.hasher.finalize();
         let new_hash_encoded_stringified = format!(
             "\"hash_z{}\"",
-            bs58::encode(new_hash.as_bytes()).into_string()
+            bs58::encode(new_hash).into_string()
         );
-        let signing_key: SigningKey = self.crypto_cache.get_ed25519_signing_key(signer_secret)?;
-        let new_signature: Signature = signing_key
-            .sign(new_hash_encoded_stringified.as_bytes())
-            .into();
+        let new_signature = self
+            .crypto
+            .sign(new_hash_encoded_stringified.as_bytes(), signer_secret)?;
 
         // Update the last signature.
         self.last_signature = Some(new_signature.clone());
+        self.track_checkpoint(size, &new_signature);
 
         Ok((new_signature, new_tx))
     }
 
+    /// Derive the encryption nonce for the transaction at the given index.
+    fn transaction_nonce(&self, tx_index: u32) -> [u8; 24] {
+        self.crypto
+            .generate_nonce(&self.nonce_generator.nonce_material(tx_index))
+    }
+
     /// Decrypt the changes JSON for the transaction at the given index.
     /// Returns the decrypted string, or an error if decryption fails.
     pub fn decrypt_next_transaction_changes_json(
@@ -283,7 +576,7 @@ impl SessionLogInternal {
         match tx {
             Transaction::Private(private_tx) => {
                 // For private transactions, decrypt the encrypted_changes field.
-                let nonce = self.nonce_generator.get_nonce(tx_index);
+                let nonce = self.transaction_nonce(tx_index);
 
                 let encrypted_val = private_tx.encrypted_changes.value;
                 let prefix = "encrypted_U";
@@ -293,15 +586,12 @@ impl SessionLogInternal {
 
                 // Decode the base64-encoded ciphertext.
                 let ciphertext_b64 = &encrypted_val[prefix.len()..];
-                let mut ciphertext = URL_SAFE.decode(ciphertext_b64)?;
+                let ciphertext = URL_SAFE.decode(ciphertext_b64)?;
 
                 // Decrypt using XSalsa20.
-                let key = self.crypto_cache.get_xsalsa20_key(&key_secret)?;
-
-                let mut cipher = XSalsa20::new(&key, &nonce.into());
-                cipher.apply_keystream(&mut ciphertext);
+                let plaintext = self.crypto.decrypt(&ciphertext, &key_secret, &nonce)?;
 
-                Ok(String::from_utf8(ciphertext)?)
+                Ok(String::from_utf8(plaintext)?)
             }
             // For trusting transactions, just return the plain changes.
             Transaction::Trusting(trusting_tx) => Ok(trusting_tx.changes),
@@ -326,7 +616,7 @@ impl SessionLogInternal {
             Transaction::Private(private_tx) => {
                 // If meta is present, decrypt it.
                 if let Some(encrypted_meta) = private_tx.meta {
-                    let nonce = self.nonce_generator.get_nonce(tx_index);
+                    let nonce = self.transaction_nonce(tx_index);
 
                     let encrypted_val = encrypted_meta.value;
                     let prefix = "encrypted_U";
@@ -336,14 +626,12 @@ impl SessionLogInternal {
 
                     // Decode the base64-encoded ciphertext.
                     let ciphertext_b64 = &encrypted_val[prefix.len()..];
-                    let mut ciphertext = URL_SAFE.decode(ciphertext_b64)?;
+                    let ciphertext = URL_SAFE.decode(ciphertext_b64)?;
 
                     // Decrypt using XSalsa20.
-                    let key: Key<XSalsa20> = self.crypto_cache.get_xsalsa20_key(&key_secret)?;
-                    let mut cipher = XSalsa20::new(&key, &nonce.into());
-                    cipher.apply_keystream(&mut ciphertext);
+                    let plaintext = self.crypto.decrypt(&ciphertext, &key_secret, &nonce)?;
 
-                    Ok(Some(String::from_utf8(ciphertext)?))
+                    Ok(Some(String::from_utf8(plaintext)?))
                 } else {
                     Ok(None)
                 }
@@ -358,6 +646,8 @@ impl SessionLogInternal {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::{decode_z, ErrorCode, SealerID, SealerSecret};
+    use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
     use rand_core::OsRng;
     use std::{collections::HashMap, fs};
 
@@ -573,9 +863,7 @@ mod tests {
         let final_hash_encoded_stringified = format!("\"{}\"", final_hash_encoded);
 
         // 3. Check that the signature is valid for our generated key
-        assert!(session
-            .public_key
-            .expect("Public key should be present")
+        assert!(public_key
             .verify(
                 final_hash_encoded_stringified.as_bytes(),
                 &(&new_signature).try_into().unwrap()
@@ -796,9 +1084,7 @@ mod tests {
         let final_hash_encoded_stringified = format!("\"{}\"", final_hash_encoded);
 
         // 3. Check that the signature is valid for our generated key
-        assert!(session
-            .public_key
-            .expect("Public key should be present")
+        assert!(public_key
             .verify(
                 final_hash_encoded_stringified.as_bytes(),
                 &(&new_signature).try_into().unwrap()
@@ -977,9 +1263,7 @@ mod tests {
             "\"hash_z{}\"",
             bs58::encode(final_hash.as_bytes()).into_string()
         );
-        assert!(session
-            .public_key
-            .expect("Public key should be present")
+        assert!(public_key
             .verify(
                 final_hash_encoded_stringified.as_bytes(),
                 &(&signature).try_into().unwrap()
@@ -1012,7 +1296,7 @@ mod tests {
             None,
         );
 
-        assert!(session.public_key.is_none());
+        assert!(session.signer_id.is_none());
         assert!(session.last_signature.is_none());
         assert!(session.transactions_json.is_empty());
     }
@@ -1029,7 +1313,7 @@ mod tests {
             Some(public_key.into()),
         );
 
-        assert!(session.public_key.is_some());
+        assert!(session.signer_id.is_some());
         assert!(session.last_signature.is_none());
         assert!(session.transactions_json.is_empty());
     }
@@ -1065,6 +1349,196 @@ mod tests {
         assert_eq!(session.last_signature, Some(wrong_signature));
     }
 
+    #[test]
+    fn test_agent_signer_id() {
+        let session_id = SessionID("sealer_z123/signer_z456_session_zABC".to_string());
+        assert_eq!(session_id.agent_signer_id(), Some(SignerID("signer_z456".to_string())));
+
+        // Account sessions don't carry their signer
+        let session_id = SessionID("co_zAccount_session_zABC".to_string());
+        assert_eq!(session_id.agent_signer_id(), None);
+    }
+
+    #[test]
+    fn test_verify_after_skip_verify() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let public_key = signing_key.verifying_key();
+
+        let mut writer = SessionLogInternal::new(
+            CoID("co_test".to_string()),
+            SessionID("session_test".to_string()),
+            Some(public_key.into()),
+        );
+        let mut reader = writer.clone();
+
+        let mut batches = Vec::new();
+        for i in 0..2 {
+            let (signature, tx) = writer.add_new_transaction(
+                &format!(r#"[{{"op":"set","key":"a","value":{i}}}]"#),
+                TransactionMode::Trusting,
+                &signing_key.clone().into(),
+                1234567890,
+                None,
+            ).unwrap();
+            let tx_json = RawValue::from_string(serde_json::to_string(&tx).unwrap()).unwrap();
+            batches.push((tx_json, signature));
+        }
+
+        // Skipped batches still advance the running hash, so the next batch verifies
+        let (tx_json, signature) = batches.remove(0);
+        reader.try_add(vec![tx_json], &signature, true).unwrap();
+        let (tx_json, signature) = batches.remove(0);
+        reader.try_add(vec![tx_json], &signature, false).unwrap();
+        assert_eq!(reader.transactions_json(), writer.transactions_json());
+    }
+
+    fn add_trusting(
+        session: &mut SessionLogInternal,
+        signing_key: &SigningKey,
+        changes_json: &str,
+    ) -> (Box<RawValue>, Signature) {
+        let (signature, tx) = session.add_new_transaction(
+            changes_json,
+            TransactionMode::Trusting,
+            &signing_key.clone().into(),
+            1234567890,
+            None,
+        ).unwrap();
+        (RawValue::from_string(serde_json::to_string(&tx).unwrap()).unwrap(), signature)
+    }
+
+    #[test]
+    fn test_detect_equivocation() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let mut writer = SessionLogInternal::new(
+            CoID("co_test".to_string()),
+            SessionID("session_test".to_string()),
+            Some(signing_key.verifying_key().into()),
+        );
+        let mut reader = writer.clone();
+
+        let (tx0, _) = add_trusting(&mut writer, &signing_key, "[0]");
+        let mut fork = writer.clone();
+        let (tx1, signature) = add_trusting(&mut writer, &signing_key, "[1]");
+        reader.try_add(vec![tx0.clone(), tx1.clone()], &signature, false).unwrap();
+
+        // Resending what we have, or extending it, is consistent
+        reader.check_consistent(0, &[tx0.clone(), tx1.clone()], &signature).unwrap();
+        let (tx2, extension) = add_trusting(&mut writer, &signing_key, "[2]");
+        reader.check_consistent(1, &[tx1.clone(), tx2], &extension).unwrap();
+
+        // The same signer signing another transaction at index 1 is caught
+        let (forked_tx1, forked_signature) = add_trusting(&mut fork, &signing_key, "[\"x\"]");
+        let err = reader
+            .check_consistent(0, &[tx0.clone(), forked_tx1.clone()], &forked_signature)
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Equivocation);
+        let CoJsonCoreError::Equivocation(evidence) = err else {
+            unreachable!()
+        };
+        assert_eq!(evidence.fork_index, 1);
+        assert_eq!(evidence.prefix_transactions, [tx0.get()]);
+        assert_eq!(evidence.accepted.signature, signature);
+        assert_eq!(evidence.accepted_transactions, [tx1.get()]);
+        assert_eq!(evidence.conflicting.signature, forked_signature);
+        assert_eq!(evidence.conflicting_transactions, [forked_tx1.get()]);
+        assert!(evidence.verify(reader.crypto()).unwrap());
+
+        // A conflicting batch that isn't validly signed is just a bad signature
+        let err = reader
+            .check_consistent(1, std::slice::from_ref(&forked_tx1), &signature)
+            .unwrap_err();
+        assert!(matches!(err, CoJsonCoreError::SignatureVerification(_)));
+
+        // A longer fork has nothing of ours at the same length to compare with
+        let (forked_tx2, longer_signature) = add_trusting(&mut fork, &signing_key, "[\"y\"]");
+        let err = reader
+            .check_consistent(0, &[tx0, forked_tx1, forked_tx2], &longer_signature)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CoJsonCoreError::SessionConflict { tx_index: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_transaction_too_large() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let mut session = SessionLogInternal::new(
+            CoID("co_test".to_string()),
+            SessionID("session_test".to_string()),
+            Some(signing_key.verifying_key().into()),
+        )
+        .with_limits(TransactionLimits {
+            max_tx_size: 16,
+            ..Default::default()
+        });
+
+        let err = session.add_new_transaction(
+            r#"[{"op":"set","key":"too long"}]"#,
+            TransactionMode::Trusting,
+            &signing_key.clone().into(),
+            1234567890,
+            None,
+        ).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::TransactionTooLarge);
+        assert_eq!(err.to_string(), "Transaction is too large to be synced (31 > 16 bytes)");
+        assert!(session.transactions_json().is_empty());
+        assert!(session.last_signature().is_none());
+
+        add_trusting(&mut session, &signing_key, "[1]");
+        assert_eq!(session.transactions_json().len(), 1);
+    }
+
+    #[test]
+    fn test_signature_checkpoints_and_content_pieces() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let limits = TransactionLimits {
+            recommended_chunk_size: 100,
+            ..Default::default()
+        };
+        let new_session = || {
+            SessionLogInternal::new(
+                CoID("co_test".to_string()),
+                SessionID("session_test".to_string()),
+                Some(signing_key.verifying_key().into()),
+            )
+            .with_limits(limits)
+        };
+
+        // 40 bytes of changes each, so every third transaction exceeds the chunk size
+        let mut writer = new_session();
+        for i in 0..10 {
+            add_trusting(&mut writer, &signing_key, &format!("[\"{i}{}\"]", "x".repeat(35)));
+        }
+        assert_eq!(writer.signature_after().keys().copied().collect::<Vec<_>>(), [2, 5, 8]);
+
+        let pieces = writer.content_pieces(0);
+        let ranges: Vec<(u32, usize)> = pieces
+            .iter()
+            .map(|piece| (piece.after, piece.new_transactions.len()))
+            .collect();
+        assert_eq!(ranges, [(0, 3), (3, 3), (6, 3), (9, 1)]);
+        assert_eq!(&pieces[3].last_signature, writer.last_signature().unwrap());
+
+        // Every piece verifies on its own, and the receiver ends up with the same checkpoints
+        let mut reader = new_session();
+        for piece in pieces {
+            reader.try_add(piece.new_transactions, &piece.last_signature, false).unwrap();
+        }
+        assert_eq!(reader.transactions_json(), writer.transactions_json());
+        assert_eq!(reader.signature_after(), writer.signature_after());
+
+        // Starting in the middle of a chunk sends the rest of it
+        let ranges: Vec<(u32, usize)> = writer
+            .content_pieces(4)
+            .iter()
+            .map(|piece| (piece.after, piece.new_transactions.len()))
+            .collect();
+        assert_eq!(ranges, [(4, 2), (6, 3), (9, 1)]);
+        assert!(writer.content_pieces(10).is_empty());
+    }
+
     #[test]
     fn test_multiple_transactions() {
         let mut csprng = OsRng;
@@ -1149,6 +1623,103 @@ mod tests {
         assert!(decrypted_meta.is_none());
     }
 
+    #[test]
+    fn test_custom_crypto_provider() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        /// Delegates to the default provider, counting signatures and verifications.
+        #[derive(Clone, Default)]
+        struct CountingProvider {
+            inner: DefaultCryptoProvider,
+            signs: Rc<Cell<usize>>,
+            verifies: Rc<Cell<usize>>,
+        }
+
+        impl CryptoProvider for CountingProvider {
+            type Hasher = blake3::Hasher;
+
+            fn new_hasher(&self) -> Self::Hasher {
+                self.inner.new_hasher()
+            }
+
+            fn random_bytes(&self, buf: &mut [u8]) {
+                self.inner.random_bytes(buf)
+            }
+
+            fn sign(&self, message: &[u8], signer_secret: &SignerSecret) -> Result<Signature, CoJsonCoreError> {
+                self.signs.set(self.signs.get() + 1);
+                self.inner.sign(message, signer_secret)
+            }
+
+            fn verify(&self, message: &[u8], signature: &Signature, signer_id: &SignerID) -> Result<bool, CoJsonCoreError> {
+                self.verifies.set(self.verifies.get() + 1);
+                self.inner.verify(message, signature, signer_id)
+            }
+
+            fn encrypt(&self, plaintext: &[u8], key_secret: &KeySecret, nonce: &[u8; 24]) -> Result<Vec<u8>, CoJsonCoreError> {
+                self.inner.encrypt(plaintext, key_secret, nonce)
+            }
+
+            fn decrypt(&self, ciphertext: &[u8], key_secret: &KeySecret, nonce: &[u8; 24]) -> Result<Vec<u8>, CoJsonCoreError> {
+                self.inner.decrypt(ciphertext, key_secret, nonce)
+            }
+
+            fn seal(&self, message: &[u8], from: &SealerSecret, to: &SealerID, nonce_material: &[u8]) -> Result<Vec<u8>, CoJsonCoreError> {
+                self.inner.seal(message, from, to, nonce_material)
+            }
+
+            fn unseal(&self, sealed_message: &[u8], sealer: &SealerSecret, from: &SealerID, nonce_material: &[u8]) -> Result<Vec<u8>, CoJsonCoreError> {
+                self.inner.unseal(sealed_message, sealer, from, nonce_material)
+            }
+        }
+
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let public_key = signing_key.verifying_key();
+        let crypto = CountingProvider::default();
+
+        let mut session = SessionLogInternal::with_crypto(
+            CoID("co_test".to_string()),
+            SessionID("session_test".to_string()),
+            Some(public_key.into()),
+            crypto.clone(),
+        );
+        let mut default_session = SessionLogInternal::new(
+            CoID("co_test".to_string()),
+            SessionID("session_test".to_string()),
+            Some(public_key.into()),
+        );
+
+        let (signature, transaction) = session.add_new_transaction(
+            r#"[{"op":"set","key":"a","value":1}]"#,
+            TransactionMode::Trusting,
+            &signing_key.clone().into(),
+            1234567890,
+            None,
+        ).unwrap();
+        assert_eq!(crypto.signs.get(), 1);
+
+        // Ed25519 signatures are deterministic, so both providers must agree on the result.
+        let (default_signature, _) = default_session.add_new_transaction(
+            r#"[{"op":"set","key":"a","value":1}]"#,
+            TransactionMode::Trusting,
+            &signing_key.into(),
+            1234567890,
+            None,
+        ).unwrap();
+        assert_eq!(signature, default_signature);
+
+        let mut verifying_session = SessionLogInternal::with_crypto(
+            CoID("co_test".to_string()),
+            SessionID("session_test".to_string()),
+            Some(public_key.into()),
+            crypto.clone(),
+        );
+        let tx_json = RawValue::from_string(serde_json::to_string(&transaction).unwrap()).unwrap();
+        verifying_session.try_add(vec![tx_json], &signature, false).unwrap();
+        assert_eq!(crypto.verifies.get(), 1);
+    }
+
     #[test]
     fn test_malformed_json_error() {
         let mut session = SessionLogInternal::new(
@@ -1293,6 +1864,7 @@ mod tests {
         let tx_id = TransactionID {
             session_id: session_id.clone(),
             tx_index: 42,
+            branch: None,
         };
 
         // Test serialization
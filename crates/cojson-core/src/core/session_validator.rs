@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+
+use serde_json::value::RawValue;
+
+use crate::core::session_log::{reaches_checkpoint, transaction_size};
+use crate::core::{
+    verify_session_hash, CoID, CoJsonCoreError, CryptoProvider, DefaultCryptoProvider,
+    IncrementalHasher, NonceGenerator, SessionID, SessionLogInternal, Signature, SignerID,
+    TransactionLimits,
+};
+
+/// Validates a session's transactions like `SessionLogInternal::try_add`, without keeping them.
+///
+/// Only the running hash, the transaction count and the signatures are kept, so relays that
+/// forward content can check signatures at a cost independent of the transactions' size. The
+/// signature checkpoints are tracked like the log does, so once the transactions are needed,
+/// `into_session_log` turns it into a full log that splits them into the same pieces.
+#[derive(Clone)]
+pub struct SessionValidator<C: CryptoProvider = DefaultCryptoProvider> {
+    signer_id: Option<SignerID>,
+    hasher: C::Hasher,
+    tx_count: u32,
+    last_signature: Option<Signature>,
+    signature_after: BTreeMap<u32, Signature>,
+    tx_size_since_checkpoint: usize,
+    limits: TransactionLimits,
+    nonce_generator: NonceGenerator,
+    crypto: C,
+}
+
+impl SessionValidator {
+    /// Create a validator for an empty session, verifying against `signer_id` if given.
+    pub fn new(co_id: CoID, session_id: SessionID, signer_id: Option<SignerID>) -> Self {
+        Self::with_crypto(co_id, session_id, signer_id, DefaultCryptoProvider::new())
+    }
+}
+
+impl<C: CryptoProvider> SessionValidator<C> {
+    pub fn with_crypto(
+        co_id: CoID,
+        session_id: SessionID,
+        signer_id: Option<SignerID>,
+        crypto: C,
+    ) -> Self {
+        Self {
+            signer_id,
+            hasher: crypto.new_hasher(),
+            tx_count: 0,
+            last_signature: None,
+            signature_after: BTreeMap::new(),
+            tx_size_since_checkpoint: 0,
+            limits: TransactionLimits::default(),
+            nonce_generator: NonceGenerator::new(co_id, session_id),
+            crypto,
+        }
+    }
+
+    /// Use other size limits than the defaults from `config.ts`, like
+    /// `SessionLogInternal::with_limits`.
+    pub fn with_limits(mut self, limits: TransactionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// A validator picking up where a session log is, e.g. to drop a log's transactions once
+    /// they've been forwarded.
+    pub fn from_session_log(log: &SessionLogInternal<C>) -> Self {
+        Self {
+            signer_id: log.signer_id().cloned(),
+            hasher: log.hasher().clone(),
+            tx_count: log.transactions_json().len() as u32,
+            last_signature: log.last_signature().cloned(),
+            signature_after: log.signature_after().clone(),
+            tx_size_since_checkpoint: log.tx_size_since_checkpoint(),
+            limits: *log.limits(),
+            nonce_generator: log.nonce_generator().clone(),
+            crypto: log.crypto().clone(),
+        }
+    }
+
+    /// The number of transactions validated so far.
+    pub fn tx_count(&self) -> u32 {
+        self.tx_count
+    }
+
+    pub fn last_signature(&self) -> Option<&Signature> {
+        self.last_signature.as_ref()
+    }
+
+    /// The signature checkpoints, keyed by the index of the transaction they're after.
+    pub fn signature_after(&self) -> &BTreeMap<u32, Signature> {
+        &self.signature_after
+    }
+
+    pub fn limits(&self) -> &TransactionLimits {
+        &self.limits
+    }
+
+    pub fn signer_id(&self) -> Option<&SignerID> {
+        self.signer_id.as_ref()
+    }
+
+    /// Validate a batch of transactions that follows the ones validated so far, exactly like
+    /// `SessionLogInternal::try_add`. The transactions aren't kept.
+    pub fn try_add(
+        &mut self,
+        transactions: &[Box<RawValue>],
+        new_signature: &Signature,
+        skip_verify: bool,
+    ) -> Result<(), CoJsonCoreError> {
+        let mut hasher = self.hasher.clone();
+        for tx in transactions {
+            hasher.update(tx.get().as_bytes());
+        }
+
+        if !skip_verify {
+            verify_session_hash(
+                &self.crypto,
+                &hasher,
+                self.signer_id.as_ref(),
+                new_signature,
+            )?;
+        }
+
+        self.hasher = hasher;
+        self.tx_count += transactions.len() as u32;
+        let size = transactions
+            .iter()
+            .map(|tx| transaction_size(tx.get()))
+            .sum();
+        if reaches_checkpoint(&mut self.tx_size_since_checkpoint, size, &self.limits) {
+            self.signature_after
+                .insert(self.tx_count - 1, new_signature.clone());
+        }
+        self.last_signature = Some(new_signature.clone());
+        Ok(())
+    }
+
+    /// Turn this into a full session log, given every transaction validated so far.
+    ///
+    /// Fails with `TransactionCountMismatch` if there are more or fewer transactions than were
+    /// validated, or `HashMismatch` if they aren't the ones that were validated.
+    pub fn into_session_log(
+        self,
+        transactions_json: Vec<String>,
+    ) -> Result<SessionLogInternal<C>, CoJsonCoreError> {
+        if transactions_json.len() != self.tx_count as usize {
+            return Err(CoJsonCoreError::TransactionCountMismatch {
+                expected: self.tx_count,
+                actual: u32::try_from(transactions_json.len()).unwrap_or(u32::MAX),
+            });
+        }
+
+        let mut hasher = self.crypto.new_hasher();
+        for tx in &transactions_json {
+            hasher.update(tx.as_bytes());
+        }
+        if hasher.finalize() != self.hasher.finalize() {
+            let encode = |hash| format!("hash_z{}", bs58::encode(hash).into_string());
+            return Err(CoJsonCoreError::HashMismatch {
+                expected: encode(self.hasher.finalize()),
+                actual: encode(hasher.finalize()),
+            });
+        }
+
+        Ok(SessionLogInternal::from_parts(
+            self.nonce_generator,
+            self.signer_id,
+            self.hasher,
+            transactions_json,
+            self.last_signature,
+            self.signature_after,
+            self.tx_size_since_checkpoint,
+            self.limits,
+            self.crypto,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TransactionMode;
+    use ed25519_dalek::SigningKey;
+
+    fn signed_batches(
+        signing_key: &SigningKey,
+        count: usize,
+    ) -> (SessionLogInternal, Vec<(Box<RawValue>, Signature)>) {
+        let mut writer = SessionLogInternal::new(
+            CoID("co_zTest".to_string()),
+            SessionID("session_test".to_string()),
+            Some(signing_key.verifying_key().into()),
+        );
+        let batches = (0..count)
+            .map(|i| {
+                let (signature, tx) = writer
+                    .add_new_transaction(
+                        &format!("[{i}]"),
+                        TransactionMode::Trusting,
+                        &signing_key.clone().into(),
+                        1234567890,
+                        None,
+                    )
+                    .unwrap();
+                let tx = RawValue::from_string(serde_json::to_string(&tx).unwrap()).unwrap();
+                (tx, signature)
+            })
+            .collect();
+        (writer, batches)
+    }
+
+    fn validator(signing_key: &SigningKey) -> SessionValidator {
+        SessionValidator::new(
+            CoID("co_zTest".to_string()),
+            SessionID("session_test".to_string()),
+            Some(signing_key.verifying_key().into()),
+        )
+    }
+
+    #[test]
+    fn test_validate_without_keeping_transactions() {
+        let signing_key = SigningKey::from_bytes(&[1; 32]);
+        let (writer, batches) = signed_batches(&signing_key, 3);
+        let mut validator = validator(&signing_key);
+
+        for (tx, signature) in &batches {
+            validator
+                .try_add(std::slice::from_ref(tx), signature, false)
+                .unwrap();
+        }
+        assert_eq!(validator.tx_count(), 3);
+        assert_eq!(validator.last_signature(), writer.last_signature());
+
+        // Out of order, the hash doesn't match
+        let mut validator = self::validator(&signing_key);
+        let (tx, signature) = &batches[1];
+        let err = validator
+            .try_add(std::slice::from_ref(tx), signature, false)
+            .unwrap_err();
+        assert!(matches!(err, CoJsonCoreError::SignatureVerification(_)));
+        assert_eq!(validator.tx_count(), 0);
+    }
+
+    #[test]
+    fn test_upgrade_to_session_log() {
+        let signing_key = SigningKey::from_bytes(&[1; 32]);
+        let (writer, mut batches) = signed_batches(&signing_key, 3);
+        let (last_tx, last_signature) = batches.pop().unwrap();
+
+        let mut validator = validator(&signing_key);
+        let signature = batches[1].1.clone();
+        let txs: Vec<Box<RawValue>> = batches.into_iter().map(|(tx, _)| tx).collect();
+        validator.try_add(&txs, &signature, false).unwrap();
+
+        assert!(matches!(
+            validator
+                .clone()
+                .into_session_log(vec![txs[0].get().to_string()]),
+            Err(CoJsonCoreError::TransactionCountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+        assert!(matches!(
+            validator
+                .clone()
+                .into_session_log(vec![txs[1].get().to_string(), txs[0].get().to_string()]),
+            Err(CoJsonCoreError::HashMismatch { .. })
+        ));
+
+        // The upgraded log keeps verifying where the validator left off
+        let mut log = validator
+            .into_session_log(txs.iter().map(|tx| tx.get().to_string()).collect())
+            .unwrap();
+        log.try_add(vec![last_tx], &last_signature, false).unwrap();
+        assert_eq!(log.transactions_json(), writer.transactions_json());
+
+        // And back again
+        let validator = SessionValidator::from_session_log(&log);
+        assert_eq!(validator.tx_count(), 3);
+        assert_eq!(validator.last_signature(), Some(&last_signature));
+    }
+
+    #[test]
+    fn test_upgrade_keeps_checkpoints() {
+        let signing_key = SigningKey::from_bytes(&[1; 32]);
+        let limits = TransactionLimits {
+            recommended_chunk_size: 100,
+            ..Default::default()
+        };
+        let mut writer = SessionLogInternal::new(
+            CoID("co_zTest".to_string()),
+            SessionID("session_test".to_string()),
+            Some(signing_key.verifying_key().into()),
+        )
+        .with_limits(limits);
+        // 40 bytes of changes each, so every third transaction is a checkpoint
+        for i in 0..10 {
+            writer
+                .add_new_transaction(
+                    &format!("[\"{i}{}\"]", "x".repeat(35)),
+                    TransactionMode::Trusting,
+                    &signing_key.clone().into(),
+                    1234567890,
+                    None,
+                )
+                .unwrap();
+        }
+        let pieces =
+            |log: &SessionLogInternal| serde_json::to_string(&log.content_pieces(0)).unwrap();
+
+        let mut validator = validator(&signing_key).with_limits(limits);
+        for piece in writer.content_pieces(0) {
+            validator
+                .try_add(&piece.new_transactions, &piece.last_signature, false)
+                .unwrap();
+        }
+        assert_eq!(validator.signature_after(), writer.signature_after());
+        let log = validator
+            .into_session_log(writer.transactions_json().clone())
+            .unwrap();
+        assert_eq!(log.limits(), &limits);
+        assert_eq!(pieces(&log), pieces(&writer));
+
+        // Dropping a log's transactions and getting them back keeps its checkpoints too
+        let log = SessionValidator::from_session_log(&writer)
+            .into_session_log(writer.transactions_json().clone())
+            .unwrap();
+        assert_eq!(pieces(&log), pieces(&writer));
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Value as JsonValue};
+
+use crate::core::{
+    CoID, CoJsonCoreError, CryptoProvider, DefaultCryptoProvider, SessionID, SessionLogInternal,
+    Signature, SignerID,
+};
+
+/// New transactions for one session, as carried in the `new` field of a content message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionNewContent {
+    /// The index the first of `new_transactions` has in the session.
+    pub after: u32,
+    pub new_transactions: Vec<Box<RawValue>>,
+    /// The signature of the session hash after the last of `new_transactions`.
+    pub last_signature: Signature,
+}
+
+/// A CoValue loaded from storage, with every session rebuilt as a session log.
+pub struct LoadedCoValue<C: CryptoProvider = DefaultCryptoProvider> {
+    pub id: CoID,
+    pub header: JsonValue,
+    pub sessions: HashMap<SessionID, SessionLogInternal<C>>,
+}
+
+/// A persistent store of session logs.
+///
+/// Backends only differ in how they lay out the data - loading always rebuilds
+/// `SessionLogInternal`s and appending always goes through `SessionLogInternal::try_add`,
+/// so backends can be swapped (or benchmarked against each other) freely.
+pub trait SessionLogStorage {
+    type Error: From<CoJsonCoreError>;
+
+    /// Load a CoValue with all of its sessions as session logs.
+    /// - `signer_for`: Resolves the signer of a session. Sessions with a signer are verified
+    ///   against every stored signature, sessions without one are trusted as stored.
+    ///
+    /// Returns None if the CoValue isn't stored.
+    fn load_session_logs_with_crypto<C, F>(
+        &self,
+        id: &CoID,
+        crypto: C,
+        signer_for: F,
+    ) -> Result<Option<LoadedCoValue<C>>, Self::Error>
+    where
+        C: CryptoProvider,
+        F: FnMut(&SessionID) -> Option<SignerID>;
+
+    /// Load a CoValue with all of its sessions as session logs, using the default crypto provider.
+    fn load_session_logs<F>(
+        &self,
+        id: &CoID,
+        signer_for: F,
+    ) -> Result<Option<LoadedCoValue>, Self::Error>
+    where
+        F: FnMut(&SessionID) -> Option<SignerID>,
+    {
+        self.load_session_logs_with_crypto(id, DefaultCryptoProvider::new(), signer_for)
+    }
+
+    /// Append new transactions of one session.
+    /// - `header`: The CoValue header, required if the CoValue isn't stored yet
+    /// - `session_log`: The log of this session, holding exactly the stored transactions
+    /// - `content`: The new content, which may overlap with what's already stored
+    ///
+    /// Transactions that are already stored are skipped. The rest are verified by adding them to
+    /// `session_log` and only persisted if verification succeeds. Like when loading, a session log
+    /// without a signer is trusted as is.
+    ///
+    /// Returns the number of stored transactions in the session afterwards.
+    fn append_transactions<C: CryptoProvider>(
+        &mut self,
+        id: &CoID,
+        header: Option<&JsonValue>,
+        session_id: &SessionID,
+        session_log: &mut SessionLogInternal<C>,
+        content: SessionNewContent,
+    ) -> Result<u32, Self::Error>;
+}
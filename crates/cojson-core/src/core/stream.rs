@@ -0,0 +1,461 @@
+use std::collections::BTreeMap;
+
+use base64::{
+    alphabet,
+    engine::{general_purpose::URL_SAFE, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine as _,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::core::{
+    stringify_changes, CoJsonCoreError, CryptoProvider, KeyID, KeySecret, SessionID,
+    SessionLogInternal, Signature, SignerSecret, Transaction, TransactionID, TransactionMode,
+    MAX_RECOMMENDED_TX_SIZE,
+};
+
+/// Raw bytes per binary chunk, as `BinaryCoStream.createFromBlob` uses.
+pub const BINARY_CHUNK_SIZE: usize = MAX_RECOMMENDED_TX_SIZE;
+
+/// Like `base64URLtoBytes`, which accepts chunks with or without padding.
+const BINARY_CHUNK_ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// One item pushed to a CoStream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoStreamItem {
+    pub value: JsonValue,
+    pub tx: TransactionID,
+    pub made_at: u64,
+}
+
+/// The items of a CoStream, one stream per session, as `RawCoStreamView` sees them.
+#[derive(Debug, Clone, Default)]
+pub struct CoStream {
+    items: BTreeMap<SessionID, Vec<CoStreamItem>>,
+    skipped: Vec<TransactionID>,
+}
+
+impl CoStream {
+    /// Read the items from a CoStream's session logs.
+    /// - `key_for`: Resolves the secrets of the read keys private transactions were made with
+    ///
+    /// Transactions that can't be read are skipped, like invalid transactions are in
+    /// `getValidTransactions`: private ones whose key can't be resolved or that don't decrypt,
+    /// and ones whose changes aren't a JSON array. `skipped` lists them.
+    pub fn from_session_logs<'a, C, F>(
+        sessions: impl IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)>,
+        mut key_for: F,
+    ) -> Self
+    where
+        C: CryptoProvider + 'a,
+        F: FnMut(&KeyID) -> Option<KeySecret>,
+    {
+        let mut items = BTreeMap::new();
+        let mut skipped = Vec::new();
+        for (session_id, log) in sessions {
+            let mut session_items = Vec::new();
+            for tx_index in 0..log.transactions_json().len() as u32 {
+                let tx = TransactionID {
+                    session_id: session_id.clone(),
+                    tx_index,
+                    branch: None,
+                };
+                let Some((changes, made_at)) = read_changes(log, tx_index, &mut key_for) else {
+                    skipped.push(tx);
+                    continue;
+                };
+                for value in changes {
+                    session_items.push(CoStreamItem {
+                        value,
+                        tx: tx.clone(),
+                        made_at,
+                    });
+                }
+            }
+            if session_items.is_empty() {
+                continue;
+            }
+            // Within a session, items are ordered like `compareStreamItems`
+            session_items.sort_by_key(|item| (item.made_at, item.tx.tx_index));
+            items.insert(session_id.clone(), session_items);
+        }
+        Self { items, skipped }
+    }
+
+    /// The transactions `from_session_logs` couldn't read, in session order.
+    pub fn skipped(&self) -> &[TransactionID] {
+        &self.skipped
+    }
+
+    /// The sessions that pushed items, in order.
+    pub fn sessions(&self) -> impl Iterator<Item = &SessionID> {
+        self.items.keys()
+    }
+
+    /// The items pushed in a session, in order.
+    pub fn items_in(&self, session_id: &SessionID) -> &[CoStreamItem] {
+        self.items.get(session_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The only stream, like `getSingleStream`. None if nothing was pushed yet, an error if
+    /// more than one session pushed items.
+    pub fn single_stream(&self) -> Result<Option<&[CoStreamItem]>, CoJsonCoreError> {
+        if self.items.len() > 1 {
+            return Err(CoJsonCoreError::MultipleStreams(self.items.len()));
+        }
+        Ok(self.items.values().next().map(Vec::as_slice))
+    }
+
+    /// Reassemble the single stream as a binary stream, like `getBinaryChunks`.
+    /// - `allow_unfinished`: Return what's there so far instead of failing before the end
+    ///
+    /// Returns None if nothing was pushed yet.
+    pub fn binary(&self, allow_unfinished: bool) -> Result<Option<BinaryStream>, CoJsonCoreError> {
+        let Some(items) = self.single_stream()? else {
+            return Ok(None);
+        };
+        let mut items = items.iter().map(|item| {
+            serde_json::from_value::<BinaryStreamItem>(item.value.clone())
+                .map_err(|_| CoJsonCoreError::InvalidStreamItem(item.tx.tx_index))
+                .map(|parsed| (item.tx.tx_index, parsed))
+        });
+
+        let info = match items.next().transpose()? {
+            Some((_, BinaryStreamItem::Start(info))) => info,
+            Some((tx_index, _)) => return Err(CoJsonCoreError::InvalidStreamItem(tx_index)),
+            None => return Ok(None),
+        };
+
+        let mut bytes = Vec::new();
+        let mut finished = false;
+        for item in items {
+            match item? {
+                (_, BinaryStreamItem::Chunk { chunk }) => {
+                    let encoded = chunk
+                        .strip_prefix("binary_U")
+                        .ok_or(CoJsonCoreError::InvalidDecodingPrefix)?;
+                    BINARY_CHUNK_ENGINE.decode_vec(encoded, &mut bytes)?;
+                }
+                (_, BinaryStreamItem::End) => {
+                    finished = true;
+                    break;
+                }
+                (tx_index, BinaryStreamItem::Start(_)) => {
+                    return Err(CoJsonCoreError::InvalidStreamItem(tx_index))
+                }
+            }
+        }
+
+        if !finished && !allow_unfinished {
+            return Err(CoJsonCoreError::BinaryStreamIncomplete);
+        }
+        if let Some(expected) = info.total_size_bytes {
+            let actual = bytes.len() as u64;
+            if actual > expected || (finished && actual != expected) {
+                return Err(CoJsonCoreError::BinaryStreamSizeMismatch { expected, actual });
+            }
+        }
+
+        Ok(Some(BinaryStream {
+            info,
+            bytes,
+            finished,
+        }))
+    }
+}
+
+/// The changes of a transaction and when it was made, or None if it can't be read.
+fn read_changes<C, F>(
+    log: &SessionLogInternal<C>,
+    tx_index: u32,
+    key_for: &mut F,
+) -> Option<(Vec<JsonValue>, u64)>
+where
+    C: CryptoProvider,
+    F: FnMut(&KeyID) -> Option<KeySecret>,
+{
+    let tx_json = &log.transactions_json()[tx_index as usize];
+    let (changes_json, made_at) = match serde_json::from_str(tx_json).ok()? {
+        Transaction::Private(tx) => {
+            let key_secret = key_for(&tx.key_used)?;
+            let changes = log
+                .decrypt_next_transaction_changes_json(tx_index, key_secret)
+                .ok()?;
+            (changes, tx.made_at)
+        }
+        Transaction::Trusting(tx) => (tx.changes, tx.made_at),
+    };
+    let changes = serde_json::from_str(&changes_json).ok()?;
+    Some((changes, made_at.as_u64().unwrap_or_default()))
+}
+
+/// What a binary stream holds, as `BinaryStreamInfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryStreamInfo {
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_size_bytes: Option<u64>,
+}
+
+/// An item of a binary CoStream: a start, `binary_U...` chunks and an end.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BinaryStreamItem {
+    Start(BinaryStreamInfo),
+    Chunk { chunk: String },
+    End,
+}
+
+/// A reassembled binary stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryStream {
+    pub info: BinaryStreamInfo,
+    pub bytes: Vec<u8>,
+    /// Whether the stream was ended. Only false when reading unfinished streams.
+    pub finished: bool,
+}
+
+/// The changes of every transaction that uploads `data` as a binary stream, one item each,
+/// ready for `add_new_transaction`.
+/// - `chunk_size`: Raw bytes per chunk, usually `BINARY_CHUNK_SIZE`
+pub fn binary_stream_changes(
+    info: &BinaryStreamInfo,
+    data: &[u8],
+    chunk_size: usize,
+) -> Result<Vec<String>, CoJsonCoreError> {
+    let chunks = data
+        .chunks(chunk_size.max(1))
+        .map(|chunk| BinaryStreamItem::Chunk {
+            chunk: format!("binary_U{}", URL_SAFE.encode(chunk)),
+        });
+    std::iter::once(BinaryStreamItem::Start(info.clone()))
+        .chain(chunks)
+        .chain(std::iter::once(BinaryStreamItem::End))
+        .map(|item| stringify_changes(&[item]))
+        .collect()
+}
+
+/// Upload `data` as a binary stream in our session of a binary CoStream, one transaction per
+/// item, like `BinaryCoStream.createFromBlob`.
+/// - `key`: The read key to encrypt with, or None for trusting transactions
+///
+/// Returns the signature after the last transaction.
+pub fn write_binary_stream<C: CryptoProvider>(
+    log: &mut SessionLogInternal<C>,
+    signer_secret: &SignerSecret,
+    key: Option<(&KeyID, &KeySecret)>,
+    made_at: u64,
+    info: &BinaryStreamInfo,
+    data: &[u8],
+) -> Result<Signature, CoJsonCoreError> {
+    let mut last_signature = None;
+    for changes_json in binary_stream_changes(info, data, BINARY_CHUNK_SIZE)? {
+        let mode = match key {
+            Some((key_id, key_secret)) => TransactionMode::Private {
+                key_id: key_id.clone(),
+                key_secret: key_secret.clone(),
+            },
+            None => TransactionMode::Trusting,
+        };
+        let (signature, _) =
+            log.add_new_transaction(&changes_json, mode, signer_secret, made_at, None)?;
+        last_signature = Some(signature);
+    }
+    Ok(last_signature.expect("binary streams have a start and an end"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::test_session;
+    use crate::core::{new_random_key_secret, CoID};
+    use serde_json::json;
+
+    fn session(seed: u8) -> (SessionID, SessionLogInternal, SignerSecret) {
+        test_session(&CoID("co_zStream".to_string()), seed)
+    }
+
+    fn info(total_size_bytes: Option<u64>) -> BinaryStreamInfo {
+        BinaryStreamInfo {
+            mime_type: "application/octet-stream".to_string(),
+            file_name: Some("data.bin".to_string()),
+            total_size_bytes,
+        }
+    }
+
+    #[test]
+    fn test_read_stream_items() {
+        let (alice, mut alice_log, alice_secret) = session(1);
+        let (bob, mut bob_log, bob_secret) = session(2);
+        let (key_id, key_secret) = new_random_key_secret();
+
+        // Made out of order, read back by madeAt
+        alice_log
+            .add_new_transaction(
+                r#"["b"]"#,
+                TransactionMode::Trusting,
+                &alice_secret,
+                20,
+                None,
+            )
+            .unwrap();
+        alice_log
+            .add_new_transaction(
+                r#"["a1","a2"]"#,
+                TransactionMode::Trusting,
+                &alice_secret,
+                10,
+                None,
+            )
+            .unwrap();
+        let private = TransactionMode::Private {
+            key_id: key_id.clone(),
+            key_secret: key_secret.clone(),
+        };
+        bob_log
+            .add_new_transaction(r#"[{"x":1}]"#, private, &bob_secret, 5, None)
+            .unwrap();
+
+        let sessions = [(&alice, &alice_log), (&bob, &bob_log)];
+        let stream =
+            CoStream::from_session_logs(sessions, |id| (id == &key_id).then(|| key_secret.clone()));
+        let values: Vec<&JsonValue> = stream
+            .items_in(&alice)
+            .iter()
+            .map(|item| &item.value)
+            .collect();
+        assert_eq!(values, [&json!("a1"), &json!("a2"), &json!("b")]);
+        assert_eq!(stream.items_in(&alice)[0].tx.tx_index, 1);
+        assert_eq!(stream.items_in(&bob)[0].value, json!({"x": 1}));
+        assert!(matches!(
+            stream.single_stream(),
+            Err(CoJsonCoreError::MultipleStreams(2))
+        ));
+
+        assert!(stream.skipped().is_empty());
+
+        // Without the key, or with the wrong one, Bob's private item is skipped
+        let bob_tx = TransactionID {
+            session_id: bob.clone(),
+            tx_index: 0,
+            branch: None,
+        };
+        let stream = CoStream::from_session_logs(sessions, |_| None);
+        assert_eq!(stream.sessions().collect::<Vec<_>>(), [&alice]);
+        assert_eq!(stream.skipped(), std::slice::from_ref(&bob_tx));
+        let (_, wrong_key) = new_random_key_secret();
+        let stream = CoStream::from_session_logs(sessions, |_| Some(wrong_key.clone()));
+        assert_eq!(stream.skipped(), [bob_tx]);
+        assert_eq!(stream.items_in(&alice).len(), 3);
+
+        // So are changes that aren't an array, leaving the rest of the session readable
+        alice_log
+            .add_new_transaction(
+                r#"{"not":"an array"}"#,
+                TransactionMode::Trusting,
+                &alice_secret,
+                30,
+                None,
+            )
+            .unwrap();
+        let sessions = [(&alice, &alice_log)];
+        let stream = CoStream::from_session_logs(sessions, |_| None);
+        assert_eq!(stream.items_in(&alice).len(), 3);
+        assert_eq!(stream.skipped()[0].tx_index, 2);
+    }
+
+    #[test]
+    fn test_write_and_read_binary_stream() {
+        let (session_id, mut log, signer_secret) = session(1);
+        let data: Vec<u8> = (0..BINARY_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let (key_id, key_secret) = new_random_key_secret();
+
+        let signature = write_binary_stream(
+            &mut log,
+            &signer_secret,
+            Some((&key_id, &key_secret)),
+            1,
+            &info(Some(data.len() as u64)),
+            &data,
+        )
+        .unwrap();
+        // Start, three chunks, end
+        assert_eq!(log.transactions_json().len(), 5);
+        assert_eq!(log.last_signature(), Some(&signature));
+        // Each full chunk is over the recommended size, so each gets a checkpoint
+        assert_eq!(
+            log.signature_after().keys().copied().collect::<Vec<_>>(),
+            [1, 2]
+        );
+
+        let stream =
+            CoStream::from_session_logs([(&session_id, &log)], |_| Some(key_secret.clone()));
+        let binary = stream.binary(false).unwrap().unwrap();
+        assert_eq!(binary.info, info(Some(data.len() as u64)));
+        assert!(binary.finished);
+        assert_eq!(binary.bytes, data);
+    }
+
+    #[test]
+    fn test_incomplete_and_mismatched_binary_streams() {
+        let (session_id, mut log, signer_secret) = session(1);
+        let mut changes = binary_stream_changes(&info(Some(5)), b"abc", 2).unwrap();
+        assert_eq!(changes.len(), 4);
+        assert_eq!(changes[1], r#"[{"chunk":"binary_UYWI=","type":"chunk"}]"#);
+
+        // Everything but the end
+        let end = changes.pop().unwrap();
+        for changes_json in &changes {
+            log.add_new_transaction(
+                changes_json,
+                TransactionMode::Trusting,
+                &signer_secret,
+                1,
+                None,
+            )
+            .unwrap();
+        }
+        let stream = CoStream::from_session_logs([(&session_id, &log)], |_| None);
+        assert!(matches!(
+            stream.binary(false),
+            Err(CoJsonCoreError::BinaryStreamIncomplete)
+        ));
+        let partial = stream.binary(true).unwrap().unwrap();
+        assert_eq!(partial.bytes, b"abc");
+        assert!(!partial.finished);
+
+        // Ended short of the announced size
+        log.add_new_transaction(&end, TransactionMode::Trusting, &signer_secret, 1, None)
+            .unwrap();
+        let stream = CoStream::from_session_logs([(&session_id, &log)], |_| None);
+        assert!(matches!(
+            stream.binary(false),
+            Err(CoJsonCoreError::BinaryStreamSizeMismatch {
+                expected: 5,
+                actual: 3
+            })
+        ));
+
+        // Not starting with a start item
+        let (session_id, mut log, signer_secret) = session(2);
+        log.add_new_transaction(
+            r#"[{"type":"end"}]"#,
+            TransactionMode::Trusting,
+            &signer_secret,
+            1,
+            None,
+        )
+        .unwrap();
+        let stream = CoStream::from_session_logs([(&session_id, &log)], |_| None);
+        assert!(matches!(
+            stream.binary(false),
+            Err(CoJsonCoreError::InvalidStreamItem(0))
+        ));
+    }
+}
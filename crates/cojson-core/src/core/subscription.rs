@@ -0,0 +1,544 @@
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::core::history::apply_map_changes;
+use crate::core::{
+    CoID, CoJsonCoreError, CoListEntry, CoValueHistory, CryptoProvider, HistoryFilter, KeyID,
+    KeySecret, KnownStateSessions, SessionID, SessionLogInternal, TransactionID,
+};
+
+/// Identifies a listener, to unsubscribe it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SubscriptionID(pub u64);
+
+/// How a CoValue's content changed in an update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ContentDiff {
+    /// Keys of a CoMap that were set to a different value or deleted.
+    #[serde(rename_all = "camelCase")]
+    Map { changed_keys: Vec<String> },
+    /// Items of a CoList or graphemes of a CoPlainText. Inserted ranges are indices into the
+    /// new content, deleted ones into the previous content.
+    List {
+        inserted: Vec<Range<usize>>,
+        deleted: Vec<Range<usize>>,
+    },
+    /// CoStreams, and content that can't be read, like changes of the wrong shape or private
+    /// transactions whose key couldn't be resolved.
+    Opaque,
+}
+
+impl ContentDiff {
+    /// The difference between a CoValue's content within `before` and its full content.
+    /// - `header`: The CoValue's header, whose `type` decides how its content is read
+    pub fn since(
+        header: &JsonValue,
+        history: &CoValueHistory,
+        before: &KnownStateSessions,
+    ) -> Result<Self, CoJsonCoreError> {
+        let previous = Content::read(header, history, &HistoryFilter::KnownState(before.clone()))?;
+        let current = Content::read(header, history, &HistoryFilter::All)?;
+        Ok(previous.diff(&current))
+    }
+}
+
+/// A CoValue's content, as far as it's diffed.
+#[derive(Debug, Clone)]
+enum Content {
+    Map(Map<String, JsonValue>),
+    List(Vec<CoListEntry>),
+    Opaque,
+}
+
+impl Content {
+    fn read(
+        header: &JsonValue,
+        history: &CoValueHistory,
+        filter: &HistoryFilter,
+    ) -> Result<Self, CoJsonCoreError> {
+        Ok(match header.get("type").and_then(JsonValue::as_str) {
+            Some("comap") => Content::Map(history.map_at(filter)?),
+            Some("colist" | "coplaintext") => Content::List(history.list_at(filter)?),
+            _ => Content::Opaque,
+        })
+    }
+
+    fn diff(&self, current: &Content) -> ContentDiff {
+        match (self, current) {
+            (Content::Map(previous), Content::Map(current)) => {
+                let changed_keys: BTreeSet<&String> = previous
+                    .keys()
+                    .chain(current.keys())
+                    .filter(|key| previous.get(*key) != current.get(*key))
+                    .collect();
+                ContentDiff::Map {
+                    changed_keys: changed_keys.into_iter().cloned().collect(),
+                }
+            }
+            (Content::List(previous), Content::List(current)) => {
+                // Items never move, so comparing which insertions are there is enough
+                let previous_ops: HashSet<_> = previous.iter().map(|entry| &entry.op_id).collect();
+                let current_ops: HashSet<_> = current.iter().map(|entry| &entry.op_id).collect();
+                ContentDiff::List {
+                    inserted: index_ranges(
+                        current
+                            .iter()
+                            .map(|entry| !previous_ops.contains(&entry.op_id)),
+                    ),
+                    deleted: index_ranges(
+                        previous
+                            .iter()
+                            .map(|entry| !current_ops.contains(&entry.op_id)),
+                    ),
+                }
+            }
+            _ => ContentDiff::Opaque,
+        }
+    }
+}
+
+/// The ranges of consecutive indices where `included` is true.
+fn index_ranges(included: impl Iterator<Item = bool>) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (idx, included) in included.enumerate() {
+        if !included {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == idx => range.end += 1,
+            _ => ranges.push(idx..idx + 1),
+        }
+    }
+    ranges
+}
+
+/// What listeners of a CoValue are told after it changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoValueUpdate {
+    pub id: CoID,
+    /// The transactions added, in session order.
+    pub new_transactions: Vec<TransactionID>,
+    pub diff: ContentDiff,
+}
+
+pub type CoValueListener = Box<dyn FnMut(&CoValueUpdate) + Send>;
+
+/// The listeners of one CoValue.
+///
+/// Whoever changes the CoValue wraps the change in `begin_batch` and `end_batch`. Batches nest,
+/// and listeners are only told once the outermost one ends, with everything added in it.
+#[derive(Default)]
+pub struct CoValueSubscriptions {
+    next_id: u64,
+    listeners: Vec<(SubscriptionID, CoValueListener)>,
+    batch_depth: usize,
+    /// The known state when the outermost batch began, if anyone was listening.
+    batch_start: Option<KnownStateSessions>,
+    /// What the last update was diffed to, so the next one only reads the new transactions.
+    last_read: Option<LastRead>,
+}
+
+struct LastRead {
+    known_state: KnownStateSessions,
+    history: CoValueHistory,
+    content: Content,
+}
+
+impl CoValueSubscriptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(
+        &mut self,
+        listener: impl FnMut(&CoValueUpdate) + Send + 'static,
+    ) -> SubscriptionID {
+        let id = SubscriptionID(self.next_id);
+        self.next_id += 1;
+        self.listeners.push((id, Box::new(listener)));
+        id
+    }
+
+    /// Returns whether the listener was subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionID) -> bool {
+        let before = self.listeners.len();
+        self.listeners.retain(|(listener_id, _)| *listener_id != id);
+        self.listeners.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// Start collecting changes, with the session logs as they are before them.
+    pub fn begin_batch<'a, C>(
+        &mut self,
+        sessions: impl IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)>,
+    ) where
+        C: CryptoProvider + 'a,
+    {
+        self.batch_depth += 1;
+        if self.batch_depth == 1 {
+            if self.listeners.is_empty() {
+                self.last_read = None;
+            } else {
+                self.batch_start = Some(known_state_sessions(sessions));
+            }
+        }
+    }
+
+    /// End a batch, telling the listeners about the transactions added since the outermost
+    /// batch began, if there are any.
+    /// - `header`: The CoValue's header, None while it isn't available
+    /// - `key_for`: Resolves read keys for the diff. Private transactions whose key can't be
+    ///   resolved are still listed, but make the diff `Opaque`.
+    ///
+    /// Returns the update the listeners were told about.
+    pub fn end_batch<'a, C, I, F>(
+        &mut self,
+        id: &CoID,
+        header: Option<&JsonValue>,
+        sessions: I,
+        key_for: F,
+    ) -> Option<CoValueUpdate>
+    where
+        C: CryptoProvider + 'a,
+        I: IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)> + Clone,
+        F: FnMut(&KeyID) -> Option<KeySecret>,
+    {
+        self.batch_depth = self.batch_depth.checked_sub(1)?;
+        if self.batch_depth > 0 {
+            return None;
+        }
+        let before = self.batch_start.take()?;
+        let header = header?;
+
+        let new_transactions: Vec<TransactionID> = sessions
+            .clone()
+            .into_iter()
+            .flat_map(|(session_id, log)| {
+                let after = before.get(session_id).copied().unwrap_or(0);
+                let count = log.transactions_json().len() as u32;
+                (after..count).map(|tx_index| TransactionID {
+                    session_id: session_id.clone(),
+                    tx_index,
+                    branch: None,
+                })
+            })
+            .collect();
+        if new_transactions.is_empty() {
+            return None;
+        }
+
+        let diff = self
+            .diff_since(header, &before, sessions, key_for)
+            .unwrap_or(ContentDiff::Opaque);
+        let update = CoValueUpdate {
+            id: id.clone(),
+            new_transactions,
+            diff,
+        };
+        for (_, listener) in &mut self.listeners {
+            listener(&update);
+        }
+        Some(update)
+    }
+
+    /// Diff the content within `before` against the current one, reading only the transactions
+    /// added since the last diff when it ended at `before`.
+    fn diff_since<'a, C, I, F>(
+        &mut self,
+        header: &JsonValue,
+        before: &KnownStateSessions,
+        sessions: I,
+        mut key_for: F,
+    ) -> Result<ContentDiff, CoJsonCoreError>
+    where
+        C: CryptoProvider + 'a,
+        I: IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)> + Clone,
+        F: FnMut(&KeyID) -> Option<KeySecret>,
+    {
+        let before_filter = HistoryFilter::KnownState(before.clone());
+        let last_read = self.last_read.take().filter(|last_read| {
+            // Transactions left out for their key are read again, in case it's known now
+            last_read.known_state == *before && last_read.history.undecryptable().is_empty()
+        });
+        let (history, previous, current) = match last_read {
+            Some(LastRead {
+                mut history,
+                content: previous,
+                ..
+            }) => {
+                let read_before = history.len();
+                history.extend(None, sessions.clone(), &mut key_for)?;
+                let appended = history
+                    .transactions(&HistoryFilter::All)
+                    .take(read_before)
+                    .all(|tx| before_filter.includes_id(&tx.current_tx));
+                let current = match &previous {
+                    // New changes that apply after all others can be applied to the map as is
+                    Content::Map(map) if appended => {
+                        let mut map = map.clone();
+                        for tx in history.transactions(&HistoryFilter::All).skip(read_before) {
+                            apply_map_changes(&mut map, &tx.changes_json)?;
+                        }
+                        Content::Map(map)
+                    }
+                    _ => Content::read(header, &history, &HistoryFilter::All)?,
+                };
+                (history, previous, current)
+            }
+            None => {
+                let history =
+                    CoValueHistory::from_session_logs(None, sessions.clone(), &mut key_for)?;
+                let previous = Content::read(header, &history, &before_filter)?;
+                let current = Content::read(header, &history, &HistoryFilter::All)?;
+                (history, previous, current)
+            }
+        };
+
+        // What changed in a transaction we can't read is unknown
+        let unreadable = history
+            .undecryptable()
+            .iter()
+            .any(|tx| !before_filter.includes_id(tx));
+        let diff = if unreadable {
+            ContentDiff::Opaque
+        } else {
+            previous.diff(&current)
+        };
+        self.last_read = Some(LastRead {
+            known_state: known_state_sessions(sessions),
+            history,
+            content: current,
+        });
+        Ok(diff)
+    }
+}
+
+/// How many transactions each session log holds.
+pub fn known_state_sessions<'a, C>(
+    sessions: impl IntoIterator<Item = (&'a SessionID, &'a SessionLogInternal<C>)>,
+) -> KnownStateSessions
+where
+    C: CryptoProvider + 'a,
+{
+    sessions
+        .into_iter()
+        .map(|(session_id, log)| (session_id.clone(), log.transactions_json().len() as u32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_utils::test_session;
+    use crate::core::{
+        new_random_key_secret, ListChanges, MapChanges, SignerSecret, TransactionMode,
+    };
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    struct Value {
+        id: CoID,
+        header: JsonValue,
+        session_id: SessionID,
+        secret: SignerSecret,
+        sessions: BTreeMap<SessionID, SessionLogInternal>,
+        subscriptions: CoValueSubscriptions,
+    }
+
+    impl Value {
+        fn new(co_type: &str) -> Self {
+            let id = CoID("co_zSubscribed".to_string());
+            let (session_id, log, secret) = test_session(&id, 1);
+            Self {
+                id,
+                header: json!({"type": co_type}),
+                session_id: session_id.clone(),
+                secret,
+                sessions: BTreeMap::from([(session_id, log)]),
+                subscriptions: CoValueSubscriptions::new(),
+            }
+        }
+
+        fn log(&mut self) -> &mut SessionLogInternal {
+            self.sessions.get_mut(&self.session_id).unwrap()
+        }
+
+        fn begin(&mut self) {
+            self.subscriptions.begin_batch(&self.sessions);
+        }
+
+        fn end(&mut self) -> Option<CoValueUpdate> {
+            self.subscriptions
+                .end_batch(&self.id, Some(&self.header), &self.sessions, |_| None)
+        }
+
+        fn write(&mut self, changes_json: &str) {
+            let made_at = self.log().transactions_json().len() as u64 + 1;
+            self.write_with(changes_json, TransactionMode::Trusting, made_at);
+        }
+
+        fn write_with(&mut self, changes_json: &str, mode: TransactionMode, made_at: u64) {
+            self.begin();
+            let secret = self.secret.clone();
+            self.log()
+                .add_new_transaction(changes_json, mode, &secret, made_at, None)
+                .unwrap();
+            self.end();
+        }
+
+        fn entries(&self) -> Vec<crate::core::CoListEntry> {
+            CoValueHistory::from_session_logs(None, &self.sessions, |_| None)
+                .unwrap()
+                .list_at(&HistoryFilter::All)
+                .unwrap()
+        }
+    }
+
+    fn record(value: &mut Value) -> Arc<Mutex<Vec<CoValueUpdate>>> {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let recorded = updates.clone();
+        value
+            .subscriptions
+            .subscribe(move |update| recorded.lock().unwrap().push(update.clone()));
+        updates
+    }
+
+    #[test]
+    fn test_map_updates_are_batched() {
+        let mut map = Value::new("comap");
+        map.write(&MapChanges::new().set("a", 1).set("b", 2).build().unwrap());
+        let updates = record(&mut map);
+
+        map.begin();
+        map.write(&MapChanges::new().set("a", 1).set("c", 3).build().unwrap());
+        map.write(&MapChanges::new().delete("b").build().unwrap());
+        assert!(updates.lock().unwrap().is_empty());
+        map.end();
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].new_transactions,
+            [1, 2].map(|tx_index| TransactionID {
+                session_id: map.session_id.clone(),
+                tx_index,
+                branch: None,
+            })
+        );
+        // Setting a key to the value it had isn't a change
+        assert_eq!(
+            updates[0].diff,
+            ContentDiff::Map {
+                changed_keys: vec!["b".to_string(), "c".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_map_updates_read_only_new_transactions() {
+        let mut map = Value::new("comap");
+        let set = |key: &str, value: i32| MapChanges::new().set(key, value).build().unwrap();
+        map.write_with(&set("a", 1), TransactionMode::Trusting, 10);
+        let updates = record(&mut map);
+        let changed_keys = |update: &CoValueUpdate| match &update.diff {
+            ContentDiff::Map { changed_keys } => changed_keys.clone(),
+            other => panic!("expected a map diff, got {other:?}"),
+        };
+
+        map.write_with(&set("a", 2), TransactionMode::Trusting, 20);
+        // Made before the last change, so it doesn't win
+        map.write_with(&set("a", 3), TransactionMode::Trusting, 15);
+        map.write_with(&set("b", 1), TransactionMode::Trusting, 30);
+        let recorded: Vec<Vec<String>> = updates.lock().unwrap().iter().map(changed_keys).collect();
+        assert_eq!(recorded, [vec!["a"], vec![], vec!["b"]]);
+
+        // A change made outside of a batch isn't part of the next one, and its diff still
+        // reads it
+        let secret = map.secret.clone();
+        map.log()
+            .add_new_transaction(&set("c", 1), TransactionMode::Trusting, &secret, 40, None)
+            .unwrap();
+        map.write_with(
+            &MapChanges::new().set("b", 2).set("c", 1).build().unwrap(),
+            TransactionMode::Trusting,
+            50,
+        );
+        assert_eq!(changed_keys(&updates.lock().unwrap()[3]), ["b"]);
+    }
+
+    #[test]
+    fn test_undecryptable_changes_are_opaque() {
+        let mut map = Value::new("comap");
+        let updates = record(&mut map);
+        let (key_id, key_secret) = new_random_key_secret();
+        map.write_with(
+            &MapChanges::new().set("a", 1).build().unwrap(),
+            TransactionMode::Private { key_id, key_secret },
+            1,
+        );
+        map.write(&MapChanges::new().set("b", 1).build().unwrap());
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates[0].diff, ContentDiff::Opaque);
+        assert_eq!(
+            updates[1].diff,
+            ContentDiff::Map {
+                changed_keys: vec!["b".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_list_ranges_and_unsubscribe() {
+        let mut list = Value::new("colist");
+        let edit = |list: &mut Value, edit: &dyn Fn(&mut ListChanges)| {
+            let entries = list.entries();
+            let mut changes = ListChanges::new(&entries, list.log().next_transaction_id());
+            edit(&mut changes);
+            let changes_json = changes.build().unwrap();
+            list.write(&changes_json);
+        };
+        edit(&mut list, &|changes| {
+            changes
+                .append(["a", "b", "c", "d"].map(|item| json!(item)), None)
+                .unwrap();
+        });
+        let updates = record(&mut list);
+
+        edit(&mut list, &|changes| {
+            changes.append([json!("x"), json!("y")], Some(0)).unwrap();
+            changes.delete(2).unwrap();
+            changes.delete(3).unwrap();
+        });
+        assert_eq!(
+            updates.lock().unwrap()[0].diff,
+            ContentDiff::List {
+                inserted: vec![Range { start: 1, end: 3 }],
+                deleted: vec![Range { start: 2, end: 4 }],
+            }
+        );
+
+        let id = list.subscriptions.subscribe(|_| {});
+        assert!(list.subscriptions.unsubscribe(id));
+        assert!(!list.subscriptions.unsubscribe(id));
+
+        // Content that isn't a CoList's still notifies
+        list.write("[1]");
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[1].diff, ContentDiff::Opaque);
+        assert_eq!(
+            serde_json::to_value(&updates[0]).unwrap()["diff"],
+            json!({"type": "list", "inserted": [{"start": 1, "end": 3}], "deleted": [{"start": 2, "end": 4}]})
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Fixtures for the tests of this crate and the crates built on it, which get them through the
+//! `test-utils` feature.
+
+use ed25519_dalek::SigningKey;
+use serde_json::{json, value::RawValue, Value as JsonValue};
+
+use crate::core::{
+    CoID, SessionID, SessionLogInternal, SessionNewContent, SignerID, SignerSecret, TransactionMode,
+};
+
+/// An agent's session ID and signer secret. The same `seed` always gives the same agent.
+pub fn test_agent(seed: u8) -> (SessionID, SignerSecret) {
+    let (session_id, _, signer_secret) = test_signer(seed);
+    (session_id, signer_secret)
+}
+
+/// `test_agent(seed)` with its signer ID.
+pub fn test_signer(seed: u8) -> (SessionID, SignerID, SignerSecret) {
+    let signing_key = SigningKey::from_bytes(&[seed; 32]);
+    let signer_id: SignerID = signing_key.verifying_key().into();
+    let session_id = SessionID(format!("sealer_zTest/{}_session_z{seed}", signer_id.0));
+    (session_id, signer_id, signing_key.into())
+}
+
+/// An empty session log of `test_agent(seed)` in `co_id`, verifying against the agent's signer.
+pub fn test_session(co_id: &CoID, seed: u8) -> (SessionID, SessionLogInternal, SignerSecret) {
+    let (session_id, signer_id, signer_secret) = test_signer(seed);
+    let log = SessionLogInternal::new(co_id.clone(), session_id.clone(), Some(signer_id));
+    (session_id, log, signer_secret)
+}
+
+/// The header of a CoMap anyone can write to, with a different ID for every `uniqueness`.
+pub fn test_header(uniqueness: &str) -> JsonValue {
+    json!({
+        "type": "comap",
+        "ruleset": {"type": "unsafeAllowAll"},
+        "meta": null,
+        "uniqueness": uniqueness,
+    })
+}
+
+/// Writes trusting transactions to a session, handing each one out as new content, the way
+/// storage and peers receive it.
+#[derive(Clone)]
+pub struct TestWriter {
+    co_id: CoID,
+    session_id: SessionID,
+    signer_id: SignerID,
+    signer_secret: SignerSecret,
+    session_log: SessionLogInternal,
+}
+
+impl TestWriter {
+    pub fn new(co_id: &CoID, seed: u8) -> Self {
+        let (session_id, signer_id, signer_secret) = test_signer(seed);
+        let session_log =
+            SessionLogInternal::new(co_id.clone(), session_id.clone(), Some(signer_id.clone()));
+        Self {
+            co_id: co_id.clone(),
+            session_id,
+            signer_id,
+            signer_secret,
+            session_log,
+        }
+    }
+
+    pub fn co_id(&self) -> &CoID {
+        &self.co_id
+    }
+
+    pub fn session_id(&self) -> &SessionID {
+        &self.session_id
+    }
+
+    pub fn signer_id(&self) -> &SignerID {
+        &self.signer_id
+    }
+
+    pub fn signer_secret(&self) -> &SignerSecret {
+        &self.signer_secret
+    }
+
+    /// Everything written so far.
+    pub fn log(&self) -> &SessionLogInternal {
+        &self.session_log
+    }
+
+    /// An empty log for the same session, to receive the written content.
+    pub fn empty_log(&self) -> SessionLogInternal {
+        SessionLogInternal::new(
+            self.co_id.clone(),
+            self.session_id.clone(),
+            Some(self.signer_id.clone()),
+        )
+    }
+
+    /// Add a transaction with `changes`, made at its index in the session.
+    pub fn add(&mut self, changes: &str) -> SessionNewContent {
+        let after = self.session_log.transactions_json().len() as u32;
+        let (last_signature, transaction) = self
+            .session_log
+            .add_new_transaction(
+                changes,
+                TransactionMode::Trusting,
+                &self.signer_secret,
+                after as u64,
+                None,
+            )
+            .unwrap();
+        SessionNewContent {
+            after,
+            new_transactions: vec![RawValue::from_string(
+                serde_json::to_string(&transaction).unwrap(),
+            )
+            .unwrap()],
+            last_signature,
+        }
+    }
+}
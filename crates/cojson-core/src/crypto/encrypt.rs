@@ -13,7 +13,7 @@ pub fn encrypt(
   // Decode the base58 key secret (removing the "keySecret_z" prefix)
   let key_secret = key_secret
     .strip_prefix("keySecret_z")
-    .ok_or(CryptoError::InvalidPrefix("key secret", "keySecret_z"))?;
+    .ok_or(CryptoError::InvalidPrefix("keySecret_z", "key secret"))?;
   let key = bs58::decode(key_secret)
     .into_vec()
     .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
@@ -36,7 +36,7 @@ pub fn decrypt(
   // Decode the base58 key secret (removing the "keySecret_z" prefix)
   let key_secret = key_secret
     .strip_prefix("keySecret_z")
-    .ok_or(CryptoError::InvalidPrefix("key secret", "keySecret_z"))?;
+    .ok_or(CryptoError::InvalidPrefix("keySecret_z", "key secret"))?;
   let key = bs58::decode(key_secret)
     .into_vec()
     .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
@@ -5,6 +5,7 @@ use std::fmt;
 pub enum CryptoError {
   InvalidKeyLength(usize, usize),
   InvalidNonceLength,
+  InvalidSealedMessageLength(usize),
   InvalidSealerSecretFormat,
   InvalidSignatureLength,
   InvalidVerifyingKey(String),
@@ -13,6 +14,11 @@ pub enum CryptoError {
   CipherError,
   InvalidPrefix(&'static str, &'static str),
   Base58Error(String),
+  InvalidSecretSeedLength(usize),
+  InvalidWordlistLength(usize),
+  InvalidPassphraseLength(usize),
+  UnknownPassphraseWord(String),
+  PassphraseChecksumMismatch,
 }
 
 impl From<CryptoError> for String {
@@ -28,6 +34,9 @@ impl fmt::Display for CryptoError {
         write!(f, "Invalid key length (expected {expected}, got {actual})")
       }
       CryptoError::InvalidNonceLength => write!(f, "Invalid nonce length"),
+      CryptoError::InvalidSealedMessageLength(len) => {
+        write!(f, "Invalid sealed message length ({len} bytes)")
+      }
       CryptoError::InvalidSealerSecretFormat => {
         write!(
           f,
@@ -43,6 +52,19 @@ impl fmt::Display for CryptoError {
         write!(f, "Invalid {} format: must start with '{}'", field, prefix)
       }
       CryptoError::Base58Error(e) => write!(f, "Invalid base58: {}", e),
+      CryptoError::InvalidSecretSeedLength(len) => {
+        write!(f, "Invalid secret seed length ({len} bytes, expected 16-32 in steps of 4)")
+      }
+      CryptoError::InvalidWordlistLength(len) => {
+        write!(f, "Invalid wordlist length (expected 2048 words, got {len})")
+      }
+      CryptoError::InvalidPassphraseLength(words) => {
+        write!(f, "Invalid passphrase length ({words} words, expected 12-24 in steps of 3)")
+      }
+      CryptoError::UnknownPassphraseWord(word) => {
+        write!(f, "Unknown passphrase word: '{word}' is not in the wordlist")
+      }
+      CryptoError::PassphraseChecksumMismatch => write!(f, "Invalid passphrase: checksum mismatch"),
     }
   }
 }
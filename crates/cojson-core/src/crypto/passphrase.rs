@@ -0,0 +1,205 @@
+use crate::crypto::error::CryptoError;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+/// Number of words a BIP39 wordlist must contain (one per 11-bit group).
+pub const WORDLIST_LENGTH: usize = 2048;
+
+const BITS_PER_WORD: usize = 11;
+
+static ENGLISH_WORDLIST: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+/// The standard BIP39 English wordlist, the same one used by the passphrase examples on the TS side.
+pub fn english_wordlist() -> &'static [&'static str] {
+  ENGLISH_WORDLIST.get_or_init(|| include_str!("wordlists/english.txt").lines().collect())
+}
+
+fn check_wordlist<S: AsRef<str>>(wordlist: &[S]) -> Result<(), CryptoError> {
+  if wordlist.len() != WORDLIST_LENGTH {
+    return Err(CryptoError::InvalidWordlistLength(wordlist.len()));
+  }
+  Ok(())
+}
+
+/// Encode a secret seed as a BIP39 mnemonic passphrase, matching `entropyToMnemonic` from `@scure/bip39`.
+/// - `secret_seed`: 16, 20, 24, 28 or 32 bytes (account secret seeds are 32 bytes, giving 24 words)
+/// - `wordlist`: 2048 words to encode with
+///
+/// Returns the words joined by single spaces, or CryptoError if the seed or wordlist length is invalid.
+pub fn secret_seed_to_passphrase<S: AsRef<str>>(
+  secret_seed: &[u8],
+  wordlist: &[S],
+) -> Result<String, CryptoError> {
+  check_wordlist(wordlist)?;
+  if !(16..=32).contains(&secret_seed.len()) || !secret_seed.len().is_multiple_of(4) {
+    return Err(CryptoError::InvalidSecretSeedLength(secret_seed.len()));
+  }
+
+  // One checksum bit per 32 bits of entropy, taken from the start of the SHA-256 hash.
+  let checksum_bits = secret_seed.len() / 4;
+  let checksum = Sha256::digest(secret_seed)[0];
+
+  let mut bits: Vec<bool> = secret_seed
+    .iter()
+    .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+    .collect();
+  bits.extend((0..checksum_bits).map(|i| (checksum >> (7 - i)) & 1 == 1));
+
+  let words: Vec<&str> = bits
+    .chunks(BITS_PER_WORD)
+    .map(|chunk| {
+      let index = chunk.iter().fold(0usize, |acc, &bit| (acc << 1) | bit as usize);
+      wordlist[index].as_ref()
+    })
+    .collect();
+
+  Ok(words.join(" "))
+}
+
+/// Decode a BIP39 mnemonic passphrase back into the secret seed, matching `mnemonicToEntropy` from `@scure/bip39`.
+/// - `passphrase`: Words separated by whitespace
+/// - `wordlist`: The 2048 words the passphrase was encoded with
+///
+/// Returns the secret seed, or CryptoError on unknown words, a bad word count or a checksum mismatch.
+pub fn passphrase_to_secret_seed<S: AsRef<str>>(
+  passphrase: &str,
+  wordlist: &[S],
+) -> Result<Box<[u8]>, CryptoError> {
+  check_wordlist(wordlist)?;
+
+  let words: Vec<&str> = passphrase.split_whitespace().collect();
+  if !(12..=24).contains(&words.len()) || !words.len().is_multiple_of(3) {
+    return Err(CryptoError::InvalidPassphraseLength(words.len()));
+  }
+
+  let mut bits = Vec::with_capacity(words.len() * BITS_PER_WORD);
+  for word in &words {
+    let index = wordlist
+      .iter()
+      .position(|candidate| candidate.as_ref() == *word)
+      .ok_or_else(|| CryptoError::UnknownPassphraseWord(word.to_string()))?;
+    bits.extend((0..BITS_PER_WORD).rev().map(|i| (index >> i) & 1 == 1));
+  }
+
+  // Every 3 words encode 32 bits of entropy plus one checksum bit.
+  let checksum_bits = words.len() / 3;
+  let (entropy_bits, checksum) = bits.split_at(bits.len() - checksum_bits);
+
+  let secret_seed: Vec<u8> = entropy_bits
+    .chunks(8)
+    .map(|chunk| chunk.iter().fold(0u8, |acc, &bit| (acc << 1) | bit as u8))
+    .collect();
+
+  let expected = Sha256::digest(&secret_seed)[0];
+  let matches = checksum
+    .iter()
+    .enumerate()
+    .all(|(i, &bit)| ((expected >> (7 - i)) & 1 == 1) == bit);
+  if !matches {
+    return Err(CryptoError::PassphraseChecksumMismatch);
+  }
+
+  Ok(secret_seed.into_boxed_slice())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_english_wordlist() {
+    let wordlist = english_wordlist();
+    assert_eq!(wordlist.len(), WORDLIST_LENGTH);
+    assert_eq!(wordlist[0], "abandon");
+    assert_eq!(wordlist[WORDLIST_LENGTH - 1], "zoo");
+  }
+
+  #[test]
+  fn test_bip39_vectors() {
+    let wordlist = english_wordlist();
+    let vectors: [(&[u8], &str); 4] = [
+      (
+        &[0u8; 16],
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+      ),
+      (
+        &[0u8; 32],
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+         abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+      ),
+      (
+        &[0x7f; 32],
+        "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth \
+         useful legal winner thank year wave sausage worth title",
+      ),
+      (
+        &[0xff; 32],
+        "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+      ),
+    ];
+
+    for (secret_seed, passphrase) in vectors {
+      assert_eq!(secret_seed_to_passphrase(secret_seed, wordlist).unwrap(), passphrase);
+      assert_eq!(&*passphrase_to_secret_seed(passphrase, wordlist).unwrap(), secret_seed);
+    }
+  }
+
+  #[test]
+  fn test_roundtrip_random_seed() {
+    let wordlist = english_wordlist();
+    let secret_seed: [u8; 32] = rand::random();
+
+    let passphrase = secret_seed_to_passphrase(&secret_seed, wordlist).unwrap();
+    assert_eq!(passphrase.split(' ').count(), 24);
+
+    // Extra whitespace is tolerated when decoding
+    let spaced = format!("  {}\n", passphrase.replace(' ', "   "));
+    assert_eq!(&*passphrase_to_secret_seed(&spaced, wordlist).unwrap(), &secret_seed);
+  }
+
+  #[test]
+  fn test_custom_wordlist() {
+    let wordlist: Vec<String> = (0..WORDLIST_LENGTH).map(|i| format!("w{i}")).collect();
+    let secret_seed = [42u8; 32];
+
+    let passphrase = secret_seed_to_passphrase(&secret_seed, &wordlist).unwrap();
+    assert!(passphrase.starts_with("w337 "));
+    assert_eq!(&*passphrase_to_secret_seed(&passphrase, &wordlist).unwrap(), &secret_seed);
+
+    // The passphrase means nothing with a different wordlist
+    assert!(matches!(
+      passphrase_to_secret_seed(&passphrase, english_wordlist()),
+      Err(CryptoError::UnknownPassphraseWord(word)) if word == "w337"
+    ));
+  }
+
+  #[test]
+  fn test_errors() {
+    let wordlist = english_wordlist();
+
+    let mut words = vec!["abandon"; 24];
+    assert!(matches!(
+      passphrase_to_secret_seed(&words.join(" "), wordlist),
+      Err(CryptoError::PassphraseChecksumMismatch)
+    ));
+
+    words[23] = "notaword";
+    assert!(matches!(
+      passphrase_to_secret_seed(&words.join(" "), wordlist),
+      Err(CryptoError::UnknownPassphraseWord(word)) if word == "notaword"
+    ));
+
+    assert!(matches!(
+      passphrase_to_secret_seed("abandon abandon", wordlist),
+      Err(CryptoError::InvalidPassphraseLength(2))
+    ));
+    assert!(matches!(
+      secret_seed_to_passphrase(&[0u8; 31], wordlist),
+      Err(CryptoError::InvalidSecretSeedLength(31))
+    ));
+    assert!(matches!(
+      secret_seed_to_passphrase(&[0u8; 32], &wordlist[..100]),
+      Err(CryptoError::InvalidWordlistLength(100))
+    ));
+  }
+}
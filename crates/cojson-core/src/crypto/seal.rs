@@ -1,4 +1,4 @@
-use crate::crypto::x25519::x25519_diffie_hellman;
+use crate::crypto::x25519::{new_x25519_private_key, x25519_diffie_hellman, x25519_public_key};
 use crate::crypto::xsalsa20::{decrypt_xsalsa20_poly1305, encrypt_xsalsa20_poly1305};
 use crate::crypto::error::CryptoError;
 use crate::hash::blake3::generate_nonce;
@@ -27,8 +27,8 @@ pub fn seal(
     sender_secret
       .strip_prefix("sealerSecret_z")
       .ok_or(CryptoError::InvalidPrefix(
-        "sealer secret",
         "sealerSecret_z",
+        "sealer secret",
       ))?;
   let sender_private_key = bs58::decode(sender_secret)
     .into_vec()
@@ -37,7 +37,7 @@ pub fn seal(
   // Decode the base58 recipient ID (removing the "sealer_z" prefix)
   let recipient_id = recipient_id
     .strip_prefix("sealer_z")
-    .ok_or(CryptoError::InvalidPrefix("sealer ID", "sealer_z"))?;
+    .ok_or(CryptoError::InvalidPrefix("sealer_z", "sealer ID"))?;
   let recipient_public_key = bs58::decode(recipient_id)
     .into_vec()
     .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
@@ -74,8 +74,8 @@ pub fn unseal(
     recipient_secret
       .strip_prefix("sealerSecret_z")
       .ok_or(CryptoError::InvalidPrefix(
-        "sealer secret",
         "sealerSecret_z",
+        "sealer secret",
       ))?;
   let recipient_private_key = bs58::decode(recipient_secret)
     .into_vec()
@@ -84,7 +84,7 @@ pub fn unseal(
   // Decode the base58 sender ID (removing the "sealer_z" prefix)
   let sender_id = sender_id
     .strip_prefix("sealer_z")
-    .ok_or(CryptoError::InvalidPrefix("sealer ID", "sealer_z"))?;
+    .ok_or(CryptoError::InvalidPrefix("sealer_z", "sealer ID"))?;
   let sender_public_key = bs58::decode(sender_id)
     .into_vec()
     .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
@@ -98,6 +98,93 @@ pub fn unseal(
   decrypt_xsalsa20_poly1305(&shared_secret, &nonce, sealed_message)
 }
 
+/// Length of the ephemeral X25519 public key prepended to anonymously sealed messages.
+pub const EPHEMERAL_PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Derive the nonce for an anonymous seal from the ephemeral and recipient public keys,
+/// so that no nonce material has to be agreed on out of band.
+fn anonymous_nonce(ephemeral_public_key: &[u8], recipient_public_key: &[u8]) -> Box<[u8]> {
+  let mut nonce_material = Vec::with_capacity(ephemeral_public_key.len() + recipient_public_key.len());
+  nonce_material.extend_from_slice(ephemeral_public_key);
+  nonce_material.extend_from_slice(recipient_public_key);
+  generate_nonce(&nonce_material)
+}
+
+/// Seal a message to a recipient without revealing or requiring a sender identity.
+/// - `message`: Raw bytes to seal
+/// - `recipient_id`: Base58-encoded recipient's public key with "sealer_z" prefix
+///
+/// Returns the ephemeral public key (32 bytes) followed by the sealed bytes,
+/// or CryptoError if the recipient ID is invalid.
+///
+/// The sealing process:
+/// 1. Generate a fresh ephemeral X25519 key pair for this message
+/// 2. Generate shared secret between the ephemeral secret and the recipient's public key
+/// 3. Derive the nonce from both public keys using BLAKE3
+/// 4. Encrypt message using XSalsa20-Poly1305 with the shared secret
+pub fn seal_anonymous(message: &[u8], recipient_id: &str) -> Result<Box<[u8]>, CryptoError> {
+  // Decode the base58 recipient ID (removing the "sealer_z" prefix)
+  let recipient_id = recipient_id
+    .strip_prefix("sealer_z")
+    .ok_or(CryptoError::InvalidPrefix("sealer_z", "sealer ID"))?;
+  let recipient_public_key = bs58::decode(recipient_id)
+    .into_vec()
+    .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
+
+  // Generate the ephemeral key pair, which is dropped after sealing
+  let ephemeral_private_key = new_x25519_private_key();
+  let ephemeral_public_key = x25519_public_key(&ephemeral_private_key)?;
+
+  let nonce = anonymous_nonce(&ephemeral_public_key, &recipient_public_key);
+
+  // Generate shared secret using X25519
+  let shared_secret = x25519_diffie_hellman(&ephemeral_private_key, &recipient_public_key)?;
+
+  // Encrypt message using XSalsa20-Poly1305
+  let ciphertext = encrypt_xsalsa20_poly1305(&shared_secret, &nonce, message)?;
+
+  let mut sealed = Vec::with_capacity(EPHEMERAL_PUBLIC_KEY_LENGTH + ciphertext.len());
+  sealed.extend_from_slice(&ephemeral_public_key);
+  sealed.extend_from_slice(&ciphertext);
+  Ok(sealed.into_boxed_slice())
+}
+
+/// Unseal a message produced by `seal_anonymous`.
+/// - `sealed_message`: The ephemeral public key followed by the sealed bytes
+/// - `recipient_secret`: Base58-encoded recipient's private key with "sealerSecret_z" prefix
+///
+/// Returns unsealed bytes or CryptoError if the input is malformed or authentication fails.
+pub fn unseal_anonymous(
+  sealed_message: &[u8],
+  recipient_secret: &str,
+) -> Result<Box<[u8]>, CryptoError> {
+  if sealed_message.len() < EPHEMERAL_PUBLIC_KEY_LENGTH {
+    return Err(CryptoError::InvalidSealedMessageLength(sealed_message.len()));
+  }
+  let (ephemeral_public_key, ciphertext) = sealed_message.split_at(EPHEMERAL_PUBLIC_KEY_LENGTH);
+
+  // Decode the base58 recipient secret (removing the "sealerSecret_z" prefix)
+  let recipient_secret =
+    recipient_secret
+      .strip_prefix("sealerSecret_z")
+      .ok_or(CryptoError::InvalidPrefix(
+        "sealerSecret_z",
+        "sealer secret",
+      ))?;
+  let recipient_private_key = bs58::decode(recipient_secret)
+    .into_vec()
+    .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
+  let recipient_public_key = x25519_public_key(&recipient_private_key)?;
+
+  let nonce = anonymous_nonce(ephemeral_public_key, &recipient_public_key);
+
+  // Generate shared secret using X25519
+  let shared_secret = x25519_diffie_hellman(&recipient_private_key, ephemeral_public_key)?;
+
+  // Decrypt message using XSalsa20-Poly1305
+  decrypt_xsalsa20_poly1305(&shared_secret, &nonce, ciphertext)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -161,4 +248,63 @@ mod tests {
     );
     assert!(result.is_err());
   }
+
+  fn new_sealer() -> (String, String) {
+    let private_key = new_x25519_private_key();
+    let public_key = x25519_public_key(&private_key).unwrap();
+    (
+      format!("sealerSecret_z{}", bs58::encode(&private_key).into_string()),
+      format!("sealer_z{}", bs58::encode(&public_key).into_string()),
+    )
+  }
+
+  #[test]
+  fn test_seal_unseal_anonymous() {
+    let (recipient_secret, recipient_id) = new_sealer();
+    let message = b"Secret message";
+
+    let sealed = seal_anonymous(message, &recipient_id).unwrap();
+    assert_eq!(
+      sealed.len(),
+      EPHEMERAL_PUBLIC_KEY_LENGTH + message.len() + 16,
+      "Sealed message should contain the ephemeral key, ciphertext and tag"
+    );
+
+    let unsealed = unseal_anonymous(&sealed, &recipient_secret).unwrap();
+    assert_eq!(&*unsealed, message);
+
+    // Every seal uses a fresh ephemeral key
+    let sealed2 = seal_anonymous(message, &recipient_id).unwrap();
+    assert_ne!(sealed[..EPHEMERAL_PUBLIC_KEY_LENGTH], sealed2[..EPHEMERAL_PUBLIC_KEY_LENGTH]);
+    assert_ne!(sealed, sealed2);
+  }
+
+  #[test]
+  fn test_unseal_anonymous_failures() {
+    let (recipient_secret, recipient_id) = new_sealer();
+    let (other_secret, _) = new_sealer();
+
+    let sealed = seal_anonymous(b"test", &recipient_id).unwrap();
+
+    // Wrong recipient
+    assert!(matches!(
+      unseal_anonymous(&sealed, &other_secret),
+      Err(CryptoError::WrongTag)
+    ));
+
+    // Tampered ephemeral key
+    let mut tampered = sealed.to_vec();
+    tampered[0] ^= 1;
+    assert!(unseal_anonymous(&tampered, &recipient_secret).is_err());
+
+    // Too short to contain the ephemeral key
+    assert!(matches!(
+      unseal_anonymous(&sealed[..10], &recipient_secret),
+      Err(CryptoError::InvalidSealedMessageLength(10))
+    ));
+
+    // Invalid recipient formats
+    assert!(seal_anonymous(b"test", "invalid_key").is_err());
+    assert!(unseal_anonymous(&sealed, "invalid_key").is_err());
+  }
 }
@@ -10,7 +10,7 @@ use bs58;
 /// Returns base58-encoded signature with "signature_z" prefix or error string.
 pub fn sign(message: &[u8], secret: &str) -> Result<String, CryptoError> {
   let secret_bytes = bs58::decode(secret.strip_prefix("signerSecret_z").ok_or(
-    CryptoError::InvalidPrefix("signer secret", "signerSecret_z"),
+    CryptoError::InvalidPrefix("signerSecret_z", "signer secret"),
   )?)
   .into_vec()
   .map_err(|e| CryptoError::Base58Error(e.to_string()))?;
@@ -2,6 +2,10 @@
 #[cfg(feature = "lzy")]
 pub use lzy;
 
+// Re-export the CoMap derive macro, so schemas only need cojson-core
+#[cfg(feature = "derive")]
+pub use cojson_derive::CoMap;
+
 pub mod core {
     pub mod nonce;
     pub mod session_log;
@@ -11,8 +15,36 @@ pub mod core {
     pub use keys::*;
     pub mod cache;
     pub use cache::*;
+    pub mod crypto_provider;
+    pub use crypto_provider::*;
     pub mod error;
     pub use error::*;
+    pub mod ops;
+    pub use ops::*;
+    pub mod group;
+    pub use group::*;
+    pub mod storage;
+    pub use storage::*;
+    pub mod equivocation;
+    pub use equivocation::*;
+    pub mod session_validator;
+    pub use session_validator::*;
+    pub mod config;
+    pub use config::*;
+    pub mod stream;
+    pub use stream::*;
+    pub mod branch;
+    pub use branch::*;
+    pub mod history;
+    pub use history::*;
+    pub mod changes;
+    pub use changes::*;
+    pub mod schema;
+    pub use schema::*;
+    pub mod subscription;
+    pub use subscription::*;
+    #[cfg(any(test, feature = "test-utils"))]
+    pub mod test_utils;
 }
 
 pub mod hash {
@@ -26,6 +58,7 @@ pub mod crypto {
     pub mod encrypt;
     pub mod signature;
     pub mod xsalsa20;
+    pub mod passphrase;
 
     pub use ed25519::*;
     pub use x25519::*;
@@ -33,6 +66,7 @@ pub mod crypto {
     pub use encrypt::*;
     pub use signature::*;
     pub use xsalsa20::*;
+    pub use passphrase::*;
     pub mod error;
     pub use error::*;
 }
@@ -0,0 +1,242 @@
+//! `#[derive(CoMap)]`, mapping a struct's fields onto CoMap keys by implementing
+//! `cojson_core::core::CoMapSchema`.
+//!
+//! Field types are read and written with serde, so enums, nested values and `CoRef`s to other
+//! CoValues work like any other field. `Option` fields are optional keys.
+//!
+//! Attributes:
+//! - `#[co_map(rename_all = "camelCase")]` on the struct, for keys in camelCase like in TS
+//! - `#[co_map(rename = "key")]` on a field, for a key of its own
+//!
+//! Anything else, or either option in the other place, is a compile error.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Fields, GenericArgument,
+    LitStr, PathArguments, Type,
+};
+
+#[proc_macro_derive(CoMap, attributes(co_map))]
+pub fn derive_co_map(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct CoMapAttrs {
+    rename: Option<String>,
+    /// Only `camelCase` is supported, field names are used as they are otherwise.
+    camel_case: bool,
+}
+
+/// Where `#[co_map(...)]` is, which decides the options it takes.
+#[derive(Clone, Copy, PartialEq)]
+enum Placement {
+    Struct,
+    Field,
+}
+
+fn parse_attrs(attrs: &[Attribute], placement: Placement) -> syn::Result<CoMapAttrs> {
+    let mut parsed = CoMapAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("co_map")) {
+        attr.parse_nested_meta(|meta| {
+            let value = || meta.value()?.parse::<LitStr>();
+            match placement {
+                Placement::Struct if meta.path.is_ident("rename_all") => {
+                    let value = value()?;
+                    if value.value() != "camelCase" {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "rename_all must be \"camelCase\"",
+                        ));
+                    }
+                    parsed.camel_case = true;
+                }
+                Placement::Field if meta.path.is_ident("rename") => {
+                    parsed.rename = Some(value()?.value());
+                }
+                Placement::Struct if meta.path.is_ident("rename") => {
+                    return Err(meta.error("`rename` goes on fields, use `rename_all` here"));
+                }
+                Placement::Field if meta.path.is_ident("rename_all") => {
+                    return Err(meta.error("`rename_all` goes on the struct, use `rename` here"));
+                }
+                Placement::Struct => return Err(meta.error("expected `rename_all`")),
+                Placement::Field => return Err(meta.error("expected `rename`")),
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+fn camel_case(name: &str) -> String {
+    let mut key = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.trim_start_matches("r#").chars() {
+        if c == '_' {
+            upper = !key.is_empty();
+        } else if upper {
+            key.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            key.push(c);
+        }
+    }
+    key
+}
+
+/// The `T` of an `Option<T>` field, which makes the key optional.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "CoMap can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            input.span(),
+            "CoMap can only be derived for structs with named fields",
+        ));
+    };
+
+    let camel_case_keys = parse_attrs(&input.attrs, Placement::Struct)?.camel_case;
+
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named fields have idents");
+        let name = ident.to_string();
+        let key = match parse_attrs(&field.attrs, Placement::Field)?.rename {
+            Some(key) => key,
+            None if camel_case_keys => camel_case(&name),
+            None => name.trim_start_matches("r#").to_string(),
+        };
+
+        if let Some(inner) = option_inner(&field.ty) {
+            reads.push(quote! {
+                #ident: ::cojson_core::core::read_optional_co_map_field::<#inner>(content, #key)?
+            });
+            writes.push(quote! {
+                (#key, ::cojson_core::core::write_optional_co_map_field(#key, &self.#ident)?)
+            });
+        } else {
+            reads.push(quote! {
+                #ident: ::cojson_core::core::read_co_map_field(content, #key)?
+            });
+            writes.push(quote! {
+                (#key, ::cojson_core::core::write_co_map_field(#key, &self.#ident)?)
+            });
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::cojson_core::core::CoMapSchema for #name #ty_generics #where_clause {
+            fn from_co_map(
+                content: &::cojson_core::core::CoMapContent,
+            ) -> ::std::result::Result<Self, ::cojson_core::core::CoJsonCoreError> {
+                ::std::result::Result::Ok(Self {
+                    #(#reads,)*
+                })
+            }
+
+            fn co_map_fields(
+                &self,
+            ) -> ::std::result::Result<
+                ::cojson_core::core::CoMapFields,
+                ::cojson_core::core::CoJsonCoreError,
+            > {
+                ::std::result::Result::Ok(::std::vec![#(#writes),*])
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn error(input: DeriveInput) -> String {
+        expand(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_rename_placement() {
+        assert!(expand(&parse_quote! {
+            #[co_map(rename_all = "camelCase")]
+            struct Task {
+                #[co_map(rename = "$labels")]
+                labels: Vec<String>,
+            }
+        })
+        .is_ok());
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Task {
+                    #[co_map(rename_all = "camelCase")]
+                    due_date: u64,
+                }
+            }),
+            "`rename_all` goes on the struct, use `rename` here"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                #[co_map(rename = "task")]
+                struct Task {
+                    title: String,
+                }
+            }),
+            "`rename` goes on fields, use `rename_all` here"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                struct Task {
+                    #[co_map(skip)]
+                    title: String,
+                }
+            }),
+            "expected `rename`"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_rename_all() {
+        for case in ["snake_case", "PascalCase", ""] {
+            assert_eq!(
+                error(parse_quote! {
+                    #[co_map(rename_all = #case)]
+                    struct Task {
+                        due_date: u64,
+                    }
+                }),
+                "rename_all must be \"camelCase\""
+            );
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use cojson_core::core::test_utils::test_session;
+use cojson_core::core::{
+    CoID, CoJsonCoreError, CoMapContent, CoMapSchema, CoRef, CoValueHistory, HistoryFilter,
+    TransactionMode,
+};
+use cojson_derive::CoMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Open,
+    Done,
+}
+
+#[derive(Debug, Clone, PartialEq, CoMap)]
+struct Person {
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, CoMap)]
+#[co_map(rename_all = "camelCase")]
+struct Task {
+    title: String,
+    status: Status,
+    due_date: Option<u64>,
+    assignee: Option<CoRef<Person>>,
+    #[co_map(rename = "$labels")]
+    labels: Vec<String>,
+}
+
+fn content(value: serde_json::Value) -> CoMapContent {
+    value.as_object().unwrap().clone()
+}
+
+#[test]
+fn test_read_co_map() {
+    let task = Task::from_co_map(&content(json!({
+        "title": "Ship it",
+        "status": "open",
+        "dueDate": 1700000000000u64,
+        "assignee": "co_zAlice",
+        "$labels": ["release"],
+    })))
+    .unwrap();
+    assert_eq!(task.status, Status::Open);
+    assert_eq!(task.due_date, Some(1700000000000));
+    assert_eq!(
+        task.assignee.as_ref().map(CoRef::id),
+        Some(&CoID("co_zAlice".to_string()))
+    );
+
+    // Optional keys can be missing, required ones can't
+    let task = Task::from_co_map(&content(
+        json!({"title": "t", "status": "done", "$labels": []}),
+    ))
+    .unwrap();
+    assert_eq!(task.assignee, None);
+    assert!(matches!(
+        Task::from_co_map(&content(json!({"title": "t", "$labels": []}))),
+        Err(CoJsonCoreError::InvalidCoMapField { key, .. }) if key == "status"
+    ));
+    assert!(matches!(
+        Task::from_co_map(&content(json!({"title": "t", "status": "closed", "$labels": []}))),
+        Err(CoJsonCoreError::InvalidCoMapField { key, .. }) if key == "status"
+    ));
+}
+
+#[test]
+fn test_write_and_read_back() {
+    let (session_id, mut log, signer_secret) = test_session(&CoID("co_zTask".to_string()), 1);
+    let mut write = |changes_json: String, made_at: u64| {
+        log.add_new_transaction(
+            &changes_json,
+            TransactionMode::Trusting,
+            &signer_secret,
+            made_at,
+            None,
+        )
+        .unwrap();
+    };
+
+    let task = Task {
+        title: "Ship it".to_string(),
+        status: Status::Open,
+        due_date: None,
+        assignee: Some(CoRef::new(CoID("co_zAlice".to_string()))),
+        labels: vec!["release".to_string()],
+    };
+    write(task.to_map_changes().unwrap().build().unwrap(), 1);
+
+    let mut updated = task.clone();
+    updated.status = Status::Done;
+    updated.assignee = None;
+    let changes = updated.changes_since(&task).unwrap();
+    assert_eq!(
+        changes.build().unwrap(),
+        r#"[{"key":"status","op":"set","value":"done"},{"key":"assignee","op":"del"}]"#
+    );
+    write(changes.build().unwrap(), 2);
+
+    let history = CoValueHistory::from_session_logs(None, [(&session_id, &log)], |_| None).unwrap();
+    let read = |filter| Task::from_co_map(&history.map_at(&filter).unwrap()).unwrap();
+    assert_eq!(read(HistoryFilter::All), updated);
+    assert_eq!(read(HistoryFilter::AtTime(1)), task);
+}
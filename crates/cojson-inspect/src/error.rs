@@ -0,0 +1,27 @@
+use cojson_core::core::CoJsonCoreError;
+use cojson_storage_sqlite::StorageError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum InspectError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid export: {0}")]
+    InvalidExport(#[source] serde_json::Error),
+
+    #[error("Invalid signatureAfter index {index:?} in session {session_id}")]
+    InvalidCheckpoint { session_id: String, index: String },
+
+    #[error(transparent)]
+    Core(#[from] CoJsonCoreError),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+
+    #[error("CoValue {0} not found")]
+    CoValueNotFound(String),
+
+    #[error("Invalid key {0:?} (expected keyID=keySecret)")]
+    InvalidKey(String),
+}
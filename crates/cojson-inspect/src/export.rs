@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, HashMap};
+
+use cojson_core::core::{CoID, KeyID, KeySecret, SessionID, Signature, SignerID};
+use cojson_storage_sqlite::{SignatureAfter, SqliteStorage};
+use serde::Deserialize;
+use serde_json::{value::RawValue, Value as JsonValue};
+
+use crate::InspectError;
+
+/// A key secret known to whoever made the export.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KnownKey {
+    pub id: KeyID,
+    pub secret: KeySecret,
+}
+
+/// One session of an exported CoValue.
+#[derive(Debug, Clone)]
+pub struct ExportedSession {
+    /// The signer to verify against, or None if it can't be resolved.
+    pub signer_id: Option<SignerID>,
+    pub transactions: Vec<Box<RawValue>>,
+    /// The signatures of the session, in order: every `signatureAfter` checkpoint, ending with
+    /// the last signature.
+    pub signatures: Vec<SignatureAfter>,
+}
+
+/// A CoValue with everything needed to verify it, wherever it came from.
+#[derive(Debug, Clone)]
+pub struct ExportedCoValue {
+    pub id: CoID,
+    /// Missing from exports made with `getCurrentContent()`-style dumps.
+    pub header: Option<JsonValue>,
+    pub known_keys: Vec<KnownKey>,
+    pub sessions: BTreeMap<SessionID, ExportedSession>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSession {
+    #[serde(rename = "signerID")]
+    signer_id: Option<SignerID>,
+    transactions: Vec<Box<RawValue>>,
+    last_signature: Option<Signature>,
+    /// Keyed by the index of the transaction each signature is after.
+    #[serde(default)]
+    signature_after: HashMap<String, Option<Signature>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonExport {
+    #[serde(rename = "coID")]
+    co_id: CoID,
+    /// The signer of every session that doesn't name its own.
+    #[serde(rename = "signerID")]
+    signer_id: Option<SignerID>,
+    header: Option<JsonValue>,
+    #[serde(default)]
+    known_keys: Vec<KnownKey>,
+    #[serde(alias = "exampleBase")]
+    sessions: BTreeMap<SessionID, JsonSession>,
+}
+
+/// The `signatureAfter` checkpoints followed by the last signature, unless that's the last
+/// checkpoint already.
+fn with_last_signature(
+    mut signatures: Vec<SignatureAfter>,
+    transaction_count: usize,
+    last_signature: Option<Signature>,
+) -> Vec<SignatureAfter> {
+    signatures.sort_by_key(|signature| signature.idx);
+    if let Some(last_signature) = last_signature {
+        let last_idx = transaction_count.saturating_sub(1) as u32;
+        if transaction_count > 0 && signatures.last().map(|s| s.idx) != Some(last_idx) {
+            signatures.push(SignatureAfter {
+                idx: last_idx,
+                signature: last_signature,
+            });
+        }
+    }
+    signatures
+}
+
+impl ExportedCoValue {
+    /// Read a JSON export: `coID`, an optional `header` and `signerID`, `knownKeys`, and the
+    /// sessions under `sessions` (or `exampleBase`, as in the test data).
+    ///
+    /// Fails with `InvalidCheckpoint` if a `signatureAfter` key isn't a transaction index.
+    pub fn from_json(json: &str) -> Result<Self, InspectError> {
+        let export: JsonExport = serde_json::from_str(json).map_err(InspectError::InvalidExport)?;
+
+        let sessions = export
+            .sessions
+            .into_iter()
+            .map(|(session_id, session)| {
+                let signer_id = session
+                    .signer_id
+                    .or_else(|| export.signer_id.clone())
+                    .or_else(|| session_id.agent_signer_id());
+                let mut checkpoints = Vec::new();
+                for (index, signature) in session.signature_after {
+                    let idx = index.parse().map_err(|_| InspectError::InvalidCheckpoint {
+                        session_id: session_id.0.clone(),
+                        index: index.clone(),
+                    })?;
+                    if let Some(signature) = signature {
+                        checkpoints.push(SignatureAfter { idx, signature });
+                    }
+                }
+                let signatures = with_last_signature(
+                    checkpoints,
+                    session.transactions.len(),
+                    session.last_signature,
+                );
+                let session = ExportedSession {
+                    signer_id,
+                    transactions: session.transactions,
+                    signatures,
+                };
+                Ok((session_id, session))
+            })
+            .collect::<Result<_, InspectError>>()?;
+
+        Ok(Self {
+            id: export.co_id,
+            header: export.header,
+            known_keys: export.known_keys,
+            sessions,
+        })
+    }
+
+    /// Read a CoValue from a `cojson-storage-sqlite` database as stored, without verifying it.
+    ///
+    /// Sessions are verified against the signer in their agent session ID.
+    pub fn from_sqlite(storage: &SqliteStorage, id: &CoID) -> Result<Self, InspectError> {
+        let co_value = storage
+            .get_co_value(id)?
+            .ok_or_else(|| InspectError::CoValueNotFound(id.0.clone()))?;
+
+        let mut sessions = BTreeMap::new();
+        for session in storage.get_co_value_sessions(co_value.row_id)? {
+            let transactions = match session.last_idx {
+                0 => Vec::new(),
+                last_idx => storage.get_transactions(session.row_id, 0, last_idx - 1)?,
+            };
+            let signatures = with_last_signature(
+                storage.get_signatures(session.row_id, 0)?,
+                transactions.len(),
+                Some(session.last_signature),
+            );
+            sessions.insert(
+                session.session_id.clone(),
+                ExportedSession {
+                    signer_id: session.session_id.agent_signer_id(),
+                    transactions,
+                    signatures,
+                },
+            );
+        }
+
+        Ok(Self {
+            id: co_value.id,
+            header: Some(co_value.header),
+            known_keys: Vec::new(),
+            sessions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inspect, key_secrets};
+    use cojson_core::core::test_utils::{test_header, TestWriter};
+    use cojson_core::core::SessionLogStorage;
+
+    #[test]
+    fn test_from_json() {
+        let json = std::fs::read_to_string("../cojson-core/data/singleTxSessionMeta.json").unwrap();
+        let export = ExportedCoValue::from_json(&json).unwrap();
+        assert_eq!(export.id.0, "co_zCwu65svAPV9DBTY12PLhR18cXQ");
+        assert!(export.header.is_none());
+        assert_eq!(export.known_keys.len(), 1);
+
+        // The session's own signer wins over the export's
+        let session = export.sessions.values().next().unwrap();
+        assert_eq!(
+            session.signer_id.as_ref().unwrap().0,
+            "signer_zCTvvz8DmbfRDrfXKfNubb4RREEmr9KTK7i9EEXSXJwx1"
+        );
+        assert_eq!(session.transactions.len(), 1);
+        assert_eq!(session.signatures.len(), 1);
+        assert_eq!(session.signatures[0].idx, 0);
+    }
+
+    #[test]
+    fn test_invalid_checkpoint_index() {
+        let json = r#"{
+            "coID": "co_zTest",
+            "knownKeys": [],
+            "sessions": {
+                "co_zTest_session_zTest": {
+                    "transactions": [],
+                    "lastSignature": null,
+                    "signatureAfter": { "first": "signature_zTest" }
+                }
+            }
+        }"#;
+        assert!(matches!(
+            ExportedCoValue::from_json(json),
+            Err(InspectError::InvalidCheckpoint { index, .. }) if index == "first"
+        ));
+    }
+
+    #[test]
+    fn test_checkpoints_end_with_last_signature() {
+        let checkpoint = |idx| SignatureAfter {
+            idx,
+            signature: Signature(format!("signature_z{idx}")),
+        };
+        let last = Signature("signature_zLast".to_string());
+
+        let signatures = with_last_signature(vec![checkpoint(4), checkpoint(1)], 6, Some(last));
+        let idxs: Vec<u32> = signatures.iter().map(|s| s.idx).collect();
+        assert_eq!(idxs, [1, 4, 5]);
+        assert_eq!(signatures[2].signature.0, "signature_zLast");
+
+        // The last signature is the final checkpoint already
+        let signatures = with_last_signature(vec![checkpoint(5)], 6, Some(checkpoint(5).signature));
+        assert_eq!(signatures.len(), 1);
+    }
+
+    #[test]
+    fn test_from_sqlite() {
+        let header = test_header("a");
+        let id = CoID::for_header(&header);
+        let mut writer = TestWriter::new(&id, 7);
+        let session_id = writer.session_id().clone();
+
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut stored_log = writer.empty_log();
+        for i in 0..3 {
+            let content = writer.add(&format!("[{i}]"));
+            storage
+                .append_transactions(&id, Some(&header), &session_id, &mut stored_log, content)
+                .unwrap();
+        }
+
+        let export = ExportedCoValue::from_sqlite(&storage, &id).unwrap();
+        assert_eq!(export.header, Some(header));
+        assert_eq!(
+            export.sessions[&session_id].signer_id.as_ref(),
+            Some(writer.signer_id())
+        );
+
+        let report = inspect(&export, &key_secrets(&export, [])).unwrap();
+        assert_eq!(report.counts.valid, 3);
+
+        assert!(matches!(
+            ExportedCoValue::from_sqlite(&storage, &CoID("co_zMissing".to_string())),
+            Err(InspectError::CoValueNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,13 @@
+//! Inspect exported CoValues: verify every session's signature chain and decrypt what the
+//! given keys allow, to debug broken CoValues outside of a browser console.
+//!
+//! CoValues are read from JSON exports (the shape of `cojson-core/data/multiTxSession.json`)
+//! or from a `cojson-storage-sqlite` database.
+
+pub mod error;
+pub mod export;
+pub mod report;
+
+pub use error::*;
+pub use export::*;
+pub use report::*;
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use cojson_core::core::{CoID, KeyID, KeySecret};
+use cojson_inspect::{inspect, key_secrets, ExportedCoValue, InspectError};
+use cojson_storage_sqlite::SqliteStorage;
+
+#[derive(Debug, Subcommand)]
+enum Source {
+    /// A JSON export with `coID`, `header`, `signerID`, `knownKeys` and `sessions`
+    Json { file: PathBuf },
+    /// A CoValue in a `cojson-storage-sqlite` database
+    Sqlite { db: PathBuf, co_id: String },
+}
+
+/// Verify the signatures of every session of a CoValue and decrypt its transactions.
+///
+/// Exits with a failure if any transaction is invalid.
+#[derive(Debug, Parser)]
+#[command(name = "cojson-inspect", version)]
+struct Args {
+    /// A key to decrypt with, as `keyID=keySecret`. Can be repeated
+    #[arg(long = "key", global = true)]
+    keys: Vec<String>,
+
+    /// Print the full report as JSON, including the decrypted changes
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    source: Source,
+}
+
+fn parse_key(key: &str) -> Result<(KeyID, KeySecret), InspectError> {
+    match key.split_once('=') {
+        Some((id, secret)) if id.starts_with("key_z") && secret.starts_with("keySecret_z") => {
+            Ok((KeyID(id.to_string()), KeySecret(secret.to_string())))
+        }
+        _ => Err(InspectError::InvalidKey(key.to_string())),
+    }
+}
+
+fn run(args: Args) -> Result<bool, InspectError> {
+    let extra_keys = args
+        .keys
+        .iter()
+        .map(|key| parse_key(key))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let export = match args.source {
+        Source::Json { file } => ExportedCoValue::from_json(&std::fs::read_to_string(file)?)?,
+        Source::Sqlite { db, co_id } => {
+            ExportedCoValue::from_sqlite(&SqliteStorage::open(db)?, &CoID(co_id))?
+        }
+    };
+    let report = inspect(&export, &key_secrets(&export, extra_keys))?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        println!("{report}");
+    }
+    Ok(!report.has_invalid())
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::from(2)
+        }
+    }
+}
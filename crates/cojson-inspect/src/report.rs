@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use cojson_core::core::{
+    CoID, KeyID, KeySecret, SessionID, SessionLogInternal, SignerID, Transaction,
+};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::{ExportedCoValue, ExportedSession, InspectError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionStatus {
+    /// Signed by the session's signer and readable.
+    Valid,
+    /// Not covered by a valid signature, or not a transaction at all.
+    Invalid,
+    /// Readable, but the session's signer is unknown, so it couldn't be verified.
+    Unverified,
+    /// Validly signed, but encrypted with a key that wasn't given or doesn't fit.
+    Undecryptable,
+}
+
+impl fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransactionStatus::Valid => "valid",
+            TransactionStatus::Invalid => "invalid",
+            TransactionStatus::Unverified => "unverified",
+            TransactionStatus::Undecryptable => "undecryptable",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReport {
+    pub idx: u32,
+    pub status: TransactionStatus,
+    pub privacy: Option<String>,
+    pub made_at: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_used: Option<KeyID>,
+    /// The decrypted changes, parsed as JSON if they are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<JsonValue>,
+    /// Why the transaction isn't valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StatusCounts {
+    pub valid: u32,
+    pub invalid: u32,
+    pub unverified: u32,
+    pub undecryptable: u32,
+}
+
+impl StatusCounts {
+    fn count(&mut self, status: TransactionStatus) {
+        match status {
+            TransactionStatus::Valid => self.valid += 1,
+            TransactionStatus::Invalid => self.invalid += 1,
+            TransactionStatus::Unverified => self.unverified += 1,
+            TransactionStatus::Undecryptable => self.undecryptable += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionReport {
+    #[serde(rename = "sessionID")]
+    pub session_id: SessionID,
+    #[serde(rename = "signerID")]
+    pub signer_id: Option<SignerID>,
+    pub counts: StatusCounts,
+    pub transactions: Vec<TransactionReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoValueReport {
+    #[serde(rename = "coID")]
+    pub id: CoID,
+    pub has_header: bool,
+    pub counts: StatusCounts,
+    pub sessions: Vec<SessionReport>,
+}
+
+impl CoValueReport {
+    pub fn has_invalid(&self) -> bool {
+        self.counts.invalid > 0
+    }
+}
+
+/// The keys to decrypt with: those in the export, plus `extra_keys`.
+pub fn key_secrets(
+    export: &ExportedCoValue,
+    extra_keys: impl IntoIterator<Item = (KeyID, KeySecret)>,
+) -> HashMap<KeyID, KeySecret> {
+    export
+        .known_keys
+        .iter()
+        .map(|key| (key.id.clone(), key.secret.clone()))
+        .chain(extra_keys)
+        .collect()
+}
+
+/// Verify and decrypt every session of `export`.
+pub fn inspect(
+    export: &ExportedCoValue,
+    keys: &HashMap<KeyID, KeySecret>,
+) -> Result<CoValueReport, InspectError> {
+    let mut counts = StatusCounts::default();
+    let sessions = export
+        .sessions
+        .iter()
+        .map(|(session_id, session)| {
+            let report = inspect_session(&export.id, session_id, session, keys)?;
+            for transaction in &report.transactions {
+                counts.count(transaction.status);
+            }
+            Ok(report)
+        })
+        .collect::<Result<Vec<_>, InspectError>>()?;
+
+    Ok(CoValueReport {
+        id: export.id.clone(),
+        has_header: export.header.is_some(),
+        counts,
+        sessions,
+    })
+}
+
+fn parse_json(json: String) -> JsonValue {
+    serde_json::from_str(&json).unwrap_or(JsonValue::String(json))
+}
+
+fn decrypt(
+    log: &SessionLogInternal,
+    idx: u32,
+    secret: Option<&KeySecret>,
+    report: &mut TransactionReport,
+) {
+    let Some(secret) = secret else {
+        report.status = TransactionStatus::Undecryptable;
+        report.error = Some("Key not given".to_string());
+        return;
+    };
+    let decrypted = log
+        .decrypt_next_transaction_changes_json(idx, secret.clone())
+        .and_then(|changes| {
+            let meta = log.decrypt_next_transaction_meta_json(idx, secret.clone())?;
+            Ok((changes, meta))
+        });
+    match decrypted {
+        Ok((changes, meta)) => {
+            report.changes = Some(parse_json(changes));
+            report.meta = meta.map(parse_json);
+        }
+        Err(err) => {
+            report.status = TransactionStatus::Undecryptable;
+            report.error = Some(err.to_string());
+            report.error_code = Some(err.code().to_string());
+        }
+    }
+}
+
+fn inspect_session(
+    co_id: &CoID,
+    session_id: &SessionID,
+    session: &ExportedSession,
+    keys: &HashMap<KeyID, KeySecret>,
+) -> Result<SessionReport, InspectError> {
+    let mut log =
+        SessionLogInternal::new(co_id.clone(), session_id.clone(), session.signer_id.clone());
+    // Why each transaction isn't validly signed, as error message and code
+    let mut signature_errors: Vec<Option<(String, String)>> = Vec::new();
+
+    let mut start = 0;
+    for checkpoint in &session.signatures {
+        let end = (checkpoint.idx as usize + 1).min(session.transactions.len());
+        if end <= start {
+            continue;
+        }
+        let chunk = session.transactions[start..end].to_vec();
+        let skip_verify = session.signer_id.is_none();
+        let error = match log.try_add(chunk.clone(), &checkpoint.signature, skip_verify) {
+            Ok(()) => None,
+            Err(err) => {
+                // Keep the chain going, so later chunks can still be verified and decrypted
+                log.try_add(chunk, &checkpoint.signature, true)?;
+                Some((err.to_string(), err.code().to_string()))
+            }
+        };
+        signature_errors.extend((start..end).map(|_| error.clone()));
+        start = end;
+    }
+
+    let mut counts = StatusCounts::default();
+    let transactions = session
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(idx, raw)| {
+            let idx = idx as u32;
+            let mut report = TransactionReport {
+                idx,
+                status: TransactionStatus::Valid,
+                privacy: None,
+                made_at: None,
+                key_used: None,
+                changes: None,
+                meta: None,
+                error: None,
+                error_code: None,
+            };
+
+            match serde_json::from_str::<Transaction>(raw.get()) {
+                Ok(Transaction::Private(tx)) => {
+                    report.privacy = Some(tx.privacy);
+                    report.made_at = Some(JsonValue::Number(tx.made_at));
+                    // The log only holds signed transactions, and the nonce depends on the index
+                    if (idx as usize) < log.transactions_json().len() {
+                        decrypt(&log, idx, keys.get(&tx.key_used), &mut report);
+                    }
+                    report.key_used = Some(tx.key_used);
+                }
+                Ok(Transaction::Trusting(tx)) => {
+                    report.privacy = Some(tx.privacy);
+                    report.made_at = Some(JsonValue::Number(tx.made_at));
+                    report.changes = Some(parse_json(tx.changes));
+                    report.meta = tx.meta.map(parse_json);
+                }
+                Err(err) => {
+                    report.status = TransactionStatus::Invalid;
+                    report.error = Some(format!("Not a transaction: {err}"));
+                    counts.count(report.status);
+                    return report;
+                }
+            }
+
+            match signature_errors.get(idx as usize) {
+                Some(Some((error, code))) => {
+                    report.status = TransactionStatus::Invalid;
+                    report.error = Some(error.clone());
+                    report.error_code = Some(code.clone());
+                }
+                Some(None) => {
+                    if session.signer_id.is_none() && report.status == TransactionStatus::Valid {
+                        report.status = TransactionStatus::Unverified;
+                    }
+                }
+                None => {
+                    report.status = TransactionStatus::Invalid;
+                    report.error = Some("Not covered by a signature".to_string());
+                    report.error_code = None;
+                }
+            }
+
+            counts.count(report.status);
+            report
+        })
+        .collect();
+
+    Ok(SessionReport {
+        session_id: session_id.clone(),
+        signer_id: session.signer_id.clone(),
+        counts,
+        transactions,
+    })
+}
+
+impl fmt::Display for CoValueReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "CoValue {}", self.id.0)?;
+        if !self.has_header {
+            writeln!(f, "  (no header in export)")?;
+        }
+        for session in &self.sessions {
+            writeln!(f)?;
+            writeln!(f, "Session {}", session.session_id.0)?;
+            match &session.signer_id {
+                Some(signer_id) => writeln!(f, "  signer: {}", signer_id.0)?,
+                None => writeln!(f, "  signer: unknown")?,
+            }
+            writeln!(f, "  {}", session.counts)?;
+            for transaction in &session.transactions {
+                if let Some(error) = &transaction.error {
+                    writeln!(f, "  #{} {}: {error}", transaction.idx, transaction.status)?;
+                }
+            }
+        }
+        writeln!(f)?;
+        write!(f, "Total: {}", self.counts)
+    }
+}
+
+impl fmt::Display for StatusCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} valid, {} invalid, {} unverified, {} undecryptable",
+            self.valid, self.invalid, self.unverified, self.undecryptable
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cojson_core::core::Signature;
+
+    fn load(name: &str) -> ExportedCoValue {
+        let json = std::fs::read_to_string(format!("../cojson-core/data/{name}.json")).unwrap();
+        ExportedCoValue::from_json(&json).unwrap()
+    }
+
+    #[test]
+    fn test_valid_trusting_session() {
+        let export = load("multiTxSession");
+        let report = inspect(&export, &key_secrets(&export, [])).unwrap();
+        assert_eq!(report.counts.valid, 5);
+        assert!(!report.has_invalid());
+
+        let first = &report.sessions[0].transactions[0];
+        assert_eq!(first.privacy.as_deref(), Some("trusting"));
+        assert_eq!(first.changes.as_ref().unwrap()[0]["value"], "admin");
+    }
+
+    #[test]
+    fn test_decrypts_with_known_keys() {
+        let export = load("singleTxSession");
+        let report = inspect(&export, &key_secrets(&export, [])).unwrap();
+        assert_eq!(report.counts.valid, 1);
+        let transaction = &report.sessions[0].transactions[0];
+        assert_eq!(transaction.privacy.as_deref(), Some("private"));
+        assert!(transaction.changes.as_ref().unwrap().is_array());
+
+        // Without the key, it's still validly signed but can't be read
+        let mut export = export;
+        let known_keys = std::mem::take(&mut export.known_keys);
+        let report = inspect(&export, &key_secrets(&export, [])).unwrap();
+        assert_eq!(report.counts.undecryptable, 1);
+        assert_eq!(
+            report.sessions[0].transactions[0].error.as_deref(),
+            Some("Key not given")
+        );
+
+        // Keys can be given besides those in the export
+        let extra_keys = known_keys.into_iter().map(|key| (key.id, key.secret));
+        let report = inspect(&export, &key_secrets(&export, extra_keys)).unwrap();
+        assert_eq!(report.counts.valid, 1);
+    }
+
+    #[test]
+    fn test_reports_invalid_signatures() {
+        let mut export = load("multiTxSession");
+        let session = export.sessions.values_mut().next().unwrap();
+        // Sign the first two transactions with the last signature, so the chunk doesn't verify
+        let last = session.signatures.pop().unwrap();
+        session
+            .signatures
+            .push(cojson_storage_sqlite::SignatureAfter {
+                idx: 1,
+                signature: last.signature,
+            });
+
+        let report = inspect(&export, &key_secrets(&export, [])).unwrap();
+        let session = &report.sessions[0];
+        assert_eq!(session.counts.invalid, 5);
+        assert_eq!(
+            session.transactions[0].error_code.as_deref(),
+            Some("SIGNATURE_VERIFICATION_FAILED")
+        );
+        assert_eq!(
+            session.transactions[4].error.as_deref(),
+            Some("Not covered by a signature")
+        );
+        assert!(report.to_string().contains("5 invalid"));
+    }
+
+    #[test]
+    fn test_unknown_signer_is_unverified() {
+        let mut export = load("multiTxSession");
+        let session = export.sessions.values_mut().next().unwrap();
+        session.signer_id = None;
+        session.signatures[0].signature = Signature("signature_zWhatever".to_string());
+
+        let report = inspect(&export, &key_secrets(&export, [])).unwrap();
+        assert_eq!(report.counts.unverified, 5);
+    }
+}
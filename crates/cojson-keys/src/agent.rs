@@ -0,0 +1,156 @@
+use cojson_core::core::{SealerID, SealerSecret, SignerID, SignerSecret};
+use cojson_core::crypto::{
+    english_wordlist, get_sealer_id, get_signer_id, passphrase_to_secret_seed,
+    secret_seed_to_passphrase, CryptoError,
+};
+use cojson_core::hash::blake3_hash_once_with_context;
+use rand::{rngs::OsRng, RngCore};
+use serde::Serialize;
+
+use crate::KeysError;
+
+/// Length of the secret seed an agent is derived from, as `secretSeedLength`.
+pub const SECRET_SEED_LENGTH: usize = 32;
+
+/// An agent's secrets with the IDs derived from them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Agent {
+    /// `sealerSecret_z.../signerSecret_z...`
+    pub agent_secret: String,
+    /// `sealer_z.../signer_z...`
+    #[serde(rename = "agentID")]
+    pub agent_id: String,
+    pub sealer_secret: SealerSecret,
+    #[serde(rename = "sealerID")]
+    pub sealer_id: SealerID,
+    pub signer_secret: SignerSecret,
+    #[serde(rename = "signerID")]
+    pub signer_id: SignerID,
+}
+
+impl Agent {
+    /// Derive the IDs of an agent secret, like `getAgentID`.
+    pub fn from_secret(agent_secret: &str) -> Result<Self, KeysError> {
+        let (sealer_secret, signer_secret) = agent_secret
+            .split_once('/')
+            .filter(|(sealer, signer)| {
+                sealer.starts_with("sealerSecret_z") && signer.starts_with("signerSecret_z")
+            })
+            .ok_or(KeysError::InvalidAgentSecret)?;
+
+        let sealer_id = SealerID(get_sealer_id(sealer_secret)?);
+        let signer_id = SignerID(get_signer_id(signer_secret)?);
+        Ok(Self {
+            agent_secret: agent_secret.to_string(),
+            agent_id: format!("{}/{}", sealer_id.0, signer_id.0),
+            sealer_secret: SealerSecret(sealer_secret.to_string()),
+            sealer_id,
+            signer_secret: SignerSecret(signer_secret.to_string()),
+            signer_id,
+        })
+    }
+
+    /// Derive an agent from a secret seed, like `agentSecretFromSecretSeed`. This is how
+    /// accounts are recovered from their passphrase.
+    pub fn from_secret_seed(secret_seed: &[u8]) -> Result<Self, KeysError> {
+        if secret_seed.len() != SECRET_SEED_LENGTH {
+            return Err(CryptoError::InvalidSecretSeedLength(secret_seed.len()).into());
+        }
+        let sealer = blake3_hash_once_with_context(secret_seed, b"seal");
+        let signer = blake3_hash_once_with_context(secret_seed, b"sign");
+        Self::from_secret(&format!(
+            "sealerSecret_z{}/signerSecret_z{}",
+            bs58::encode(sealer).into_string(),
+            bs58::encode(signer).into_string()
+        ))
+    }
+}
+
+/// A fresh random secret seed, like `newRandomSecretSeed`.
+pub fn new_secret_seed() -> [u8; SECRET_SEED_LENGTH] {
+    let mut secret_seed = [0u8; SECRET_SEED_LENGTH];
+    OsRng.fill_bytes(&mut secret_seed);
+    secret_seed
+}
+
+/// An agent along with the seed it was derived from, for provisioning new accounts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SeededAgent {
+    #[serde(flatten)]
+    pub agent: Agent,
+    /// The secret seed as a BIP39 English passphrase, to recover the agent with.
+    pub passphrase: String,
+}
+
+impl SeededAgent {
+    pub fn random() -> Result<Self, KeysError> {
+        Self::from_secret_seed(&new_secret_seed())
+    }
+
+    pub fn from_secret_seed(secret_seed: &[u8]) -> Result<Self, KeysError> {
+        Ok(Self {
+            agent: Agent::from_secret_seed(secret_seed)?,
+            passphrase: secret_seed_to_passphrase(secret_seed, english_wordlist())?,
+        })
+    }
+
+    /// Recover an agent from the passphrase of its secret seed.
+    pub fn from_passphrase(passphrase: &str) -> Result<Self, KeysError> {
+        Self::from_secret_seed(&passphrase_to_secret_seed(passphrase, english_wordlist())?)
+    }
+}
+
+/// The signer secret of an agent secret, or a signer secret as is.
+pub fn signer_secret_of(secret: &str) -> &str {
+    secret.split_once('/').map_or(secret, |(_, signer)| signer)
+}
+
+/// The sealer secret of an agent secret, or a sealer secret as is.
+pub fn sealer_secret_of(secret: &str) -> &str {
+    secret.split_once('/').map_or(secret, |(sealer, _)| sealer)
+}
+
+/// The signer ID of an agent ID, or a signer ID as is.
+pub fn signer_id_of(id: &str) -> &str {
+    signer_secret_of(id)
+}
+
+/// The sealer ID of an agent ID, or a sealer ID as is.
+pub fn sealer_id_of(id: &str) -> &str {
+    sealer_secret_of(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_from_secret() {
+        let agent = SeededAgent::random().unwrap().agent;
+        let again = Agent::from_secret(&agent.agent_secret).unwrap();
+        assert_eq!(again, agent);
+        assert!(agent.agent_id.starts_with("sealer_z"));
+        assert_eq!(signer_id_of(&agent.agent_id), agent.signer_id.0);
+        assert_eq!(sealer_id_of(&agent.agent_id), agent.sealer_id.0);
+
+        assert!(matches!(
+            Agent::from_secret("signerSecret_zA/sealerSecret_zB"),
+            Err(KeysError::InvalidAgentSecret)
+        ));
+    }
+
+    #[test]
+    fn test_recover_from_passphrase() {
+        let seeded = SeededAgent::from_secret_seed(&[1; SECRET_SEED_LENGTH]).unwrap();
+        assert_eq!(seeded.passphrase.split(' ').count(), 24);
+
+        let recovered = SeededAgent::from_passphrase(&seeded.passphrase).unwrap();
+        assert_eq!(recovered, seeded);
+
+        // Other seeds give other agents
+        let other = Agent::from_secret_seed(&[2; SECRET_SEED_LENGTH]).unwrap();
+        assert_ne!(other.agent_secret, seeded.agent.agent_secret);
+        assert!(Agent::from_secret_seed(&[1; 16]).is_err());
+    }
+}
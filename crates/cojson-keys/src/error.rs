@@ -0,0 +1,30 @@
+use cojson_core::core::CoJsonCoreError;
+use cojson_core::crypto::CryptoError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum KeysError {
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Core(#[from] CoJsonCoreError),
+
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+
+    #[error("Invalid base64: {0}")]
+    InvalidBase64(#[from] base64::DecodeError),
+
+    #[error("Invalid agent secret (expected sealerSecret_z.../signerSecret_z...)")]
+    InvalidAgentSecret,
+
+    #[error("Expected a value starting with {0}")]
+    InvalidPrefix(&'static str),
+
+    #[error("Missing secret (set $COJSON_KEYS_SECRET or pass it on stdin)")]
+    MissingSecret,
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,10 @@
+//! Agent and key management on top of `cojson_core::crypto`, producing the same encodings as
+//! `CryptoProvider` on the TS side, so secrets and payloads can be handed to JS as-is.
+
+pub mod agent;
+pub mod error;
+pub mod payload;
+
+pub use agent::*;
+pub use error::*;
+pub use payload::*;
@@ -0,0 +1,225 @@
+use std::io::BufRead;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use cojson_core::core::{CoID, KeySecret, SessionID, Signature};
+use cojson_keys::{
+    decrypt_json, encrypt_json, seal_json, seal_nonce_material, sign_json, unseal_json,
+    verify_json, Agent, KeysError, NewKey, SeededAgent,
+};
+use serde_json::{json, Value as JsonValue};
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Generate a new agent secret, with the passphrase to recover it
+    NewAgent,
+    /// Recover an agent secret from its passphrase, given as the secret
+    Recover,
+    /// Derive the agent, sealer and signer IDs of an agent secret, given as the secret
+    AgentId,
+    /// Generate a new read key
+    NewKey,
+    /// Sign a JSON message with an agent secret or signer secret, given as the secret
+    Sign { message: String },
+    /// Verify the signature of a JSON message. Exits with a failure if it doesn't match
+    Verify {
+        /// An agent ID or signer ID
+        #[arg(long)]
+        signer: String,
+        #[arg(long)]
+        signature: String,
+        message: String,
+    },
+    /// Seal a JSON message for a recipient, as in key revelations, with the sender's agent
+    /// secret or sealer secret given as the secret
+    Seal {
+        /// The recipient's agent ID or sealer ID
+        #[arg(long)]
+        to: String,
+        #[command(flatten)]
+        nonce: SealNonce,
+        message: String,
+    },
+    /// Unseal a `sealed_U...` message, with the recipient's agent secret or sealer secret given
+    /// as the secret
+    Unseal {
+        /// The sender's agent ID or sealer ID
+        #[arg(long)]
+        from: String,
+        #[command(flatten)]
+        nonce: SealNonce,
+        sealed: String,
+    },
+    /// Encrypt a JSON value with a key secret, given as the secret
+    Encrypt {
+        /// The JSON nonce material, e.g. `{"in":"co_z...","tx":{"sessionID":"...","txIndex":0}}`
+        #[arg(long)]
+        nonce_material: String,
+        value: String,
+    },
+    /// Decrypt an `encrypted_U...` value with a key secret, given as the secret
+    Decrypt {
+        #[arg(long)]
+        nonce_material: String,
+        encrypted: String,
+    },
+}
+
+/// The transaction a sealed message is part of, which its nonce is derived from.
+#[derive(Debug, clap::Args)]
+struct SealNonce {
+    /// The CoValue the message is stored in
+    #[arg(long = "in")]
+    co_id: String,
+    #[arg(long)]
+    session: String,
+    #[arg(long)]
+    tx_index: u32,
+}
+
+impl SealNonce {
+    fn material(&self) -> JsonValue {
+        seal_nonce_material(
+            &CoID(self.co_id.clone()),
+            &SessionID(self.session.clone()),
+            self.tx_index,
+        )
+    }
+}
+
+/// The environment variable secrets are read from.
+const SECRET_ENV: &str = "COJSON_KEYS_SECRET";
+
+/// Manage COJSON agents and keys, and sign, seal or encrypt with them.
+///
+/// Secrets and passphrases are never taken as arguments, which other users can see in the
+/// process list. They're read from $COJSON_KEYS_SECRET if it's set, or else from the first
+/// line of stdin.
+#[derive(Debug, Parser)]
+#[command(name = "cojson-keys", version)]
+struct Args {
+    /// Print results as a JSON object, for scripts
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn parse(json: &str) -> Result<JsonValue, KeysError> {
+    Ok(serde_json::from_str(json)?)
+}
+
+/// The secret a command works with, from `SECRET_ENV` or the first line of stdin.
+fn read_secret() -> Result<String, KeysError> {
+    if let Some(secret) = std::env::var_os(SECRET_ENV) {
+        return secret.into_string().map_err(|_| KeysError::MissingSecret);
+    }
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    let secret = line.trim_end_matches(['\r', '\n']);
+    match secret.is_empty() {
+        true => Err(KeysError::MissingSecret),
+        false => Ok(secret.to_string()),
+    }
+}
+
+/// Returns the result and whether the command succeeded.
+fn run(command: Command) -> Result<(JsonValue, bool), KeysError> {
+    let result = match command {
+        Command::NewAgent => serde_json::to_value(SeededAgent::random()?)?,
+        Command::Recover => serde_json::to_value(SeededAgent::from_passphrase(&read_secret()?)?)?,
+        Command::AgentId => {
+            let agent = Agent::from_secret(&read_secret()?)?;
+            json!({
+                "agentID": agent.agent_id,
+                "sealerID": agent.sealer_id,
+                "signerID": agent.signer_id,
+            })
+        }
+        Command::NewKey => serde_json::to_value(NewKey::random())?,
+        Command::Sign { message } => {
+            json!({ "signature": sign_json(&parse(&message)?, &read_secret()?)? })
+        }
+        Command::Verify {
+            signer,
+            signature,
+            message,
+        } => {
+            let valid = verify_json(&Signature(signature), &parse(&message)?, &signer)?;
+            return Ok((json!({ "valid": valid }), valid));
+        }
+        Command::Seal { to, nonce, message } => {
+            let sealed = seal_json(&parse(&message)?, &read_secret()?, &to, &nonce.material())?;
+            json!({ "sealed": sealed })
+        }
+        Command::Unseal {
+            from,
+            nonce,
+            sealed,
+        } => {
+            let message = unseal_json(&sealed, &read_secret()?, &from, &nonce.material())?;
+            json!({ "message": message })
+        }
+        Command::Encrypt {
+            nonce_material,
+            value,
+        } => {
+            let encrypted = encrypt_json(
+                &parse(&value)?,
+                &KeySecret(read_secret()?),
+                &parse(&nonce_material)?,
+            )?;
+            json!({ "encrypted": encrypted })
+        }
+        Command::Decrypt {
+            nonce_material,
+            encrypted,
+        } => {
+            let key_secret = KeySecret(read_secret()?);
+            let value = decrypt_json(&encrypted, &key_secret, &parse(&nonce_material)?)?;
+            json!({ "value": value })
+        }
+    };
+    Ok((result, true))
+}
+
+/// One `name: value` line per field, with strings unquoted.
+fn print_text(result: &JsonValue) {
+    let JsonValue::Object(fields) = result else {
+        println!("{result}");
+        return;
+    };
+    for (name, value) in fields {
+        match value {
+            JsonValue::String(value) => println!("{name}: {value}"),
+            value => println!("{name}: {value}"),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args.command) {
+        Ok((result, succeeded)) => {
+            if args.json {
+                println!("{result}");
+            } else {
+                print_text(&result);
+            }
+            if succeeded {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(err) => {
+            if args.json {
+                println!("{}", json!({ "error": err.to_string() }));
+            } else {
+                eprintln!("{err}");
+            }
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Signing, sealing and encrypting JSON values the way `CryptoProvider` does: values are
+//! stable-stringified first, and ciphertexts are base64url-encoded behind a `sealed_U` or
+//! `encrypted_U` prefix.
+
+use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use cojson_core::core::{
+    new_random_key_secret, stable_stringify, CoID, KeyID, KeySecret, SessionID, Signature,
+    TransactionID,
+};
+use cojson_core::crypto::{decrypt, encrypt, seal, sign, unseal, verify};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+
+use crate::{sealer_id_of, sealer_secret_of, signer_id_of, signer_secret_of, KeysError};
+
+fn strip<'a>(value: &'a str, prefix: &'static str) -> Result<&'a str, KeysError> {
+    value
+        .strip_prefix(prefix)
+        .ok_or(KeysError::InvalidPrefix(prefix))
+}
+
+/// A new read key, like `newRandomKeySecret`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewKey {
+    #[serde(rename = "keyID")]
+    pub key_id: KeyID,
+    #[serde(rename = "keySecret")]
+    pub key_secret: KeySecret,
+}
+
+impl NewKey {
+    pub fn random() -> Self {
+        let (key_id, key_secret) = new_random_key_secret();
+        Self { key_id, key_secret }
+    }
+}
+
+/// The nonce material of sealed values: `{in: coID, tx: transactionID}`.
+pub fn seal_nonce_material(co_id: &CoID, session_id: &SessionID, tx_index: u32) -> JsonValue {
+    let tx = TransactionID {
+        session_id: session_id.clone(),
+        tx_index,
+        branch: None,
+    };
+    json!({ "in": co_id, "tx": tx })
+}
+
+/// Sign a JSON value.
+/// - `secret`: A signer secret, or an agent secret to sign with its signer
+pub fn sign_json(message: &JsonValue, secret: &str) -> Result<Signature, KeysError> {
+    let signature = sign(
+        stable_stringify(message).as_bytes(),
+        signer_secret_of(secret),
+    )?;
+    Ok(Signature(signature))
+}
+
+/// Check the signature of a JSON value.
+/// - `signer`: A signer ID, or an agent ID to check against its signer
+pub fn verify_json(
+    signature: &Signature,
+    message: &JsonValue,
+    signer: &str,
+) -> Result<bool, KeysError> {
+    Ok(verify(
+        &signature.0,
+        stable_stringify(message).as_bytes(),
+        signer_id_of(signer),
+    )?)
+}
+
+/// Seal a JSON value for a recipient, like `seal`.
+/// - `from`: The sender's sealer secret, or agent secret
+/// - `to`: The recipient's sealer ID, or agent ID
+///
+/// Returns a `sealed_U...` string.
+pub fn seal_json(
+    message: &JsonValue,
+    from: &str,
+    to: &str,
+    nonce_material: &JsonValue,
+) -> Result<String, KeysError> {
+    let sealed = seal(
+        stable_stringify(message).as_bytes(),
+        sealer_secret_of(from),
+        sealer_id_of(to),
+        stable_stringify(nonce_material).as_bytes(),
+    )?;
+    Ok(format!("sealed_U{}", URL_SAFE.encode(sealed)))
+}
+
+/// Unseal a value sealed with `seal_json`.
+/// - `secret`: The recipient's sealer secret, or agent secret
+/// - `from`: The sender's sealer ID, or agent ID
+pub fn unseal_json(
+    sealed: &str,
+    secret: &str,
+    from: &str,
+    nonce_material: &JsonValue,
+) -> Result<JsonValue, KeysError> {
+    let sealed = URL_SAFE.decode(strip(sealed, "sealed_U")?)?;
+    let message = unseal(
+        &sealed,
+        sealer_secret_of(secret),
+        sealer_id_of(from),
+        stable_stringify(nonce_material).as_bytes(),
+    )?;
+    Ok(serde_json::from_slice(&message)?)
+}
+
+/// Encrypt a JSON value with a key secret, like `encrypt`.
+///
+/// Returns an `encrypted_U...` string.
+pub fn encrypt_json(
+    value: &JsonValue,
+    key_secret: &KeySecret,
+    nonce_material: &JsonValue,
+) -> Result<String, KeysError> {
+    let ciphertext = encrypt(
+        stable_stringify(value).as_bytes(),
+        &key_secret.0,
+        stable_stringify(nonce_material).as_bytes(),
+    )?;
+    Ok(format!("encrypted_U{}", URL_SAFE.encode(ciphertext)))
+}
+
+/// Decrypt a value encrypted with `encrypt_json`.
+pub fn decrypt_json(
+    encrypted: &str,
+    key_secret: &KeySecret,
+    nonce_material: &JsonValue,
+) -> Result<JsonValue, KeysError> {
+    let ciphertext = URL_SAFE.decode(strip(encrypted, "encrypted_U")?)?;
+    let plaintext = decrypt(
+        &ciphertext,
+        &key_secret.0,
+        stable_stringify(nonce_material).as_bytes(),
+    )?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededAgent;
+    use cojson_core::core::{decrypt_key_secret, unseal_key_secret};
+
+    #[test]
+    fn test_sign_and_verify() {
+        let agent = SeededAgent::random().unwrap().agent;
+        let message = json!({"b": 1, "a": [true, null]});
+        let signature = sign_json(&message, &agent.agent_secret).unwrap();
+
+        assert!(verify_json(&signature, &message, &agent.agent_id).unwrap());
+        assert!(verify_json(&signature, &message, &agent.signer_id.0).unwrap());
+        assert!(!verify_json(&signature, &json!({"a": 1}), &agent.agent_id).unwrap());
+    }
+
+    #[test]
+    fn test_seal_and_unseal() {
+        let alice = SeededAgent::random().unwrap().agent;
+        let bob = SeededAgent::random().unwrap().agent;
+        let key = NewKey::random();
+        let co_id = CoID("co_zGroup".to_string());
+        let session_id = SessionID(format!("{}_session_z1", alice.agent_id));
+        let nonce_material = seal_nonce_material(&co_id, &session_id, 3);
+
+        let sealed = seal_json(
+            &json!(key.key_secret),
+            &alice.agent_secret,
+            &bob.agent_id,
+            &nonce_material,
+        )
+        .unwrap();
+        let unsealed =
+            unseal_json(&sealed, &bob.agent_secret, &alice.agent_id, &nonce_material).unwrap();
+        assert_eq!(unsealed, json!(key.key_secret));
+
+        // Same format as key revelations in groups
+        let tx = TransactionID {
+            session_id,
+            tx_index: 3,
+            branch: None,
+        };
+        let revealed =
+            unseal_key_secret(&sealed, &bob.sealer_secret, &alice.sealer_id, &co_id, &tx).unwrap();
+        assert_eq!(revealed, key.key_secret);
+
+        let wrong_tx = seal_nonce_material(&co_id, &tx.session_id, 4);
+        assert!(unseal_json(&sealed, &bob.agent_secret, &alice.agent_id, &wrong_tx).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let key = NewKey::random();
+        let encrypting = NewKey::random();
+        let nonce_material = json!({"encryptedID": key.key_id, "encryptingID": encrypting.key_id});
+
+        let encrypted = encrypt_json(
+            &json!(key.key_secret),
+            &encrypting.key_secret,
+            &nonce_material,
+        )
+        .unwrap();
+        assert!(encrypted.starts_with("encrypted_U"));
+        assert_eq!(
+            decrypt_json(&encrypted, &encrypting.key_secret, &nonce_material).unwrap(),
+            json!(key.key_secret)
+        );
+
+        // Same format as keys encrypted for other keys in groups
+        let decrypted = decrypt_key_secret(
+            &encrypted,
+            &key.key_id,
+            (&encrypting.key_id, &encrypting.key_secret),
+        )
+        .unwrap();
+        assert_eq!(decrypted, key.key_secret);
+
+        assert!(matches!(
+            decrypt_json("sealed_Uabc", &encrypting.key_secret, &nonce_material),
+            Err(KeysError::InvalidPrefix("encrypted_U"))
+        ));
+    }
+}
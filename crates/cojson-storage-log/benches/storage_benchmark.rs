@@ -0,0 +1,147 @@
+//! Compares the log-structured storage against SQLite on an exported session log.
+//!
+//! Reads `../cojson-core/data/multiTxSession.json` by default; point `STORAGE_BENCH_DATA` at any
+//! file in the same format to benchmark on other data. Every session is stored under
+//! `STORAGE_BENCH_COPIES` different CoIDs (100 by default) to get a realistically sized store.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+use cojson_core::core::{
+    CoID, SessionID, SessionLogInternal, SessionLogStorage, SessionNewContent, Signature, SignerID,
+};
+use cojson_storage_log::LogStorage;
+use cojson_storage_sqlite::SqliteStorage;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde::Deserialize;
+use serde_json::{json, value::RawValue};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedSession {
+    transactions: Vec<Box<RawValue>>,
+    last_signature: Signature,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    #[serde(rename = "signerID")]
+    signer_id: SignerID,
+    example_base: HashMap<String, ExportedSession>,
+}
+
+fn load_export() -> Export {
+    let path = env::var("STORAGE_BENCH_DATA")
+        .unwrap_or_else(|_| "../cojson-core/data/multiTxSession.json".to_string());
+    let data = fs::read_to_string(&path).expect("Failed to read benchmark data");
+    serde_json::from_str(&data).expect("Invalid benchmark data")
+}
+
+fn co_ids() -> Vec<CoID> {
+    let copies: usize = env::var("STORAGE_BENCH_COPIES")
+        .ok()
+        .and_then(|copies| copies.parse().ok())
+        .unwrap_or(100);
+    (0..copies).map(|i| CoID(format!("co_zBench{i}"))).collect()
+}
+
+fn fill<S: SessionLogStorage>(storage: &mut S, export: &Export, co_ids: &[CoID])
+where
+    S::Error: std::fmt::Debug,
+{
+    for co_id in co_ids {
+        // Headers are unique in the SQLite schema, just like CoIDs
+        let header = json!({
+            "type": "comap",
+            "ruleset": {"type": "unsafeAllowAll"},
+            "meta": null,
+            "uniqueness": co_id.0,
+        });
+        for (session_id, session) in &export.example_base {
+            let session_id = SessionID(session_id.clone());
+            let mut session_log = SessionLogInternal::new(
+                co_id.clone(),
+                session_id.clone(),
+                Some(export.signer_id.clone()),
+            );
+            storage
+                .append_transactions(
+                    co_id,
+                    Some(&header),
+                    &session_id,
+                    &mut session_log,
+                    SessionNewContent {
+                        after: 0,
+                        new_transactions: session.transactions.clone(),
+                        last_signature: session.last_signature.clone(),
+                    },
+                )
+                .unwrap();
+        }
+    }
+}
+
+fn load_all<S: SessionLogStorage>(storage: &S, export: &Export, co_ids: &[CoID])
+where
+    S::Error: std::fmt::Debug,
+{
+    for co_id in co_ids {
+        let loaded = storage
+            .load_session_logs(co_id, |_| Some(export.signer_id.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.sessions.len(), export.example_base.len());
+    }
+}
+
+fn storage_benchmark(c: &mut Criterion) {
+    let export = load_export();
+    let co_ids = co_ids();
+
+    let mut group = c.benchmark_group("Storage");
+    group.measurement_time(Duration::from_secs(10));
+    group.sample_size(10);
+
+    group.bench_function("sqlite/append", |b| {
+        b.iter_batched(
+            || SqliteStorage::open_in_memory().unwrap(),
+            |mut storage| fill(&mut storage, &export, &co_ids),
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("log/append", |b| {
+        b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |dir| {
+                let mut storage = LogStorage::open(dir.path()).unwrap();
+                fill(&mut storage, &export, &co_ids);
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    let sqlite_dir = tempfile::tempdir().unwrap();
+    let mut sqlite = SqliteStorage::open(sqlite_dir.path().join("bench.sqlite")).unwrap();
+    fill(&mut sqlite, &export, &co_ids);
+    group.bench_function("sqlite/load", |b| {
+        b.iter(|| load_all(&sqlite, &export, &co_ids))
+    });
+
+    let log_dir = tempfile::tempdir().unwrap();
+    let mut log = LogStorage::open(log_dir.path()).unwrap();
+    fill(&mut log, &export, &co_ids);
+    let sqlite_size = fs::metadata(sqlite_dir.path().join("bench.sqlite"))
+        .unwrap()
+        .len();
+    println!(
+        "Size on disk: sqlite {sqlite_size} bytes, log {} bytes",
+        log.size()
+    );
+    group.bench_function("log/load", |b| b.iter(|| load_all(&log, &export, &co_ids)));
+}
+
+criterion_group!(benches, storage_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use cojson_core::core::CoJsonCoreError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LogStorageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Core(#[from] CoJsonCoreError),
+
+    #[error("Invalid JSON in stored {0}")]
+    InvalidJson(&'static str, #[source] serde_json::Error),
+
+    #[error("Failed to decompress record: {0:?}")]
+    Decompression(lzy::DecompressionError),
+
+    #[error("Corrupted record in segment {segment} at offset {offset}")]
+    Corrupted { segment: u64, offset: u64 },
+
+    #[error("CoValue {0} not found and no header provided")]
+    CoValueNotFound(String),
+
+    #[error("Missing transactions in session {session_id} (stored {stored}, new content starts after {after})")]
+    MissingTransactions {
+        session_id: String,
+        stored: u32,
+        after: u32,
+    },
+
+    #[error("Session log for {session_id} is out of sync with storage (log has {in_log} transactions, stored {stored})")]
+    SessionLogOutOfSync {
+        session_id: String,
+        in_log: u32,
+        stored: u32,
+    },
+}
@@ -0,0 +1,13 @@
+//! Append-only, log-structured storage for session logs.
+//!
+//! Every append becomes one `lzy`-compressed record at the end of the active segment file.
+//! The index from (CoID, SessionID) to record offsets lives in memory and is rebuilt by scanning
+//! the segments on open, which is also when torn writes from a crash get truncated away.
+
+pub mod error;
+pub mod record;
+pub mod segment;
+pub mod storage;
+
+pub use error::*;
+pub use storage::*;
@@ -0,0 +1,234 @@
+//! The on-disk record format.
+//!
+//! ```text
+//! frame := body_len: u32 LE | checksum: [u8; 4] | body
+//! body  := kind: u8 | co_id: str | session_id: str | after: u32 LE | count: u32 LE | payload
+//! str   := len: u32 LE | UTF-8 bytes
+//! ```
+//!
+//! The checksum is the first 4 bytes of the BLAKE3 hash of the body, and the payload is
+//! `lzy`-compressed JSON. Everything needed to index a record sits before the payload,
+//! so opening a store never has to decompress anything.
+
+use cojson_core::core::Signature;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::LogStorageError;
+
+/// Length of the body length prefix plus the checksum.
+pub const FRAME_HEADER_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RecordKind {
+    /// A CoValue header, with an empty session ID.
+    Header = 1,
+    /// A batch of transactions of one session, signed by its last signature.
+    Transactions = 2,
+}
+
+impl RecordKind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(RecordKind::Header),
+            2 => Some(RecordKind::Transactions),
+            _ => None,
+        }
+    }
+}
+
+/// The uncompressed part of a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordMeta {
+    pub kind: RecordKind,
+    pub co_id: String,
+    pub session_id: String,
+    /// The session index of the first transaction in the record.
+    pub after: u32,
+    /// The number of transactions in the record.
+    pub count: u32,
+}
+
+/// The payload of a `RecordKind::Transactions` record.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionsPayload {
+    #[serde(rename = "lastSignature")]
+    pub last_signature: Signature,
+    #[serde(rename = "newTransactions")]
+    pub new_transactions: Vec<Box<RawValue>>,
+}
+
+/// Borrowed version of `TransactionsPayload`, to encode without cloning the transactions.
+#[derive(Serialize)]
+pub struct TransactionsPayloadRef<'a> {
+    #[serde(rename = "lastSignature")]
+    pub last_signature: &'a Signature,
+    #[serde(rename = "newTransactions")]
+    pub new_transactions: &'a [Box<RawValue>],
+}
+
+/// Outcome of reading a frame from the start of a buffer.
+#[derive(Debug)]
+pub enum FrameRead<'a> {
+    Complete {
+        meta: RecordMeta,
+        /// The still compressed payload.
+        payload: &'a [u8],
+        /// The length of the whole frame.
+        len: usize,
+    },
+    /// The frame is incomplete or doesn't match its checksum - what a torn write leaves behind.
+    Torn,
+}
+
+fn checksum(body: &[u8]) -> [u8; 4] {
+    let hash = blake3::hash(body);
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&hash.as_bytes()[..4]);
+    checksum
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Encode a record into a frame, compressing the payload.
+pub fn encode_frame(meta: &RecordMeta, payload: &[u8]) -> Vec<u8> {
+    let compressed = lzy::compress(payload);
+
+    let mut body =
+        Vec::with_capacity(17 + meta.co_id.len() + meta.session_id.len() + compressed.len());
+    body.push(meta.kind as u8);
+    write_str(&mut body, &meta.co_id);
+    write_str(&mut body, &meta.session_id);
+    body.extend_from_slice(&meta.after.to_le_bytes());
+    body.extend_from_slice(&meta.count.to_le_bytes());
+    body.extend_from_slice(&compressed);
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&checksum(&body));
+    frame.extend_from_slice(&body);
+    frame
+}
+
+/// Small cursor over a frame body, where running out of bytes means the frame is torn.
+struct BodyReader<'a> {
+    body: &'a [u8],
+}
+
+impl<'a> BodyReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.body.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.body.split_at(n);
+        self.body = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+/// Read the frame at the start of `buf`.
+pub fn read_frame(buf: &[u8]) -> FrameRead<'_> {
+    if buf.len() < FRAME_HEADER_LEN {
+        return FrameRead::Torn;
+    }
+    let body_len = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+    let Some(body) = buf[FRAME_HEADER_LEN..].get(..body_len) else {
+        return FrameRead::Torn;
+    };
+    if checksum(body) != buf[4..FRAME_HEADER_LEN] {
+        return FrameRead::Torn;
+    }
+
+    let mut reader = BodyReader { body };
+    let meta = (|| {
+        Some(RecordMeta {
+            kind: RecordKind::from_u8(reader.take(1)?[0])?,
+            co_id: reader.str()?,
+            session_id: reader.str()?,
+            after: reader.u32()?,
+            count: reader.u32()?,
+        })
+    })();
+
+    match meta {
+        Some(meta) => FrameRead::Complete {
+            meta,
+            payload: reader.body,
+            len: FRAME_HEADER_LEN + body_len,
+        },
+        None => FrameRead::Torn,
+    }
+}
+
+/// Decompress a payload returned by `read_frame`.
+pub fn decompress_payload(payload: &[u8]) -> Result<Vec<u8>, LogStorageError> {
+    lzy::decompress(payload).map_err(LogStorageError::Decompression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> RecordMeta {
+        RecordMeta {
+            kind: RecordKind::Transactions,
+            co_id: "co_zTest".to_string(),
+            session_id: "sealer_z1/signer_z1_session_z1".to_string(),
+            after: 3,
+            count: 2,
+        }
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let payload = br#"{"lastSignature":"signature_z1","newTransactions":[{},{}]}"#;
+        let frame = encode_frame(&meta(), payload);
+
+        // Followed by the start of another frame, which must be left alone
+        let mut buf = frame.clone();
+        buf.extend_from_slice(&frame[..5]);
+
+        match read_frame(&buf) {
+            FrameRead::Complete {
+                meta: read_meta,
+                payload: compressed,
+                len,
+            } => {
+                assert_eq!(read_meta, meta());
+                assert_eq!(len, frame.len());
+                assert_eq!(decompress_payload(compressed).unwrap(), payload);
+            }
+            FrameRead::Torn => panic!("expected a complete frame"),
+        }
+        assert!(matches!(read_frame(&buf[frame.len()..]), FrameRead::Torn));
+    }
+
+    #[test]
+    fn test_torn_frames() {
+        let frame = encode_frame(&meta(), b"payload");
+
+        for len in [0, 3, FRAME_HEADER_LEN, frame.len() - 1] {
+            assert!(matches!(read_frame(&frame[..len]), FrameRead::Torn));
+        }
+
+        let mut flipped = frame.clone();
+        *flipped.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(read_frame(&flipped), FrameRead::Torn));
+
+        // Zeroed space, as left by a preallocated but unwritten tail
+        assert!(matches!(read_frame(&[0u8; 64]), FrameRead::Torn));
+    }
+}
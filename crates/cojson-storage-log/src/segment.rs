@@ -0,0 +1,139 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_SUFFIX: &str = ".log";
+/// Suffix of segments being written by a compaction, until they're complete.
+pub const COMPACTING_SUFFIX: &str = ".compacting";
+/// Records the first segment written by the last completed compaction.
+const MANIFEST: &str = "manifest";
+const MANIFEST_TMP: &str = "manifest.tmp";
+
+/// An open segment file and its current length.
+#[derive(Debug)]
+pub struct Segment {
+    pub file: File,
+    pub len: u64,
+}
+
+impl Segment {
+    /// Open a segment for reading and appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { file, len })
+    }
+
+    /// Read exactly `buf.len()` bytes at `offset`, without moving any shared cursor.
+    pub fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::FileExt::read_exact_at(&self.file, buf, offset)
+        }
+        #[cfg(windows)]
+        {
+            let mut read = 0;
+            while read < buf.len() {
+                let n = std::os::windows::fs::FileExt::seek_read(
+                    &self.file,
+                    &mut buf[read..],
+                    offset + read as u64,
+                )?;
+                if n == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                read += n;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The path of the segment with the given ID.
+pub fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{SEGMENT_PREFIX}{id:08}{SEGMENT_SUFFIX}"))
+}
+
+/// The path a compaction writes the segment with the given ID to before renaming it into place.
+pub fn compacting_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!(
+        "{SEGMENT_PREFIX}{id:08}{SEGMENT_SUFFIX}{COMPACTING_SUFFIX}"
+    ))
+}
+
+/// List the IDs of all segments in a directory, in order.
+pub fn list_segments(dir: &Path) -> io::Result<Vec<u64>> {
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let id = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(SEGMENT_PREFIX))
+            .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+            .and_then(|id| id.parse().ok());
+        if let Some(id) = id {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Remove segments left behind by a compaction that didn't finish.
+pub fn remove_compacting(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.ends_with(COMPACTING_SUFFIX))
+        {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// The ID of the first segment written by the last completed compaction, or 0 if there was none.
+/// Segments before it were superseded by that compaction.
+pub fn read_manifest(dir: &Path) -> io::Result<u64> {
+    match fs::read_to_string(dir.join(MANIFEST)) {
+        Ok(contents) => contents.trim().parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid manifest: {contents:?}"),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Durably record that segments before `first_live` are superseded.
+pub fn write_manifest(dir: &Path, first_live: u64) -> io::Result<()> {
+    let tmp = dir.join(MANIFEST_TMP);
+    let mut file = File::create(&tmp)?;
+    io::Write::write_all(&mut file, format!("{first_live}\n").as_bytes())?;
+    file.sync_all()?;
+    fs::rename(tmp, dir.join(MANIFEST))?;
+    sync_dir(dir)
+}
+
+/// Make file creations, renames and removals in a directory durable.
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        File::open(dir)?.sync_all()
+    }
+    #[cfg(not(unix))]
+    {
+        // Directories can't be opened as files here, and renames are durable once they return
+        let _ = dir;
+        Ok(())
+    }
+}
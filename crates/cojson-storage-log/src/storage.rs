@@ -0,0 +1,1012 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use cojson_core::core::{
+    transaction_size, CoID, CryptoProvider, LoadedCoValue, SessionID, SessionLogInternal,
+    SessionLogStorage, SessionNewContent, Signature, SignerID, MAX_RECOMMENDED_TX_SIZE,
+};
+use serde_json::{value::RawValue, Value as JsonValue};
+
+use crate::record::{
+    decompress_payload, encode_frame, read_frame, FrameRead, RecordKind, RecordMeta,
+    TransactionsPayload, TransactionsPayloadRef,
+};
+use crate::segment::{
+    compacting_path, list_segments, read_manifest, remove_compacting, segment_path, sync_dir,
+    write_manifest, Segment,
+};
+use crate::LogStorageError;
+
+#[derive(Debug, Clone)]
+pub struct LogStorageOptions {
+    /// Start a new segment once appending would grow the active one past this many bytes.
+    pub max_segment_size: u64,
+    /// When compacting, merge consecutive records of a session until their transactions reach
+    /// this size, as counted for signature checkpoints. Defaults to `MAX_RECOMMENDED_TX_SIZE`, so
+    /// merged records stay streamable.
+    pub compaction_record_size: usize,
+    /// Flush every append to disk before returning.
+    pub sync_writes: bool,
+}
+
+impl Default for LogStorageOptions {
+    fn default() -> Self {
+        Self {
+            max_segment_size: 64 * 1024 * 1024,
+            compaction_record_size: MAX_RECOMMENDED_TX_SIZE,
+            sync_writes: true,
+        }
+    }
+}
+
+/// Sizes before and after a compaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub records_before: usize,
+    pub records_after: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Location {
+    segment: u64,
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    location: Location,
+    after: u32,
+    count: u32,
+}
+
+#[derive(Debug, Default)]
+struct IndexEntry {
+    header: Option<Location>,
+    sessions: HashMap<SessionID, Vec<Block>>,
+}
+
+impl IndexEntry {
+    fn session_len(&self, session_id: &SessionID) -> u32 {
+        self.sessions
+            .get(session_id)
+            .and_then(|blocks| blocks.last())
+            .map_or(0, |block| block.after + block.count)
+    }
+
+    fn records(&self) -> usize {
+        self.header.iter().count() + self.sessions.values().map(Vec::len).sum::<usize>()
+    }
+
+    /// Add a record found while scanning a segment.
+    ///
+    /// A compaction that crashed while renaming its segments into place leaves records that
+    /// duplicate ones already indexed, those are skipped.
+    fn index_record(
+        &mut self,
+        meta: RecordMeta,
+        location: Location,
+    ) -> Result<(), LogStorageError> {
+        match meta.kind {
+            RecordKind::Header => {
+                self.header.get_or_insert(location);
+            }
+            RecordKind::Transactions => {
+                let session_id = SessionID(meta.session_id);
+                let session_len = self.session_len(&session_id);
+                if meta.after + meta.count <= session_len {
+                    return Ok(());
+                }
+                if meta.after != session_len {
+                    return Err(LogStorageError::Corrupted {
+                        segment: location.segment,
+                        offset: location.offset,
+                    });
+                }
+                self.sessions.entry(session_id).or_default().push(Block {
+                    location,
+                    after: meta.after,
+                    count: meta.count,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Session log storage in append-only segment files.
+pub struct LogStorage {
+    dir: PathBuf,
+    options: LogStorageOptions,
+    segments: BTreeMap<u64, Segment>,
+    index: HashMap<CoID, IndexEntry>,
+}
+
+impl LogStorage {
+    /// Open (or create) a store in a directory with the default options.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, LogStorageError> {
+        Self::open_with_options(dir, LogStorageOptions::default())
+    }
+
+    /// Open (or create) a store in a directory, rebuilding the index from its segments.
+    ///
+    /// A torn record at the end of the last segment (from a crash mid-append) is truncated away,
+    /// while an invalid record anywhere else is reported as corruption.
+    pub fn open_with_options<P: AsRef<Path>>(
+        dir: P,
+        options: LogStorageOptions,
+    ) -> Result<Self, LogStorageError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        remove_compacting(&dir)?;
+
+        let mut storage = Self {
+            dir,
+            options,
+            segments: BTreeMap::new(),
+            index: HashMap::new(),
+        };
+
+        // Finish removing the segments a completed compaction superseded
+        let first_live = read_manifest(&storage.dir)?;
+        let (superseded, ids): (Vec<u64>, Vec<u64>) = list_segments(&storage.dir)?
+            .into_iter()
+            .partition(|id| *id < first_live);
+        if !superseded.is_empty() {
+            for id in superseded {
+                fs::remove_file(segment_path(&storage.dir, id))?;
+            }
+            sync_dir(&storage.dir)?;
+        }
+
+        for (i, id) in ids.iter().enumerate() {
+            storage.scan_segment(*id, i == ids.len() - 1)?;
+        }
+        if storage.segments.is_empty() {
+            storage.add_segment(first_live)?;
+        }
+
+        Ok(storage)
+    }
+
+    fn scan_segment(&mut self, id: u64, is_last: bool) -> Result<(), LogStorageError> {
+        let mut segment = Segment::open(&segment_path(&self.dir, id))?;
+        let buf = fs::read(segment_path(&self.dir, id))?;
+
+        let mut offset = 0;
+        while offset < buf.len() {
+            match read_frame(&buf[offset..]) {
+                FrameRead::Complete { meta, len, .. } => {
+                    let location = Location {
+                        segment: id,
+                        offset: offset as u64,
+                        len: len as u64,
+                    };
+                    self.index
+                        .entry(CoID(meta.co_id.clone()))
+                        .or_default()
+                        .index_record(meta, location)?;
+                    offset += len;
+                }
+                FrameRead::Torn if is_last => {
+                    segment.file.set_len(offset as u64)?;
+                    segment.file.sync_all()?;
+                    segment.len = offset as u64;
+                    break;
+                }
+                FrameRead::Torn => {
+                    return Err(LogStorageError::Corrupted {
+                        segment: id,
+                        offset: offset as u64,
+                    });
+                }
+            }
+        }
+
+        self.segments.insert(id, segment);
+        Ok(())
+    }
+
+    fn add_segment(&mut self, id: u64) -> Result<(), LogStorageError> {
+        let segment = Segment::open(&segment_path(&self.dir, id))?;
+        sync_dir(&self.dir)?;
+        self.segments.insert(id, segment);
+        Ok(())
+    }
+
+    /// The IDs of all stored CoValues.
+    pub fn co_values(&self) -> impl Iterator<Item = &CoID> {
+        self.index
+            .iter()
+            .filter(|(_, entry)| entry.header.is_some())
+            .map(|(id, _)| id)
+    }
+
+    /// The number of stored transactions in a session.
+    pub fn session_len(&self, id: &CoID, session_id: &SessionID) -> u32 {
+        self.index
+            .get(id)
+            .map_or(0, |entry| entry.session_len(session_id))
+    }
+
+    /// The total size of all segments in bytes.
+    pub fn size(&self) -> u64 {
+        self.segments.values().map(|segment| segment.len).sum()
+    }
+
+    fn read_frame_at(&self, location: Location) -> Result<Vec<u8>, LogStorageError> {
+        let segment = self
+            .segments
+            .get(&location.segment)
+            .ok_or(LogStorageError::Corrupted {
+                segment: location.segment,
+                offset: location.offset,
+            })?;
+        let mut frame = vec![0u8; location.len as usize];
+        segment.read_exact_at(&mut frame, location.offset)?;
+        Ok(frame)
+    }
+
+    fn read_payload(&self, location: Location) -> Result<Vec<u8>, LogStorageError> {
+        let frame = self.read_frame_at(location)?;
+        match read_frame(&frame) {
+            FrameRead::Complete { payload, .. } => decompress_payload(payload),
+            FrameRead::Torn => Err(LogStorageError::Corrupted {
+                segment: location.segment,
+                offset: location.offset,
+            }),
+        }
+    }
+
+    fn read_header(&self, location: Location) -> Result<JsonValue, LogStorageError> {
+        serde_json::from_slice(&self.read_payload(location)?)
+            .map_err(|e| LogStorageError::InvalidJson("header", e))
+    }
+
+    fn read_transactions(&self, block: &Block) -> Result<TransactionsPayload, LogStorageError> {
+        serde_json::from_slice(&self.read_payload(block.location)?)
+            .map_err(|e| LogStorageError::InvalidJson("transactions", e))
+    }
+
+    /// Append frames to the active segment in a single write, rolling over to a new segment first
+    /// if they don't fit. Returns the segment and offset they were written at.
+    fn write_frames(&mut self, frames: &[u8]) -> Result<(u64, u64), LogStorageError> {
+        let (&active_id, active) = self.segments.last_key_value().unwrap();
+        if active.len > 0 && active.len + frames.len() as u64 > self.options.max_segment_size {
+            self.add_segment(active_id + 1)?;
+        }
+
+        let sync_writes = self.options.sync_writes;
+        let mut last = self.segments.last_entry().unwrap();
+        let id = *last.key();
+        let segment = last.get_mut();
+        let offset = segment.len;
+        let written = segment.file.write_all(frames).and_then(|_| {
+            if sync_writes {
+                segment.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = written {
+            // Don't leave a torn record in the middle of the segment for the next append to follow
+            let _ = segment.file.set_len(offset);
+            return Err(e.into());
+        }
+        segment.len += frames.len() as u64;
+
+        Ok((id, offset))
+    }
+
+    /// Rewrite all segments, merging consecutive records of each session and laying out every
+    /// CoValue contiguously.
+    ///
+    /// New segments are written under temporary names and renamed into place, then the manifest
+    /// records that the old ones are superseded before they're removed. If that's interrupted
+    /// before the manifest is written, the leftovers are cleaned up (or deduplicated) on the next
+    /// open. After it, the next open finishes removing the old segments and never reads them, so
+    /// no data is lost either way.
+    pub fn compact(&mut self) -> Result<CompactionStats, LogStorageError> {
+        let bytes_before = self.size();
+        let records_before = self.index.values().map(IndexEntry::records).sum();
+
+        let first_id = self.segments.keys().last().map_or(0, |id| id + 1);
+        let mut writer = CompactionWriter::new(&self.dir, first_id, self.options.max_segment_size);
+        let mut index: HashMap<CoID, IndexEntry> = HashMap::new();
+
+        let mut co_ids: Vec<&CoID> = self.index.keys().collect();
+        co_ids.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for co_id in co_ids {
+            let entry = &self.index[co_id];
+            let new_entry = index.entry(co_id.clone()).or_default();
+
+            if let Some(location) = entry.header {
+                new_entry.header = Some(writer.write(&self.read_frame_at(location)?)?);
+            }
+
+            let mut session_ids: Vec<&SessionID> = entry.sessions.keys().collect();
+            session_ids.sort_by(|a, b| a.0.cmp(&b.0));
+
+            for session_id in session_ids {
+                let mut merged = MergedRecord::new(co_id, session_id);
+                let mut blocks = Vec::new();
+
+                for block in &entry.sessions[session_id] {
+                    let payload = self.read_transactions(block)?;
+                    let size = payload
+                        .new_transactions
+                        .iter()
+                        .map(|tx| transaction_size(tx.get()))
+                        .sum();
+                    if !merged.transactions.is_empty()
+                        && merged.size + size > self.options.compaction_record_size
+                    {
+                        blocks.push(merged.flush(&mut writer)?);
+                    }
+                    merged.push(payload, size);
+                }
+                if !merged.transactions.is_empty() {
+                    blocks.push(merged.flush(&mut writer)?);
+                }
+
+                new_entry.sessions.insert(session_id.clone(), blocks);
+            }
+        }
+
+        let new_ids = writer.finish()?;
+        write_manifest(&self.dir, first_id)?;
+
+        let old_ids: Vec<u64> = self.segments.keys().copied().collect();
+        self.segments.clear();
+        for id in old_ids {
+            fs::remove_file(segment_path(&self.dir, id))?;
+        }
+        sync_dir(&self.dir)?;
+
+        for id in new_ids {
+            self.segments
+                .insert(id, Segment::open(&segment_path(&self.dir, id))?);
+        }
+        if self.segments.is_empty() {
+            self.add_segment(first_id)?;
+        }
+        self.index = index;
+
+        Ok(CompactionStats {
+            bytes_before,
+            bytes_after: self.size(),
+            records_before,
+            records_after: self.index.values().map(IndexEntry::records).sum(),
+        })
+    }
+}
+
+/// Consecutive transactions of a session being merged into one record during compaction.
+struct MergedRecord<'a> {
+    co_id: &'a CoID,
+    session_id: &'a SessionID,
+    after: u32,
+    transactions: Vec<Box<RawValue>>,
+    last_signature: Option<Signature>,
+    size: usize,
+}
+
+impl<'a> MergedRecord<'a> {
+    fn new(co_id: &'a CoID, session_id: &'a SessionID) -> Self {
+        Self {
+            co_id,
+            session_id,
+            after: 0,
+            transactions: Vec::new(),
+            last_signature: None,
+            size: 0,
+        }
+    }
+
+    fn push(&mut self, payload: TransactionsPayload, size: usize) {
+        self.transactions.extend(payload.new_transactions);
+        self.last_signature = Some(payload.last_signature);
+        self.size += size;
+    }
+
+    /// Write the merged record and start a new one after it.
+    /// The last signature covers the whole record, since it signs the session hash up to there.
+    fn flush(&mut self, writer: &mut CompactionWriter) -> Result<Block, LogStorageError> {
+        let transactions = std::mem::take(&mut self.transactions);
+        let count = transactions.len() as u32;
+        let last_signature = self.last_signature.take().unwrap();
+
+        let payload = serde_json::to_vec(&TransactionsPayloadRef {
+            last_signature: &last_signature,
+            new_transactions: &transactions,
+        })
+        .map_err(|e| LogStorageError::InvalidJson("transactions", e))?;
+        let frame = encode_frame(
+            &RecordMeta {
+                kind: RecordKind::Transactions,
+                co_id: self.co_id.0.clone(),
+                session_id: self.session_id.0.clone(),
+                after: self.after,
+                count,
+            },
+            &payload,
+        );
+
+        let block = Block {
+            location: writer.write(&frame)?,
+            after: self.after,
+            count,
+        };
+        self.after += count;
+        self.size = 0;
+        Ok(block)
+    }
+}
+
+/// Writes the segments of a compaction under their temporary names.
+struct CompactionWriter<'a> {
+    dir: &'a Path,
+    max_segment_size: u64,
+    next_id: u64,
+    current: Option<(u64, Segment)>,
+    written: Vec<u64>,
+}
+
+impl<'a> CompactionWriter<'a> {
+    fn new(dir: &'a Path, first_id: u64, max_segment_size: u64) -> Self {
+        Self {
+            dir,
+            max_segment_size,
+            next_id: first_id,
+            current: None,
+            written: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<Location, LogStorageError> {
+        let full = self.current.as_ref().is_some_and(|(_, segment)| {
+            segment.len > 0 && segment.len + frame.len() as u64 > self.max_segment_size
+        });
+        if full || self.current.is_none() {
+            self.close_current()?;
+            let id = self.next_id;
+            self.next_id += 1;
+            self.current = Some((id, Segment::open(&compacting_path(self.dir, id))?));
+        }
+
+        let (id, segment) = self.current.as_mut().unwrap();
+        let offset = segment.len;
+        segment.file.write_all(frame)?;
+        segment.len += frame.len() as u64;
+
+        Ok(Location {
+            segment: *id,
+            offset,
+            len: frame.len() as u64,
+        })
+    }
+
+    fn close_current(&mut self) -> Result<(), LogStorageError> {
+        if let Some((id, segment)) = self.current.take() {
+            segment.file.sync_all()?;
+            self.written.push(id);
+        }
+        Ok(())
+    }
+
+    /// Flush all segments and rename them into place, returning their IDs.
+    fn finish(mut self) -> Result<Vec<u64>, LogStorageError> {
+        self.close_current()?;
+        for id in &self.written {
+            fs::rename(compacting_path(self.dir, *id), segment_path(self.dir, *id))?;
+        }
+        sync_dir(self.dir)?;
+        Ok(self.written)
+    }
+}
+
+impl SessionLogStorage for LogStorage {
+    type Error = LogStorageError;
+
+    /// Sessions are replayed record by record, checking each record's signature.
+    fn load_session_logs_with_crypto<C, F>(
+        &self,
+        id: &CoID,
+        crypto: C,
+        mut signer_for: F,
+    ) -> Result<Option<LoadedCoValue<C>>, Self::Error>
+    where
+        C: CryptoProvider,
+        F: FnMut(&SessionID) -> Option<SignerID>,
+    {
+        let Some(entry) = self.index.get(id) else {
+            return Ok(None);
+        };
+        let Some(header_location) = entry.header else {
+            return Ok(None);
+        };
+        let header = self.read_header(header_location)?;
+
+        let mut sessions = HashMap::new();
+        for (session_id, blocks) in &entry.sessions {
+            let signer_id = signer_for(session_id);
+            let skip_verify = signer_id.is_none();
+            let mut session_log = SessionLogInternal::with_crypto(
+                id.clone(),
+                session_id.clone(),
+                signer_id,
+                crypto.clone(),
+            );
+
+            for block in blocks {
+                let payload = self.read_transactions(block)?;
+                session_log.try_add(
+                    payload.new_transactions,
+                    &payload.last_signature,
+                    skip_verify,
+                )?;
+            }
+
+            sessions.insert(session_id.clone(), session_log);
+        }
+
+        Ok(Some(LoadedCoValue {
+            id: id.clone(),
+            header,
+            sessions,
+        }))
+    }
+
+    /// New transactions are written as a single record (preceded by the header record for a new
+    /// CoValue) in one write. If verification fails, nothing is written.
+    fn append_transactions<C: CryptoProvider>(
+        &mut self,
+        id: &CoID,
+        header: Option<&JsonValue>,
+        session_id: &SessionID,
+        session_log: &mut SessionLogInternal<C>,
+        content: SessionNewContent,
+    ) -> Result<u32, Self::Error> {
+        let has_header = self
+            .index
+            .get(id)
+            .is_some_and(|entry| entry.header.is_some());
+        let header = match header {
+            Some(header) if !has_header => Some(header),
+            _ if has_header => None,
+            _ => return Err(LogStorageError::CoValueNotFound(id.0.clone())),
+        };
+
+        let last_idx = self.session_len(id, session_id);
+        if last_idx < content.after {
+            return Err(LogStorageError::MissingTransactions {
+                session_id: session_id.0.clone(),
+                stored: last_idx,
+                after: content.after,
+            });
+        }
+
+        let in_log = session_log.transactions_json().len() as u32;
+        if in_log != last_idx {
+            return Err(LogStorageError::SessionLogOutOfSync {
+                session_id: session_id.0.clone(),
+                in_log,
+                stored: last_idx,
+            });
+        }
+
+        let new_transactions: Vec<Box<RawValue>> = content
+            .new_transactions
+            .into_iter()
+            .skip((last_idx - content.after) as usize)
+            .collect();
+        let count = new_transactions.len() as u32;
+
+        let mut frames = Vec::new();
+        if let Some(header) = header {
+            frames.extend(encode_frame(
+                &RecordMeta {
+                    kind: RecordKind::Header,
+                    co_id: id.0.clone(),
+                    session_id: String::new(),
+                    after: 0,
+                    count: 0,
+                },
+                header.to_string().as_bytes(),
+            ));
+        }
+        let header_len = frames.len() as u64;
+
+        if count > 0 {
+            let payload = serde_json::to_vec(&TransactionsPayloadRef {
+                last_signature: &content.last_signature,
+                new_transactions: &new_transactions,
+            })
+            .map_err(|e| LogStorageError::InvalidJson("transactions", e))?;
+            frames.extend(encode_frame(
+                &RecordMeta {
+                    kind: RecordKind::Transactions,
+                    co_id: id.0.clone(),
+                    session_id: session_id.0.clone(),
+                    after: last_idx,
+                    count,
+                },
+                &payload,
+            ));
+
+            let skip_verify = session_log.signer_id().is_none();
+            session_log.try_add(new_transactions, &content.last_signature, skip_verify)?;
+        }
+
+        if frames.is_empty() {
+            return Ok(last_idx);
+        }
+
+        // (If the write fails after verification, the session log is already ahead of storage.)
+        let (segment, offset) = self.write_frames(&frames)?;
+
+        let entry = self.index.entry(id.clone()).or_default();
+        if header_len > 0 {
+            entry.header = Some(Location {
+                segment,
+                offset,
+                len: header_len,
+            });
+        }
+        if count > 0 {
+            entry
+                .sessions
+                .entry(session_id.clone())
+                .or_default()
+                .push(Block {
+                    location: Location {
+                        segment,
+                        offset: offset + header_len,
+                        len: frames.len() as u64 - header_len,
+                    },
+                    after: last_idx,
+                    count,
+                });
+        }
+
+        Ok(last_idx + count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cojson_core::core::test_utils::{test_header, TestWriter};
+    use cojson_core::core::CoJsonCoreError;
+
+    fn co_id() -> CoID {
+        CoID("co_zTest".to_string())
+    }
+
+    fn writer() -> TestWriter {
+        TestWriter::new(&co_id(), 1)
+    }
+
+    fn append(
+        writer: &mut TestWriter,
+        storage: &mut LogStorage,
+        stored_log: &mut SessionLogInternal,
+        n: usize,
+    ) {
+        for i in 0..n {
+            let content = writer.add(&format!(
+                r#"[{{"op":"set","key":"{i}","value":"{}"}}]"#,
+                "x".repeat(100)
+            ));
+            storage
+                .append_transactions(
+                    &co_id(),
+                    Some(&test_header("test")),
+                    writer.session_id(),
+                    stored_log,
+                    content,
+                )
+                .unwrap();
+        }
+    }
+
+    fn assert_loads(writer: &TestWriter, storage: &LogStorage) {
+        let loaded = storage
+            .load_session_logs(&co_id(), |_| Some(writer.signer_id().clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.header, test_header("test"));
+        let loaded_log = &loaded.sessions[writer.session_id()];
+        assert_eq!(
+            loaded_log.transactions_json(),
+            writer.log().transactions_json()
+        );
+        assert_eq!(loaded_log.last_signature(), writer.log().last_signature());
+    }
+
+    fn small_segments() -> LogStorageOptions {
+        LogStorageOptions {
+            max_segment_size: 1024,
+            compaction_record_size: 2048,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_append_load_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 20);
+        assert_eq!(storage.session_len(&co_id(), writer.session_id()), 20);
+        assert!(list_segments(dir.path()).unwrap().len() > 1);
+        assert_loads(&writer, &storage);
+        drop(storage);
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        assert_eq!(storage.co_values().collect::<Vec<_>>(), vec![&co_id()]);
+        assert_loads(&writer, &storage);
+
+        // Appending continues from the reloaded log
+        let mut stored_log = storage
+            .load_session_logs(&co_id(), |_| Some(writer.signer_id().clone()))
+            .unwrap()
+            .unwrap()
+            .sessions
+            .remove(writer.session_id())
+            .unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 1);
+        assert_loads(&writer, &storage);
+
+        assert!(storage
+            .load_session_logs(&CoID("co_zMissing".to_string()), |_| None)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_append_skips_stored_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = LogStorage::open(dir.path()).unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let first = writer.add("[]");
+        let second = writer.add("[]");
+        let both = SessionNewContent {
+            after: 0,
+            new_transactions: first
+                .new_transactions
+                .iter()
+                .chain(&second.new_transactions)
+                .cloned()
+                .collect(),
+            last_signature: second.last_signature.clone(),
+        };
+
+        storage
+            .append_transactions(
+                &co_id(),
+                Some(&test_header("test")),
+                writer.session_id(),
+                &mut stored_log,
+                first,
+            )
+            .unwrap();
+        let stored = storage
+            .append_transactions(&co_id(), None, writer.session_id(), &mut stored_log, both)
+            .unwrap();
+
+        assert_eq!(stored, 2);
+        assert_loads(&writer, &storage);
+    }
+
+    #[test]
+    fn test_append_rejects_invalid_signature() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = LogStorage::open(dir.path()).unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut content = writer.add("[]");
+        content.last_signature = TestWriter::new(&co_id(), 2).add("[]").last_signature;
+
+        let result = storage.append_transactions(
+            &co_id(),
+            Some(&test_header("test")),
+            writer.session_id(),
+            &mut stored_log,
+            content,
+        );
+        assert!(matches!(
+            result,
+            Err(LogStorageError::Core(
+                CoJsonCoreError::SignatureVerification(_)
+            ))
+        ));
+
+        assert_eq!(storage.size(), 0);
+        assert_eq!(storage.co_values().count(), 0);
+        assert!(stored_log.transactions_json().is_empty());
+
+        let content = writer.add("[]");
+        let result = storage.append_transactions(
+            &co_id(),
+            None,
+            writer.session_id(),
+            &mut stored_log,
+            content,
+        );
+        assert!(matches!(result, Err(LogStorageError::CoValueNotFound(_))));
+    }
+
+    #[test]
+    fn test_recovers_from_torn_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut storage = LogStorage::open(dir.path()).unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 3);
+        let size = storage.size();
+        drop(storage);
+
+        // Simulate a crash halfway through writing the next record
+        let mut next_writer = TestWriter::new(&co_id(), 2);
+        let content = next_writer.add("[]");
+        let frame = encode_frame(
+            &RecordMeta {
+                kind: RecordKind::Transactions,
+                co_id: co_id().0,
+                session_id: writer.session_id().0.clone(),
+                after: 3,
+                count: 1,
+            },
+            content.new_transactions[0].get().as_bytes(),
+        );
+        let path = segment_path(dir.path(), 0);
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&frame[..frame.len() / 2]).unwrap();
+        drop(file);
+
+        let mut storage = LogStorage::open(dir.path()).unwrap();
+        assert_eq!(storage.size(), size);
+        assert_eq!(fs::metadata(&path).unwrap().len(), size);
+        assert_loads(&writer, &storage);
+
+        append(&mut writer, &mut storage, &mut stored_log, 1);
+        drop(storage);
+        assert_loads(&writer, &LogStorage::open(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_corruption_before_the_last_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 20);
+        drop(storage);
+
+        let path = segment_path(dir.path(), 0);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[20] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        let result = LogStorage::open_with_options(dir.path(), small_segments());
+        assert!(matches!(
+            result,
+            Err(LogStorageError::Corrupted {
+                segment: 0,
+                offset: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 40);
+
+        let stats = storage.compact().unwrap();
+        assert_eq!(stats.records_before, 41);
+        assert!(stats.records_after < stats.records_before);
+        assert!(stats.bytes_after < stats.bytes_before);
+        assert_eq!(stats.bytes_after, storage.size());
+        assert_loads(&writer, &storage);
+
+        // Compacted segments replace the old ones and keep accepting appends
+        assert!(list_segments(dir.path()).unwrap().iter().all(|id| *id > 0));
+        append(&mut writer, &mut storage, &mut stored_log, 2);
+        drop(storage);
+        assert_loads(
+            &writer,
+            &LogStorage::open_with_options(dir.path(), small_segments()).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_interrupted_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 20);
+        let segments = list_segments(dir.path()).unwrap();
+        drop(storage);
+
+        // A crash after renaming some compacted segments leaves duplicates of the old records,
+        // a crash before that leaves temporary segments behind
+        let last = *segments.last().unwrap();
+        fs::copy(
+            segment_path(dir.path(), 0),
+            segment_path(dir.path(), last + 1),
+        )
+        .unwrap();
+        fs::write(compacting_path(dir.path(), last + 2), b"partial").unwrap();
+
+        let storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        assert_eq!(storage.session_len(&co_id(), writer.session_id()), 20);
+        assert!(!compacting_path(dir.path(), last + 2).exists());
+        assert_loads(&writer, &storage);
+    }
+
+    #[test]
+    fn test_compaction_interrupted_while_removing_old_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup = tempfile::tempdir().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        append(&mut writer, &mut storage, &mut stored_log, 20);
+        let old_segments = list_segments(dir.path()).unwrap();
+        assert!(old_segments.len() > 2);
+        for id in &old_segments {
+            fs::copy(
+                segment_path(dir.path(), *id),
+                segment_path(backup.path(), *id),
+            )
+            .unwrap();
+        }
+        storage.compact().unwrap();
+        drop(storage);
+
+        // A crash right after removing only the first old segment leaves the rest, whose records
+        // don't start at the beginning of their session
+        for id in &old_segments[1..] {
+            fs::copy(
+                segment_path(backup.path(), *id),
+                segment_path(dir.path(), *id),
+            )
+            .unwrap();
+        }
+
+        let mut storage = LogStorage::open_with_options(dir.path(), small_segments()).unwrap();
+        assert_eq!(storage.session_len(&co_id(), writer.session_id()), 20);
+        assert!(old_segments
+            .iter()
+            .all(|id| !segment_path(dir.path(), *id).exists()));
+        assert_loads(&writer, &storage);
+
+        append(&mut writer, &mut storage, &mut stored_log, 1);
+        drop(storage);
+        assert_loads(
+            &writer,
+            &LogStorage::open_with_options(dir.path(), small_segments()).unwrap(),
+        );
+    }
+}
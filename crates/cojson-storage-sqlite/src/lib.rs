@@ -0,0 +1,10 @@
+//! SQLite storage for CoValues, sharing its schema with `cojson-storage-sqlite` on the TS side,
+//! so the same database file can be opened from either runtime.
+
+pub mod error;
+pub mod migrations;
+pub mod storage;
+
+pub use error::*;
+pub use migrations::*;
+pub use storage::*;
@@ -0,0 +1,66 @@
+use rusqlite::Connection;
+
+/// Schema migrations keyed by `user_version`, kept identical to `sqliteMigrations.ts`.
+/// Version 2 was never used on the TS side, so it's skipped here too.
+pub const MIGRATIONS: &[(u32, &[&str])] = &[
+    (
+        1,
+        &[
+            "CREATE TABLE IF NOT EXISTS transactions (
+                ses INTEGER,
+                idx INTEGER,
+                tx TEXT NOT NULL,
+                PRIMARY KEY (ses, idx)
+            ) WITHOUT ROWID;",
+            "CREATE TABLE IF NOT EXISTS sessions (
+                rowID INTEGER PRIMARY KEY,
+                coValue INTEGER NOT NULL,
+                sessionID TEXT NOT NULL,
+                lastIdx INTEGER,
+                lastSignature TEXT,
+                UNIQUE (sessionID, coValue)
+            );",
+            "CREATE INDEX IF NOT EXISTS sessionsByCoValue ON sessions (coValue);",
+            "CREATE TABLE IF NOT EXISTS coValues (
+                rowID INTEGER PRIMARY KEY,
+                id TEXT NOT NULL UNIQUE,
+                header TEXT NOT NULL UNIQUE
+            );",
+            "CREATE INDEX IF NOT EXISTS coValuesByID ON coValues (id);",
+        ],
+    ),
+    (
+        3,
+        &[
+            "CREATE TABLE IF NOT EXISTS signatureAfter (
+                ses INTEGER,
+                idx INTEGER,
+                signature TEXT NOT NULL,
+                PRIMARY KEY (ses, idx)
+            ) WITHOUT ROWID;",
+            "ALTER TABLE sessions ADD COLUMN bytesSinceLastSignature INTEGER;",
+        ],
+    ),
+];
+
+/// Read the schema version from `PRAGMA user_version`.
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Apply all migrations newer than the current `user_version`, recording each version as it's applied.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version = schema_version(conn)?;
+
+    for (migration_version, queries) in MIGRATIONS {
+        if *migration_version <= version {
+            continue;
+        }
+        for query in *queries {
+            conn.execute(query, [])?;
+        }
+        conn.pragma_update(None, "user_version", migration_version)?;
+    }
+
+    Ok(())
+}
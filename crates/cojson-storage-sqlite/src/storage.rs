@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cojson_core::core::{
+    reaches_checkpoint, transaction_size, CoID, CryptoProvider, LoadedCoValue, SessionID,
+    SessionLogInternal, SessionLogStorage, SessionNewContent, Signature, SignerID,
+};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde_json::{value::RawValue, Value as JsonValue};
+
+use crate::{migrate, StorageError};
+
+/// A row of the `coValues` table, with the header parsed.
+#[derive(Debug, Clone)]
+pub struct StoredCoValue {
+    pub row_id: i64,
+    pub id: CoID,
+    pub header: JsonValue,
+}
+
+/// A row of the `sessions` table.
+#[derive(Debug, Clone)]
+pub struct StoredSession {
+    pub row_id: i64,
+    pub co_value: i64,
+    pub session_id: SessionID,
+    /// Number of stored transactions in the session.
+    pub last_idx: u32,
+    pub last_signature: Signature,
+    pub bytes_since_last_signature: Option<u64>,
+}
+
+impl StoredSession {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            row_id: row.get("rowID")?,
+            co_value: row.get("coValue")?,
+            session_id: SessionID(row.get("sessionID")?),
+            last_idx: row.get("lastIdx")?,
+            last_signature: Signature(row.get("lastSignature")?),
+            bytes_since_last_signature: row.get("bytesSinceLastSignature")?,
+        })
+    }
+}
+
+/// A row of the `signatureAfter` table: the signature of the session hash after the transaction at `idx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureAfter {
+    pub idx: u32,
+    pub signature: Signature,
+}
+
+/// CoValue storage on top of a SQLite database using the `cojson-storage-sqlite` schema.
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    /// Open (or create) a database file and bring its schema up to date.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Open a fresh in-memory database, mostly useful for tests.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, running any pending migrations on it.
+    pub fn from_connection(conn: Connection) -> Result<Self, StorageError> {
+        migrate(&conn)?;
+        Ok(Self { conn })
+    }
+
+    /// Get a reference to the underlying connection.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Get a CoValue row by ID.
+    pub fn get_co_value(&self, id: &CoID) -> Result<Option<StoredCoValue>, StorageError> {
+        let row = self
+            .conn
+            .query_row(
+                "SELECT rowID, header FROM coValues WHERE id = ?",
+                [&id.0],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        let Some((row_id, header)) = row else {
+            return Ok(None);
+        };
+        let header =
+            serde_json::from_str(&header).map_err(|e| StorageError::InvalidJson("header", e))?;
+
+        Ok(Some(StoredCoValue {
+            row_id,
+            id: id.clone(),
+            header,
+        }))
+    }
+
+    /// Get all session rows of a CoValue.
+    pub fn get_co_value_sessions(
+        &self,
+        co_value_row_id: i64,
+    ) -> Result<Vec<StoredSession>, StorageError> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT * FROM sessions WHERE coValue = ?")?;
+        let sessions = stmt
+            .query_map([co_value_row_id], StoredSession::from_row)?
+            .collect::<Result<_, _>>()?;
+        Ok(sessions)
+    }
+
+    /// Get a single session row of a CoValue.
+    pub fn get_single_co_value_session(
+        &self,
+        co_value_row_id: i64,
+        session_id: &SessionID,
+    ) -> Result<Option<StoredSession>, StorageError> {
+        Ok(get_single_co_value_session(
+            &self.conn,
+            co_value_row_id,
+            session_id,
+        )?)
+    }
+
+    /// Get the raw JSON of the transactions with `from_idx <= idx <= to_idx` in a session, in order.
+    pub fn get_transactions(
+        &self,
+        session_row_id: i64,
+        from_idx: u32,
+        to_idx: u32,
+    ) -> Result<Vec<Box<RawValue>>, StorageError> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT tx FROM transactions WHERE ses = ? AND idx >= ? AND idx <= ? ORDER BY idx",
+        )?;
+        let rows = stmt
+            .query_map(params![session_row_id, from_idx, to_idx], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|tx| {
+                RawValue::from_string(tx).map_err(|e| StorageError::InvalidJson("transaction", e))
+            })
+            .collect()
+    }
+
+    /// Get the `signatureAfter` checkpoints of a session from `from_idx` onwards, in order.
+    pub fn get_signatures(
+        &self,
+        session_row_id: i64,
+        from_idx: u32,
+    ) -> Result<Vec<SignatureAfter>, StorageError> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT idx, signature FROM signatureAfter WHERE ses = ? AND idx >= ? ORDER BY idx",
+        )?;
+        let signatures = stmt
+            .query_map(params![session_row_id, from_idx], |row| {
+                Ok(SignatureAfter {
+                    idx: row.get(0)?,
+                    signature: Signature(row.get(1)?),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(signatures)
+    }
+
+    /// The stored signatures of a session: its `signatureAfter` checkpoints, followed by
+    /// the last signature if it isn't a checkpoint already.
+    fn checkpoints(&self, session: &StoredSession) -> Result<Vec<SignatureAfter>, StorageError> {
+        let mut signatures = self.get_signatures(session.row_id, 0)?;
+
+        let last_is_checkpoint =
+            signatures.last().map(|s| &s.signature) == Some(&session.last_signature);
+        if session.last_idx > 0 && !last_is_checkpoint {
+            signatures.push(SignatureAfter {
+                idx: session.last_idx - 1,
+                signature: session.last_signature.clone(),
+            });
+        }
+
+        Ok(signatures)
+    }
+}
+
+impl SessionLogStorage for SqliteStorage {
+    type Error = StorageError;
+
+    /// Sessions are replayed in the chunks they were signed in - the `signatureAfter`
+    /// checkpoints followed by the last signature - so every stored signature gets checked.
+    fn load_session_logs_with_crypto<C, F>(
+        &self,
+        id: &CoID,
+        crypto: C,
+        mut signer_for: F,
+    ) -> Result<Option<LoadedCoValue<C>>, Self::Error>
+    where
+        C: CryptoProvider,
+        F: FnMut(&SessionID) -> Option<SignerID>,
+    {
+        let Some(co_value) = self.get_co_value(id)? else {
+            return Ok(None);
+        };
+
+        let mut sessions = HashMap::new();
+        for session in self.get_co_value_sessions(co_value.row_id)? {
+            let signer_id = signer_for(&session.session_id);
+            let skip_verify = signer_id.is_none();
+            let mut session_log = SessionLogInternal::with_crypto(
+                id.clone(),
+                session.session_id.clone(),
+                signer_id,
+                crypto.clone(),
+            );
+
+            for checkpoint in self.checkpoints(&session)? {
+                let from_idx = session_log.transactions_json().len() as u32;
+                let transactions =
+                    self.get_transactions(session.row_id, from_idx, checkpoint.idx)?;
+                session_log.try_add(transactions, &checkpoint.signature, skip_verify)?;
+            }
+
+            sessions.insert(session.session_id, session_log);
+        }
+
+        Ok(Some(LoadedCoValue {
+            id: co_value.id,
+            header: co_value.header,
+            sessions,
+        }))
+    }
+
+    /// Appends the same way `StorageApiSync.store` does on the TS side: new transactions are written
+    /// in a single SQLite transaction, together with the CoValue row, the session row and a
+    /// `signatureAfter` checkpoint when one is due. If verification fails, nothing is written.
+    fn append_transactions<C: CryptoProvider>(
+        &mut self,
+        id: &CoID,
+        header: Option<&JsonValue>,
+        session_id: &SessionID,
+        session_log: &mut SessionLogInternal<C>,
+        content: SessionNewContent,
+    ) -> Result<u32, Self::Error> {
+        let tx = self.conn.transaction()?;
+
+        let co_value_row_id = upsert_co_value(&tx, id, header)?
+            .ok_or_else(|| StorageError::CoValueNotFound(id.0.clone()))?;
+        let session_row = get_single_co_value_session(&tx, co_value_row_id, session_id)?;
+        let last_idx = session_row.as_ref().map_or(0, |s| s.last_idx);
+
+        if last_idx < content.after {
+            return Err(StorageError::MissingTransactions {
+                session_id: session_id.0.clone(),
+                stored: last_idx,
+                after: content.after,
+            });
+        }
+
+        let in_log = session_log.transactions_json().len() as u32;
+        if in_log != last_idx {
+            return Err(StorageError::SessionLogOutOfSync {
+                session_id: session_id.0.clone(),
+                in_log,
+                stored: last_idx,
+            });
+        }
+
+        let new_transactions: Vec<Box<RawValue>> = content
+            .new_transactions
+            .into_iter()
+            .skip((last_idx - content.after) as usize)
+            .collect();
+
+        if new_transactions.is_empty() {
+            // Still commit, in case the CoValue row is new
+            tx.commit()?;
+            return Ok(last_idx);
+        }
+
+        let new_transactions_size = new_transactions
+            .iter()
+            .map(|tx| transaction_size(tx.get()))
+            .sum();
+        let mut bytes_since_last_signature = session_row
+            .and_then(|s| s.bytes_since_last_signature)
+            .unwrap_or(0) as usize;
+        let write_checkpoint = reaches_checkpoint(
+            &mut bytes_since_last_signature,
+            new_transactions_size,
+            session_log.limits(),
+        );
+
+        let new_last_idx = last_idx + new_transactions.len() as u32;
+
+        let session_row_id: i64 = tx.query_row(
+            "INSERT INTO sessions (coValue, sessionID, lastIdx, lastSignature, bytesSinceLastSignature) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(coValue, sessionID) DO UPDATE SET lastIdx=excluded.lastIdx, lastSignature=excluded.lastSignature, bytesSinceLastSignature=excluded.bytesSinceLastSignature
+             RETURNING rowID",
+            params![
+                co_value_row_id,
+                session_id.0,
+                new_last_idx,
+                content.last_signature.0,
+                bytes_since_last_signature,
+            ],
+            |row| row.get(0),
+        )?;
+
+        if write_checkpoint {
+            tx.execute(
+                "INSERT INTO signatureAfter (ses, idx, signature) VALUES (?, ?, ?)",
+                params![session_row_id, new_last_idx - 1, content.last_signature.0],
+            )?;
+        }
+
+        {
+            let mut stmt =
+                tx.prepare_cached("INSERT INTO transactions (ses, idx, tx) VALUES (?, ?, ?)")?;
+            for (i, transaction) in new_transactions.iter().enumerate() {
+                stmt.execute(params![
+                    session_row_id,
+                    last_idx + i as u32,
+                    transaction.get()
+                ])?;
+            }
+        }
+
+        // Verify last, so a bad signature rolls back everything written above.
+        // (If the commit itself fails, the session log is already ahead of the database.)
+        let skip_verify = session_log.signer_id().is_none();
+        session_log.try_add(new_transactions, &content.last_signature, skip_verify)?;
+        tx.commit()?;
+
+        Ok(new_last_idx)
+    }
+}
+
+fn get_single_co_value_session(
+    conn: &Connection,
+    co_value_row_id: i64,
+    session_id: &SessionID,
+) -> rusqlite::Result<Option<StoredSession>> {
+    conn.query_row(
+        "SELECT * FROM sessions WHERE coValue = ? AND sessionID = ?",
+        params![co_value_row_id, session_id.0],
+        StoredSession::from_row,
+    )
+    .optional()
+}
+
+/// Insert the CoValue row if there's a header for it, returning its row ID.
+fn upsert_co_value(
+    conn: &Connection,
+    id: &CoID,
+    header: Option<&JsonValue>,
+) -> Result<Option<i64>, StorageError> {
+    if let Some(header) = header {
+        let inserted = conn
+            .query_row(
+                "INSERT INTO coValues (id, header) VALUES (?, ?) ON CONFLICT(id) DO NOTHING RETURNING rowID",
+                params![id.0, header.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if inserted.is_some() {
+            return Ok(inserted);
+        }
+    }
+
+    Ok(conn
+        .query_row("SELECT rowID FROM coValues WHERE id = ?", [&id.0], |row| {
+            row.get(0)
+        })
+        .optional()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cojson_core::core::test_utils::{test_agent, test_header, TestWriter};
+    use cojson_core::core::CoJsonCoreError;
+
+    fn co_id() -> CoID {
+        CoID("co_zTest".to_string())
+    }
+
+    fn session_id() -> SessionID {
+        test_agent(1).0
+    }
+
+    fn writer() -> TestWriter {
+        TestWriter::new(&co_id(), 1)
+    }
+
+    fn count(storage: &SqliteStorage, table: &str) -> u32 {
+        storage
+            .connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_migrations() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        assert_eq!(crate::schema_version(storage.connection()).unwrap(), 3);
+
+        // Running the migrations again is a no-op
+        migrate(storage.connection()).unwrap();
+        assert_eq!(count(&storage, "signatureAfter"), 0);
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        for i in 0..3 {
+            let content = writer.add(&format!(r#"[{{"op":"set","key":"a","value":{i}}}]"#));
+            let stored = storage
+                .append_transactions(
+                    &co_id(),
+                    Some(&test_header("test")),
+                    &session_id(),
+                    &mut stored_log,
+                    content,
+                )
+                .unwrap();
+            assert_eq!(stored, i + 1);
+        }
+
+        let signer_id = writer.signer_id().clone();
+        let loaded = storage
+            .load_session_logs(&co_id(), |_| Some(signer_id.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.header, test_header("test"));
+
+        let loaded_log = &loaded.sessions[&session_id()];
+        assert_eq!(
+            loaded_log.transactions_json(),
+            writer.log().transactions_json()
+        );
+        assert_eq!(loaded_log.last_signature(), writer.log().last_signature());
+
+        // Loading without a signer trusts the stored transactions
+        let trusted = storage
+            .load_session_logs(&co_id(), |_| None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(trusted.sessions[&session_id()].transactions_json().len(), 3);
+
+        assert!(storage
+            .load_session_logs(&CoID("co_zMissing".to_string()), |_| None)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_append_skips_stored_transactions() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let first = writer.add(r#"[{"op":"set","key":"a","value":1}]"#);
+        let second = writer.add(r#"[{"op":"set","key":"b","value":2}]"#);
+        let both = SessionNewContent {
+            after: 0,
+            new_transactions: first
+                .new_transactions
+                .iter()
+                .chain(&second.new_transactions)
+                .cloned()
+                .collect(),
+            last_signature: second.last_signature.clone(),
+        };
+
+        storage
+            .append_transactions(
+                &co_id(),
+                Some(&test_header("test")),
+                &session_id(),
+                &mut stored_log,
+                first,
+            )
+            .unwrap();
+        let stored = storage
+            .append_transactions(&co_id(), None, &session_id(), &mut stored_log, both)
+            .unwrap();
+
+        assert_eq!(stored, 2);
+        assert_eq!(count(&storage, "transactions"), 2);
+        assert_eq!(
+            stored_log.transactions_json(),
+            writer.log().transactions_json()
+        );
+    }
+
+    #[test]
+    fn test_append_rejects_invalid_signature() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let mut content = writer.add(r#"[{"op":"set","key":"a","value":1}]"#);
+        content.last_signature = TestWriter::new(&co_id(), 2).add("[]").last_signature;
+
+        let result = storage.append_transactions(
+            &co_id(),
+            Some(&test_header("test")),
+            &session_id(),
+            &mut stored_log,
+            content,
+        );
+        assert!(matches!(
+            result,
+            Err(StorageError::Core(CoJsonCoreError::SignatureVerification(
+                _
+            )))
+        ));
+
+        // Nothing was written, not even the CoValue row
+        assert_eq!(count(&storage, "coValues"), 0);
+        assert_eq!(count(&storage, "sessions"), 0);
+        assert_eq!(count(&storage, "transactions"), 0);
+        assert!(stored_log.transactions_json().is_empty());
+    }
+
+    #[test]
+    fn test_append_trusts_logs_without_signer() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut writer = writer();
+        let mut stored_log = SessionLogInternal::new(co_id(), session_id(), None);
+
+        let content = writer.add(r#"[{"op":"set","key":"a","value":1}]"#);
+        let stored = storage
+            .append_transactions(
+                &co_id(),
+                Some(&test_header("test")),
+                &session_id(),
+                &mut stored_log,
+                content,
+            )
+            .unwrap();
+        assert_eq!(stored, 1);
+        assert_eq!(count(&storage, "transactions"), 1);
+    }
+
+    #[test]
+    fn test_append_errors() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        let first = writer.add("[]");
+        let second = writer.add("[]");
+
+        let result =
+            storage.append_transactions(&co_id(), None, &session_id(), &mut stored_log, first);
+        assert!(matches!(result, Err(StorageError::CoValueNotFound(id)) if id == co_id().0));
+
+        let result = storage.append_transactions(
+            &co_id(),
+            Some(&test_header("test")),
+            &session_id(),
+            &mut stored_log,
+            second,
+        );
+        assert!(matches!(
+            result,
+            Err(StorageError::MissingTransactions {
+                stored: 0,
+                after: 1,
+                ..
+            })
+        ));
+
+        let third = writer.add("[]");
+        let mut ahead_log = writer.log().clone();
+        let result = storage.append_transactions(
+            &co_id(),
+            Some(&test_header("test")),
+            &session_id(),
+            &mut ahead_log,
+            SessionNewContent { after: 0, ..third },
+        );
+        assert!(matches!(
+            result,
+            Err(StorageError::SessionLogOutOfSync {
+                in_log: 3,
+                stored: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_signature_checkpoints() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let mut writer = writer();
+        let mut stored_log = writer.empty_log();
+
+        // Each transaction is ~40 KiB, so every third one crosses MAX_RECOMMENDED_TX_SIZE
+        let value = "x".repeat(40 * 1024);
+        for i in 0..7 {
+            let content = writer.add(&format!(
+                r#"[{{"op":"set","key":"{i}","value":"{value}"}}]"#
+            ));
+            storage
+                .append_transactions(
+                    &co_id(),
+                    Some(&test_header("test")),
+                    &session_id(),
+                    &mut stored_log,
+                    content,
+                )
+                .unwrap();
+        }
+
+        let co_value = storage.get_co_value(&co_id()).unwrap().unwrap();
+        let session = storage
+            .get_single_co_value_session(co_value.row_id, &session_id())
+            .unwrap()
+            .unwrap();
+        let checkpoints: Vec<u32> = storage
+            .get_signatures(session.row_id, 0)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.idx)
+            .collect();
+        assert_eq!(checkpoints, vec![2, 5]);
+        assert_eq!(session.last_idx, 7);
+
+        // Loading verifies each checkpoint chunk and the final signature
+        let signer_id = writer.signer_id().clone();
+        let loaded = storage
+            .load_session_logs(&co_id(), |_| Some(signer_id.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            loaded.sessions[&session_id()].transactions_json(),
+            writer.log().transactions_json()
+        );
+    }
+}
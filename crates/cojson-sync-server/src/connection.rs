@@ -0,0 +1,259 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use cojson_sync::{
+    bounded_in_memory_channel, in_memory_channel, InMemoryIncoming, InMemoryOutgoing,
+    IncomingPeerChannel, OutgoingPeerChannel, Peer, PeerID, PeerRole, DEFAULT_MAX_QUEUED_MESSAGES,
+};
+use tungstenite::{Error as WsError, Message, WebSocket};
+
+use crate::protocol::{
+    deserialize_messages, OutgoingBatcher, PingMessage, MAX_OUTGOING_MESSAGES_CHUNK_BYTES,
+};
+use crate::server::NodeEvent;
+use crate::ServerError;
+
+/// How often the server sends a ping, as in `startSyncServer.ts`.
+pub const PING_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// How long to wait for incoming data before sending queued messages. Doubles as the delay
+/// that lets outgoing messages accumulate into batches.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How much is written per poll, so messages queued meanwhile can still overtake the rest.
+const MAX_BYTES_PER_POLL: usize = 4 * MAX_OUTGOING_MESSAGES_CHUNK_BYTES;
+
+/// How many messages the node hands to a connection thread at a time. The rest wait in the
+/// peer's priority queue.
+const CHANNEL_CAPACITY: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    pub ping_interval: Duration,
+    /// Reported in pings.
+    pub dc: String,
+    /// Batch outgoing messages before the client shows it can handle batches.
+    pub batching_by_default: bool,
+    /// Messages held in the node's priority queue for the client. A client that falls this
+    /// far behind is disconnected, and catches up when it reconnects.
+    pub max_queued_messages: usize,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            ping_interval: PING_INTERVAL,
+            dc: "unknown".to_string(),
+            // Like jazz-run, start unbatched until the client sends a batch itself
+            batching_by_default: false,
+            max_queued_messages: DEFAULT_MAX_QUEUED_MESSAGES,
+        }
+    }
+}
+
+/// The node's end of a WebSocket connection, and the channels the connection thread uses.
+pub(crate) fn websocket_peer(
+    id: PeerID,
+    role: PeerRole,
+    options: &ConnectionOptions,
+) -> (Peer, InMemoryOutgoing, InMemoryIncoming) {
+    let (to_node, from_socket) = in_memory_channel();
+    let (to_socket, from_node) = bounded_in_memory_channel(CHANNEL_CAPACITY);
+    let peer =
+        Peer::new(id, role, from_socket, to_socket).with_queue_limit(options.max_queued_messages);
+    (peer, to_node, from_node)
+}
+
+/// Whether a request is a plain HTTP health check rather than a WebSocket upgrade.
+/// Peeks, so the handshake can still read the whole request.
+pub(crate) fn is_health_check(stream: &TcpStream) -> std::io::Result<bool> {
+    const HEALTH_REQUEST: &[u8] = b"GET /health ";
+    let mut buf = [0u8; HEALTH_REQUEST.len()];
+    loop {
+        let len = stream.peek(&mut buf)?;
+        if len == 0 || buf[..len] != HEALTH_REQUEST[..len] {
+            return Ok(false);
+        }
+        if len == buf.len() {
+            return Ok(true);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+pub(crate) fn respond_health(mut stream: TcpStream) -> std::io::Result<()> {
+    // Read the request before answering, so closing doesn't reset the connection
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let len = stream.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..len]);
+    }
+    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")?;
+    stream.flush()
+}
+
+fn is_timeout(err: &WsError) -> bool {
+    matches!(err, WsError::Io(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
+
+/// Shuttle messages between a WebSocket and the node until either side goes away.
+pub(crate) fn run_connection(
+    mut websocket: WebSocket<TcpStream>,
+    mut to_node: InMemoryOutgoing,
+    mut from_node: InMemoryIncoming,
+    events: Sender<NodeEvent>,
+    options: &ConnectionOptions,
+) -> Result<(), ServerError> {
+    let result = pump(
+        &mut websocket,
+        &mut to_node,
+        &mut from_node,
+        &events,
+        options,
+    );
+
+    // Let the node drop the peer
+    to_node.close();
+    let _ = events.send(NodeEvent::Wake);
+    result
+}
+
+fn pump(
+    websocket: &mut WebSocket<TcpStream>,
+    to_node: &mut InMemoryOutgoing,
+    from_node: &mut InMemoryIncoming,
+    events: &Sender<NodeEvent>,
+    options: &ConnectionOptions,
+) -> Result<(), ServerError> {
+    websocket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut batcher = OutgoingBatcher::new(options.batching_by_default);
+    let mut last_ping = Instant::now();
+    let mut node_disconnected = false;
+
+    loop {
+        let mut taken = 0;
+        while !node_disconnected && batcher.pending_bytes() < MAX_BYTES_PER_POLL {
+            match from_node.try_recv() {
+                Ok(Some(message)) => {
+                    batcher.push(&message);
+                    taken += 1;
+                }
+                Ok(None) => break,
+                Err(_) => node_disconnected = true,
+            }
+        }
+        // Let the node refill the channel from its queue
+        if taken > 0 {
+            let _ = events.send(NodeEvent::Wake);
+        }
+        for frame in batcher.flush() {
+            websocket.write(Message::Text(frame))?;
+        }
+
+        if last_ping.elapsed() >= options.ping_interval {
+            websocket.write(Message::Text(PingMessage::now(&options.dc).to_json()))?;
+            last_ping = Instant::now();
+        }
+        websocket.flush()?;
+
+        if node_disconnected {
+            websocket.close(None)?;
+            websocket.flush()?;
+            return Ok(());
+        }
+
+        match websocket.read() {
+            Ok(Message::Text(frame)) => {
+                if frame.is_empty() {
+                    continue;
+                }
+                // Malformed frames are dropped, like on the TS side
+                let Ok(messages) = deserialize_messages(&frame) else {
+                    continue;
+                };
+                if messages.len() > 1 {
+                    batcher.set_batching(true);
+                }
+                for message in messages {
+                    if to_node.push(message).is_err() {
+                        return Ok(());
+                    }
+                }
+                let _ = events.send(NodeEvent::Wake);
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(err) if is_timeout(&err) => {}
+            Err(WsError::ConnectionClosed | WsError::AlreadyClosed) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cojson_core::core::CoID;
+    use cojson_sync::{NewContentMessage, SyncMessage, PRIORITY_HIGH, PRIORITY_LOW};
+    use std::collections::BTreeMap;
+    use std::net::TcpListener;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    fn content(id: &str, priority: u8) -> SyncMessage {
+        SyncMessage::Content(NewContentMessage {
+            id: CoID(id.to_string()),
+            header: None,
+            priority,
+            new: BTreeMap::new(),
+            expect_content_until: None,
+        })
+    }
+
+    #[test]
+    fn test_group_content_overtakes_uploads() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let (mut websocket, _) = tungstenite::client(format!("ws://{addr}"), stream).unwrap();
+            let mut ids = Vec::new();
+            while ids.len() < 4 {
+                if let Message::Text(frame) = websocket.read().unwrap() {
+                    ids.extend(
+                        deserialize_messages(&frame)
+                            .unwrap()
+                            .iter()
+                            .map(|message| message.id().0.clone()),
+                    );
+                }
+            }
+            ids
+        });
+        let websocket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+
+        let options = ConnectionOptions::default();
+        let (mut peer, to_node, from_node) =
+            websocket_peer("client".to_string(), PeerRole::Client, &options);
+        for _ in 0..2 {
+            peer.enqueue(content("co_zUpload", PRIORITY_LOW)).unwrap();
+        }
+        peer.enqueue(content("co_zFile", PRIORITY_LOW)).unwrap();
+        peer.enqueue(content("co_zGroup", PRIORITY_HIGH)).unwrap();
+        assert_eq!(peer.flush(), Ok(4));
+        peer.outgoing.close();
+
+        let (events, _events) = channel();
+        run_connection(websocket, to_node, from_node, events, &options).unwrap();
+        assert_eq!(
+            client.join().unwrap(),
+            ["co_zGroup", "co_zUpload", "co_zFile", "co_zUpload"]
+        );
+    }
+}
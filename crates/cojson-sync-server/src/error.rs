@@ -0,0 +1,24 @@
+use cojson_storage_log::LogStorageError;
+use cojson_storage_sqlite::StorageError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+
+    #[error(transparent)]
+    Sqlite(#[from] StorageError),
+
+    #[error(transparent)]
+    Log(#[from] LogStorageError),
+}
+
+impl From<tungstenite::Error> for ServerError {
+    fn from(err: tungstenite::Error) -> Self {
+        ServerError::WebSocket(Box::new(err))
+    }
+}
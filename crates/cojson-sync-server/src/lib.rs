@@ -0,0 +1,11 @@
+//! A sync server speaking the WebSocket protocol of `cojson-transport-ws`, so TS clients can
+//! sync with it like with `jazz-run sync`, backed by the Rust core and storage.
+
+pub mod connection;
+pub mod error;
+pub mod protocol;
+pub mod server;
+
+pub use connection::ConnectionOptions;
+pub use error::*;
+pub use server::*;
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+use cojson_sync_server::{EventHandler, ServerOptions, ServerStorage, SyncServer};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum StorageFormat {
+    /// A SQLite file, compatible with `cojson-storage-sqlite`
+    Sqlite,
+    /// A directory of append-only log segments
+    Log,
+}
+
+/// Run a COJSON sync server that `cojson-transport-ws` clients can connect to.
+#[derive(Debug, Parser)]
+#[command(name = "cojson-sync-server", version)]
+struct Args {
+    /// The host to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// The port for the WebSocket server
+    #[arg(long, default_value_t = 4200)]
+    port: u16,
+
+    /// Keep everything in memory instead of storing it
+    #[arg(long)]
+    in_memory: bool,
+
+    /// Where to store the data: a file for SQLite, a directory for the log format
+    #[arg(long, default_value = "sync-db/storage.db")]
+    db: PathBuf,
+
+    /// How to store the data
+    #[arg(long, value_enum, default_value_t = StorageFormat::Sqlite)]
+    storage: StorageFormat,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let storage = match (args.in_memory, args.storage) {
+        (true, _) => ServerStorage::InMemory,
+        (false, StorageFormat::Sqlite) => ServerStorage::Sqlite(args.db),
+        (false, StorageFormat::Log) => ServerStorage::Log(args.db),
+    };
+    let options = ServerOptions {
+        storage,
+        on_event: Some(EventHandler::new(|event| eprintln!("{event}"))),
+        ..ServerOptions::default()
+    };
+
+    match SyncServer::start((args.host.as_str(), args.port), options) {
+        Ok(server) => {
+            println!("COJSON sync server listening on {}", server.url());
+            server.wait();
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to start sync server: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
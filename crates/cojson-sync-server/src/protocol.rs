@@ -0,0 +1,201 @@
+//! The framing of `cojson-transport-ws`: every WebSocket text frame holds one or more sync
+//! messages as newline-delimited JSON, next to `ping` keepalives that aren't sync messages.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cojson_sync::{NodeError, SyncMessage};
+use serde::{Deserialize, Serialize};
+
+/// Frames are closed once they reach this many bytes, as in `BatchedOutgoingMessages.ts`.
+pub const MAX_OUTGOING_MESSAGES_CHUNK_BYTES: usize = 25_000;
+
+/// The keepalive the server sends to clients, which time out without it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PingMessage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Milliseconds since the Unix epoch.
+    pub time: u64,
+    /// The data center the server runs in.
+    pub dc: String,
+}
+
+impl PingMessage {
+    pub fn now(dc: &str) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        Self {
+            kind: "ping".to_string(),
+            time,
+            dc: dc.to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Just the tag, to tell sync messages from anything else in a frame.
+#[derive(Deserialize)]
+struct Action<'a> {
+    #[serde(borrow)]
+    action: Option<&'a str>,
+}
+
+/// The sync messages in a frame, skipping pings and anything else without an `action`.
+///
+/// Like `deserializeMessages`, a frame with any invalid line is rejected as a whole.
+pub fn deserialize_messages(frame: &str) -> Result<Vec<SyncMessage>, NodeError> {
+    let mut messages = Vec::new();
+    for line in frame.split('\n') {
+        let action: Action = serde_json::from_str(line).map_err(NodeError::InvalidMessage)?;
+        if action.action.is_some() {
+            messages.push(SyncMessage::from_json(line)?);
+        }
+    }
+    Ok(messages)
+}
+
+/// Packs outgoing messages into frames.
+///
+/// Without batching every message is a frame of its own. With batching, messages are joined
+/// until a frame reaches `MAX_OUTGOING_MESSAGES_CHUNK_BYTES`.
+#[derive(Debug, Default)]
+pub struct OutgoingBatcher {
+    batching: bool,
+    backlog: String,
+    frames: Vec<String>,
+}
+
+impl OutgoingBatcher {
+    pub fn new(batching: bool) -> Self {
+        Self {
+            batching,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_batching(&self) -> bool {
+        self.batching
+    }
+
+    /// Clients that send batched frames can receive them, so the first one switches batching on.
+    pub fn set_batching(&mut self, batching: bool) {
+        self.batching = batching;
+    }
+
+    pub fn push(&mut self, message: &SyncMessage) {
+        let json = message.to_json();
+        if !self.batching {
+            self.frames.push(json);
+            return;
+        }
+
+        if !self.backlog.is_empty()
+            && self.backlog.len() + 1 + json.len() >= MAX_OUTGOING_MESSAGES_CHUNK_BYTES
+        {
+            self.frames.push(std::mem::take(&mut self.backlog));
+        }
+        if !self.backlog.is_empty() {
+            self.backlog.push('\n');
+        }
+        self.backlog.push_str(&json);
+        if self.backlog.len() >= MAX_OUTGOING_MESSAGES_CHUNK_BYTES {
+            self.frames.push(std::mem::take(&mut self.backlog));
+        }
+    }
+
+    /// Bytes waiting to be sent, in frames and the partial one.
+    pub fn pending_bytes(&self) -> usize {
+        self.backlog.len() + self.frames.iter().map(String::len).sum::<usize>()
+    }
+
+    /// The frames to send, including a final partial one.
+    pub fn flush(&mut self) -> Vec<String> {
+        if !self.backlog.is_empty() {
+            self.frames.push(std::mem::take(&mut self.backlog));
+        }
+        std::mem::take(&mut self.frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cojson_core::core::CoID;
+    use cojson_sync::{CoValueKnownState, DoneMessage};
+
+    fn load(id: &str) -> SyncMessage {
+        SyncMessage::Load(CoValueKnownState::empty(CoID(id.to_string())))
+    }
+
+    #[test]
+    fn test_deserialize_batched_frame() {
+        let frame = format!(
+            "{}\n{}\n{}",
+            load("co_zA").to_json(),
+            PingMessage::now("unknown").to_json(),
+            SyncMessage::Done(DoneMessage {
+                id: CoID("co_zB".to_string())
+            })
+            .to_json(),
+        );
+        let messages = deserialize_messages(&frame).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].id().0, "co_zA");
+        assert_eq!(messages[1].id().0, "co_zB");
+
+        // One bad line rejects the frame
+        let frame = format!("{}\nnot json", load("co_zA").to_json());
+        assert!(deserialize_messages(&frame).is_err());
+    }
+
+    #[test]
+    fn test_ping_format() {
+        let ping: serde_json::Value =
+            serde_json::from_str(&PingMessage::now("unknown").to_json()).unwrap();
+        assert_eq!(ping["type"], "ping");
+        assert_eq!(ping["dc"], "unknown");
+        assert!(ping["time"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_batching() {
+        let mut batcher = OutgoingBatcher::new(false);
+        batcher.push(&load("co_zA"));
+        batcher.push(&load("co_zB"));
+        assert_eq!(batcher.flush().len(), 2);
+
+        batcher.set_batching(true);
+        batcher.push(&load("co_zA"));
+        batcher.push(&load("co_zB"));
+        let frames = batcher.flush();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(deserialize_messages(&frames[0]).unwrap().len(), 2);
+        assert!(batcher.flush().is_empty());
+    }
+
+    #[test]
+    fn test_batches_are_chunked() {
+        let message = load("co_zA");
+        let len = message.to_json().len();
+        let count = 2 * MAX_OUTGOING_MESSAGES_CHUNK_BYTES / len + 1;
+
+        let mut batcher = OutgoingBatcher::new(true);
+        for _ in 0..count {
+            batcher.push(&message);
+        }
+        let frames = batcher.flush();
+        assert_eq!(frames.len(), 3);
+        for frame in &frames[..2] {
+            assert!(frame.len() < MAX_OUTGOING_MESSAGES_CHUNK_BYTES);
+        }
+        let total: usize = frames
+            .iter()
+            .map(|frame| deserialize_messages(frame).unwrap().len())
+            .sum();
+        assert_eq!(total, count);
+    }
+}
@@ -0,0 +1,642 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use cojson_core::core::SessionID;
+use cojson_storage_log::LogStorage;
+use cojson_storage_sqlite::SqliteStorage;
+use cojson_sync::{
+    LocalNode, NodeError, Peer, PeerID, PeerRole, QueueMetrics, SessionSigner, StoragePeer,
+};
+
+use crate::connection::{
+    is_health_check, respond_health, run_connection, websocket_peer, ConnectionOptions,
+};
+use crate::ServerError;
+
+/// How long a new connection may take to send its request.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the node checks for work when nothing wakes it up.
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the server keeps its CoValues.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerStorage {
+    /// Nothing survives a restart.
+    InMemory,
+    /// A SQLite database file, with the same schema as `cojson-storage-sqlite` on the TS side.
+    Sqlite(PathBuf),
+    /// A directory of log segments.
+    Log(PathBuf),
+}
+
+/// Something that went wrong while serving. The server keeps running either way.
+#[derive(Debug)]
+pub enum ServerEvent {
+    /// The node rejected a message from a client.
+    MessageRejected { peer_id: String, error: NodeError },
+    /// Storing or loading CoValues failed.
+    Storage(NodeError),
+    /// A connection ended with an error.
+    ConnectionFailed { peer_id: String, error: ServerError },
+}
+
+impl fmt::Display for ServerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerEvent::MessageRejected { peer_id, error } => {
+                write!(f, "Error handling message from {peer_id}: {error}")
+            }
+            ServerEvent::Storage(error) => write!(f, "Storage error: {error}"),
+            ServerEvent::ConnectionFailed { peer_id, error } => {
+                write!(f, "Error on connection {peer_id}: {error}")
+            }
+        }
+    }
+}
+
+/// Receives the server's events, on the node and connection threads.
+#[derive(Clone)]
+pub struct EventHandler(Arc<dyn Fn(ServerEvent) + Send + Sync>);
+
+impl EventHandler {
+    pub fn new(handler: impl Fn(ServerEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+}
+
+impl fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventHandler")
+    }
+}
+
+/// Decides how the sessions clients send are checked, as in `LocalNode::with_signer_resolver`.
+#[derive(Clone)]
+pub struct SignerResolver(Arc<ResolveSigner>);
+
+type ResolveSigner = dyn Fn(&SessionID) -> Option<SessionSigner> + Send + Sync;
+
+impl SignerResolver {
+    pub fn new(
+        resolver: impl Fn(&SessionID) -> Option<SessionSigner> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(resolver))
+    }
+}
+
+impl fmt::Debug for SignerResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SignerResolver")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub storage: ServerStorage,
+    pub connection: ConnectionOptions,
+    /// Gets every error while serving. Without one, they're dropped.
+    pub on_event: Option<EventHandler>,
+    /// Finds the signers of account sessions. Without one, only agent sessions, which name
+    /// their signer, are accepted and account sessions are refused.
+    pub signer_resolver: Option<SignerResolver>,
+}
+
+impl ServerOptions {
+    fn emit(&self, event: ServerEvent) {
+        if let Some(EventHandler(handler)) = &self.on_event {
+            handler(event);
+        }
+    }
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            storage: ServerStorage::InMemory,
+            connection: ConnectionOptions::default(),
+            on_event: None,
+            signer_resolver: None,
+        }
+    }
+}
+
+pub(crate) enum NodeEvent {
+    Connected(Box<Peer>),
+    /// New messages arrived from a peer.
+    Wake,
+    Shutdown,
+}
+
+type ErrorSource = Box<dyn FnMut() -> Vec<NodeError> + Send>;
+
+type SharedQueueMetrics = Arc<Mutex<BTreeMap<PeerID, QueueMetrics>>>;
+
+/// A sync server, like the one `jazz-run sync` starts, that clients connect to with
+/// `cojson-transport-ws`.
+///
+/// One thread owns the `LocalNode` and every connection gets a thread that moves messages
+/// between its WebSocket and the node.
+pub struct SyncServer {
+    local_addr: SocketAddr,
+    events: Sender<NodeEvent>,
+    shutting_down: Arc<AtomicBool>,
+    queue_metrics: SharedQueueMetrics,
+    accept_thread: Option<JoinHandle<()>>,
+    node_thread: Option<JoinHandle<()>>,
+}
+
+impl SyncServer {
+    /// Open the storage, bind to `addr` and start serving.
+    pub fn start(addr: impl ToSocketAddrs, options: ServerOptions) -> Result<Self, ServerError> {
+        let mut node = LocalNode::with_random_agent();
+        if let Some(SignerResolver(resolver)) = options.signer_resolver.clone() {
+            node = node.with_signer_resolver(move |session_id| resolver(session_id));
+        }
+        let storage_errors: ErrorSource = match &options.storage {
+            ServerStorage::InMemory => Box::new(Vec::new),
+            ServerStorage::Sqlite(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                attach_storage(&mut node, SqliteStorage::open(path)?, &options)
+            }
+            ServerStorage::Log(dir) => attach_storage(&mut node, LogStorage::open(dir)?, &options),
+        };
+
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let (events, events_receiver) = channel();
+        let shutting_down = Arc::new(AtomicBool::new(false));
+        let queue_metrics = SharedQueueMetrics::default();
+
+        let node_thread = {
+            let options = options.clone();
+            let queue_metrics = queue_metrics.clone();
+            thread::spawn(move || {
+                run_node(
+                    node,
+                    events_receiver,
+                    storage_errors,
+                    &queue_metrics,
+                    &options,
+                )
+            })
+        };
+
+        let accept_thread = {
+            let events = events.clone();
+            let shutting_down = shutting_down.clone();
+            thread::spawn(move || accept_connections(listener, events, shutting_down, options))
+        };
+
+        Ok(Self {
+            local_addr,
+            events,
+            shutting_down,
+            queue_metrics,
+            accept_thread: Some(accept_thread),
+            node_thread: Some(node_thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The URL clients connect to.
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.local_addr)
+    }
+
+    /// The outgoing queue of every connected peer, as of the node's last round of messages.
+    pub fn queue_metrics(&self) -> BTreeMap<PeerID, QueueMetrics> {
+        self.queue_metrics.lock().unwrap().clone()
+    }
+
+    /// Serve until the server is shut down from another thread or the process exits.
+    pub fn wait(mut self) {
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+    }
+
+    /// Stop accepting connections and disconnect every client.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let _ = self.events.send(NodeEvent::Shutdown);
+        // Wake up the accept loop, which checks the flag on every connection
+        let _ = TcpStream::connect(self.local_addr);
+
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+        if let Some(node_thread) = self.node_thread.take() {
+            let _ = node_thread.join();
+        }
+    }
+}
+
+impl Drop for SyncServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn attach_storage<S>(node: &mut LocalNode, storage: S, options: &ServerOptions) -> ErrorSource
+where
+    S: cojson_core::core::SessionLogStorage + Send + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut storage = StoragePeer::new(storage);
+    if let Some(SignerResolver(resolver)) = options.signer_resolver.clone() {
+        storage = storage.with_signer_resolver(move |session_id| resolver(session_id));
+    }
+    node.add_peer(storage.peer("storage"));
+    Box::new(move || storage.take_errors())
+}
+
+fn run_node(
+    mut node: LocalNode,
+    events: Receiver<NodeEvent>,
+    mut storage_errors: ErrorSource,
+    queue_metrics: &SharedQueueMetrics,
+    options: &ServerOptions,
+) {
+    loop {
+        let mut event = match events.recv_timeout(IDLE_INTERVAL) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        while let Some(current) = event {
+            match current {
+                NodeEvent::Connected(peer) => node.add_peer(*peer),
+                NodeEvent::Wake => {}
+                NodeEvent::Shutdown => return,
+            }
+            event = events.try_recv().ok();
+        }
+
+        while node.process_incoming() > 0 {}
+        *queue_metrics.lock().unwrap() = node
+            .peer_ids()
+            .filter_map(|peer_id| Some((peer_id.clone(), node.queue_metrics(peer_id)?.clone())))
+            .collect();
+
+        for (peer_id, error) in node.take_errors() {
+            options.emit(ServerEvent::MessageRejected { peer_id, error });
+        }
+        for error in storage_errors() {
+            options.emit(ServerEvent::Storage(error));
+        }
+    }
+}
+
+fn accept_connections(
+    listener: TcpListener,
+    events: Sender<NodeEvent>,
+    shutting_down: Arc<AtomicBool>,
+    options: ServerOptions,
+) {
+    let mut next_connection = 0u64;
+    for stream in listener.incoming() {
+        if shutting_down.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let Ok(remote_addr) = stream.peer_addr() else {
+            continue;
+        };
+
+        let peer_id = format!("{remote_addr}@{next_connection}");
+        next_connection += 1;
+        let events = events.clone();
+        let options = options.clone();
+        thread::spawn(move || {
+            if let Err(error) =
+                serve_connection(stream, peer_id.clone(), events, &options.connection)
+            {
+                options.emit(ServerEvent::ConnectionFailed { peer_id, error });
+            }
+        });
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    peer_id: String,
+    events: Sender<NodeEvent>,
+    options: &ConnectionOptions,
+) -> Result<(), ServerError> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    if is_health_check(&stream)? {
+        respond_health(stream)?;
+        return Ok(());
+    }
+
+    let websocket = tungstenite::accept(stream).map_err(|err| match err {
+        tungstenite::HandshakeError::Failure(err) => err.into(),
+        tungstenite::HandshakeError::Interrupted(_) => {
+            ServerError::Io(std::io::ErrorKind::TimedOut.into())
+        }
+    })?;
+
+    let (peer, to_node, from_node) = websocket_peer(peer_id, PeerRole::Client, options);
+    if events.send(NodeEvent::Connected(Box::new(peer))).is_err() {
+        return Ok(());
+    }
+    run_connection(websocket, to_node, from_node, events, options)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::deserialize_messages;
+    use cojson_core::core::CoID;
+    use cojson_sync::test_utils::test_signer;
+    use cojson_sync::{
+        CoValueKnownState, CoValueState, NewContentMessage, SyncMessage, PRIORITY_LOW,
+    };
+    use serde_json::{json, Value as JsonValue};
+    use std::collections::BTreeMap;
+    use std::io::{Read, Write};
+    use std::time::Instant;
+    use tungstenite::{Message, WebSocket};
+
+    fn header(uniqueness: &str) -> JsonValue {
+        json!({"type": "comap", "ruleset": {"type": "unsafeAllowAll"}, "meta": null, "uniqueness": uniqueness})
+    }
+
+    fn raw_client(server: &SyncServer) -> WebSocket<TcpStream> {
+        let stream = TcpStream::connect(server.local_addr()).unwrap();
+        let (websocket, _) = tungstenite::client(server.url(), stream).unwrap();
+        websocket
+    }
+
+    /// A Rust node connected to the server over a WebSocket, like a TS client would be.
+    fn client_node(server: &SyncServer) -> LocalNode {
+        let websocket = raw_client(server);
+        let options = ConnectionOptions {
+            ping_interval: Duration::from_secs(3600),
+            ..ConnectionOptions::default()
+        };
+        let (peer, to_node, from_node) =
+            websocket_peer("server".to_string(), PeerRole::Server, &options);
+        let (events, _) = channel();
+        thread::spawn(move || run_connection(websocket, to_node, from_node, events, &options));
+
+        let mut node = LocalNode::with_random_agent();
+        node.add_peer(peer);
+        node
+    }
+
+    fn wait_until(node: &mut LocalNode, mut done: impl FnMut(&LocalNode) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done(node) {
+            assert!(Instant::now() < deadline, "timed out");
+            node.process_incoming();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_health_check() {
+        let server = SyncServer::start("127.0.0.1:0", ServerOptions::default()).unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\nok"));
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_sync_between_clients() {
+        let server = SyncServer::start("127.0.0.1:0", ServerOptions::default()).unwrap();
+        let mut alice = client_node(&server);
+        let mut bob = client_node(&server);
+
+        let id = alice.create_co_value(header("a"));
+        alice.make_transaction(&id, "[1]", 1).unwrap();
+        alice.make_transaction(&id, "[2]", 2).unwrap();
+        let expected = alice.known_state(&id);
+
+        bob.load(&id);
+        wait_until(&mut bob, |bob| bob.known_state(&id) == expected);
+
+        // Bob is subscribed now, so he gets alice's next change pushed
+        alice.make_transaction(&id, "[3]", 3).unwrap();
+        let expected = alice.known_state(&id);
+        wait_until(&mut bob, |bob| bob.known_state(&id) == expected);
+        assert!(bob.take_errors().is_empty());
+
+        // Everything for both clients went through their queues on the server
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let sent_to_both = |metrics: &BTreeMap<PeerID, QueueMetrics>| {
+            metrics.len() == 2
+                && metrics.values().all(|metrics| {
+                    metrics.depth() == 0 && metrics.levels.iter().any(|level| level.pulled > 0)
+                })
+        };
+        while !sent_to_both(&server.queue_metrics()) {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_batching_and_pings() {
+        let options = ServerOptions {
+            connection: ConnectionOptions {
+                ping_interval: Duration::from_millis(50),
+                ..ConnectionOptions::default()
+            },
+            ..ServerOptions::default()
+        };
+        let server = SyncServer::start("127.0.0.1:0", options).unwrap();
+        let mut websocket = raw_client(&server);
+
+        // Two loads in one frame, so the server switches to batching as well
+        let load = |uniqueness: &str| {
+            SyncMessage::Load(CoValueKnownState::empty(CoID::for_header(&header(
+                uniqueness,
+            ))))
+            .to_json()
+        };
+        let frame = format!("{}\n{}", load("a"), load("b"));
+        websocket.send(Message::Text(frame)).unwrap();
+
+        let mut known = 0;
+        let mut pings = 0;
+        while known < 2 || pings < 1 {
+            let Message::Text(frame) = websocket.read().unwrap() else {
+                continue;
+            };
+            let frame_value: Result<JsonValue, _> = serde_json::from_str(&frame);
+            if frame_value.is_ok_and(|value| value["type"] == "ping") {
+                pings += 1;
+                continue;
+            }
+            let messages = deserialize_messages(&frame).unwrap();
+            assert_eq!(messages.len(), 2, "expected both answers in one frame");
+            for message in messages {
+                let SyncMessage::Known(msg) = message else {
+                    panic!("expected known");
+                };
+                assert!(!msg.known_state.header);
+                known += 1;
+            }
+        }
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_reports_rejected_messages() {
+        let (event_sender, event_receiver) = channel();
+        let event_sender = std::sync::Mutex::new(event_sender);
+        let options = ServerOptions {
+            on_event: Some(EventHandler::new(move |event| {
+                let _ = event_sender.lock().unwrap().send(event);
+            })),
+            ..ServerOptions::default()
+        };
+        let server = SyncServer::start("127.0.0.1:0", options).unwrap();
+        let mut websocket = raw_client(&server);
+
+        // A header that doesn't hash to the ID it's sent for
+        let content = SyncMessage::Content(NewContentMessage {
+            id: CoID::for_header(&header("a")),
+            header: Some(header("b")),
+            priority: PRIORITY_LOW,
+            new: BTreeMap::new(),
+            expect_content_until: None,
+        });
+        websocket.send(Message::Text(content.to_json())).unwrap();
+
+        let event = event_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert!(matches!(
+            event,
+            ServerEvent::MessageRejected {
+                error: NodeError::HeaderMismatch { .. },
+                ..
+            }
+        ));
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_account_sessions_need_a_resolver() {
+        let (_, account_signer, account_secret) = test_signer(3);
+        let account_session = SessionID("co_zAccount_session_zA".to_string());
+        let mut co_value = CoValueState::from_header(header("a"));
+        co_value
+            .make_transaction(&account_session, &account_secret, "[1]", 1)
+            .unwrap();
+        let content = co_value.new_content_since(None).unwrap();
+
+        let (event_sender, event_receiver) = channel();
+        let event_sender = std::sync::Mutex::new(event_sender);
+        let options = ServerOptions {
+            on_event: Some(EventHandler::new(move |event| {
+                let _ = event_sender.lock().unwrap().send(event);
+            })),
+            ..ServerOptions::default()
+        };
+        let server = SyncServer::start("127.0.0.1:0", options.clone()).unwrap();
+        let mut websocket = raw_client(&server);
+        websocket
+            .send(Message::Text(
+                SyncMessage::Content(content.clone()).to_json(),
+            ))
+            .unwrap();
+        let event = event_receiver
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert!(matches!(
+            event,
+            ServerEvent::MessageRejected {
+                error: NodeError::UnknownSigner { .. },
+                ..
+            }
+        ));
+        server.shutdown();
+
+        let options = ServerOptions {
+            signer_resolver: Some(SignerResolver::new(move |_| {
+                Some(SessionSigner::Verify(account_signer.clone()))
+            })),
+            ..options
+        };
+        let server = SyncServer::start("127.0.0.1:0", options).unwrap();
+        let mut websocket = raw_client(&server);
+        websocket
+            .send(Message::Text(
+                SyncMessage::Content(content.clone()).to_json(),
+            ))
+            .unwrap();
+        let mut bob = client_node(&server).with_signer_resolver(|_| Some(SessionSigner::Trusted));
+        bob.load(&content.id);
+        wait_until(&mut bob, |bob| {
+            bob.known_state(&content.id)
+                .sessions
+                .contains_key(&account_session)
+        });
+        assert!(event_receiver.try_recv().is_err());
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_persists_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        for storage in [
+            ServerStorage::Sqlite(dir.path().join("sqlite").join("storage.db")),
+            ServerStorage::Log(dir.path().join("log")),
+        ] {
+            let options = ServerOptions {
+                storage,
+                ..ServerOptions::default()
+            };
+
+            let server = SyncServer::start("127.0.0.1:0", options.clone()).unwrap();
+            let mut alice = client_node(&server);
+            let id = alice.create_co_value(header("a"));
+            alice.make_transaction(&id, "[1]", 1).unwrap();
+            let expected = alice.known_state(&id);
+
+            // Wait until the server acknowledged everything
+            let mut bob = client_node(&server);
+            bob.load(&id);
+            wait_until(&mut bob, |bob| bob.known_state(&id) == expected);
+            server.shutdown();
+
+            let server = SyncServer::start("127.0.0.1:0", options).unwrap();
+            let mut carol = client_node(&server);
+            carol.load(&id);
+            wait_until(&mut carol, |carol| carol.known_state(&id) == expected);
+            server.shutdown();
+        }
+    }
+}
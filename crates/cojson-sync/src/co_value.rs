@@ -0,0 +1,628 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use cojson_core::core::{
+    CoID, CoValueSubscriptions, CoValueUpdate, KeyID, KeySecret, LoadedCoValue, SessionID,
+    SessionLogInternal, SessionLogStorage, SessionNewContent, SignerID, SignerSecret,
+    SubscriptionID, TransactionMode,
+};
+use cojson_core::crypto::get_signer_id;
+use serde_json::{value::RawValue, Value as JsonValue};
+
+use crate::{priority_for_header, CoValueKnownState, NewContentMessage, NodeError};
+
+/// How the transactions of an incoming session are checked, as decided by a signer resolver.
+///
+/// Resolvers return None for sessions they can't vouch for, which are refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionSigner {
+    /// Verify every signature against this signer.
+    Verify(SignerID),
+    /// Apply the transactions without verifying them, for sessions checked some other way.
+    Trusted,
+}
+
+impl SessionSigner {
+    /// The signer in an agent session ID. Account sessions don't carry one, so they get None.
+    pub fn for_agent_session(session_id: &SessionID) -> Option<Self> {
+        session_id.agent_signer_id().map(SessionSigner::Verify)
+    }
+
+    /// The signer session logs are created with. Trusted sessions have none.
+    pub fn signer_id(&self) -> Option<&SignerID> {
+        match self {
+            SessionSigner::Verify(signer_id) => Some(signer_id),
+            SessionSigner::Trusted => None,
+        }
+    }
+}
+
+/// Resolves the secrets of read keys, so listeners get diffs of private transactions too.
+pub type KeyResolver = Arc<dyn Fn(&KeyID) -> Option<KeySecret> + Send + Sync>;
+
+/// A CoValue held by a node: its header (once known) and a session log per session.
+pub struct CoValueState {
+    id: CoID,
+    header: Option<JsonValue>,
+    sessions: BTreeMap<SessionID, SessionLogInternal>,
+    subscriptions: CoValueSubscriptions,
+    key_resolver: Option<KeyResolver>,
+}
+
+impl CoValueState {
+    /// A CoValue we only know the ID of so far.
+    pub fn unavailable(id: CoID) -> Self {
+        Self {
+            id,
+            header: None,
+            sessions: BTreeMap::new(),
+            subscriptions: CoValueSubscriptions::new(),
+            key_resolver: None,
+        }
+    }
+
+    /// A CoValue as loaded from storage.
+    pub fn from_loaded(loaded: LoadedCoValue) -> Self {
+        Self {
+            id: loaded.id,
+            header: Some(loaded.header),
+            sessions: loaded.sessions.into_iter().collect(),
+            subscriptions: CoValueSubscriptions::new(),
+            key_resolver: None,
+        }
+    }
+
+    /// A new CoValue, with its ID derived from the header.
+    pub fn from_header(header: JsonValue) -> Self {
+        Self {
+            id: CoID::for_header(&header),
+            header: Some(header),
+            sessions: BTreeMap::new(),
+            subscriptions: CoValueSubscriptions::new(),
+            key_resolver: None,
+        }
+    }
+
+    pub fn id(&self) -> &CoID {
+        &self.id
+    }
+
+    pub fn header(&self) -> Option<&JsonValue> {
+        self.header.as_ref()
+    }
+
+    /// Whether the header is known, which is what makes a CoValue usable.
+    pub fn is_available(&self) -> bool {
+        self.header.is_some()
+    }
+
+    pub fn sessions(&self) -> &BTreeMap<SessionID, SessionLogInternal> {
+        &self.sessions
+    }
+
+    /// Call `listener` after every change that adds transactions, with the transactions and
+    /// how the content changed. Content from one message is one change.
+    pub fn subscribe(
+        &mut self,
+        listener: impl FnMut(&CoValueUpdate) + Send + 'static,
+    ) -> SubscriptionID {
+        self.subscriptions.subscribe(listener)
+    }
+
+    /// Returns whether the listener was subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionID) -> bool {
+        self.subscriptions.unsubscribe(id)
+    }
+
+    /// Hold back notifications until the matching `end_batch`, to tell listeners about several
+    /// changes at once.
+    pub fn begin_batch(&mut self) {
+        self.subscriptions.begin_batch(&self.sessions);
+    }
+
+    /// Notify the listeners once the outermost batch ends.
+    ///
+    /// Private transactions whose key isn't resolved by the resolver set with
+    /// `set_key_resolver` make the diff `ContentDiff::Opaque`.
+    pub fn end_batch(&mut self) -> Option<CoValueUpdate> {
+        let key_resolver = &self.key_resolver;
+        self.subscriptions
+            .end_batch(&self.id, self.header.as_ref(), &self.sessions, |key_id| {
+                key_resolver.as_ref().and_then(|resolve| resolve(key_id))
+            })
+    }
+
+    /// Resolve read keys with `resolver` when diffing for listeners.
+    pub fn set_key_resolver(&mut self, resolver: Option<KeyResolver>) {
+        self.key_resolver = resolver;
+    }
+
+    pub fn known_state(&self) -> CoValueKnownState {
+        CoValueKnownState {
+            id: self.id.clone(),
+            header: self.header.is_some(),
+            sessions: self
+                .sessions
+                .iter()
+                .map(|(session_id, log)| (session_id.clone(), log.transactions_json().len() as u32))
+                .collect(),
+        }
+    }
+
+    /// Everything a node with the given known state is missing, or None if it has it all.
+    pub fn new_content_since(
+        &self,
+        known: Option<&CoValueKnownState>,
+    ) -> Option<NewContentMessage> {
+        let header = self.header.as_ref()?;
+        let send_header = !known.is_some_and(|known| known.header);
+
+        let mut new = BTreeMap::new();
+        for (session_id, log) in &self.sessions {
+            let after = known
+                .and_then(|known| known.sessions.get(session_id))
+                .copied()
+                .unwrap_or(0);
+            let transactions = log.transactions_json();
+            if after as usize >= transactions.len() {
+                continue;
+            }
+            let Some(last_signature) = log.last_signature() else {
+                continue;
+            };
+
+            new.insert(
+                session_id.clone(),
+                SessionNewContent {
+                    after,
+                    new_transactions: transactions[after as usize..]
+                        .iter()
+                        .map(|tx| {
+                            RawValue::from_string(tx.clone())
+                                .expect("session logs only hold valid JSON")
+                        })
+                        .collect(),
+                    last_signature: last_signature.clone(),
+                },
+            );
+        }
+
+        if !send_header && new.is_empty() {
+            return None;
+        }
+
+        Some(NewContentMessage {
+            id: self.id.clone(),
+            header: send_header.then(|| header.clone()),
+            priority: priority_for_header(header),
+            new,
+            expect_content_until: None,
+        })
+    }
+
+    /// Apply incoming content, verifying every session against its signer.
+    /// - `signer_for`: Resolves how a session is checked. Sessions it returns None for are
+    ///   refused with `UnknownSigner`.
+    ///
+    /// Transactions we already have are skipped. Sessions are applied independently, so an
+    /// error in one session leaves the others applied.
+    ///
+    /// Returns whether anything was added.
+    pub fn try_add_content<F>(
+        &mut self,
+        content: &NewContentMessage,
+        signer_for: F,
+    ) -> Result<bool, NodeError>
+    where
+        F: FnMut(&SessionID) -> Option<SessionSigner>,
+    {
+        self.add_content_with(content, signer_for, |_, _, _, log, new_content| {
+            let skip_verify = log.signer_id().is_none();
+            log.try_add(
+                new_content.new_transactions,
+                &new_content.last_signature,
+                skip_verify,
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Apply incoming content like `try_add_content`, persisting it in `storage` on the way.
+    ///
+    /// The session logs must hold exactly what `storage` has, as after loading them with
+    /// `from_loaded`.
+    pub fn store_content<S, F>(
+        &mut self,
+        storage: &mut S,
+        content: &NewContentMessage,
+        signer_for: F,
+    ) -> Result<bool, NodeError>
+    where
+        S: SessionLogStorage,
+        S::Error: std::error::Error + Send + Sync + 'static,
+        F: FnMut(&SessionID) -> Option<SessionSigner>,
+    {
+        self.add_content_with(
+            content,
+            signer_for,
+            |id, header, session_id, log, new_content| {
+                storage
+                    .append_transactions(id, Some(header), session_id, log, new_content)
+                    .map_err(|err| NodeError::Storage(Box::new(err)))?;
+                Ok(())
+            },
+        )
+    }
+
+    fn add_content_with<F, A>(
+        &mut self,
+        content: &NewContentMessage,
+        signer_for: F,
+        add: A,
+    ) -> Result<bool, NodeError>
+    where
+        F: FnMut(&SessionID) -> Option<SessionSigner>,
+        A: FnMut(
+            &CoID,
+            &JsonValue,
+            &SessionID,
+            &mut SessionLogInternal,
+            SessionNewContent,
+        ) -> Result<(), NodeError>,
+    {
+        self.begin_batch();
+        let result = self.add_sessions_content(content, signer_for, add);
+        self.end_batch();
+        result
+    }
+
+    fn add_sessions_content<F, A>(
+        &mut self,
+        content: &NewContentMessage,
+        mut signer_for: F,
+        mut add: A,
+    ) -> Result<bool, NodeError>
+    where
+        F: FnMut(&SessionID) -> Option<SessionSigner>,
+        A: FnMut(
+            &CoID,
+            &JsonValue,
+            &SessionID,
+            &mut SessionLogInternal,
+            SessionNewContent,
+        ) -> Result<(), NodeError>,
+    {
+        let mut added = false;
+
+        if self.header.is_none() {
+            let Some(header) = &content.header else {
+                return Err(NodeError::UnknownCoValue(self.id.clone()));
+            };
+            let actual = CoID::for_header(header);
+            if actual != self.id {
+                return Err(NodeError::HeaderMismatch {
+                    id: self.id.clone(),
+                    actual,
+                });
+            }
+            self.header = Some(header.clone());
+            added = true;
+        }
+        let header = self.header.as_ref().unwrap();
+
+        let mut first_error = None;
+        for (session_id, session_content) in &content.new {
+            let have = self
+                .sessions
+                .get(session_id)
+                .map_or(0, |log| log.transactions_json().len() as u32);
+
+            if session_content.after > have {
+                first_error.get_or_insert(NodeError::MissingTransactions {
+                    id: self.id.clone(),
+                    session_id: session_id.0.clone(),
+                    have,
+                    after: session_content.after,
+                });
+                continue;
+            }
+
+            // Before skipping what we have, make sure it's what the peer has too
+            if let Some(log) = self.sessions.get(session_id) {
+                if let Err(err) = log.check_consistent(
+                    session_content.after,
+                    &session_content.new_transactions,
+                    &session_content.last_signature,
+                ) {
+                    first_error.get_or_insert(err.into());
+                    continue;
+                }
+            }
+
+            let already_have = (have - session_content.after) as usize;
+            if already_have >= session_content.new_transactions.len() {
+                continue;
+            }
+            let new_content = SessionNewContent {
+                after: have,
+                new_transactions: session_content.new_transactions[already_have..].to_vec(),
+                last_signature: session_content.last_signature.clone(),
+            };
+
+            // Logs without a signer skip verification, so only trusted sessions may have one
+            let unknown_signer = || NodeError::UnknownSigner {
+                id: self.id.clone(),
+                session_id: session_id.0.clone(),
+            };
+            let signer = match signer_for(session_id) {
+                Some(signer) => signer,
+                None => {
+                    first_error.get_or_insert(unknown_signer());
+                    continue;
+                }
+            };
+            let held_without_signer = self
+                .sessions
+                .get(session_id)
+                .is_some_and(|log| log.signer_id().is_none());
+            if held_without_signer && signer != SessionSigner::Trusted {
+                first_error.get_or_insert(unknown_signer());
+                continue;
+            }
+
+            let result = match self.sessions.get_mut(session_id) {
+                Some(log) => add(&self.id, header, session_id, log, new_content),
+                None => {
+                    // Only keep a new session once its first transactions verified
+                    let mut log = SessionLogInternal::new(
+                        self.id.clone(),
+                        session_id.clone(),
+                        signer.signer_id().cloned(),
+                    );
+                    let result = add(&self.id, header, session_id, &mut log, new_content);
+                    if result.is_ok() {
+                        self.sessions.insert(session_id.clone(), log);
+                    }
+                    result
+                }
+            };
+
+            match result {
+                Ok(()) => added = true,
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(added),
+        }
+    }
+
+    /// Add a trusting transaction to one of our own sessions.
+    pub fn make_transaction(
+        &mut self,
+        session_id: &SessionID,
+        signer_secret: &SignerSecret,
+        changes_json: &str,
+        made_at: u64,
+    ) -> Result<(), NodeError> {
+        if self.header.is_none() {
+            return Err(NodeError::UnknownCoValue(self.id.clone()));
+        }
+
+        self.begin_batch();
+        let log = self.sessions.entry(session_id.clone()).or_insert_with(|| {
+            // Our own sessions verify against our signer when peers send them back
+            let signer_id = get_signer_id(&signer_secret.0).ok().map(SignerID);
+            SessionLogInternal::new(self.id.clone(), session_id.clone(), signer_id)
+        });
+        let result = log.add_new_transaction(
+            changes_json,
+            TransactionMode::Trusting,
+            signer_secret,
+            made_at,
+            None,
+        );
+        self.end_batch();
+        result?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{test_agent as agent, test_header};
+    use cojson_core::core::{new_random_key_secret, CoJsonCoreError, ContentDiff};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn header() -> JsonValue {
+        test_header("test")
+    }
+
+    #[test]
+    fn test_new_content_since() {
+        let (session_id, signer_secret) = agent(1);
+        let mut co_value = CoValueState::from_header(header());
+        assert_eq!(co_value.id(), &CoID::for_header(&header()));
+
+        co_value
+            .make_transaction(
+                &session_id,
+                &signer_secret,
+                r#"[{"op":"set","key":"a","value":1}]"#,
+                1,
+            )
+            .unwrap();
+        co_value
+            .make_transaction(
+                &session_id,
+                &signer_secret,
+                r#"[{"op":"set","key":"b","value":2}]"#,
+                2,
+            )
+            .unwrap();
+
+        let everything = co_value.new_content_since(None).unwrap();
+        assert_eq!(everything.header, Some(header()));
+        assert_eq!(everything.new[&session_id].after, 0);
+        assert_eq!(everything.new[&session_id].new_transactions.len(), 2);
+
+        let mut known = co_value.known_state();
+        assert!(co_value.new_content_since(Some(&known)).is_none());
+
+        known.sessions.insert(session_id.clone(), 1);
+        let diff = co_value.new_content_since(Some(&known)).unwrap();
+        assert!(diff.header.is_none());
+        assert_eq!(diff.new[&session_id].after, 1);
+        assert_eq!(diff.new[&session_id].new_transactions.len(), 1);
+
+        // Nothing to offer without a header
+        assert!(CoValueState::unavailable(co_value.id().clone())
+            .new_content_since(None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_try_add_content() {
+        let (session_id, signer_secret) = agent(1);
+        let mut source = CoValueState::from_header(header());
+        for i in 0..3 {
+            source
+                .make_transaction(&session_id, &signer_secret, "[]", i)
+                .unwrap();
+        }
+        let content = source.new_content_since(None).unwrap();
+
+        let mut target = CoValueState::unavailable(source.id().clone());
+        assert!(target
+            .try_add_content(&content, SessionSigner::for_agent_session)
+            .unwrap());
+        assert_eq!(target.known_state(), source.known_state());
+
+        // Applying the same content again is a no-op
+        assert!(!target
+            .try_add_content(&content, SessionSigner::for_agent_session)
+            .unwrap());
+
+        // Overlapping content only adds the new part
+        source
+            .make_transaction(&session_id, &signer_secret, "[]", 3)
+            .unwrap();
+        let mut overlapping = source.new_content_since(None).unwrap();
+        overlapping.header = None;
+        assert!(target
+            .try_add_content(&overlapping, SessionSigner::for_agent_session)
+            .unwrap());
+        assert_eq!(target.known_state(), source.known_state());
+        assert_eq!(
+            target.sessions()[&session_id].transactions_json(),
+            source.sessions()[&session_id].transactions_json()
+        );
+    }
+
+    #[test]
+    fn test_try_add_content_rejects_bad_content() {
+        let (session_id, signer_secret) = agent(1);
+        let mut source = CoValueState::from_header(header());
+        for i in 0..3 {
+            source
+                .make_transaction(&session_id, &signer_secret, "[]", i)
+                .unwrap();
+        }
+
+        // Missing header
+        let mut target = CoValueState::unavailable(source.id().clone());
+        let mut content = source.new_content_since(None).unwrap();
+        content.header = None;
+        assert!(matches!(
+            target.try_add_content(&content, SessionSigner::for_agent_session),
+            Err(NodeError::UnknownCoValue(_))
+        ));
+
+        // Header of another CoValue
+        let mut content = source.new_content_since(None).unwrap();
+        content.header = Some(json!({"type": "comap", "uniqueness": "other"}));
+        assert!(matches!(
+            target.try_add_content(&content, SessionSigner::for_agent_session),
+            Err(NodeError::HeaderMismatch { .. })
+        ));
+
+        // Gap before the new transactions
+        let mut known = source.known_state();
+        known.sessions.insert(session_id.clone(), 1);
+        let mut content = source.new_content_since(Some(&known)).unwrap();
+        content.header = Some(header());
+        assert!(matches!(
+            target.try_add_content(&content, SessionSigner::for_agent_session),
+            Err(NodeError::MissingTransactions {
+                have: 0,
+                after: 1,
+                ..
+            })
+        ));
+
+        // Signed by someone else
+        let (_, other_secret) = agent(2);
+        let mut forged = CoValueState::from_header(header());
+        forged
+            .make_transaction(&session_id, &other_secret, "[]", 0)
+            .unwrap();
+        let content = forged.new_content_since(None).unwrap();
+        let mut target = CoValueState::unavailable(source.id().clone());
+        assert!(matches!(
+            target.try_add_content(&content, SessionSigner::for_agent_session),
+            Err(NodeError::Core(CoJsonCoreError::SignatureVerification(_)))
+        ));
+        assert!(target.is_available());
+        assert!(target.known_state().sessions.is_empty());
+    }
+
+    #[test]
+    fn test_private_changes_need_a_key_resolver() {
+        let (session_id, signer_secret) = agent(1);
+        let (key_id, key_secret) = new_random_key_secret();
+        let mut source = CoValueState::from_header(header());
+        let mut log = SessionLogInternal::new(source.id().clone(), session_id.clone(), None);
+        log.add_new_transaction(
+            r#"[{"op":"set","key":"a","value":1}]"#,
+            TransactionMode::Private {
+                key_id: key_id.clone(),
+                key_secret: key_secret.clone(),
+            },
+            &signer_secret,
+            1,
+            None,
+        )
+        .unwrap();
+        source.sessions.insert(session_id, log);
+        let content = source.new_content_since(None).unwrap();
+
+        let diff_with = |resolver: Option<KeyResolver>| {
+            let mut target = CoValueState::from_header(header());
+            target.set_key_resolver(resolver);
+            let diffs = Arc::new(Mutex::new(Vec::new()));
+            let recorded = diffs.clone();
+            target.subscribe(move |update| recorded.lock().unwrap().push(update.diff.clone()));
+            target
+                .try_add_content(&content, SessionSigner::for_agent_session)
+                .unwrap();
+            let diffs = diffs.lock().unwrap();
+            assert_eq!(diffs.len(), 1);
+            diffs[0].clone()
+        };
+
+        assert_eq!(diff_with(None), ContentDiff::Opaque);
+        let resolver: KeyResolver = Arc::new(move |id| (id == &key_id).then(|| key_secret.clone()));
+        assert_eq!(
+            diff_with(Some(resolver)),
+            ContentDiff::Map {
+                changed_keys: vec!["a".to_string()]
+            }
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use cojson_core::core::{CoID, CoJsonCoreError};
+use thiserror::Error;
+
+use crate::{PeerID, SyncMessage};
+
+#[derive(Error, Debug)]
+pub enum NodeError {
+    #[error(transparent)]
+    Core(#[from] CoJsonCoreError),
+
+    #[error("Invalid sync message: {0}")]
+    InvalidMessage(#[source] serde_json::Error),
+
+    #[error("CoValue {} is not available", .0 .0)]
+    UnknownCoValue(CoID),
+
+    #[error("Header doesn't match CoValue {} (it hashes to {})", .id.0, .actual.0)]
+    HeaderMismatch { id: CoID, actual: CoID },
+
+    #[error("Missing transactions in session {session_id} of {} (have {have}, new content starts after {after})", .id.0)]
+    MissingTransactions {
+        id: CoID,
+        session_id: String,
+        have: u32,
+        after: u32,
+    },
+
+    #[error("No signer to verify session {session_id} of {} against", .id.0)]
+    UnknownSigner { id: CoID, session_id: String },
+
+    #[error("Storage error: {0}")]
+    Storage(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error(transparent)]
+    QueueFull(#[from] QueueFull),
+}
+
+/// A `PriorityMessageQueue` at its limit, handing back the message that didn't fit.
+#[derive(Error, Debug)]
+#[error("Message queue is full ({limit} messages)")]
+pub struct QueueFull {
+    pub limit: usize,
+    pub message: Box<SyncMessage>,
+}
+
+/// A failed `Simulation` run. Every variant carries the seed that reproduces it.
+#[derive(Error, Debug)]
+pub enum SimulationError {
+    #[error("{field} must be between 0 and 1, got {probability}")]
+    InvalidProbability {
+        field: &'static str,
+        probability: f64,
+    },
+
+    #[error("Seed {seed}: network still busy after {ticks} ticks")]
+    NotQuiet { seed: u64, ticks: u64 },
+
+    #[error(
+        "Seed {seed}: node {node} failed at tick {tick} handling a message from {peer}: {error}"
+    )]
+    Node {
+        seed: u64,
+        tick: u64,
+        node: usize,
+        peer: PeerID,
+        #[source]
+        error: NodeError,
+    },
+
+    #[error("Seed {seed}: CoValue {} differs between node {first} and node {other}", .id.0)]
+    Diverged {
+        seed: u64,
+        id: CoID,
+        first: usize,
+        other: usize,
+    },
+}
@@ -0,0 +1,29 @@
+//! Peer-to-peer sync of CoValues, speaking the same `load`/`known`/`content`/`done` protocol as
+//! `packages/cojson/src/sync.ts`.
+//!
+//! A `LocalNode` holds CoValues and talks to peers through abstract channels, so the same node
+//! runs over WebSockets or over the in-memory pairs from `connected_peers` in tests. Storage is
+//! attached as a peer too, through `StoragePeer`.
+//!
+//! `Simulation` runs nodes over a virtual network that delays, reorders, duplicates and drops
+//! messages, deterministically from a seed, to test that they still converge.
+
+pub mod co_value;
+pub mod error;
+pub mod messages;
+pub mod node;
+pub mod peer;
+pub mod queue;
+pub mod simulation;
+pub mod storage_peer;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+pub use co_value::*;
+pub use error::*;
+pub use messages::*;
+pub use node::*;
+pub use peer::*;
+pub use queue::*;
+pub use simulation::*;
+pub use storage_peer::*;
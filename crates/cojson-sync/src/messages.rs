@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use cojson_core::core::{CoID, SessionID, SessionNewContent};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::NodeError;
+
+/// Priority of a content message, as in `priority.ts`. Lower values are sent first.
+pub type CoValuePriority = u8;
+
+pub const PRIORITY_HIGH: CoValuePriority = 0;
+pub const PRIORITY_MEDIUM: CoValuePriority = 3;
+pub const PRIORITY_LOW: CoValuePriority = 6;
+
+/// Accounts and groups gate access to everything else, so they go first.
+/// Binary streams are bulky and least urgent.
+pub fn priority_for_header(header: &JsonValue) -> CoValuePriority {
+    let co_type = header.get("type").and_then(JsonValue::as_str);
+    let meta_type = header.pointer("/meta/type").and_then(JsonValue::as_str);
+    let ruleset_type = header.pointer("/ruleset/type").and_then(JsonValue::as_str);
+
+    if co_type == Some("costream") && meta_type == Some("binary") {
+        PRIORITY_LOW
+    } else if ruleset_type == Some("group") {
+        // Accounts are groups too
+        PRIORITY_HIGH
+    } else {
+        PRIORITY_MEDIUM
+    }
+}
+
+/// What a node has of a CoValue: whether it has the header and how many transactions per session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoValueKnownState {
+    pub id: CoID,
+    pub header: bool,
+    pub sessions: BTreeMap<SessionID, u32>,
+}
+
+impl CoValueKnownState {
+    /// The known state of a CoValue that isn't available at all.
+    pub fn empty(id: CoID) -> Self {
+        Self {
+            id,
+            header: false,
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    /// Merge in another known state, keeping the larger transaction count of each session.
+    pub fn combine_with(&mut self, other: &CoValueKnownState) {
+        self.header |= other.header;
+        for (session_id, &count) in &other.sessions {
+            let known = self.sessions.entry(session_id.clone()).or_insert(0);
+            *known = (*known).max(count);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownStateMessage {
+    #[serde(flatten)]
+    pub known_state: CoValueKnownState,
+    /// Set when the receiver's assumption about the sender's state was wrong and must be replaced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_correction: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_dependency_of: Option<CoID>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewContentMessage {
+    pub id: CoID,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<JsonValue>,
+    pub priority: CoValuePriority,
+    pub new: BTreeMap<SessionID, SessionNewContent>,
+    /// Transaction counts the sender will reach with follow-up content messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_content_until: Option<BTreeMap<SessionID, u32>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoneMessage {
+    pub id: CoID,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum SyncMessage {
+    Load(CoValueKnownState),
+    Known(KnownStateMessage),
+    Content(NewContentMessage),
+    Done(DoneMessage),
+}
+
+/// Just the tag of a message, to pick the payload type.
+#[derive(Deserialize)]
+struct MessageAction<'a> {
+    action: &'a str,
+}
+
+impl SyncMessage {
+    /// Parse a message from its JSON encoding.
+    ///
+    /// Transactions are kept as raw JSON, which serde can't buffer for an internally tagged enum,
+    /// so the tag is read first and the payload parsed directly into its type.
+    pub fn from_json(json: &str) -> Result<Self, NodeError> {
+        let action: MessageAction =
+            serde_json::from_str(json).map_err(NodeError::InvalidMessage)?;
+        let message = match action.action {
+            "load" => serde_json::from_str(json).map(SyncMessage::Load),
+            "known" => serde_json::from_str(json).map(SyncMessage::Known),
+            "content" => serde_json::from_str(json).map(SyncMessage::Content),
+            "done" => serde_json::from_str(json).map(SyncMessage::Done),
+            other => Err(serde::de::Error::unknown_variant(
+                other,
+                &["load", "known", "content", "done"],
+            )),
+        };
+        message.map_err(NodeError::InvalidMessage)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// The CoValue the message is about.
+    pub fn id(&self) -> &CoID {
+        match self {
+            SyncMessage::Load(msg) => &msg.id,
+            SyncMessage::Known(msg) => &msg.known_state.id,
+            SyncMessage::Content(msg) => &msg.id,
+            SyncMessage::Done(msg) => &msg.id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_ts_messages() {
+        let load = SyncMessage::from_json(
+            r#"{"action":"load","id":"co_zA","header":true,"sessions":{"co_zB_session_zC":2}}"#,
+        )
+        .unwrap();
+        let SyncMessage::Load(load) = load else {
+            panic!("expected load");
+        };
+        assert!(load.header);
+        assert_eq!(load.sessions[&SessionID("co_zB_session_zC".to_string())], 2);
+
+        let known = SyncMessage::from_json(
+            r#"{"action":"known","id":"co_zA","header":false,"sessions":{},"isCorrection":true}"#,
+        )
+        .unwrap();
+        let SyncMessage::Known(known) = known else {
+            panic!("expected known");
+        };
+        assert_eq!(known.is_correction, Some(true));
+        assert_eq!(
+            known.known_state,
+            CoValueKnownState::empty(CoID("co_zA".to_string()))
+        );
+
+        let content = SyncMessage::from_json(
+            r#"{"action":"content","id":"co_zA","priority":3,"new":{"co_zB_session_zC":{"after":1,"newTransactions":[{"privacy":"trusting","madeAt":1,"changes":"[]"}],"lastSignature":"signature_z1"}}}"#,
+        )
+        .unwrap();
+        let SyncMessage::Content(content) = content else {
+            panic!("expected content");
+        };
+        let session = &content.new[&SessionID("co_zB_session_zC".to_string())];
+        assert_eq!(session.after, 1);
+        assert_eq!(
+            session.new_transactions[0].get(),
+            r#"{"privacy":"trusting","madeAt":1,"changes":"[]"}"#
+        );
+
+        assert!(SyncMessage::from_json(r#"{"action":"shout","id":"co_zA"}"#).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_keeps_raw_transactions() {
+        let json = r#"{"action":"content","id":"co_zA","header":{"type":"comap"},"priority":0,"new":{"co_zB_session_zC":{"after":0,"newTransactions":[{"b":1,"a":2}],"lastSignature":"signature_z1"}}}"#;
+        let message = SyncMessage::from_json(json).unwrap();
+        assert_eq!(message.to_json(), json);
+
+        let done = SyncMessage::Done(DoneMessage {
+            id: CoID("co_zA".to_string()),
+        });
+        assert_eq!(done.to_json(), r#"{"action":"done","id":"co_zA"}"#);
+    }
+
+    #[test]
+    fn test_priority_for_header() {
+        let group = json!({"type": "comap", "ruleset": {"type": "group"}, "meta": null});
+        let comap = json!({"type": "comap", "ruleset": {"type": "ownedByGroup", "group": "co_zG"}, "meta": null});
+        let binary = json!({"type": "costream", "ruleset": {"type": "ownedByGroup", "group": "co_zG"}, "meta": {"type": "binary"}});
+
+        assert_eq!(priority_for_header(&group), PRIORITY_HIGH);
+        assert_eq!(priority_for_header(&comap), PRIORITY_MEDIUM);
+        assert_eq!(priority_for_header(&binary), PRIORITY_LOW);
+    }
+
+    #[test]
+    fn test_combine_known_states() {
+        let session_a = SessionID("co_zB_session_zA".to_string());
+        let session_b = SessionID("co_zB_session_zB".to_string());
+        let mut known = CoValueKnownState::empty(CoID("co_zA".to_string()));
+        known.sessions.insert(session_a.clone(), 3);
+
+        let mut other = CoValueKnownState::empty(CoID("co_zA".to_string()));
+        other.header = true;
+        other.sessions.insert(session_a.clone(), 1);
+        other.sessions.insert(session_b.clone(), 2);
+
+        known.combine_with(&other);
+        assert!(known.header);
+        assert_eq!(known.sessions[&session_a], 3);
+        assert_eq!(known.sessions[&session_b], 2);
+    }
+}
@@ -0,0 +1,931 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use cojson_core::core::{
+    CoID, CoJsonCoreError, CoValueUpdate, EquivocationLog, KeyID, KeySecret, SessionID,
+    SignerSecret, SubscriptionID,
+};
+use cojson_core::crypto::{
+    get_sealer_id, get_signer_id, new_ed25519_signing_key, new_x25519_private_key,
+};
+use serde_json::Value as JsonValue;
+
+use crate::{
+    CoValueKnownState, CoValueState, Disconnected, KeyResolver, KnownStateMessage,
+    NewContentMessage, NodeError, Peer, PeerID, PeerRole, QueueMetrics, SessionSigner, SyncMessage,
+};
+
+type SignerResolver = Box<dyn FnMut(&SessionID) -> Option<SessionSigner> + Send>;
+
+struct PeerState {
+    peer: Peer,
+    /// What we assume the peer has of every CoValue it syncs with us. Updated optimistically when
+    /// we send content and replaced when the peer corrects us.
+    known_states: HashMap<CoID, CoValueKnownState>,
+}
+
+/// CoValues we asked our servers for on behalf of other peers.
+struct PendingLoad {
+    /// Peers waiting for the CoValue.
+    waiting: BTreeSet<PeerID>,
+    /// Servers that haven't told us they don't have it either.
+    asked: BTreeSet<PeerID>,
+}
+
+/// A node holding CoValues and syncing them with its peers, like `LocalNode` and `SyncManager`
+/// on the TS side.
+///
+/// Nothing happens in the background: messages are handled in `process_incoming`, so the
+/// interleaving of several nodes is fully controlled by the caller.
+pub struct LocalNode {
+    session_id: SessionID,
+    signer_secret: SignerSecret,
+    signer_resolver: SignerResolver,
+    key_resolver: Option<KeyResolver>,
+    co_values: BTreeMap<CoID, CoValueState>,
+    peers: BTreeMap<PeerID, PeerState>,
+    pending_loads: HashMap<CoID, PendingLoad>,
+    errors: Vec<(PeerID, NodeError)>,
+    equivocations: EquivocationLog,
+}
+
+impl LocalNode {
+    /// Create a node that writes to `session_id`, signing with `signer_secret`.
+    ///
+    /// Incoming sessions are verified against the signer in their agent session ID. Account
+    /// sessions don't carry one, so they are refused unless a resolver set with
+    /// `with_signer_resolver` finds their signer or trusts them.
+    pub fn new(session_id: SessionID, signer_secret: SignerSecret) -> Self {
+        Self {
+            session_id,
+            signer_secret,
+            signer_resolver: Box::new(SessionSigner::for_agent_session),
+            key_resolver: None,
+            co_values: BTreeMap::new(),
+            peers: BTreeMap::new(),
+            pending_loads: HashMap::new(),
+            errors: Vec::new(),
+            equivocations: EquivocationLog::new(),
+        }
+    }
+
+    /// Create a node with a fresh agent and a new session for it, like `newRandomAgentSecret`
+    /// and `newRandomSessionID` on the TS side.
+    pub fn with_random_agent() -> Self {
+        let signer_secret = SignerSecret(format!(
+            "signerSecret_z{}",
+            bs58::encode(new_ed25519_signing_key()).into_string()
+        ));
+        let sealer_secret = format!(
+            "sealerSecret_z{}",
+            bs58::encode(new_x25519_private_key()).into_string()
+        );
+        let agent_id = format!(
+            "{}/{}",
+            get_sealer_id(&sealer_secret).expect("fresh sealer secrets are valid"),
+            get_signer_id(&signer_secret.0).expect("fresh signer secrets are valid"),
+        );
+        let session_id = SessionID(format!(
+            "{agent_id}_session_z{}",
+            bs58::encode(rand::random::<[u8; 8]>()).into_string()
+        ));
+        Self::new(session_id, signer_secret)
+    }
+
+    /// Decide how incoming sessions are checked with `resolver`. Sessions it returns None for
+    /// are refused, and only the ones it returns `SessionSigner::Trusted` for are applied
+    /// without verification.
+    pub fn with_signer_resolver(
+        mut self,
+        resolver: impl FnMut(&SessionID) -> Option<SessionSigner> + Send + 'static,
+    ) -> Self {
+        self.signer_resolver = Box::new(resolver);
+        self
+    }
+
+    /// Resolve read keys with `resolver`, so subscribers get diffs of private transactions.
+    /// Without one, updates with private transactions have a `ContentDiff::Opaque` diff.
+    pub fn with_key_resolver(
+        mut self,
+        resolver: impl Fn(&KeyID) -> Option<KeySecret> + Send + Sync + 'static,
+    ) -> Self {
+        self.key_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    pub fn session_id(&self) -> &SessionID {
+        &self.session_id
+    }
+
+    /// Connect a peer. We ask server peers for everything we have, so both sides catch up.
+    pub fn add_peer(&mut self, peer: Peer) {
+        let peer_id = peer.id.clone();
+        let role = peer.role;
+        if let Some(mut old) = self.peers.insert(
+            peer_id.clone(),
+            PeerState {
+                peer,
+                known_states: HashMap::new(),
+            },
+        ) {
+            old.peer.outgoing.close();
+        }
+
+        if role == PeerRole::Server {
+            let loads: Vec<CoValueKnownState> = self
+                .co_values
+                .values()
+                .map(CoValueState::known_state)
+                .collect();
+            for known_state in loads {
+                self.send(&peer_id, SyncMessage::Load(known_state));
+            }
+        }
+    }
+
+    /// Disconnect a peer, closing our side of the channel.
+    pub fn remove_peer(&mut self, peer_id: &str) {
+        if let Some(mut state) = self.peers.remove(peer_id) {
+            state.peer.outgoing.close();
+        }
+    }
+
+    pub fn peer_ids(&self) -> impl Iterator<Item = &PeerID> {
+        self.peers.keys()
+    }
+
+    /// The outgoing queue of a connected peer: how many messages wait for its channel, by
+    /// priority.
+    pub fn queue_metrics(&self, peer_id: &str) -> Option<&QueueMetrics> {
+        self.peers
+            .get(peer_id)
+            .map(|state| state.peer.queue_metrics())
+    }
+
+    /// Create a CoValue from its header and push it to our servers.
+    pub fn create_co_value(&mut self, header: JsonValue) -> CoID {
+        let co_value = CoValueState::from_header(header);
+        let id = co_value.id().clone();
+        self.co_values.entry(id.clone()).or_insert(co_value);
+        self.sync_to_peers(&id, None);
+        id
+    }
+
+    /// Add a trusting transaction in our session and sync it to every interested peer.
+    pub fn make_transaction(
+        &mut self,
+        id: &CoID,
+        changes_json: &str,
+        made_at: u64,
+    ) -> Result<(), NodeError> {
+        let co_value = self
+            .co_values
+            .get_mut(id)
+            .ok_or_else(|| NodeError::UnknownCoValue(id.clone()))?;
+        co_value.make_transaction(&self.session_id, &self.signer_secret, changes_json, made_at)?;
+        self.sync_to_peers(id, None);
+        Ok(())
+    }
+
+    /// Ask our servers for a CoValue.
+    ///
+    /// Returns whether it is available already. Otherwise it becomes available once the
+    /// answers are processed.
+    pub fn load(&mut self, id: &CoID) -> bool {
+        if self.co_value(id).is_some() {
+            return true;
+        }
+        self.co_values
+            .entry(id.clone())
+            .or_insert_with(|| CoValueState::unavailable(id.clone()));
+        self.load_from_servers(id, None);
+        false
+    }
+
+    /// A CoValue, if its header is known.
+    pub fn co_value(&self, id: &CoID) -> Option<&CoValueState> {
+        self.co_values
+            .get(id)
+            .filter(|co_value| co_value.is_available())
+    }
+
+    pub fn co_value_ids(&self) -> impl Iterator<Item = &CoID> {
+        self.co_values.keys()
+    }
+
+    pub fn known_state(&self, id: &CoID) -> CoValueKnownState {
+        self.co_values
+            .get(id)
+            .map(CoValueState::known_state)
+            .unwrap_or_else(|| CoValueKnownState::empty(id.clone()))
+    }
+
+    /// Call `listener` whenever transactions are added to a CoValue, whether we made them or a
+    /// peer sent them. The CoValue doesn't need to be available yet.
+    ///
+    /// Everything handled in one `process_incoming` is one notification per CoValue.
+    pub fn subscribe(
+        &mut self,
+        id: &CoID,
+        listener: impl FnMut(&CoValueUpdate) + Send + 'static,
+    ) -> SubscriptionID {
+        let co_value = self
+            .co_values
+            .entry(id.clone())
+            .or_insert_with(|| CoValueState::unavailable(id.clone()));
+        co_value.set_key_resolver(self.key_resolver.clone());
+        co_value.subscribe(listener)
+    }
+
+    /// Returns whether the listener was subscribed.
+    pub fn unsubscribe(&mut self, id: &CoID, subscription: SubscriptionID) -> bool {
+        self.co_values
+            .get_mut(id)
+            .is_some_and(|co_value| co_value.unsubscribe(subscription))
+    }
+
+    /// Handle every message that has arrived from our peers, dropping disconnected peers.
+    /// Queued messages go out first to the peers whose channels have room again.
+    ///
+    /// Returns the number of messages handled.
+    pub fn process_incoming(&mut self) -> usize {
+        self.flush_peers();
+        // CoValues created on the way have no listeners yet, so they don't need batching
+        let batched: Vec<CoID> = self.co_values.keys().cloned().collect();
+        for co_value in self.co_values.values_mut() {
+            co_value.begin_batch();
+        }
+        let handled = self.handle_incoming();
+        for id in &batched {
+            if let Some(co_value) = self.co_values.get_mut(id) {
+                co_value.end_batch();
+            }
+        }
+        handled
+    }
+
+    fn handle_incoming(&mut self) -> usize {
+        let mut handled = 0;
+        let peer_ids: Vec<PeerID> = self.peers.keys().cloned().collect();
+        for peer_id in peer_ids {
+            while let Some(state) = self.peers.get_mut(&peer_id) {
+                match state.peer.incoming.try_recv() {
+                    Ok(Some(message)) => {
+                        self.handle_message(&peer_id, message);
+                        handled += 1;
+                    }
+                    Ok(None) => break,
+                    Err(Disconnected) => {
+                        self.remove_peer(&peer_id);
+                        break;
+                    }
+                }
+            }
+        }
+        handled
+    }
+
+    /// Errors from handling incoming messages since the last call, with the peer that sent them.
+    pub fn take_errors(&mut self) -> Vec<(PeerID, NodeError)> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Sessions we've seen forked by their signer, with the evidence for each.
+    pub fn equivocations(&self) -> &EquivocationLog {
+        &self.equivocations
+    }
+
+    /// Handle one message from a connected peer.
+    pub fn handle_message(&mut self, peer_id: &str, message: SyncMessage) {
+        match message {
+            SyncMessage::Load(msg) => self.handle_load(peer_id, msg),
+            SyncMessage::Known(msg) => self.handle_known(peer_id, msg),
+            SyncMessage::Content(msg) => self.handle_content(peer_id, msg),
+            SyncMessage::Done(msg) => {
+                if let Some(state) = self.peers.get_mut(peer_id) {
+                    state.known_states.remove(&msg.id);
+                }
+            }
+        }
+    }
+
+    fn handle_load(&mut self, peer_id: &str, msg: CoValueKnownState) {
+        let id = msg.id.clone();
+        let Some(state) = self.peers.get_mut(peer_id) else {
+            return;
+        };
+        state.known_states.insert(id.clone(), msg);
+
+        if self.co_value(&id).is_some() {
+            // Like `handleLoad`, tell our known state even when sending content, so the peer
+            // pushes what it has that we don't
+            self.send_known(peer_id, &id, None);
+            self.send_new_content(peer_id, &id);
+            return;
+        }
+
+        self.co_values
+            .entry(id.clone())
+            .or_insert_with(|| CoValueState::unavailable(id.clone()));
+        if self.load_from_servers(&id, Some(peer_id)) {
+            if let Some(pending) = self.pending_loads.get_mut(&id) {
+                pending.waiting.insert(peer_id.to_string());
+            }
+        } else {
+            self.send_known(peer_id, &id, None);
+        }
+    }
+
+    fn handle_known(&mut self, peer_id: &str, msg: KnownStateMessage) {
+        let id = msg.known_state.id.clone();
+        let Some(state) = self.peers.get_mut(peer_id) else {
+            return;
+        };
+        let is_server = state.peer.role == PeerRole::Server;
+        let peer_has_header = msg.known_state.header;
+
+        if msg.is_correction == Some(true) {
+            state.known_states.insert(id.clone(), msg.known_state);
+        } else {
+            state
+                .known_states
+                .entry(id.clone())
+                .and_modify(|known| known.combine_with(&msg.known_state))
+                .or_insert(msg.known_state);
+        }
+
+        if self.co_value(&id).is_some() {
+            self.send_new_content(peer_id, &id);
+        } else if is_server && !peer_has_header {
+            self.server_lacks(peer_id, &id);
+        }
+    }
+
+    fn handle_content(&mut self, peer_id: &str, msg: NewContentMessage) {
+        let id = msg.id.clone();
+        if !self.peers.contains_key(peer_id) {
+            return;
+        }
+
+        let before = self.known_state(&id);
+        let co_value = self
+            .co_values
+            .entry(id.clone())
+            .or_insert_with(|| CoValueState::unavailable(id.clone()));
+        let result = co_value.try_add_content(&msg, &mut self.signer_resolver);
+
+        // The peer only counts as having what we accepted from it, so sessions that failed to
+        // verify are asked for again instead of being assumed synced. When something failed, we
+        // can't tell which of the sessions we already had were consistent with ours, so only the
+        // ones this message extended count.
+        let accepted = self.known_state(&id);
+        let all_accepted = result.is_ok();
+        if let Some(state) = self.peers.get_mut(peer_id) {
+            let known = state
+                .known_states
+                .entry(id.clone())
+                .or_insert_with(|| CoValueKnownState::empty(id.clone()));
+            known.header |= accepted.header;
+            for (session_id, content) in &msg.new {
+                let count = content.after + content.new_transactions.len() as u32;
+                let have = accepted.sessions.get(session_id).copied().unwrap_or(0);
+                let had = before.sessions.get(session_id).copied().unwrap_or(0);
+                if have < count || !(all_accepted || had < have) {
+                    continue;
+                }
+                let session_known = known.sessions.entry(session_id.clone()).or_insert(0);
+                *session_known = (*session_known).max(count);
+            }
+        }
+
+        match result {
+            Ok(_) => self.send_known(peer_id, &id, None),
+            // The sender assumed we have more than we do, so tell it what we actually have
+            Err(NodeError::UnknownCoValue(_) | NodeError::MissingTransactions { .. }) => {
+                let correction = self.known_state(&id);
+                if let Some(state) = self.peers.get_mut(peer_id) {
+                    state.known_states.insert(id.clone(), correction);
+                }
+                self.send_known(peer_id, &id, Some(true));
+            }
+            Err(err) => {
+                if let NodeError::Core(CoJsonCoreError::Equivocation(evidence)) = &err {
+                    self.equivocations.record((**evidence).clone());
+                }
+                self.errors.push((peer_id.to_string(), err));
+            }
+        }
+
+        // Sessions that applied cleanly are kept even if others failed
+        let added = self.known_state(&id) != before;
+        if added && self.co_value(&id).is_some() {
+            self.pending_loads.remove(&id);
+            self.sync_to_peers(&id, Some(peer_id));
+        }
+    }
+
+    /// Forward a load to our servers, except `except`.
+    ///
+    /// Returns whether there was a server to ask.
+    fn load_from_servers(&mut self, id: &CoID, except: Option<&str>) -> bool {
+        let servers: Vec<PeerID> = self
+            .peers
+            .iter()
+            .filter(|(peer_id, state)| {
+                state.peer.role == PeerRole::Server && Some(peer_id.as_str()) != except
+            })
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        if servers.is_empty() {
+            return false;
+        }
+
+        let pending = self
+            .pending_loads
+            .entry(id.clone())
+            .or_insert_with(|| PendingLoad {
+                waiting: BTreeSet::new(),
+                asked: BTreeSet::new(),
+            });
+        let to_ask: Vec<PeerID> = servers
+            .into_iter()
+            .filter(|server| pending.asked.insert(server.clone()))
+            .collect();
+
+        let known_state = self.known_state(id);
+        for server in to_ask {
+            self.send(&server, SyncMessage::Load(known_state.clone()));
+        }
+        true
+    }
+
+    /// A server doesn't have a CoValue we are loading. Once no server is left to ask, tell the
+    /// waiting peers that it's unavailable.
+    fn server_lacks(&mut self, server_id: &str, id: &CoID) {
+        let Some(pending) = self.pending_loads.get_mut(id) else {
+            return;
+        };
+        pending.asked.remove(server_id);
+        if !pending.asked.is_empty() {
+            return;
+        }
+
+        let pending = self.pending_loads.remove(id).unwrap();
+        for peer_id in pending.waiting {
+            self.send_known(&peer_id, id, None);
+        }
+    }
+
+    /// Send new content to every peer that syncs the CoValue with us: servers always, clients
+    /// once they loaded it.
+    fn sync_to_peers(&mut self, id: &CoID, except: Option<&str>) {
+        let peer_ids: Vec<PeerID> = self
+            .peers
+            .iter()
+            .filter(|(peer_id, state)| {
+                Some(peer_id.as_str()) != except
+                    && (state.peer.role == PeerRole::Server || state.known_states.contains_key(id))
+            })
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in peer_ids {
+            self.send_new_content(&peer_id, id);
+        }
+    }
+
+    /// Send a peer what it's missing of a CoValue, assuming it'll have it afterwards.
+    ///
+    /// Returns whether there was anything to send.
+    fn send_new_content(&mut self, peer_id: &str, id: &CoID) -> bool {
+        let (Some(co_value), Some(state)) = (self.co_values.get(id), self.peers.get_mut(peer_id))
+        else {
+            return false;
+        };
+        let Some(content) = co_value.new_content_since(state.known_states.get(id)) else {
+            return false;
+        };
+
+        state
+            .known_states
+            .entry(id.clone())
+            .and_modify(|known| known.combine_with(&co_value.known_state()))
+            .or_insert_with(|| co_value.known_state());
+        self.send(peer_id, SyncMessage::Content(content));
+        true
+    }
+
+    fn send_known(&mut self, peer_id: &str, id: &CoID, is_correction: Option<bool>) {
+        let known_state = self.known_state(id);
+        self.send(
+            peer_id,
+            SyncMessage::Known(KnownStateMessage {
+                known_state,
+                is_correction,
+                as_dependency_of: None,
+            }),
+        );
+    }
+
+    /// Queue a message for a peer and send what its channel takes.
+    ///
+    /// A peer whose queue is full can't keep up, so it's disconnected. It catches up from its
+    /// known states when it reconnects.
+    fn send(&mut self, peer_id: &str, message: SyncMessage) {
+        let Some(state) = self.peers.get_mut(peer_id) else {
+            return;
+        };
+        if let Err(full) = state.peer.enqueue(message) {
+            self.errors.push((peer_id.to_string(), full.into()));
+            self.remove_peer(peer_id);
+            return;
+        }
+        if state.peer.flush().is_err() {
+            self.remove_peer(peer_id);
+        }
+    }
+
+    /// Send queued messages to every peer whose channel has room again.
+    fn flush_peers(&mut self) {
+        let disconnected: Vec<PeerID> = self
+            .peers
+            .iter_mut()
+            .filter_map(|(peer_id, state)| state.peer.flush().err().map(|_| peer_id.clone()))
+            .collect();
+        for peer_id in disconnected {
+            self.remove_peer(&peer_id);
+        }
+    }
+}
+
+/// Process messages on all nodes until none are left in flight.
+///
+/// Returns the number of messages handled.
+pub fn sync_until_idle(nodes: &mut [&mut LocalNode]) -> usize {
+    let mut total = 0;
+    loop {
+        let handled: usize = nodes.iter_mut().map(|node| node.process_incoming()).sum();
+        if handled == 0 {
+            return total;
+        }
+        total += handled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connected_peers;
+    use crate::test_utils::{test_agent, test_header as header, test_node as node, test_signer};
+    use cojson_core::core::ContentDiff;
+
+    /// Connect `client` to `server`, with `server` acting as the server.
+    fn connect(client: &mut LocalNode, server: &mut LocalNode, client_id: &str, server_id: &str) {
+        let (client_as_peer, server_as_peer) =
+            connected_peers(client_id, server_id, PeerRole::Client, PeerRole::Server);
+        client.add_peer(server_as_peer);
+        server.add_peer(client_as_peer);
+    }
+
+    #[test]
+    fn test_random_agent() {
+        let mut node = LocalNode::with_random_agent();
+        assert!(node.session_id().agent_signer_id().is_some());
+        assert_ne!(
+            node.session_id(),
+            LocalNode::with_random_agent().session_id()
+        );
+
+        // Our own transactions verify against the signer in the session ID
+        let mut other = LocalNode::with_random_agent();
+        connect(&mut other, &mut node, "other", "node");
+        let id = node.create_co_value(header("a"));
+        node.make_transaction(&id, "[1]", 1).unwrap();
+        other.load(&id);
+        sync_until_idle(&mut [&mut node, &mut other]);
+        assert_eq!(other.known_state(&id), node.known_state(&id));
+        assert!(other.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_load_from_server() {
+        let mut server = node(1);
+        let mut client = node(2);
+        let id = server.create_co_value(header("a"));
+        server.make_transaction(&id, "[1]", 1).unwrap();
+        server.make_transaction(&id, "[2]", 2).unwrap();
+
+        connect(&mut client, &mut server, "client", "server");
+        assert!(!client.load(&id));
+        assert!(client.co_value(&id).is_none());
+
+        sync_until_idle(&mut [&mut client, &mut server]);
+        assert!(client.co_value(&id).is_some());
+        assert_eq!(client.known_state(&id), server.known_state(&id));
+        assert!(client.take_errors().is_empty());
+        assert!(server.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_unavailable_co_value() {
+        let mut server = node(1);
+        let mut client = node(2);
+        connect(&mut client, &mut server, "client", "server");
+
+        let id = CoID::for_header(&header("missing"));
+        client.load(&id);
+        sync_until_idle(&mut [&mut client, &mut server]);
+
+        assert!(client.co_value(&id).is_none());
+        assert!(server.co_value(&id).is_none());
+        assert_eq!(client.known_state(&id), CoValueKnownState::empty(id));
+    }
+
+    #[test]
+    fn test_push_to_server_and_relay_to_subscribed_clients() {
+        let mut server = node(1);
+        let mut alice = node(2);
+        let mut bob = node(3);
+        connect(&mut alice, &mut server, "alice", "server");
+        connect(&mut bob, &mut server, "bob", "server");
+
+        // Created on alice and pushed to the server without anyone asking
+        let id = alice.create_co_value(header("a"));
+        alice.make_transaction(&id, "[1]", 1).unwrap();
+        sync_until_idle(&mut [&mut alice, &mut bob, &mut server]);
+        assert_eq!(server.known_state(&id), alice.known_state(&id));
+        assert!(bob.co_value(&id).is_none());
+
+        bob.load(&id);
+        sync_until_idle(&mut [&mut alice, &mut bob, &mut server]);
+        assert_eq!(bob.known_state(&id), alice.known_state(&id));
+
+        // Now that bob loaded it, he gets alice's updates and the other way round
+        alice.make_transaction(&id, "[2]", 2).unwrap();
+        bob.make_transaction(&id, "[3]", 3).unwrap();
+        sync_until_idle(&mut [&mut alice, &mut bob, &mut server]);
+
+        let known_state = server.known_state(&id);
+        assert_eq!(known_state.sessions.len(), 2);
+        assert_eq!(known_state.sessions[alice.session_id()], 2);
+        assert_eq!(known_state.sessions[bob.session_id()], 1);
+        assert_eq!(alice.known_state(&id), known_state);
+        assert_eq!(bob.known_state(&id), known_state);
+    }
+
+    #[test]
+    fn test_subscribe_to_synced_changes() {
+        let mut server = node(1);
+        let mut alice = node(2);
+        let mut bob = node(3);
+        connect(&mut alice, &mut server, "alice", "server");
+        connect(&mut bob, &mut server, "bob", "server");
+
+        let id = alice.create_co_value(header("a"));
+        let updates = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = updates.clone();
+        let subscription = bob.subscribe(&id, move |update: &CoValueUpdate| {
+            recorded.lock().unwrap().push(update.clone())
+        });
+
+        alice
+            .make_transaction(&id, r#"[{"op":"set","key":"a","value":1}]"#, 1)
+            .unwrap();
+        alice
+            .make_transaction(&id, r#"[{"op":"set","key":"b","value":2}]"#, 2)
+            .unwrap();
+        sync_until_idle(&mut [&mut alice, &mut server]);
+        bob.load(&id);
+        sync_until_idle(&mut [&mut alice, &mut bob, &mut server]);
+
+        // Both transactions arrive in one message, so they are one update
+        let update = updates.lock().unwrap().pop().unwrap();
+        assert!(updates.lock().unwrap().is_empty());
+        assert_eq!(update.new_transactions.len(), 2);
+        assert_eq!(
+            update.diff,
+            ContentDiff::Map {
+                changed_keys: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+
+        assert!(bob.unsubscribe(&id, subscription));
+        bob.make_transaction(&id, r#"[{"op":"del","key":"a"}]"#, 3)
+            .unwrap();
+        assert!(updates.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_through_intermediate_server() {
+        let mut core = node(1);
+        let mut edge = node(2);
+        let mut client = node(3);
+        connect(&mut edge, &mut core, "edge", "core");
+        connect(&mut client, &mut edge, "client", "edge");
+
+        let id = core.create_co_value(header("a"));
+        core.make_transaction(&id, "[1]", 1).unwrap();
+
+        client.load(&id);
+        sync_until_idle(&mut [&mut core, &mut edge, &mut client]);
+        assert_eq!(edge.known_state(&id), core.known_state(&id));
+        assert_eq!(client.known_state(&id), core.known_state(&id));
+    }
+
+    #[test]
+    fn test_catch_up_on_reconnect() {
+        let mut server = node(1);
+        let mut client = node(2);
+        connect(&mut client, &mut server, "client", "server");
+
+        let id = client.create_co_value(header("a"));
+        client.make_transaction(&id, "[1]", 1).unwrap();
+        sync_until_idle(&mut [&mut client, &mut server]);
+
+        client.remove_peer("server");
+        server.process_incoming();
+        assert_eq!(server.peer_ids().count(), 0);
+
+        // Offline edits reach the server once we connect again
+        client.make_transaction(&id, "[2]", 2).unwrap();
+        client.make_transaction(&id, "[3]", 3).unwrap();
+        assert_eq!(server.known_state(&id).sessions[client.session_id()], 1);
+
+        connect(&mut client, &mut server, "client", "server");
+        sync_until_idle(&mut [&mut client, &mut server]);
+        assert_eq!(server.known_state(&id), client.known_state(&id));
+    }
+
+    #[test]
+    fn test_correction_after_wrong_assumption() {
+        let mut server = node(1);
+        let mut client = node(2);
+        connect(&mut client, &mut server, "client", "server");
+
+        let id = client.create_co_value(header("a"));
+        client.make_transaction(&id, "[1]", 1).unwrap();
+
+        // The server never sees the header or the first transaction
+        let mut lost = 0;
+        while let Some(state) = server.peers.get_mut("client") {
+            match state.peer.incoming.try_recv().unwrap() {
+                Some(_) => lost += 1,
+                None => break,
+            }
+        }
+        assert_eq!(lost, 2);
+
+        // So the next content lacks both, which the server corrects
+        client.make_transaction(&id, "[2]", 2).unwrap();
+        sync_until_idle(&mut [&mut client, &mut server]);
+        assert_eq!(server.known_state(&id), client.known_state(&id));
+        assert!(server.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_record_equivocation() {
+        let mut server = node(1);
+        let alice = node(2);
+        for peer_id in ["bob", "carol"] {
+            let (peer, _) = connected_peers(peer_id, "server", PeerRole::Client, PeerRole::Server);
+            server.add_peer(peer);
+        }
+
+        // Alice signs two different first transactions in the same session
+        let forks: Vec<NewContentMessage> = ["[1]", "[2]"]
+            .into_iter()
+            .map(|changes| {
+                let mut fork = CoValueState::from_header(header("a"));
+                fork.make_transaction(alice.session_id(), &test_agent(2).1, changes, 1)
+                    .unwrap();
+                fork.new_content_since(None).unwrap()
+            })
+            .collect();
+        let id = forks[0].id.clone();
+        server.handle_message("bob", SyncMessage::Content(forks[0].clone()));
+        assert!(server.take_errors().is_empty());
+        server.handle_message("carol", SyncMessage::Content(forks[1].clone()));
+
+        let errors = server.take_errors();
+        assert_eq!(errors[0].0, "carol");
+        assert!(matches!(
+            errors[0].1,
+            NodeError::Core(CoJsonCoreError::Equivocation(_))
+        ));
+        let evidence = &server.equivocations().evidence()[0];
+        assert_eq!(&evidence.session_id, alice.session_id());
+        assert_eq!(evidence.fork_index, 0);
+
+        // The first fork stays
+        let co_value = server.co_value(&id).unwrap();
+        assert!(co_value.sessions()[alice.session_id()].transactions_json()[0].contains("[1]"));
+    }
+
+    #[test]
+    fn test_reject_forged_content() {
+        let mut server = node(1);
+        let client = node(2);
+        let (client_as_peer, _server_as_peer) =
+            connected_peers("client", "server", PeerRole::Client, PeerRole::Server);
+        server.add_peer(client_as_peer);
+
+        // Content for the client's session, signed by someone else
+        let mut forged = CoValueState::from_header(header("a"));
+        let (_, forger) = test_agent(9);
+        forged
+            .make_transaction(client.session_id(), &forger, "[1]", 1)
+            .unwrap();
+        let content = forged.new_content_since(None).unwrap();
+        server.handle_message("client", SyncMessage::Content(content));
+
+        let errors = server.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "client");
+        assert!(matches!(errors[0].1, NodeError::Core(_)));
+        assert!(!server
+            .known_state(forged.id())
+            .sessions
+            .contains_key(client.session_id()));
+
+        // The client isn't assumed to have the rejected transaction
+        let client_known = &server.peers["client"].known_states[forged.id()];
+        assert!(client_known.header);
+        assert!(!client_known.sessions.contains_key(client.session_id()));
+    }
+
+    #[test]
+    fn test_account_sessions_need_a_resolver() {
+        let account_session = SessionID("co_zAccount_session_zA".to_string());
+        let (_, account_signer, account_secret) = test_signer(3);
+        let (_, forger) = test_agent(9);
+        let content = |signer_secret: &SignerSecret| {
+            let mut co_value = CoValueState::from_header(header("a"));
+            co_value
+                .make_transaction(&account_session, signer_secret, "[1]", 1)
+                .unwrap();
+            co_value.new_content_since(None).unwrap()
+        };
+        let send = |server: &mut LocalNode, content: NewContentMessage| {
+            let (client_as_peer, _) =
+                connected_peers("client", "server", PeerRole::Client, PeerRole::Server);
+            server.add_peer(client_as_peer);
+            let id = content.id.clone();
+            server.handle_message("client", SyncMessage::Content(content));
+            let has_session = server
+                .known_state(&id)
+                .sessions
+                .contains_key(&account_session);
+            (server.take_errors(), has_session)
+        };
+
+        // Account sessions don't name their signer, so they're refused by default
+        let (errors, has_session) = send(&mut node(1), content(&forger));
+        assert!(matches!(errors[0].1, NodeError::UnknownSigner { .. }));
+        assert!(!has_session);
+
+        // With the account's signer resolved, forgeries are caught
+        let resolver = move |_: &SessionID| Some(SessionSigner::Verify(account_signer.clone()));
+        let mut server = node(1).with_signer_resolver(resolver);
+        let (errors, has_session) = send(&mut server, content(&forger));
+        assert!(matches!(
+            errors[0].1,
+            NodeError::Core(CoJsonCoreError::SignatureVerification(_))
+        ));
+        assert!(!has_session);
+        let (errors, has_session) = send(&mut server, content(&account_secret));
+        assert!(errors.is_empty());
+        assert!(has_session);
+
+        // Trusting sessions without verifying them takes an explicit opt-in
+        let mut server = node(1).with_signer_resolver(|_| Some(SessionSigner::Trusted));
+        let (errors, has_session) = send(&mut server, content(&forger));
+        assert!(errors.is_empty());
+        assert!(has_session);
+    }
+
+    #[test]
+    fn test_disconnect_peers_that_fall_behind() {
+        let mut client = node(1);
+        client.create_co_value(header("a"));
+        client.create_co_value(header("b"));
+        let connect_stalled = |client: &mut LocalNode, limit| {
+            // Nothing receives from a channel without capacity, so every message stays queued
+            let (outgoing, _) = crate::bounded_in_memory_channel(0);
+            let (_, incoming) = crate::in_memory_channel();
+            let server = Peer::new("server", PeerRole::Server, incoming, outgoing);
+            client.add_peer(server.with_queue_limit(limit));
+        };
+
+        connect_stalled(&mut client, 10);
+        let metrics = client.queue_metrics("server").unwrap();
+        assert_eq!(metrics.depth(), 2);
+        assert_eq!(metrics.levels[0].pushed, 2);
+        assert!(client.take_errors().is_empty());
+
+        connect_stalled(&mut client, 1);
+        assert!(client.queue_metrics("server").is_none());
+        let errors = client.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "server");
+        assert!(matches!(errors[0].1, NodeError::QueueFull(_)));
+    }
+}
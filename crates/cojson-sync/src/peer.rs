@@ -0,0 +1,299 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+
+use crate::{PriorityMessageQueue, QueueFull, QueueMetrics, SyncMessage, PRIORITY_HIGH};
+
+pub type PeerID = String;
+
+/// How a node treats a peer, as in `sync.ts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerRole {
+    /// A peer we load from and push everything to.
+    Server,
+    /// A peer that only gets the CoValues it asked for.
+    Client,
+}
+
+/// The other side of a channel went away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disconnected;
+
+/// Messages arriving from a peer.
+pub trait IncomingPeerChannel: Send {
+    /// The next message, if one has arrived already. Never blocks.
+    fn try_recv(&mut self) -> Result<Option<SyncMessage>, Disconnected>;
+}
+
+/// Messages going to a peer.
+pub trait OutgoingPeerChannel: Send {
+    fn push(&mut self, message: SyncMessage) -> Result<(), Disconnected>;
+
+    /// Whether the channel takes another message now. Until it does, messages wait in the
+    /// peer's queue, where later ones of a higher priority can still overtake them.
+    fn is_ready(&self) -> bool {
+        true
+    }
+
+    /// Stop sending, letting the other side see the disconnect.
+    fn close(&mut self);
+}
+
+/// A connection to another node.
+///
+/// Messages for the peer go through its `PriorityMessageQueue` before the outgoing channel,
+/// so content of high priority CoValues is sent first whenever the channel is busy.
+pub struct Peer {
+    pub id: PeerID,
+    pub role: PeerRole,
+    pub incoming: Box<dyn IncomingPeerChannel>,
+    pub outgoing: Box<dyn OutgoingPeerChannel>,
+    queue: PriorityMessageQueue,
+}
+
+impl Peer {
+    pub fn new(
+        id: impl Into<PeerID>,
+        role: PeerRole,
+        incoming: impl IncomingPeerChannel + 'static,
+        outgoing: impl OutgoingPeerChannel + 'static,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            role,
+            incoming: Box::new(incoming),
+            outgoing: Box::new(outgoing),
+            // Control messages are small and unblock the other side, so they go first
+            queue: PriorityMessageQueue::new(PRIORITY_HIGH),
+        }
+    }
+
+    /// Hold at most `max_messages` in the outgoing queue.
+    pub fn with_queue_limit(mut self, max_messages: usize) -> Self {
+        self.queue = PriorityMessageQueue::with_limit(PRIORITY_HIGH, max_messages);
+        self
+    }
+
+    /// Queue a message for the peer, to be sent by `flush`. Hands it back if the queue is full.
+    pub fn enqueue(&mut self, message: SyncMessage) -> Result<(), QueueFull> {
+        self.queue.push(message)
+    }
+
+    /// Move queued messages to the outgoing channel, highest priority first, for as long as
+    /// the channel is ready.
+    ///
+    /// Returns the number of messages sent.
+    pub fn flush(&mut self) -> Result<usize, Disconnected> {
+        let mut sent = 0;
+        while self.outgoing.is_ready() {
+            let Some(message) = self.queue.pull() else {
+                break;
+            };
+            self.outgoing.push(message)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    pub fn queue_metrics(&self) -> &QueueMetrics {
+        self.queue.metrics()
+    }
+}
+
+/// Messages pushed to a bounded in-memory channel and not received yet.
+#[derive(Debug, Clone)]
+struct InFlight {
+    count: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+/// Receiving half of an in-memory channel.
+pub struct InMemoryIncoming {
+    receiver: Receiver<SyncMessage>,
+    in_flight: Option<InFlight>,
+}
+
+impl IncomingPeerChannel for InMemoryIncoming {
+    fn try_recv(&mut self) -> Result<Option<SyncMessage>, Disconnected> {
+        match self.receiver.try_recv() {
+            Ok(message) => {
+                if let Some(in_flight) = &self.in_flight {
+                    in_flight.count.fetch_sub(1, Ordering::SeqCst);
+                }
+                Ok(Some(message))
+            }
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(Disconnected),
+        }
+    }
+}
+
+/// Sending half of an in-memory channel.
+pub struct InMemoryOutgoing {
+    sender: Option<Sender<SyncMessage>>,
+    in_flight: Option<InFlight>,
+}
+
+impl OutgoingPeerChannel for InMemoryOutgoing {
+    fn push(&mut self, message: SyncMessage) -> Result<(), Disconnected> {
+        let sender = self.sender.as_ref().ok_or(Disconnected)?;
+        sender.send(message).map_err(|_| Disconnected)?;
+        if let Some(in_flight) = &self.in_flight {
+            in_flight.count.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.in_flight
+            .as_ref()
+            .is_none_or(|in_flight| in_flight.count.load(Ordering::SeqCst) < in_flight.capacity)
+    }
+
+    fn close(&mut self) {
+        self.sender = None;
+    }
+}
+
+/// An in-memory channel, delivering messages in order.
+pub fn in_memory_channel() -> (InMemoryOutgoing, InMemoryIncoming) {
+    in_memory_channel_with(None)
+}
+
+/// An in-memory channel that is ready for more once fewer than `capacity` messages are in it.
+///
+/// Pushing past the capacity still works: it only tells the sending peer to keep messages in
+/// its queue.
+pub fn bounded_in_memory_channel(capacity: usize) -> (InMemoryOutgoing, InMemoryIncoming) {
+    in_memory_channel_with(Some(InFlight {
+        count: Arc::new(AtomicUsize::new(0)),
+        capacity,
+    }))
+}
+
+fn in_memory_channel_with(in_flight: Option<InFlight>) -> (InMemoryOutgoing, InMemoryIncoming) {
+    let (sender, receiver) = channel();
+    (
+        InMemoryOutgoing {
+            sender: Some(sender),
+            in_flight: in_flight.clone(),
+        },
+        InMemoryIncoming {
+            receiver,
+            in_flight,
+        },
+    )
+}
+
+/// Two peers connected to each other in memory, like `connectedPeers` in `streamUtils.ts`.
+///
+/// Returns `(peer1_as_peer, peer2_as_peer)`: the first is added to the second node and the
+/// second to the first node. Nothing is delivered until the receiving node processes its
+/// incoming messages, which keeps tests deterministic.
+pub fn connected_peers(
+    peer1_id: impl Into<PeerID>,
+    peer2_id: impl Into<PeerID>,
+    peer1_role: PeerRole,
+    peer2_role: PeerRole,
+) -> (Peer, Peer) {
+    let (to_peer2, from_peer1) = in_memory_channel();
+    let (to_peer1, from_peer2) = in_memory_channel();
+
+    let peer2_as_peer = Peer::new(peer2_id, peer2_role, from_peer2, to_peer2);
+    let peer1_as_peer = Peer::new(peer1_id, peer1_role, from_peer1, to_peer1);
+    (peer1_as_peer, peer2_as_peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DoneMessage, NewContentMessage, PRIORITY_LOW};
+    use cojson_core::core::CoID;
+    use std::collections::BTreeMap;
+
+    fn done(id: &str) -> SyncMessage {
+        SyncMessage::Done(DoneMessage {
+            id: CoID(id.to_string()),
+        })
+    }
+
+    fn content(id: &str, priority: u8) -> SyncMessage {
+        SyncMessage::Content(NewContentMessage {
+            id: CoID(id.to_string()),
+            header: None,
+            priority,
+            new: BTreeMap::new(),
+            expect_content_until: None,
+        })
+    }
+
+    #[test]
+    fn test_connected_peers() {
+        let (mut client_as_peer, mut server_as_peer) =
+            connected_peers("client", "server", PeerRole::Client, PeerRole::Server);
+        assert_eq!(server_as_peer.id, "server");
+        assert_eq!(server_as_peer.role, PeerRole::Server);
+        assert_eq!(client_as_peer.id, "client");
+        assert_eq!(client_as_peer.role, PeerRole::Client);
+
+        server_as_peer.outgoing.push(done("co_zA")).unwrap();
+        server_as_peer.outgoing.push(done("co_zB")).unwrap();
+        client_as_peer.outgoing.push(done("co_zC")).unwrap();
+
+        let received = client_as_peer.incoming.try_recv().unwrap().unwrap();
+        assert_eq!(received.id().0, "co_zA");
+        let received = client_as_peer.incoming.try_recv().unwrap().unwrap();
+        assert_eq!(received.id().0, "co_zB");
+        assert!(client_as_peer.incoming.try_recv().unwrap().is_none());
+
+        let received = server_as_peer.incoming.try_recv().unwrap().unwrap();
+        assert_eq!(received.id().0, "co_zC");
+    }
+
+    #[test]
+    fn test_close_disconnects() {
+        let (mut client_as_peer, mut server_as_peer) =
+            connected_peers("client", "server", PeerRole::Client, PeerRole::Server);
+
+        server_as_peer.outgoing.push(done("co_zA")).unwrap();
+        server_as_peer.outgoing.close();
+        assert_eq!(
+            server_as_peer.outgoing.push(done("co_zB")),
+            Err(Disconnected)
+        );
+
+        // Already sent messages still arrive
+        assert!(client_as_peer.incoming.try_recv().unwrap().is_some());
+        assert!(matches!(
+            client_as_peer.incoming.try_recv(),
+            Err(Disconnected)
+        ));
+    }
+
+    #[test]
+    fn test_queue_while_the_channel_is_busy() {
+        let (outgoing, mut received) = bounded_in_memory_channel(1);
+        let (_, incoming) = in_memory_channel();
+        let mut peer = Peer::new("client", PeerRole::Client, incoming, outgoing);
+
+        peer.enqueue(content("co_zUpload", PRIORITY_LOW)).unwrap();
+        peer.enqueue(content("co_zUpload", PRIORITY_LOW)).unwrap();
+        assert_eq!(peer.flush(), Ok(1));
+        peer.enqueue(content("co_zGroup", PRIORITY_HIGH)).unwrap();
+        assert_eq!(peer.flush(), Ok(0));
+        assert_eq!(peer.queue_metrics().depth(), 2);
+
+        // Once the channel has room, the group goes before the rest of the upload
+        let mut ids = Vec::new();
+        while let Some(message) = received.try_recv().unwrap() {
+            ids.push(message.id().0.clone());
+            peer.flush().unwrap();
+        }
+        assert_eq!(ids, ["co_zUpload", "co_zGroup", "co_zUpload"]);
+        assert_eq!(peer.queue_metrics().levels[2].pulled, 2);
+
+        let mut peer = peer.with_queue_limit(1);
+        peer.enqueue(done("co_zA")).unwrap();
+        assert_eq!(peer.enqueue(done("co_zB")).unwrap_err().limit, 1);
+    }
+}
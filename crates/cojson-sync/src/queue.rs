@@ -0,0 +1,258 @@
+//! The outgoing message queue of a peer, as in `queue/PriorityBasedMessageQueue.ts`.
+//!
+//! Content is queued by the priority of its CoValue, so account and group updates go out
+//! before bulk binary streams. Within a priority, CoValues take turns, so one large upload
+//! can't hold back everything else at its level.
+
+use std::collections::{HashMap, VecDeque};
+
+use cojson_core::core::CoID;
+
+use crate::{
+    CoValuePriority, QueueFull, SyncMessage, PRIORITY_HIGH, PRIORITY_LOW, PRIORITY_MEDIUM,
+};
+
+/// How many messages a queue holds before refusing more, by default.
+pub const DEFAULT_MAX_QUEUED_MESSAGES: usize = 10_000;
+
+const PRIORITIES: [CoValuePriority; 3] = [PRIORITY_HIGH, PRIORITY_MEDIUM, PRIORITY_LOW];
+
+fn level_index(priority: CoValuePriority) -> usize {
+    if priority < PRIORITY_MEDIUM {
+        0
+    } else if priority < PRIORITY_LOW {
+        1
+    } else {
+        2
+    }
+}
+
+/// Counters for one priority level, like the `jazz.messagequeue.*` metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelMetrics {
+    pub priority: CoValuePriority,
+    /// Messages currently queued.
+    pub depth: usize,
+    pub pushed: u64,
+    pub pulled: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueMetrics {
+    /// High, medium and low priority, in that order.
+    pub levels: [LevelMetrics; 3],
+    /// Messages refused because the queue was full.
+    pub rejected: u64,
+    /// The largest total depth seen so far.
+    pub peak_depth: usize,
+}
+
+impl QueueMetrics {
+    /// Messages currently queued across all priorities.
+    pub fn depth(&self) -> usize {
+        self.levels.iter().map(|level| level.depth).sum()
+    }
+}
+
+/// The messages of one priority, with a queue per CoValue served round-robin.
+#[derive(Debug, Default)]
+struct Level {
+    queues: HashMap<CoID, VecDeque<SyncMessage>>,
+    /// CoValues with queued messages, in the order they get their next turn.
+    turns: VecDeque<CoID>,
+}
+
+impl Level {
+    fn push(&mut self, message: SyncMessage) {
+        let id = message.id().clone();
+        let queue = self.queues.entry(id.clone()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(id);
+        }
+        queue.push_back(message);
+    }
+
+    fn pull(&mut self) -> Option<SyncMessage> {
+        let id = self.turns.pop_front()?;
+        let queue = self.queues.get_mut(&id).unwrap();
+        let message = queue.pop_front();
+        if queue.is_empty() {
+            self.queues.remove(&id);
+        } else {
+            self.turns.push_back(id);
+        }
+        message
+    }
+}
+
+/// A bounded queue that hands out messages by priority, then round-robin across CoValues.
+///
+/// Content messages carry their priority. Everything else is queued at the default priority
+/// given on creation.
+#[derive(Debug)]
+pub struct PriorityMessageQueue {
+    default_priority: CoValuePriority,
+    max_messages: usize,
+    levels: [Level; 3],
+    metrics: QueueMetrics,
+}
+
+impl PriorityMessageQueue {
+    pub fn new(default_priority: CoValuePriority) -> Self {
+        Self::with_limit(default_priority, DEFAULT_MAX_QUEUED_MESSAGES)
+    }
+
+    /// A queue that refuses messages once it holds `max_messages`.
+    pub fn with_limit(default_priority: CoValuePriority, max_messages: usize) -> Self {
+        let mut metrics = QueueMetrics::default();
+        for (level, priority) in metrics.levels.iter_mut().zip(PRIORITIES) {
+            level.priority = priority;
+        }
+        Self {
+            default_priority,
+            max_messages,
+            levels: Default::default(),
+            metrics,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.metrics.depth()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the next push would be refused. Callers use this to stop taking messages from
+    /// their source, leaving them there until the queue drains.
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.max_messages
+    }
+
+    /// Queue `message`, or hand it back if the queue is full.
+    pub fn push(&mut self, message: SyncMessage) -> Result<(), QueueFull> {
+        if self.is_full() {
+            self.metrics.rejected += 1;
+            return Err(QueueFull {
+                limit: self.max_messages,
+                message: Box::new(message),
+            });
+        }
+
+        let priority = match &message {
+            SyncMessage::Content(content) => content.priority,
+            _ => self.default_priority,
+        };
+        let index = level_index(priority);
+        self.levels[index].push(message);
+
+        let level = &mut self.metrics.levels[index];
+        level.depth += 1;
+        level.pushed += 1;
+        self.metrics.peak_depth = self.metrics.peak_depth.max(self.metrics.depth());
+        Ok(())
+    }
+
+    /// The next message to send: from the highest priority with anything queued, taking turns
+    /// between CoValues of that priority.
+    pub fn pull(&mut self) -> Option<SyncMessage> {
+        let (index, message) = self
+            .levels
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, level)| level.pull().map(|message| (index, message)))?;
+
+        let level = &mut self.metrics.levels[index];
+        level.depth -= 1;
+        level.pulled += 1;
+        Some(message)
+    }
+
+    pub fn metrics(&self) -> &QueueMetrics {
+        &self.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CoValueKnownState, NewContentMessage};
+    use std::collections::BTreeMap;
+
+    fn id(name: &str) -> CoID {
+        CoID(format!("co_z{name}"))
+    }
+
+    fn content(name: &str, priority: CoValuePriority) -> SyncMessage {
+        SyncMessage::Content(NewContentMessage {
+            id: id(name),
+            header: None,
+            priority,
+            new: BTreeMap::new(),
+            expect_content_until: None,
+        })
+    }
+
+    fn load(name: &str) -> SyncMessage {
+        SyncMessage::Load(CoValueKnownState::empty(id(name)))
+    }
+
+    fn pull_ids(queue: &mut PriorityMessageQueue) -> Vec<String> {
+        std::iter::from_fn(|| queue.pull())
+            .map(|message| message.id().0.trim_start_matches("co_z").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_higher_priority_first() {
+        let mut queue = PriorityMessageQueue::new(PRIORITY_MEDIUM);
+        queue.push(content("Binary", PRIORITY_LOW)).unwrap();
+        queue.push(load("Load")).unwrap();
+        queue.push(content("Group", PRIORITY_HIGH)).unwrap();
+
+        assert_eq!(pull_ids(&mut queue), ["Group", "Load", "Binary"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_round_robin_within_priority() {
+        let mut queue = PriorityMessageQueue::new(PRIORITY_HIGH);
+        for _ in 0..3 {
+            queue.push(content("Upload", PRIORITY_LOW)).unwrap();
+        }
+        queue.push(content("Other", PRIORITY_LOW)).unwrap();
+        queue.push(content("Third", PRIORITY_LOW)).unwrap();
+
+        assert_eq!(
+            pull_ids(&mut queue),
+            ["Upload", "Other", "Third", "Upload", "Upload"]
+        );
+    }
+
+    #[test]
+    fn test_limit_and_metrics() {
+        let mut queue = PriorityMessageQueue::with_limit(PRIORITY_HIGH, 2);
+        queue.push(load("A")).unwrap();
+        queue.push(content("B", PRIORITY_LOW)).unwrap();
+        assert!(queue.is_full());
+
+        let err = queue.push(content("C", PRIORITY_MEDIUM)).unwrap_err();
+        assert_eq!(err.limit, 2);
+        assert_eq!(err.message.id(), &id("C"));
+
+        let metrics = queue.metrics();
+        assert_eq!(metrics.depth(), 2);
+        assert_eq!(metrics.rejected, 1);
+        assert_eq!(metrics.levels[0].priority, PRIORITY_HIGH);
+        assert_eq!(metrics.levels[0].depth, 1);
+        assert_eq!(metrics.levels[2].pushed, 1);
+
+        queue.pull().unwrap();
+        assert!(!queue.is_full());
+        let metrics = queue.metrics();
+        assert_eq!(metrics.levels[0].depth, 0);
+        assert_eq!(metrics.levels[0].pulled, 1);
+        assert_eq!(metrics.peak_depth, 2);
+    }
+}
@@ -0,0 +1,620 @@
+//! A deterministic virtual network for testing sync between `LocalNode`s.
+//!
+//! Everything random about the network (latencies, drops, duplicates) comes from one ChaCha8
+//! RNG seeded from the simulation's seed, which gives the same stream on every platform and
+//! `rand` release, and nodes are driven in a fixed order, so a run is fully
+//! determined by its seed and workload. Failures carry the seed to replay them with.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use cojson_core::core::CoID;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    Disconnected, IncomingPeerChannel, LocalNode, OutgoingPeerChannel, Peer, PeerRole,
+    SimulationError, SyncMessage,
+};
+
+/// How the virtual network misbehaves. Times are in ticks of `Simulation::step`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    /// Messages take between `min_latency` and `max_latency` ticks to arrive, so messages on
+    /// the same link can overtake each other. At least one tick.
+    pub min_latency: u64,
+    pub max_latency: u64,
+    /// Chance of a message being lost. Transports are reliable while connected, so a lost
+    /// message takes its connection down with it, like a dropped WebSocket.
+    pub drop_probability: f64,
+    /// Chance of a message being delivered twice.
+    pub duplicate_probability: f64,
+    /// Ticks until a dropped connection comes back.
+    pub reconnect_delay: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            min_latency: 1,
+            max_latency: 5,
+            drop_probability: 0.0,
+            duplicate_probability: 0.0,
+            reconnect_delay: 10,
+        }
+    }
+}
+
+/// What happened on the virtual network so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub disconnects: u64,
+    pub reconnects: u64,
+}
+
+/// One direction of a connection.
+#[derive(Default)]
+struct Link {
+    /// Arrived, waiting for the receiving node to process them.
+    mailbox: VecDeque<SyncMessage>,
+    closed: bool,
+}
+
+struct NetworkState {
+    now: u64,
+    config: NetworkConfig,
+    rng: ChaCha8Rng,
+    links: Vec<Link>,
+    /// Keyed by `(deliver_at, seq)`, so delivery order is deterministic.
+    in_flight: BTreeMap<(u64, u64), (usize, SyncMessage)>,
+    next_seq: u64,
+    /// Links that lost a message since the simulation last looked.
+    broken: BTreeSet<usize>,
+    stats: NetworkStats,
+}
+
+impl NetworkState {
+    fn transmit(&mut self, link: usize, message: SyncMessage) -> Result<(), Disconnected> {
+        if self.links[link].closed {
+            return Err(Disconnected);
+        }
+        self.stats.sent += 1;
+
+        if self.rng.gen_bool(self.config.drop_probability) {
+            self.stats.dropped += 1;
+            self.broken.insert(link);
+            return Ok(());
+        }
+
+        let copies = if self.rng.gen_bool(self.config.duplicate_probability) {
+            self.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let min_latency = self.config.min_latency.max(1);
+            let latency = self
+                .rng
+                .gen_range(min_latency..=self.config.max_latency.max(min_latency));
+            let seq = self.next_seq;
+            self.next_seq += 1;
+            self.in_flight
+                .insert((self.now + latency, seq), (link, message.clone()));
+        }
+        Ok(())
+    }
+
+    /// Move every message due by now into its link's mailbox.
+    fn deliver_due(&mut self) {
+        while let Some(entry) = self.in_flight.first_entry() {
+            if entry.key().0 > self.now {
+                break;
+            }
+            let (link, message) = entry.remove();
+            if !self.links[link].closed {
+                self.links[link].mailbox.push_back(message);
+                self.stats.delivered += 1;
+            }
+        }
+    }
+
+    fn close(&mut self, link: usize) {
+        self.links[link].closed = true;
+        self.links[link].mailbox.clear();
+        self.in_flight.retain(|_, (to, _)| *to != link);
+        self.broken.remove(&link);
+    }
+
+    /// Nothing left to deliver, and no lost message whose connection still has to go down.
+    fn is_idle(&self) -> bool {
+        self.in_flight.is_empty()
+            && self.broken.is_empty()
+            && self.links.iter().all(|link| link.mailbox.is_empty())
+    }
+}
+
+/// Sending half of a simulated link.
+pub struct SimOutgoing {
+    network: Arc<Mutex<NetworkState>>,
+    link: usize,
+}
+
+impl OutgoingPeerChannel for SimOutgoing {
+    fn push(&mut self, message: SyncMessage) -> Result<(), Disconnected> {
+        self.network.lock().unwrap().transmit(self.link, message)
+    }
+
+    fn close(&mut self) {
+        self.network.lock().unwrap().close(self.link);
+    }
+}
+
+/// Receiving half of a simulated link.
+pub struct SimIncoming {
+    network: Arc<Mutex<NetworkState>>,
+    link: usize,
+}
+
+impl IncomingPeerChannel for SimIncoming {
+    fn try_recv(&mut self) -> Result<Option<SyncMessage>, Disconnected> {
+        let mut network = self.network.lock().unwrap();
+        let link = &mut network.links[self.link];
+        match link.mailbox.pop_front() {
+            Some(message) => Ok(Some(message)),
+            None if link.closed => Err(Disconnected),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A client node connected to a server node.
+struct Connection {
+    client: usize,
+    server: usize,
+    /// `(client to server, server to client)`, or None while disconnected.
+    links: Option<(usize, usize)>,
+    reconnect_at: Option<u64>,
+}
+
+/// Nodes on a virtual network, driven tick by tick.
+pub struct Simulation {
+    seed: u64,
+    network: Arc<Mutex<NetworkState>>,
+    /// For the workload, kept apart from the network's RNG so tests can draw from it without
+    /// shifting the network's decisions.
+    rng: ChaCha8Rng,
+    nodes: Vec<LocalNode>,
+    connections: Vec<Connection>,
+    partitions: BTreeSet<(usize, usize)>,
+}
+
+impl Simulation {
+    /// Fails if a probability in `config` is outside `[0, 1]`.
+    pub fn new(seed: u64, config: NetworkConfig) -> Result<Self, SimulationError> {
+        for (field, probability) in [
+            ("drop_probability", config.drop_probability),
+            ("duplicate_probability", config.duplicate_probability),
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(SimulationError::InvalidProbability { field, probability });
+            }
+        }
+        let network = NetworkState {
+            now: 0,
+            config,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            links: Vec::new(),
+            in_flight: BTreeMap::new(),
+            next_seq: 0,
+            broken: BTreeSet::new(),
+            stats: NetworkStats::default(),
+        };
+        Ok(Self {
+            seed,
+            network: Arc::new(Mutex::new(network)),
+            rng: ChaCha8Rng::seed_from_u64(seed.wrapping_add(0x9e37_79b9_7f4a_7c15)),
+            nodes: Vec::new(),
+            connections: Vec::new(),
+            partitions: BTreeSet::new(),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The current tick.
+    pub fn now(&self) -> u64 {
+        self.network.lock().unwrap().now
+    }
+
+    /// A seeded RNG for generating the workload.
+    pub fn rng(&mut self) -> &mut ChaCha8Rng {
+        &mut self.rng
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.network.lock().unwrap().stats
+    }
+
+    /// Add a node, returning its index.
+    pub fn add_node(&mut self, node: LocalNode) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    pub fn node(&self, index: usize) -> &LocalNode {
+        &self.nodes[index]
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut LocalNode {
+        &mut self.nodes[index]
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The peer ID node `index` is known as to the nodes it's connected to.
+    pub fn peer_id(index: usize) -> String {
+        format!("node{index}")
+    }
+
+    /// Connect `client` to `server`, with `server` acting as its server. The connection comes
+    /// back by itself whenever it drops, unless the two are partitioned.
+    pub fn connect(&mut self, client: usize, server: usize) {
+        self.connections.push(Connection {
+            client,
+            server,
+            links: None,
+            reconnect_at: None,
+        });
+        let connection = self.connections.len() - 1;
+        if !self.is_partitioned(client, server) {
+            self.open(connection);
+        }
+    }
+
+    /// Cut every connection between `a` and `b` until `heal` is called.
+    pub fn partition(&mut self, a: usize, b: usize) {
+        self.partitions.insert((a.min(b), a.max(b)));
+        for connection in 0..self.connections.len() {
+            let Connection { client, server, .. } = self.connections[connection];
+            if (client.min(server), client.max(server)) == (a.min(b), a.max(b)) {
+                self.disconnect(connection);
+                self.connections[connection].reconnect_at = None;
+            }
+        }
+    }
+
+    /// Let `a` and `b` reconnect.
+    pub fn heal(&mut self, a: usize, b: usize) {
+        self.partitions.remove(&(a.min(b), a.max(b)));
+        let now = self.now();
+        for connection in &mut self.connections {
+            let Connection { client, server, .. } = *connection;
+            if (client.min(server), client.max(server)) == (a.min(b), a.max(b))
+                && connection.links.is_none()
+            {
+                connection.reconnect_at = Some(now);
+            }
+        }
+    }
+
+    fn is_partitioned(&self, a: usize, b: usize) -> bool {
+        self.partitions.contains(&(a.min(b), a.max(b)))
+    }
+
+    fn open(&mut self, connection: usize) {
+        let Connection { client, server, .. } = self.connections[connection];
+        let (to_server, to_client) = {
+            let mut network = self.network.lock().unwrap();
+            network.links.push(Link::default());
+            network.links.push(Link::default());
+            (network.links.len() - 2, network.links.len() - 1)
+        };
+        let channel = |link| {
+            (
+                SimIncoming {
+                    network: self.network.clone(),
+                    link,
+                },
+                SimOutgoing {
+                    network: self.network.clone(),
+                    link,
+                },
+            )
+        };
+        let (from_client, to_server_out) = channel(to_server);
+        let (from_server, to_client_out) = channel(to_client);
+
+        self.connections[connection].links = Some((to_server, to_client));
+        self.connections[connection].reconnect_at = None;
+        self.nodes[server].add_peer(Peer::new(
+            Self::peer_id(client),
+            PeerRole::Client,
+            from_client,
+            to_client_out,
+        ));
+        self.nodes[client].add_peer(Peer::new(
+            Self::peer_id(server),
+            PeerRole::Server,
+            from_server,
+            to_server_out,
+        ));
+    }
+
+    fn disconnect(&mut self, connection: usize) {
+        let Connection { client, server, .. } = self.connections[connection];
+        let Some((to_server, to_client)) = self.connections[connection].links.take() else {
+            return;
+        };
+        {
+            let mut network = self.network.lock().unwrap();
+            network.close(to_server);
+            network.close(to_client);
+            network.stats.disconnects += 1;
+        }
+        self.nodes[client].remove_peer(&Self::peer_id(server));
+        self.nodes[server].remove_peer(&Self::peer_id(client));
+    }
+
+    /// Advance one tick: deliver due messages, let every node handle what arrived, and bring
+    /// dropped connections back.
+    ///
+    /// Returns the number of messages handled, or the first error a node ran into.
+    pub fn step(&mut self) -> Result<usize, SimulationError> {
+        let (now, broken) = {
+            let mut network = self.network.lock().unwrap();
+            network.now += 1;
+            network.deliver_due();
+            (network.now, std::mem::take(&mut network.broken))
+        };
+
+        let mut handled = 0;
+        for index in 0..self.nodes.len() {
+            handled += self.nodes[index].process_incoming();
+            if let Some((peer, error)) = self.nodes[index].take_errors().into_iter().next() {
+                return Err(SimulationError::Node {
+                    seed: self.seed,
+                    tick: now,
+                    node: index,
+                    peer,
+                    error,
+                });
+            }
+        }
+
+        let reconnect_delay = self.network.lock().unwrap().config.reconnect_delay;
+        for connection in 0..self.connections.len() {
+            let links = self.connections[connection].links;
+            if links.is_some_and(|(a, b)| broken.contains(&a) || broken.contains(&b)) {
+                self.disconnect(connection);
+                self.connections[connection].reconnect_at = Some(now + reconnect_delay);
+            }
+
+            let Connection {
+                client,
+                server,
+                reconnect_at,
+                ..
+            } = self.connections[connection];
+            if reconnect_at.is_some_and(|at| at <= now) && !self.is_partitioned(client, server) {
+                self.open(connection);
+                self.network.lock().unwrap().stats.reconnects += 1;
+            }
+        }
+
+        Ok(handled)
+    }
+
+    /// Whether nothing is in flight and no connection is waiting to come back.
+    pub fn is_quiet(&self) -> bool {
+        self.network.lock().unwrap().is_idle()
+            && self.connections.iter().all(|connection| {
+                connection.reconnect_at.is_none()
+                    || self.is_partitioned(connection.client, connection.server)
+            })
+    }
+
+    /// Step until the network is quiet, for at most `max_ticks`.
+    ///
+    /// Returns the number of ticks it took.
+    pub fn run_until_quiet(&mut self, max_ticks: u64) -> Result<u64, SimulationError> {
+        for ticks in 0..max_ticks {
+            if self.is_quiet() {
+                return Ok(ticks);
+            }
+            self.step()?;
+        }
+        if self.is_quiet() {
+            return Ok(max_ticks);
+        }
+        Err(SimulationError::NotQuiet {
+            seed: self.seed,
+            ticks: max_ticks,
+        })
+    }
+
+    /// Check that every node has the same known state and the same transactions for `ids`.
+    pub fn check_convergence(&self, ids: &[CoID]) -> Result<(), SimulationError> {
+        for id in ids {
+            let first = &self.nodes[0];
+            for (index, node) in self.nodes.iter().enumerate().skip(1) {
+                let same_known_state = node.known_state(id) == first.known_state(id);
+                let same_content = match (first.co_value(id), node.co_value(id)) {
+                    (Some(a), Some(b)) => a.sessions().iter().all(|(session_id, log)| {
+                        b.sessions()
+                            .get(session_id)
+                            .map(|other| other.transactions_json())
+                            == Some(log.transactions_json())
+                    }),
+                    (None, None) => true,
+                    _ => false,
+                };
+                if !same_known_state || !same_content {
+                    return Err(SimulationError::Diverged {
+                        seed: self.seed,
+                        id: id.clone(),
+                        first: 0,
+                        other: index,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_node as node;
+    use serde_json::json;
+
+    fn faulty() -> NetworkConfig {
+        NetworkConfig {
+            min_latency: 1,
+            max_latency: 8,
+            drop_probability: 0.03,
+            duplicate_probability: 0.1,
+            reconnect_delay: 5,
+        }
+    }
+
+    /// A server with three clients writing to one CoValue, with client 1 partitioned from the
+    /// server for a while.
+    fn run(seed: u64, config: NetworkConfig) -> Result<(Simulation, CoID), SimulationError> {
+        let mut sim = Simulation::new(seed, config)?;
+        let server = sim.add_node(node(1));
+        let clients: Vec<usize> = (2..5).map(|i| sim.add_node(node(i))).collect();
+        for &client in &clients {
+            sim.connect(client, server);
+        }
+
+        let id = sim.node_mut(clients[0]).create_co_value(json!({
+            "type": "comap",
+            "ruleset": {"type": "unsafeAllowAll"},
+            "meta": null,
+            "uniqueness": "sim",
+        }));
+        sim.run_until_quiet(10_000)?;
+        for &client in &clients[1..] {
+            sim.node_mut(client).load(&id);
+        }
+        sim.run_until_quiet(10_000)?;
+
+        for tick in 0..100 {
+            match tick {
+                30 => sim.partition(clients[1], server),
+                60 => sim.heal(clients[1], server),
+                _ => {}
+            }
+            if sim.rng().gen_bool(0.3) {
+                let client = clients[sim.rng().gen_range(0..clients.len())];
+                let value: u32 = sim.rng().gen();
+                let made_at = sim.now();
+                sim.node_mut(client)
+                    .make_transaction(&id, &format!("[{value}]"), made_at)
+                    .unwrap();
+            }
+            sim.step()?;
+        }
+
+        sim.run_until_quiet(10_000)?;
+        sim.check_convergence(std::slice::from_ref(&id))?;
+        Ok((sim, id))
+    }
+
+    #[test]
+    fn test_converges_despite_faults() {
+        for seed in 0..10 {
+            let (sim, id) = run(seed, faulty()).unwrap_or_else(|err| panic!("{err}"));
+            let known = sim.node(0).known_state(&id);
+            assert_eq!(known.sessions.len(), 3, "seed {seed}");
+
+            let stats = sim.stats();
+            assert!(stats.duplicated > 0, "seed {seed}: {stats:?}");
+        }
+    }
+
+    #[test]
+    fn test_reliable_network_never_reconnects() {
+        let (sim, _) = run(7, NetworkConfig::default()).unwrap();
+        let stats = sim.stats();
+        assert_eq!(stats.dropped, 0);
+        // Only the partition disconnects
+        assert_eq!(stats.disconnects, 1);
+        assert_eq!(stats.reconnects, 1);
+    }
+
+    #[test]
+    fn test_same_seed_same_run() {
+        let (first, id) = run(42, faulty()).unwrap();
+        let (second, _) = run(42, faulty()).unwrap();
+        assert_eq!(first.stats(), second.stats());
+        assert_eq!(first.now(), second.now());
+        assert_eq!(
+            first.node(0).known_state(&id),
+            second.node(0).known_state(&id)
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_probabilities() {
+        for probability in [-0.1, 1.5, f64::NAN] {
+            let config = NetworkConfig {
+                duplicate_probability: probability,
+                ..NetworkConfig::default()
+            };
+            assert!(matches!(
+                Simulation::new(1, config),
+                Err(SimulationError::InvalidProbability {
+                    field: "duplicate_probability",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_partitioned_node_catches_up_on_heal() {
+        let mut sim = Simulation::new(3, NetworkConfig::default()).unwrap();
+        let server = sim.add_node(node(1));
+        let alice = sim.add_node(node(2));
+        let bob = sim.add_node(node(3));
+        sim.connect(alice, server);
+        sim.connect(bob, server);
+
+        let id = sim.node_mut(alice).create_co_value(json!({
+            "type": "comap",
+            "ruleset": {"type": "unsafeAllowAll"},
+            "meta": null,
+            "uniqueness": "a",
+        }));
+        sim.run_until_quiet(100).unwrap();
+        sim.node_mut(bob).load(&id);
+        sim.run_until_quiet(100).unwrap();
+
+        sim.partition(bob, server);
+        sim.node_mut(alice).make_transaction(&id, "[1]", 1).unwrap();
+        sim.run_until_quiet(100).unwrap();
+        assert!(matches!(
+            sim.check_convergence(std::slice::from_ref(&id)),
+            Err(SimulationError::Diverged {
+                seed: 3,
+                other: 2,
+                ..
+            })
+        ));
+
+        sim.heal(bob, server);
+        sim.run_until_quiet(100).unwrap();
+        sim.check_convergence(&[id]).unwrap();
+    }
+}
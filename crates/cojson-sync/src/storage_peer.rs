@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use cojson_core::core::{CoID, SessionID, SessionLogStorage};
+
+use crate::{
+    CoValueKnownState, CoValueState, Disconnected, IncomingPeerChannel, KnownStateMessage,
+    NewContentMessage, NodeError, OutgoingPeerChannel, Peer, PeerID, PeerRole, SessionSigner,
+    SyncMessage,
+};
+
+type SignerResolver = Box<dyn FnMut(&SessionID) -> Option<SessionSigner> + Send>;
+
+struct StorageState<S> {
+    storage: S,
+    signer_resolver: SignerResolver,
+    /// CoValues touched so far, with session logs matching what's stored.
+    co_values: BTreeMap<CoID, CoValueState>,
+    responses: VecDeque<SyncMessage>,
+    closed: bool,
+    errors: Vec<NodeError>,
+}
+
+impl<S> StorageState<S>
+where
+    S: SessionLogStorage,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    fn co_value(&mut self, id: &CoID) -> Result<&mut CoValueState, NodeError> {
+        if !self.co_values.contains_key(id) {
+            // What's stored was checked when it was stored. Sessions loaded without a signer are
+            // only extended once the resolver trusts them.
+            let resolver = &mut self.signer_resolver;
+            let co_value = match self
+                .storage
+                .load_session_logs(id, |session_id| {
+                    resolver(session_id).and_then(|signer| signer.signer_id().cloned())
+                })
+                .map_err(|err| NodeError::Storage(Box::new(err)))?
+            {
+                Some(loaded) => CoValueState::from_loaded(loaded),
+                None => CoValueState::unavailable(id.clone()),
+            };
+            self.co_values.insert(id.clone(), co_value);
+        }
+        Ok(self.co_values.get_mut(id).unwrap())
+    }
+
+    fn handle(&mut self, message: SyncMessage) {
+        let result = match message {
+            SyncMessage::Load(msg) => self.handle_load(msg),
+            SyncMessage::Content(msg) => self.handle_content(msg),
+            SyncMessage::Known(_) | SyncMessage::Done(_) => Ok(()),
+        };
+        if let Err(err) = result {
+            self.errors.push(err);
+        }
+    }
+
+    fn handle_load(&mut self, msg: CoValueKnownState) -> Result<(), NodeError> {
+        let co_value = self.co_value(&msg.id)?;
+        let response = match co_value.new_content_since(Some(&msg)) {
+            Some(content) => SyncMessage::Content(content),
+            None => known(co_value.known_state(), None),
+        };
+        self.responses.push_back(response);
+        Ok(())
+    }
+
+    fn handle_content(&mut self, msg: NewContentMessage) -> Result<(), NodeError> {
+        self.co_value(&msg.id)?;
+        let co_value = self.co_values.get_mut(&msg.id).unwrap();
+        let result = co_value.store_content(&mut self.storage, &msg, &mut self.signer_resolver);
+        let known_state = co_value.known_state();
+
+        match result {
+            Ok(_) => {
+                self.responses.push_back(known(known_state, None));
+                Ok(())
+            }
+            // Ask the node for what we are missing
+            Err(NodeError::UnknownCoValue(_) | NodeError::MissingTransactions { .. }) => {
+                self.responses.push_back(known(known_state, Some(true)));
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn known(known_state: CoValueKnownState, is_correction: Option<bool>) -> SyncMessage {
+    SyncMessage::Known(KnownStateMessage {
+        known_state,
+        is_correction,
+        as_dependency_of: None,
+    })
+}
+
+/// Storage as a peer of a `LocalNode`.
+///
+/// The node treats it like a server: it loads CoValues it doesn't have from it and pushes every
+/// change to it. Messages are handled as soon as the node sends them, so answers are ready by
+/// the node's next `process_incoming`.
+pub struct StoragePeer<S> {
+    state: Arc<Mutex<StorageState<S>>>,
+}
+
+impl<S> Clone for StoragePeer<S> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S> StoragePeer<S>
+where
+    S: SessionLogStorage + Send + 'static,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Serve `storage`, verifying sessions against the signer in their agent session ID.
+    pub fn new(storage: S) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(StorageState {
+                storage,
+                signer_resolver: Box::new(SessionSigner::for_agent_session),
+                co_values: BTreeMap::new(),
+                responses: VecDeque::new(),
+                closed: false,
+                errors: Vec::new(),
+            })),
+        }
+    }
+
+    /// Decide how stored sessions are checked with `resolver`, as in
+    /// `LocalNode::with_signer_resolver`.
+    pub fn with_signer_resolver(
+        self,
+        resolver: impl FnMut(&SessionID) -> Option<SessionSigner> + Send + 'static,
+    ) -> Self {
+        self.state.lock().unwrap().signer_resolver = Box::new(resolver);
+        self
+    }
+
+    /// The peer to add to the node.
+    pub fn peer(&self, id: impl Into<PeerID>) -> Peer {
+        self.state.lock().unwrap().closed = false;
+        Peer::new(id, PeerRole::Server, self.clone(), self.clone())
+    }
+
+    /// Errors from storing or loading since the last call.
+    pub fn take_errors(&self) -> Vec<NodeError> {
+        std::mem::take(&mut self.state.lock().unwrap().errors)
+    }
+
+    /// Access the underlying storage.
+    pub fn with_storage<T>(&self, f: impl FnOnce(&mut S) -> T) -> T {
+        f(&mut self.state.lock().unwrap().storage)
+    }
+}
+
+impl<S> IncomingPeerChannel for StoragePeer<S>
+where
+    S: SessionLogStorage + Send,
+{
+    fn try_recv(&mut self) -> Result<Option<SyncMessage>, Disconnected> {
+        let mut state = self.state.lock().unwrap();
+        match state.responses.pop_front() {
+            Some(message) => Ok(Some(message)),
+            None if state.closed => Err(Disconnected),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<S> OutgoingPeerChannel for StoragePeer<S>
+where
+    S: SessionLogStorage + Send,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    fn push(&mut self, message: SyncMessage) -> Result<(), Disconnected> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(Disconnected);
+        }
+        state.handle(message);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.state.lock().unwrap().closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{test_header, test_node as node};
+    use crate::{connected_peers, sync_until_idle};
+    use cojson_storage_sqlite::SqliteStorage;
+    use serde_json::Value as JsonValue;
+
+    fn header() -> JsonValue {
+        test_header("a")
+    }
+
+    #[test]
+    fn test_changes_are_stored_and_served() {
+        let storage = StoragePeer::new(SqliteStorage::open_in_memory().unwrap());
+        let mut server = node(1);
+        server.add_peer(storage.peer("storage"));
+
+        let mut client = node(2);
+        let (client_as_peer, server_as_peer) =
+            connected_peers("client", "server", PeerRole::Client, PeerRole::Server);
+        client.add_peer(server_as_peer);
+        server.add_peer(client_as_peer);
+
+        let id = client.create_co_value(header());
+        client.make_transaction(&id, "[1]", 1).unwrap();
+        client.make_transaction(&id, "[2]", 2).unwrap();
+        sync_until_idle(&mut [&mut client, &mut server]);
+        assert!(storage.take_errors().is_empty());
+        storage.with_storage(|storage| {
+            let stored = storage.get_co_value(&id).unwrap().unwrap();
+            let sessions = storage.get_co_value_sessions(stored.row_id).unwrap();
+            assert_eq!(sessions.len(), 1);
+            assert_eq!(sessions[0].last_idx, 2);
+        });
+
+        // A fresh server loads it from storage for a fresh client
+        let mut restarted = node(3);
+        restarted.add_peer(storage.peer("storage"));
+        let mut other_client = node(4);
+        let (client_as_peer, server_as_peer) =
+            connected_peers("other", "server", PeerRole::Client, PeerRole::Server);
+        other_client.add_peer(server_as_peer);
+        restarted.add_peer(client_as_peer);
+
+        other_client.load(&id);
+        sync_until_idle(&mut [&mut other_client, &mut restarted]);
+        assert_eq!(other_client.known_state(&id), client.known_state(&id));
+        assert!(storage.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_co_value() {
+        let storage = StoragePeer::new(SqliteStorage::open_in_memory().unwrap());
+        let mut server = node(1);
+        server.add_peer(storage.peer("storage"));
+
+        let id = CoID::for_header(&header());
+        assert!(!server.load(&id));
+        sync_until_idle(&mut [&mut server]);
+        assert!(server.co_value(&id).is_none());
+        assert!(storage.take_errors().is_empty());
+    }
+}
@@ -0,0 +1,12 @@
+//! Fixtures for the tests of this crate and the crates built on it, which get them through the
+//! `test-utils` feature. The fixtures of `cojson-core` are re-exported alongside.
+
+pub use cojson_core::core::test_utils::*;
+
+use crate::LocalNode;
+
+/// A node running as `test_agent(seed)`.
+pub fn test_node(seed: u8) -> LocalNode {
+    let (session_id, signer_secret) = test_agent(seed);
+    LocalNode::new(session_id, signer_secret)
+}
@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use cojson_core::core::{CoID, KeyID, KeySecret, SignerSecret, SubscriptionID, TransactionID};
+use cojson_core::crypto::signature::get_signer_id;
+use cojson_sync::LocalNode;
+
+use crate::{
+    now_millis, read_content, HttpEndpoint, SignedPayload, WebhookError, WebhookPayload,
+    SIGNATURE_HEADER, SIGNER_HEADER, TIMESTAMP_HEADER,
+};
+
+/// How often a delivery is attempted and how long to wait in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after the given failed attempt, counting from 1. Doubles every time.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookOptions {
+    /// The `https://` URLs every payload is POSTed to. `http://` works too, but leaves the
+    /// payloads readable on the way.
+    pub endpoints: Vec<String>,
+    pub retry: RetryPolicy,
+    /// For connecting, sending and waiting for the response, per attempt.
+    pub timeout: Duration,
+    /// Send the content along with the known state. This includes decrypted private
+    /// transactions.
+    pub include_content: bool,
+    /// Read keys for private transactions in the content.
+    pub read_keys: HashMap<KeyID, KeySecret>,
+}
+
+impl Default for WebhookOptions {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(10),
+            include_content: false,
+            read_keys: HashMap::new(),
+        }
+    }
+}
+
+/// A payload an endpoint didn't accept, after the last attempt.
+#[derive(Debug)]
+pub struct DeliveryFailure {
+    pub endpoint: String,
+    pub co_id: CoID,
+    pub attempts: u32,
+    pub error: WebhookError,
+}
+
+/// A serialized payload waiting to be signed and delivered.
+struct QueuedPayload {
+    co_id: CoID,
+    body: String,
+}
+
+/// The delivery thread of one endpoint, so a slow endpoint doesn't hold up the others.
+/// Payloads are delivered in order.
+struct EndpointDelivery {
+    payloads: Sender<Arc<QueuedPayload>>,
+    thread: JoinHandle<()>,
+}
+
+type PendingUpdates = Arc<Mutex<BTreeMap<CoID, Vec<TransactionID>>>>;
+
+/// Sends a signed payload to every endpoint when a watched CoValue's known state advances.
+///
+/// Changes are collected from the node's subscriptions as messages are processed and turned
+/// into payloads by `dispatch`, one per CoValue however many updates it had since.
+pub struct WebhookDispatcher {
+    include_content: bool,
+    read_keys: HashMap<KeyID, KeySecret>,
+    watched: BTreeMap<CoID, SubscriptionID>,
+    pending: PendingUpdates,
+    deliveries: Vec<EndpointDelivery>,
+    failures: Arc<Mutex<Vec<DeliveryFailure>>>,
+}
+
+impl WebhookDispatcher {
+    /// Start a delivery thread per endpoint, signing payloads with `signer_secret`.
+    pub fn new(signer_secret: SignerSecret, options: WebhookOptions) -> Result<Self, WebhookError> {
+        // Payloads are signed on the delivery threads, so check the secret here
+        get_signer_id(&signer_secret.0)?;
+        let endpoints = options
+            .endpoints
+            .iter()
+            .map(|url| HttpEndpoint::parse(url, options.timeout))
+            .collect::<Result<Vec<_>, _>>()?;
+        let failures = Arc::new(Mutex::new(Vec::new()));
+
+        let deliveries = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                let (payloads, receiver) = channel::<Arc<QueuedPayload>>();
+                let signer_secret = signer_secret.clone();
+                let retry = options.retry.clone();
+                let failures = failures.clone();
+                let thread = thread::spawn(move || {
+                    for payload in receiver {
+                        if let Err((attempts, error)) =
+                            deliver(&endpoint, &payload, &signer_secret, &retry)
+                        {
+                            failures.lock().unwrap().push(DeliveryFailure {
+                                endpoint: endpoint.url().to_string(),
+                                co_id: payload.co_id.clone(),
+                                attempts,
+                                error,
+                            });
+                        }
+                    }
+                });
+                EndpointDelivery { payloads, thread }
+            })
+            .collect();
+
+        Ok(Self {
+            include_content: options.include_content,
+            read_keys: options.read_keys,
+            watched: BTreeMap::new(),
+            pending: Arc::new(Mutex::new(BTreeMap::new())),
+            deliveries,
+            failures,
+        })
+    }
+
+    /// Watch a CoValue, loading it from the node's servers if the node doesn't have it yet.
+    pub fn watch(&mut self, node: &mut LocalNode, id: &CoID) {
+        if self.watched.contains_key(id) {
+            return;
+        }
+        let pending = self.pending.clone();
+        let subscription = node.subscribe(id, move |update| {
+            pending
+                .lock()
+                .unwrap()
+                .entry(update.id.clone())
+                .or_default()
+                .extend(update.new_transactions.iter().cloned());
+        });
+        self.watched.insert(id.clone(), subscription);
+        node.load(id);
+    }
+
+    /// Returns whether the CoValue was watched.
+    pub fn unwatch(&mut self, node: &mut LocalNode, id: &CoID) -> bool {
+        let Some(subscription) = self.watched.remove(id) else {
+            return false;
+        };
+        node.unsubscribe(id, subscription);
+        self.pending.lock().unwrap().remove(id);
+        true
+    }
+
+    /// Queue a payload for every watched CoValue that changed since the last call, to be
+    /// delivered in the background.
+    ///
+    /// Returns the number of payloads queued.
+    pub fn dispatch(&mut self, node: &LocalNode) -> Result<usize, WebhookError> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut dispatched = 0;
+        for (id, new_transactions) in pending {
+            let Some(co_value) = node.co_value(&id) else {
+                continue;
+            };
+            // Content that can't be read is left out, the known state still tells what changed
+            let content = if self.include_content {
+                read_content(co_value, |key_id| self.read_keys.get(key_id).cloned())
+                    .ok()
+                    .flatten()
+            } else {
+                None
+            };
+            let payload = WebhookPayload {
+                co_id: id,
+                known_state: co_value.known_state(),
+                new_transactions,
+                content,
+            };
+            let queued = Arc::new(QueuedPayload {
+                co_id: payload.co_id.clone(),
+                body: serde_json::to_string(&payload)?,
+            });
+            for delivery in &self.deliveries {
+                let _ = delivery.payloads.send(queued.clone());
+            }
+            dispatched += 1;
+        }
+        Ok(dispatched)
+    }
+
+    /// Payloads that couldn't be delivered since the last call.
+    pub fn take_failures(&self) -> Vec<DeliveryFailure> {
+        std::mem::take(&mut *self.failures.lock().unwrap())
+    }
+
+    /// Wait until every queued payload is delivered or given up on.
+    ///
+    /// Returns the payloads that couldn't be delivered.
+    pub fn shutdown(self) -> Vec<DeliveryFailure> {
+        for delivery in self.deliveries {
+            drop(delivery.payloads);
+            let _ = delivery.thread.join();
+        }
+        std::mem::take(&mut *self.failures.lock().unwrap())
+    }
+}
+
+/// POST a payload until the endpoint accepts it, it fails in a way retrying won't fix, or we
+/// run out of attempts.
+///
+/// Every attempt is signed anew, so its timestamp is the time it was sent.
+fn deliver(
+    endpoint: &HttpEndpoint,
+    payload: &QueuedPayload,
+    signer_secret: &SignerSecret,
+    retry: &RetryPolicy,
+) -> Result<(), (u32, WebhookError)> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let signed = SignedPayload::sign(
+            payload.co_id.clone(),
+            payload.body.clone(),
+            signer_secret,
+            now_millis(),
+        )
+        .map_err(|err| (attempt, err))?;
+        let timestamp = signed.timestamp.to_string();
+        let headers = [
+            (SIGNATURE_HEADER, signed.signature.as_str()),
+            (SIGNER_HEADER, signed.signer_id.0.as_str()),
+            (TIMESTAMP_HEADER, timestamp.as_str()),
+        ];
+        let error = match endpoint.post(&headers, signed.body.as_bytes()) {
+            Ok(status) if (200..300).contains(&status) => return Ok(()),
+            Ok(status) => WebhookError::Status {
+                endpoint: endpoint.url().to_string(),
+                status,
+            },
+            Err(err) => err,
+        };
+        if attempt >= retry.max_attempts || !error.is_retryable() {
+            return Err((attempt, error));
+        }
+        thread::sleep(retry.backoff(attempt));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify_payload;
+    use cojson_core::core::SignerID;
+    use cojson_sync::test_utils::{test_agent, test_header, test_node};
+    use serde_json::{json, Value as JsonValue};
+    use std::collections::VecDeque;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// A request as the stand-in endpoint received it.
+    struct Received {
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    /// A local HTTP endpoint answering with the given statuses in turn, then 200.
+    fn stand_in(statuses: &[u16]) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // `localhost` may resolve to `::1` first, which nothing listens on
+        let url = format!(
+            "http://localhost:{}/hook",
+            listener.local_addr().unwrap().port()
+        );
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorded = received.clone();
+        let mut statuses: VecDeque<u16> = statuses.iter().copied().collect();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    headers.insert(name.to_lowercase(), value.to_string());
+                }
+                let mut body = vec![0; headers["content-length"].parse().unwrap()];
+                reader.read_exact(&mut body).unwrap();
+                recorded.lock().unwrap().push(Received { headers, body });
+
+                let status = statuses.pop_front().unwrap_or(200);
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Whatever\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn node() -> (LocalNode, SignerSecret) {
+        let (_, secret) = test_agent(1);
+        (test_node(1), secret)
+    }
+
+    fn options(endpoints: Vec<String>) -> WebhookOptions {
+        WebhookOptions {
+            endpoints,
+            retry: RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(50),
+            },
+            timeout: Duration::from_secs(5),
+            include_content: true,
+            ..WebhookOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.backoff(1), Duration::from_millis(500));
+        assert_eq!(retry.backoff(3), Duration::from_secs(2));
+        assert_eq!(retry.backoff(40), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_signed_payload_with_retries() {
+        let (mut node, secret) = node();
+        let (url, received) = stand_in(&[503]);
+        let mut dispatcher = WebhookDispatcher::new(secret, options(vec![url])).unwrap();
+
+        let id = node.create_co_value(test_header("webhook"));
+        dispatcher.watch(&mut node, &id);
+        assert_eq!(dispatcher.dispatch(&node).unwrap(), 0);
+
+        node.make_transaction(&id, r#"[{"op":"set","key":"a","value":1}]"#, 1)
+            .unwrap();
+        node.make_transaction(&id, r#"[{"op":"set","key":"b","value":2}]"#, 2)
+            .unwrap();
+        assert_eq!(dispatcher.dispatch(&node).unwrap(), 1);
+        assert!(dispatcher.shutdown().is_empty());
+
+        // Refused once, then delivered again unchanged
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].body, received[1].body);
+
+        let request = &received[1];
+        let signer_id = SignerID(request.headers[&SIGNER_HEADER.to_lowercase()].clone());
+        assert_eq!(
+            &signer_id,
+            node.session_id().agent_signer_id().as_ref().unwrap()
+        );
+        let signature = &request.headers[&SIGNATURE_HEADER.to_lowercase()];
+        let timestamp = &request.headers[&TIMESTAMP_HEADER.to_lowercase()];
+        let max_age = Duration::from_secs(60);
+        let verify = |body: &[u8], timestamp: &str| {
+            verify_payload(body, timestamp, signature, &signer_id, max_age).unwrap()
+        };
+        assert!(verify(&request.body, timestamp));
+        assert!(!verify(b"{}", timestamp));
+        let later = (timestamp.parse::<u64>().unwrap() + 1).to_string();
+        assert!(!verify(&request.body, &later));
+
+        // Each attempt is signed when it's sent
+        let first = &received[0].headers;
+        assert_ne!(&first[&SIGNATURE_HEADER.to_lowercase()], signature);
+
+        // An old delivery doesn't verify anymore, even with a valid signature
+        let (_, secret) = test_agent(1);
+        let stale = SignedPayload::sign(
+            id.clone(),
+            String::from_utf8(request.body.clone()).unwrap(),
+            &secret,
+            1000,
+        )
+        .unwrap();
+        assert!(!verify_payload(
+            stale.body.as_bytes(),
+            &stale.timestamp.to_string(),
+            &stale.signature,
+            &signer_id,
+            max_age
+        )
+        .unwrap());
+
+        let payload: WebhookPayload = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(payload.co_id, id);
+        assert_eq!(payload.known_state, node.known_state(&id));
+        assert_eq!(payload.new_transactions.len(), 2);
+        assert_eq!(payload.content, Some(json!({"a": 1, "b": 2})));
+        let body: JsonValue = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["knownState"]["sessions"][&node.session_id().0], 2);
+    }
+
+    #[test]
+    fn test_give_up_on_client_errors() {
+        let (mut node, secret) = node();
+        let (url, received) = stand_in(&[400]);
+        let mut dispatcher = WebhookDispatcher::new(secret.clone(), options(vec![url])).unwrap();
+
+        let id = node.create_co_value(json!({"type": "costream", "uniqueness": "webhook"}));
+        dispatcher.watch(&mut node, &id);
+        node.make_transaction(&id, "[1]", 1).unwrap();
+        dispatcher.dispatch(&node).unwrap();
+
+        let failures = dispatcher.shutdown();
+        assert_eq!(received.lock().unwrap().len(), 1);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].attempts, 1);
+        assert_eq!(failures[0].co_id, id);
+        assert!(matches!(
+            failures[0].error,
+            WebhookError::Status { status: 400, .. }
+        ));
+
+        assert!(matches!(
+            WebhookDispatcher::new(secret, options(vec!["ftp://example.com".to_string()])),
+            Err(WebhookError::InvalidEndpoint { .. })
+        ));
+    }
+}
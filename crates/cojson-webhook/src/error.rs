@@ -0,0 +1,42 @@
+use cojson_core::core::CoJsonCoreError;
+use cojson_core::crypto::CryptoError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Invalid endpoint {url:?}: {reason}")]
+    InvalidEndpoint { url: String, reason: &'static str },
+
+    #[error("Could not deliver to {endpoint}: {source}")]
+    Transport {
+        endpoint: String,
+        #[source]
+        source: Box<ureq::Transport>,
+    },
+
+    #[error("{endpoint} answered with status {status}")]
+    Status { endpoint: String, status: u16 },
+
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Core(#[from] CoJsonCoreError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl WebhookError {
+    /// Whether delivering again may succeed: the endpoint couldn't be reached, failed, or asked
+    /// us to slow down. Other client errors won't go away by retrying.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WebhookError::Transport { .. } => true,
+            WebhookError::Status { status, .. } => {
+                *status >= 500 || *status == 408 || *status == 429
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use ureq::{Agent, AgentBuilder};
+use url::Url;
+
+use crate::WebhookError;
+
+/// An `https://` (or `http://`) URL to POST payloads to.
+///
+/// Requests go through `ureq` with rustls, verifying servers against the webpki roots. Every
+/// address the host resolves to is tried in turn until one accepts the connection.
+#[derive(Debug, Clone)]
+pub struct HttpEndpoint {
+    url: String,
+    agent: Agent,
+}
+
+impl HttpEndpoint {
+    /// Parse an endpoint URL, giving up on each request after `timeout`.
+    pub fn parse(url: &str, timeout: Duration) -> Result<Self, WebhookError> {
+        let invalid = |reason| WebhookError::InvalidEndpoint {
+            url: url.to_string(),
+            reason,
+        };
+        let parsed = Url::parse(url).map_err(|_| invalid("not a valid URL"))?;
+        if !matches!(parsed.scheme(), "https" | "http") {
+            return Err(invalid("only https:// and http:// endpoints are supported"));
+        }
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(invalid("missing host"));
+        }
+
+        Ok(Self {
+            url: url.to_string(),
+            agent: AgentBuilder::new().timeout(timeout).build(),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// POST `body` as JSON with extra `headers`, returning the status the endpoint answered with.
+    pub fn post(&self, headers: &[(&str, &str)], body: &[u8]) -> Result<u16, WebhookError> {
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json");
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        match request.send_bytes(body) {
+            Ok(response) => Ok(response.status()),
+            // Not an error for us: the caller decides which statuses are worth retrying
+            Err(ureq::Error::Status(status, _)) => Ok(status),
+            Err(ureq::Error::Transport(transport)) => Err(WebhookError::Transport {
+                endpoint: self.url.clone(),
+                source: Box::new(transport),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Result<HttpEndpoint, WebhookError> {
+        HttpEndpoint::parse(url, Duration::from_secs(1))
+    }
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert!(parse("https://example.com/hooks/jazz").is_ok());
+        assert!(parse("http://localhost:8080/hooks/jazz").is_ok());
+        assert!(parse("https://[::1]:8080/").is_ok());
+        assert_eq!(
+            parse("https://example.com").unwrap().url(),
+            "https://example.com"
+        );
+
+        assert!(matches!(
+            parse("ftp://example.com/hook"),
+            Err(WebhookError::InvalidEndpoint { .. })
+        ));
+        assert!(parse("example.com/hook").is_err());
+        assert!(parse("https://example.com:x/hook").is_err());
+        assert!(parse("https://[::1/hook").is_err());
+        assert!(parse("https://").is_err());
+    }
+
+    #[test]
+    fn test_unreachable_endpoint() {
+        // Nothing listens on the discard port
+        let endpoint = parse("http://127.0.0.1:9/hook").unwrap();
+        let error = endpoint.post(&[], b"{}").unwrap_err();
+        assert!(matches!(error, WebhookError::Transport { .. }));
+        assert!(error.is_retryable());
+    }
+}
@@ -0,0 +1,19 @@
+//! Webhooks for CoValue changes, like `packages/jazz-webhook`: a `WebhookDispatcher` watches
+//! CoValues on a `LocalNode` and POSTs a signed payload to every endpoint whenever their known
+//! state advances, retrying with backoff until the endpoint accepts it.
+//!
+//! Payloads are signed with the worker's signer secret over the body and the time of sending,
+//! so receivers verify them against its signer ID with `verify_payload` and reject old ones.
+//!
+//! Endpoints should be `https://` URLs: with `include_content`, payloads carry the decrypted
+//! content of private transactions.
+
+pub mod dispatcher;
+pub mod error;
+pub mod http;
+pub mod payload;
+
+pub use dispatcher::*;
+pub use error::*;
+pub use http::*;
+pub use payload::*;
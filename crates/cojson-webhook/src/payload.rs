@@ -0,0 +1,148 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cojson_core::core::{
+    CoID, CoValueHistory, HistoryFilter, KeyID, KeySecret, SignerID, SignerSecret, TransactionID,
+};
+use cojson_core::crypto::signature::{get_signer_id, sign, verify};
+use cojson_sync::{CoValueKnownState, CoValueState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::WebhookError;
+
+/// The header with the payload's signature.
+pub const SIGNATURE_HEADER: &str = "X-Jazz-Webhook-Signature";
+
+/// The header with the signer ID the payload's signature verifies against.
+pub const SIGNER_HEADER: &str = "X-Jazz-Webhook-Signer";
+
+/// The header with the time the payload was signed at, in milliseconds since the Unix epoch.
+pub const TIMESTAMP_HEADER: &str = "X-Jazz-Webhook-Timestamp";
+
+/// What an endpoint is sent when a CoValue it watches changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub co_id: CoID,
+    pub known_state: CoValueKnownState,
+    /// The transactions added since the last payload for this CoValue.
+    pub new_transactions: Vec<TransactionID>,
+    /// The content as of `known_state`, if asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<JsonValue>,
+}
+
+/// A payload serialized and signed, ready to be delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedPayload {
+    pub co_id: CoID,
+    pub body: String,
+    /// Milliseconds since the Unix epoch, signed along with the body.
+    pub timestamp: u64,
+    pub signature: String,
+    pub signer_id: SignerID,
+}
+
+impl WebhookPayload {
+    /// Serialize and sign the payload as sent at `timestamp`.
+    pub fn sign(
+        &self,
+        signer_secret: &SignerSecret,
+        timestamp: u64,
+    ) -> Result<SignedPayload, WebhookError> {
+        let body = serde_json::to_string(self)?;
+        SignedPayload::sign(self.co_id.clone(), body, signer_secret, timestamp)
+    }
+}
+
+impl SignedPayload {
+    /// Sign a serialized payload as sent at `timestamp`, in milliseconds since the Unix epoch.
+    pub fn sign(
+        co_id: CoID,
+        body: String,
+        signer_secret: &SignerSecret,
+        timestamp: u64,
+    ) -> Result<Self, WebhookError> {
+        Ok(Self {
+            co_id,
+            signature: sign(
+                &signed_message(timestamp, body.as_bytes()),
+                &signer_secret.0,
+            )?,
+            signer_id: SignerID(get_signer_id(&signer_secret.0)?),
+            timestamp,
+            body,
+        })
+    }
+}
+
+/// The timestamp and the body, joined by a dot, so the signature doesn't verify for the same
+/// body sent at another time.
+fn signed_message(timestamp: u64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{timestamp}.").into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// Check a delivered payload's body against the timestamp, signature and signer ID from its
+/// headers.
+///
+/// Payloads signed more than `max_age` away from now don't verify, so captured deliveries
+/// can't be replayed later. Receivers that must not handle a payload twice also remember the
+/// signatures they've seen within `max_age`.
+pub fn verify_payload(
+    body: &[u8],
+    timestamp: &str,
+    signature: &str,
+    signer_id: &SignerID,
+    max_age: Duration,
+) -> Result<bool, WebhookError> {
+    let Ok(timestamp) = timestamp.parse::<u64>() else {
+        return Ok(false);
+    };
+    if u128::from(now_millis().abs_diff(timestamp)) > max_age.as_millis() {
+        return Ok(false);
+    }
+    Ok(verify(
+        signature,
+        &signed_message(timestamp, body),
+        &signer_id.0,
+    )?)
+}
+
+/// A CoValue's content, like `toJSON`: an object for CoMaps, an array for CoLists and a string
+/// for CoPlainTexts. None for other types.
+/// - `key_for`: Resolves read keys. Private transactions whose key can't be resolved are left
+///   out.
+pub fn read_content<F>(
+    co_value: &CoValueState,
+    key_for: F,
+) -> Result<Option<JsonValue>, WebhookError>
+where
+    F: FnMut(&KeyID) -> Option<KeySecret>,
+{
+    let Some(header) = co_value.header() else {
+        return Ok(None);
+    };
+    let history = CoValueHistory::from_session_logs(None, co_value.sessions(), key_for)?;
+    let all = HistoryFilter::All;
+    Ok(match header.get("type").and_then(JsonValue::as_str) {
+        Some("comap") => Some(JsonValue::Object(history.map_at(&all)?)),
+        Some("colist") => Some(JsonValue::Array(
+            history
+                .list_at(&all)?
+                .into_iter()
+                .map(|entry| entry.value)
+                .collect(),
+        )),
+        Some("coplaintext") => Some(JsonValue::String(history.plain_text_at(&all)?)),
+        _ => None,
+    })
+}